@@ -0,0 +1,60 @@
+/*
+ * C interface to beneath-a-steel-sky-extract, built with
+ * `cargo rustc --release --lib --features ffi --crate-type cdylib` (or
+ * `staticlib`). See src/ffi.rs for the details of each function.
+ *
+ * Data returned through `data` and `len` belongs to the caller and is
+ * given back with sky_free(). Functions returning int return SKY_OK or
+ * one of the negative error codes.
+ */
+
+#ifndef SKY_EXTRACT_H
+#define SKY_EXTRACT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SKY_OK 0
+#define SKY_ERROR_NULL -1
+#define SKY_ERROR_NOT_FOUND -2
+#define SKY_ERROR_NO_HEADER -3
+#define SKY_ERROR_DECOMPRESS -4
+
+typedef struct SkyArchive SkyArchive;
+
+typedef struct SkyHeader {
+    uint16_t flags;
+    uint16_t x;
+    uint16_t y;
+    uint16_t width;
+    uint16_t height;
+    uint16_t sp_size;
+    uint16_t tot_size;
+    uint16_t n_sprites;
+    int16_t offset_x;
+    int16_t offset_y;
+    uint16_t compressed_size;
+} SkyHeader;
+
+SkyArchive *sky_open_archive(const char *path);
+void sky_close_archive(SkyArchive *archive);
+
+size_t sky_resource_count(const SkyArchive *archive);
+int sky_resource_id(const SkyArchive *archive, size_t index, uint16_t *id);
+
+int sky_get_resource(const SkyArchive *archive, uint16_t id, uint8_t **data, size_t *len);
+int sky_get_header(const SkyArchive *archive, uint16_t id, SkyHeader *header);
+
+int sky_decompress_rnc1(const uint8_t *src, size_t src_len, uint8_t **data, size_t *len);
+
+void sky_free(uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
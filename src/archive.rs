@@ -0,0 +1,751 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bytes_ext::{ReadBytesExt, WriteBytesExt},
+    coverage::{self, Coverage},
+    fat::FatImage,
+    iso::{self, IsoImage},
+    resource::{read_resource, Resource},
+    version::{self, Version},
+};
+
+/// A record from the dinner table describing where a resource is stored.
+#[derive(Copy, Clone, Debug)]
+pub struct Entry {
+    pub number: u16,
+    pub offset: u32,
+    pub size: u32,
+    pub has_file_header: bool,
+    pub uses_file_header: bool,
+}
+
+/// There are no more resources than there are ids, so a larger entry count
+/// means the file isn't a dinner table.
+const MAX_ENTRIES: u32 = 0x1_0000;
+
+/// Tables with fewer entries than this aren't checked for looking like a
+/// dinner table, as there is too little to go by.
+const MIN_CHECKED_ENTRIES: usize = 8;
+
+/// Reads the dinner table. Fails with an error of kind
+/// [`io::ErrorKind::InvalidData`] if the entry count is more than there
+/// can be or the file ends before all entries are read.
+pub fn read_dinner_table<R: Read + ReadBytesExt>(file: &mut R) -> io::Result<Vec<Entry>> {
+    let entry_count = file
+        .read_le_u32()
+        .map_err(|_| not_a_dinner_table("it is too short to hold an entry count".to_owned()))?;
+    if entry_count > MAX_ENTRIES {
+        // A table of another platform would be big endian.
+        let hint = if entry_count.swap_bytes() <= MAX_ENTRIES {
+            ", or as many as it could if it were big endian"
+        } else {
+            ""
+        };
+        return Err(not_a_dinner_table(format!(
+            "it claims {entry_count} entries{hint}"
+        )));
+    }
+
+    let mut directory = Vec::with_capacity(entry_count as usize);
+    for n in 0..entry_count {
+        let entry = read_dinner_entry(file).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                not_a_dinner_table(format!("it ends after {n} of its {entry_count} entries"))
+            }
+            _ => e,
+        })?;
+        directory.push(entry);
+    }
+
+    Ok(directory)
+}
+
+fn read_dinner_entry<R: Read + ReadBytesExt>(file: &mut R) -> io::Result<Entry> {
+    let number = file.read_le_u16()?;
+    let offset = file.read_le_u24()?;
+    let size = file.read_le_u24()?;
+
+    let has_file_header = size >> 23 == 0;
+    let uses_file_header = (size >> 22) & 1 == 0;
+    let size = size & 0x3f_ff_ff;
+
+    Ok(Entry {
+        number,
+        offset,
+        size,
+        has_file_header,
+        uses_file_header,
+    })
+}
+
+/// Checks that `directory` looks like a dinner table for a `sky.dsk` of
+/// `dsk_len` bytes. The resources are stored in about the order of the
+/// table and within the file, so a table of which more than half the
+/// entries run past the end of the data, or of which more than a quarter
+/// go back to an earlier offset than the entry before, is taken for some
+/// other file. Damaged or truncated copies get through, to be reported
+/// entry by entry.
+pub fn check_dinner_table(directory: &[Entry], dsk_len: u64) -> io::Result<()> {
+    let count = directory.len();
+    if count < MIN_CHECKED_ENTRIES {
+        return Ok(());
+    }
+
+    let past_end = directory
+        .iter()
+        .filter(|e| e.offset as u64 + e.size as u64 > dsk_len)
+        .count();
+    if past_end * 2 > count {
+        return Err(not_a_dinner_table(format!(
+            "{past_end} of its {count} entries run past the end of the {dsk_len} bytes of sky.dsk"
+        )));
+    }
+
+    let backwards = directory
+        .windows(2)
+        .filter(|pair| pair[1].offset < pair[0].offset)
+        .count();
+    if backwards * 4 > count {
+        return Err(not_a_dinner_table(format!(
+            "{backwards} of its {count} entries are stored before the entry preceding them"
+        )));
+    }
+
+    Ok(())
+}
+
+/// The error for a file read as `sky.dnr` that isn't one, for `reason`.
+fn not_a_dinner_table(reason: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("this doesn't look like sky.dnr: {reason}"),
+    )
+}
+
+/// Writes `directory` in the format read by [`read_dinner_table`].
+///
+/// Offsets and sizes are stored in 24 bits, with the top two bits of the
+/// size holding the inverted header flags, so offsets must fit in 24 bits
+/// and sizes in 22.
+pub fn write_dinner_table<W: Write>(file: &mut W, directory: &[Entry]) -> io::Result<()> {
+    file.write_le_u32(directory.len() as u32)?;
+
+    for entry in directory {
+        if entry.offset > 0xff_ff_ff || entry.size > 0x3f_ff_ff {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("entry {} does not fit in the dinner table", entry.number),
+            ));
+        }
+
+        let mut size = entry.size;
+        if !entry.has_file_header {
+            size |= 1 << 23;
+        }
+        if !entry.uses_file_header {
+            size |= 1 << 22;
+        }
+
+        file.write_le_u16(entry.number)?;
+        file.write_all(&entry.offset.to_le_bytes()[0..3])?;
+        file.write_all(&size.to_le_bytes()[0..3])?;
+    }
+
+    Ok(())
+}
+
+/// Reads the bytes of `entry` from `file`. Fails if the entry runs past the
+/// end of the file, as entries of damaged or truncated copies can.
+pub fn read_entry<R: Read + Seek>(entry: &Entry, file: &mut R) -> io::Result<Vec<u8>> {
+    read_entry_within(entry, entry.size, file)
+}
+
+/// Reads `entry` like [`read_entry`], but no more than `len` of its bytes,
+/// for entries cut short by the end of their volume.
+fn read_entry_within<R: Read + Seek>(entry: &Entry, len: u32, file: &mut R) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(entry.offset as u64))?;
+
+    let mut buf = Vec::<u8>::new();
+    file.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() < entry.size as usize {
+        return Err(past_end(entry, buf.len()));
+    }
+
+    Ok(buf)
+}
+
+/// The error for `entry` running past the end of `sky.dsk` with only
+/// `available` of its bytes there.
+fn past_end(entry: &Entry, available: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!(
+            "entry runs past the end of sky.dsk, {} of its {} bytes at offset {} are missing",
+            entry.size as usize - available,
+            entry.size,
+            entry.offset
+        ),
+    )
+}
+
+/// The directory `path` refers to: `path` itself or, for a file, the
+/// directory containing it.
+fn game_dir(path: &Path) -> io::Result<&Path> {
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        path.parent().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("invalid path `{}`", path.display()),
+            )
+        })
+    }
+}
+
+/// The directories searched for the game's files: the game directory
+/// `path` refers to, followed by the directories in it in order of name,
+/// so the directory a download of the game or one of its demos was
+/// unpacked in can be given as well.
+fn search_dirs(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = game_dir(path)?;
+
+    let mut subdirs: Vec<_> = dir
+        .read_dir()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    subdirs.sort();
+
+    Ok(std::iter::once(dir.to_path_buf()).chain(subdirs).collect())
+}
+
+/// Looks for the file `name` in the game directory `path`, see
+/// [`find_data_files`].
+pub fn find_file<P: AsRef<Path>>(path: P, name: &str) -> io::Result<Option<PathBuf>> {
+    for dir in search_dirs(path.as_ref())? {
+        let found = dir
+            .read_dir()?
+            .flatten()
+            .find(|entry| entry.file_name().eq_ignore_ascii_case(name))
+            .map(|entry| entry.path());
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Lists the files in the game directory `path` with the extension `ext`,
+/// matched without regard to case, see [`find_data_files`].
+pub fn find_files_with_extension<P: AsRef<Path>>(path: P, ext: &str) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for dir in search_dirs(path.as_ref())? {
+        let mut files: Vec<_> = dir
+            .read_dir()?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+            })
+            .collect();
+        files.sort();
+        found.extend(files);
+    }
+
+    Ok(found)
+}
+
+/// Locates `sky.dnr` and `sky.dsk` in `path`, which may be either the
+/// game directory or a file inside it. If they aren't there, the
+/// directories inside the game directory are searched as well. File names
+/// are matched without regard to case.
+pub fn find_data_files<P: AsRef<Path>>(path: P) -> io::Result<(PathBuf, PathBuf)> {
+    let path = path.as_ref();
+
+    let not_found = |name| io::Error::new(io::ErrorKind::NotFound, format!("{name} not found"));
+    let dnr = find_file(path, "sky.dnr")?.ok_or_else(|| not_found("sky.dnr"))?;
+    // The two belong together, so `sky.dsk` is looked for next to `sky.dnr`.
+    let dsk = find_file(&dnr, "sky.dsk")?.ok_or_else(|| not_found("sky.dsk"))?;
+
+    Ok((dnr, dsk))
+}
+
+/// The extensions of images of floppy disks.
+const DISK_IMAGE_EXTENSIONS: [&str; 2] = ["img", "ima"];
+
+/// Whether `path` names an image of a floppy disk, going by its extension.
+pub fn is_disk_image(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        DISK_IMAGE_EXTENSIONS
+            .iter()
+            .any(|e| ext.eq_ignore_ascii_case(e))
+    })
+}
+
+/// The floppy disk images the game is read from: `path` itself if it is
+/// one, otherwise the images in the game directory `path` refers to if
+/// there is no `sky.dnr` there, see [`find_data_files`].
+pub fn find_disk_images<P: AsRef<Path>>(path: P) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if path.is_file() && is_disk_image(path) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if find_file(path, "sky.dnr")?.is_some() {
+        return Ok(Vec::new());
+    }
+
+    let mut images = Vec::new();
+    for ext in DISK_IMAGE_EXTENSIONS {
+        images.extend(find_files_with_extension(path, ext)?);
+    }
+    images.sort();
+    images.dedup();
+
+    Ok(images)
+}
+
+/// Reads `sky.dnr` and `sky.dsk` from the floppy disk images `images`,
+/// each from the first image holding it.
+pub fn read_disk_images(images: &[PathBuf]) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut dnr = None;
+    let mut dsk = None;
+    for path in images {
+        let data = std::fs::read(path)?;
+        let image = FatImage::new(&data)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        for (name, file) in [("sky.dnr", &mut dnr), ("sky.dsk", &mut dsk)] {
+            if file.is_none() {
+                if let Some(entry) = image.find(name)? {
+                    *file = Some(image.read(&entry)?);
+                }
+            }
+        }
+    }
+
+    let not_found = |name| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{name} not found in the disk images"),
+        )
+    };
+    Ok((
+        dnr.ok_or_else(|| not_found("sky.dnr"))?,
+        dsk.ok_or_else(|| not_found("sky.dsk"))?,
+    ))
+}
+
+/// The extensions of images of CDs, in the order they are looked for.
+const CD_IMAGE_EXTENSIONS: [&str; 3] = ["iso", "cue", "bin"];
+
+/// Whether `path` names an image of a CD or its cue sheet, going by its
+/// extension.
+pub fn is_cd_image(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        CD_IMAGE_EXTENSIONS
+            .iter()
+            .any(|e| ext.eq_ignore_ascii_case(e))
+    })
+}
+
+/// The CD image the game is read from: `path` itself if it is one,
+/// otherwise the first image in the game directory `path` refers to if
+/// there is no `sky.dnr` there, see [`find_data_files`].
+pub fn find_cd_image<P: AsRef<Path>>(path: P) -> io::Result<Option<PathBuf>> {
+    let path = path.as_ref();
+    if path.is_file() && is_cd_image(path) {
+        return Ok(Some(path.to_path_buf()));
+    }
+    if find_file(path, "sky.dnr")?.is_some() {
+        return Ok(None);
+    }
+
+    for ext in CD_IMAGE_EXTENSIONS {
+        if let Some(image) = find_files_with_extension(path, ext)?.into_iter().next() {
+            return Ok(Some(image));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Reads `sky.dnr` and `sky.dsk` from the CD image `path`, which may also
+/// be the cue sheet of a `.bin` image.
+pub fn read_cd_image(path: &Path) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let is_cue = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cue"));
+    let image_path = if is_cue {
+        iso::cue_image(path)?
+    } else {
+        path.to_path_buf()
+    };
+
+    let in_image =
+        |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", image_path.display()));
+    let mut image = IsoImage::new(BufReader::new(File::open(&image_path)?)).map_err(in_image)?;
+    let mut read = |name: &str| -> io::Result<Vec<u8>> {
+        let entry = image.find(name)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{name} not found in the CD image"),
+            )
+        })?;
+        image.read(&entry)
+    };
+
+    Ok((read("sky.dnr")?, read("sky.dsk")?))
+}
+
+/// `sky.dsk` opened from a file, which remembers its path so further
+/// readers can be opened on it, see [`Archive::reader`].
+pub struct DskFile {
+    path: PathBuf,
+    file: BufReader<File>,
+}
+
+impl DskFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = BufReader::new(File::open(&path)?);
+        Ok(DskFile { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Read for DskFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for DskFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+/// The `sky.dsk` data of an archive, from which readers positioned
+/// independently of it and of each other can be made.
+pub trait Source {
+    type Reader<'a>: Read + Seek
+    where
+        Self: 'a;
+
+    fn reader(&self) -> io::Result<Self::Reader<'_>>;
+}
+
+impl Source for DskFile {
+    type Reader<'a> = BufReader<File>;
+
+    /// Opens the file again, for a position of its own.
+    fn reader(&self) -> io::Result<BufReader<File>> {
+        Ok(BufReader::new(File::open(&self.path)?))
+    }
+}
+
+impl<T: AsRef<[u8]>> Source for Cursor<T> {
+    type Reader<'a>
+        = Cursor<&'a [u8]>
+    where
+        T: 'a;
+
+    fn reader(&self) -> io::Result<Cursor<&[u8]>> {
+        Ok(Cursor::new(self.get_ref().as_ref()))
+    }
+}
+
+/// The game's resource archive: the dinner table directory together with a
+/// reader over the `sky.dsk` data.
+pub struct Archive<R> {
+    directory: Vec<Entry>,
+    dsk: R,
+    /// Where the data of each volume starts, for an archive merged from
+    /// several, see [`Archive::merge`].
+    volumes: Vec<u32>,
+}
+
+impl Archive<DskFile> {
+    /// Opens the archive found in `path`, see [`find_data_files`].
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let (sky_dnr_path, sky_dsk_path) = find_data_files(path)?;
+
+        let mut sky_dnr_file = BufReader::new(File::open(sky_dnr_path)?);
+        let sky_dsk_file = DskFile::open(sky_dsk_path)?;
+
+        Archive::new(&mut sky_dnr_file, sky_dsk_file)
+    }
+}
+
+impl Archive<Cursor<Vec<u8>>> {
+    /// Opens the archive found in `path` and reads all of `sky.dsk` into
+    /// memory, see [`find_data_files`]. `path` may also be an image of a
+    /// CD or a floppy disk, or a directory of them, see [`find_cd_image`]
+    /// and [`find_disk_images`].
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if let Some(image) = find_cd_image(&path)? {
+            let (dnr, dsk) = read_cd_image(&image)?;
+            return Archive::from_bytes(&dnr, dsk);
+        }
+
+        let images = find_disk_images(&path)?;
+        if !images.is_empty() {
+            let (dnr, dsk) = read_disk_images(&images)?;
+            return Archive::from_bytes(&dnr, dsk);
+        }
+
+        let (sky_dnr_path, sky_dsk_path) = find_data_files(path)?;
+
+        let mut sky_dnr_file = BufReader::new(File::open(sky_dnr_path)?);
+        let sky_dsk = std::fs::read(sky_dsk_path)?;
+
+        Archive::new(&mut sky_dnr_file, Cursor::new(sky_dsk))
+    }
+
+    /// Merges the archives of the volumes of a release split over several,
+    /// each with a `sky.dnr` and `sky.dsk` of its own, into one. The data of
+    /// each volume follows that of the one before, with the offsets of its
+    /// entries moved along to match, and the directory is sorted by id. A
+    /// resource in more than one volume is taken from the first, and reads
+    /// of an entry stop at the end of its volume.
+    pub fn merge(volumes: Vec<Self>) -> io::Result<Self> {
+        let mut directory = Vec::new();
+        let mut ids = HashSet::new();
+        let mut dsk = Vec::new();
+        let mut starts = Vec::new();
+        for volume in volumes {
+            let start = u32::try_from(dsk.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "volumes too large to merge")
+            })?;
+            for entry in volume.directory {
+                if ids.insert(entry.number) {
+                    let offset = entry.offset.checked_add(start).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "volumes too large to merge")
+                    })?;
+                    directory.push(Entry { offset, ..entry });
+                }
+            }
+            starts.push(start);
+            dsk.extend(volume.dsk.into_inner());
+        }
+        directory.sort_by_key(|e| e.number);
+
+        Ok(Archive {
+            directory,
+            dsk: Cursor::new(dsk),
+            volumes: starts,
+        })
+    }
+
+    /// Opens an archive from the contents of `sky.dnr` and `sky.dsk`, for
+    /// where there are no files to open, such as in a browser.
+    pub fn from_bytes(dnr: &[u8], dsk: Vec<u8>) -> io::Result<Self> {
+        Archive::new(&mut Cursor::new(dnr), Cursor::new(dsk))
+    }
+}
+
+impl<T: AsRef<[u8]>> Archive<Cursor<T>> {
+    /// The raw bytes of `entry` for an archive held in memory, cut short by
+    /// the end of the data. Unlike [`Archive::read_entry`] this only needs
+    /// shared access, so entries can be read from several threads.
+    pub fn entry_bytes(&self, entry: &Entry) -> &[u8] {
+        self.dsk_bytes(entry.offset, within_volume(&self.volumes, entry))
+    }
+
+    /// The raw bytes of `entry` for an archive held in memory, failing like
+    /// [`Archive::read_entry`] if the entry runs past the end of the data.
+    pub fn checked_entry_bytes(&self, entry: &Entry) -> io::Result<&[u8]> {
+        let data = self.entry_bytes(entry);
+        if data.len() < entry.size as usize {
+            return Err(past_end(entry, data.len()));
+        }
+
+        Ok(data)
+    }
+
+    /// How the directory covers the data, see [`coverage::check`].
+    pub fn coverage(&self) -> Coverage {
+        coverage::check(&self.directory, self.dsk.get_ref().as_ref())
+    }
+
+    /// The bytes of the data from `offset` on, `len` of them or as many as
+    /// there are.
+    pub fn dsk_bytes(&self, offset: u32, len: u32) -> &[u8] {
+        let dsk = self.dsk.get_ref().as_ref();
+        let start = (offset as usize).min(dsk.len());
+        let end = (start + len as usize).min(dsk.len());
+
+        &dsk[start..end]
+    }
+
+    /// Looks up and decodes the resource with the given id, see
+    /// [`Archive::checked_entry_bytes`].
+    pub fn get_resource(&self, id: u16) -> Option<Resource> {
+        let entry = self.entry(id)?;
+        read_resource(entry, self.checked_entry_bytes(entry).ok()?.to_vec()).ok()
+    }
+}
+
+impl<R> Archive<R> {
+    pub fn entries(&self) -> &[Entry] {
+        &self.directory
+    }
+
+    /// The number of volumes the archive was merged from, one unless made
+    /// with [`Archive::merge`].
+    pub fn volume_count(&self) -> usize {
+        self.volumes.len()
+    }
+
+    /// The volume `entry` was read from, counted from zero.
+    pub fn volume(&self, entry: &Entry) -> usize {
+        volume(&self.volumes, entry)
+    }
+
+    /// The release the archive belongs to, if known.
+    pub fn version(&self) -> Option<&'static Version> {
+        version::detect(self.directory.len())
+    }
+
+    pub fn entry(&self, id: u16) -> Option<&Entry> {
+        self.directory.iter().find(|e| e.number == id)
+    }
+
+    /// Finds the palette whose id is closest to `id`. Palettes are recognised
+    /// as headerless entries of 768 bytes.
+    pub fn nearest_palette(&self, id: u16) -> Option<&Entry> {
+        self.directory
+            .iter()
+            .filter(|e| !e.has_file_header && e.size == 768)
+            .min_by_key(|e| e.number.abs_diff(id))
+    }
+}
+
+impl<R: Read + Seek> Archive<R> {
+    /// Reads the dinner table from `dnr` and uses `dsk` for resource data.
+    /// Fails if the table doesn't look like one for `dsk`, see
+    /// [`check_dinner_table`].
+    pub fn new<D: Read>(dnr: &mut D, mut dsk: R) -> io::Result<Self> {
+        let directory = read_dinner_table(dnr)?;
+        let dsk_len = dsk.seek(SeekFrom::End(0))?;
+        check_dinner_table(&directory, dsk_len)?;
+
+        Ok(Archive {
+            directory,
+            dsk,
+            volumes: vec![0],
+        })
+    }
+
+    /// Reads the raw, undecoded bytes of `entry`.
+    pub fn read_entry(&mut self, entry: &Entry) -> io::Result<Vec<u8>> {
+        let len = within_volume(&self.volumes, entry);
+        read_entry_within(entry, len, &mut self.dsk)
+    }
+
+    /// Reads `entry` and decodes its header and, if compressed, its data.
+    pub fn read_resource(&mut self, entry: &Entry) -> io::Result<Resource> {
+        let data = self.read_entry(entry)?;
+        read_resource(entry, data)
+    }
+
+    /// Looks up and decodes the resource with the given id.
+    pub fn resource(&mut self, id: u16) -> Option<Resource> {
+        let entry = *self.entry(id)?;
+        self.read_resource(&entry).ok()
+    }
+
+    /// Returns an iterator decoding every resource in directory order.
+    pub fn resources(&mut self) -> Resources<'_, R> {
+        Resources {
+            archive: self,
+            index: 0,
+        }
+    }
+}
+
+impl<R: Source> Archive<R> {
+    /// A reader of the entries with a position of its own in the data, so
+    /// entries can be read with shared access to the archive, one reader
+    /// for each thread.
+    pub fn reader(&self) -> io::Result<EntryReader<'_, R::Reader<'_>>> {
+        Ok(EntryReader {
+            directory: &self.directory,
+            volumes: &self.volumes,
+            dsk: self.dsk.reader()?,
+        })
+    }
+}
+
+/// Reads the entries of an archive independently of other readers, see
+/// [`Archive::reader`].
+pub struct EntryReader<'a, D> {
+    directory: &'a [Entry],
+    volumes: &'a [u32],
+    dsk: D,
+}
+
+impl<D: Read + Seek> EntryReader<'_, D> {
+    /// Reads the raw, undecoded bytes of `entry`.
+    pub fn read_entry(&mut self, entry: &Entry) -> io::Result<Vec<u8>> {
+        let len = within_volume(self.volumes, entry);
+        read_entry_within(entry, len, &mut self.dsk)
+    }
+
+    /// Reads `entry` and decodes its header and, if compressed, its data.
+    pub fn read_resource(&mut self, entry: &Entry) -> io::Result<Resource> {
+        let data = self.read_entry(entry)?;
+        read_resource(entry, data)
+    }
+
+    /// Looks up and decodes the resource with the given id.
+    pub fn resource(&mut self, id: u16) -> Option<Resource> {
+        let entry = *self.directory.iter().find(|e| e.number == id)?;
+        self.read_resource(&entry).ok()
+    }
+}
+
+/// The volume of `entry`, given where each volume starts.
+fn volume(volumes: &[u32], entry: &Entry) -> usize {
+    volumes
+        .partition_point(|&start| start <= entry.offset)
+        .saturating_sub(1)
+}
+
+/// How many of the bytes of `entry` lie before the start of the volume
+/// after its own, the last volume running to the end of the data.
+fn within_volume(volumes: &[u32], entry: &Entry) -> u32 {
+    match volumes.get(volume(volumes, entry) + 1) {
+        Some(&end) => entry.size.min(end.saturating_sub(entry.offset)),
+        None => entry.size,
+    }
+}
+
+pub struct Resources<'a, R> {
+    archive: &'a mut Archive<R>,
+    index: usize,
+}
+
+impl<R: Read + Seek> Iterator for Resources<'_, R> {
+    type Item = io::Result<Resource>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = *self.archive.directory.get(self.index)?;
+        self.index += 1;
+
+        Some(self.archive.read_resource(&entry))
+    }
+}
@@ -0,0 +1,151 @@
+//! Texture atlases of sprite frames.
+//!
+//! Frames are trimmed to the box around their visible pixels and packed
+//! into as few atlases as they fit, tallest first along shelves that run
+//! across the atlas. Each frame keeps a pixel of transparent space around
+//! it, so filtering in an engine doesn't bleed neighbours into it. Atlases
+//! are cropped to what they use, up to [`MAX_ATLAS_SIZE`] in either
+//! direction.
+
+use crate::sprite::Frame;
+
+/// The largest width and height of an atlas, which every engine supports.
+pub const MAX_ATLAS_SIZE: usize = 2048;
+
+/// Transparent pixels between frames.
+const PADDING: usize = 1;
+
+/// A frame cut down to its visible pixels.
+pub struct Trimmed {
+    pub frame: Frame,
+    /// Position of the trimmed frame in the untrimmed one.
+    pub x: usize,
+    pub y: usize,
+}
+
+/// Cuts `frame` down to the box around the pixels that aren't
+/// `transparent`. A frame without any comes out empty.
+pub fn trim(frame: &Frame, transparent: u8) -> Trimmed {
+    let visible = |x: usize, y: usize| frame.data[y * frame.width + x] != transparent;
+    let rows: Vec<_> = (0..frame.height)
+        .filter(|&y| (0..frame.width).any(|x| visible(x, y)))
+        .collect();
+    let columns: Vec<_> = (0..frame.width)
+        .filter(|&x| rows.iter().any(|&y| visible(x, y)))
+        .collect();
+
+    let (Some(&top), Some(&bottom), Some(&left), Some(&right)) =
+        (rows.first(), rows.last(), columns.first(), columns.last())
+    else {
+        return Trimmed {
+            frame: Frame {
+                width: 0,
+                height: 0,
+                data: Vec::new(),
+            },
+            x: 0,
+            y: 0,
+        };
+    };
+
+    let width = right - left + 1;
+    let data = (top..=bottom)
+        .flat_map(|y| &frame.data[y * frame.width + left..y * frame.width + right + 1])
+        .copied()
+        .collect();
+
+    Trimmed {
+        frame: Frame {
+            width,
+            height: bottom - top + 1,
+            data,
+        },
+        x: left,
+        y: top,
+    }
+}
+
+/// Where a frame went: the atlas and its top left corner there.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+    pub atlas: usize,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// Packs `frames` into atlases of at most `max_size` by `max_size` pixels,
+/// with everything not covered by a frame `transparent`. Returns the
+/// atlases and the placement of each frame, in the order of `frames`, or
+/// `None` if a frame is too large for an atlas. Empty frames are placed at
+/// the top left of the first atlas.
+pub fn pack(
+    frames: &[Frame],
+    max_size: usize,
+    transparent: u8,
+) -> Option<(Vec<Frame>, Vec<Placement>)> {
+    if frames
+        .iter()
+        .any(|f| f.width + 2 * PADDING > max_size || f.height + 2 * PADDING > max_size)
+    {
+        return None;
+    }
+
+    let mut order: Vec<_> = (0..frames.len())
+        .filter(|&n| frames[n].width > 0 && frames[n].height > 0)
+        .collect();
+    order.sort_by_key(|&n| std::cmp::Reverse((frames[n].height, frames[n].width)));
+
+    let origin = Placement {
+        atlas: 0,
+        x: PADDING,
+        y: PADDING,
+    };
+    let mut placements = vec![origin; frames.len()];
+    // The size used of each atlas so far.
+    let mut sizes = vec![(0, 0)];
+    let (mut shelf_x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+    for n in order {
+        let (width, height) = (
+            frames[n].width + 2 * PADDING,
+            frames[n].height + 2 * PADDING,
+        );
+        if shelf_x + width > max_size {
+            shelf_y += shelf_height;
+            (shelf_x, shelf_height) = (0, 0);
+        }
+        if shelf_y + height > max_size {
+            sizes.push((0, 0));
+            (shelf_x, shelf_y, shelf_height) = (0, 0, 0);
+        }
+
+        let atlas = sizes.len() - 1;
+        placements[n] = Placement {
+            atlas,
+            x: shelf_x + PADDING,
+            y: shelf_y + PADDING,
+        };
+        let size = &mut sizes[atlas];
+        *size = (size.0.max(shelf_x + width), size.1.max(shelf_y + height));
+        shelf_x += width;
+        shelf_height = shelf_height.max(height);
+    }
+
+    let mut atlases: Vec<_> = sizes
+        .into_iter()
+        .map(|(width, height)| Frame {
+            width,
+            height,
+            data: vec![transparent; width * height],
+        })
+        .collect();
+    for (frame, placement) in frames.iter().zip(&placements) {
+        let atlas = &mut atlases[placement.atlas];
+        for y in 0..frame.height {
+            let out_ofs = (placement.y + y) * atlas.width + placement.x;
+            atlas.data[out_ofs..out_ofs + frame.width]
+                .copy_from_slice(&frame.data[y * frame.width..(y + 1) * frame.width]);
+        }
+    }
+
+    Some((atlases, placements))
+}
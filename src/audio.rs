@@ -0,0 +1,327 @@
+use std::{
+    f64::consts::PI,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    bytes_ext::{ReadBytesExt, WriteBytesExt},
+    flac, vorbis,
+};
+
+/// Sample rate of the game's digitized sounds and speech.
+pub const SAMPLE_RATE: u32 = 11025;
+
+/// The rate sounds are brought to when resampled.
+pub const RESAMPLE_RATE: u32 = 44100;
+
+/// Zero crossings of the resampling filter on either side of a sample.
+const RESAMPLE_TAPS: i64 = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+    /// Vorbis in an Ogg container, which is lossy.
+    Ogg,
+    /// FLAC in an Ogg container.
+    OggFlac,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 4] = [
+        AudioFormat::Wav,
+        AudioFormat::Flac,
+        AudioFormat::Ogg,
+        AudioFormat::OggFlac,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::OggFlac => "oga",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AudioFormat> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.name()
+    }
+}
+
+/// Mono PCM audio, optionally with the samples from `loop_start` to the end
+/// repeating while it plays.
+#[derive(Clone, Debug)]
+pub struct Sound {
+    pub sample_rate: u32,
+    /// 8 or 16.
+    pub bits_per_sample: u8,
+    /// Signed samples in the range of `bits_per_sample`.
+    pub samples: Vec<i16>,
+    pub loop_start: Option<usize>,
+}
+
+impl Sound {
+    /// The game's unsigned 8-bit samples.
+    pub fn from_u8(samples: &[u8], sample_rate: u32) -> Sound {
+        Sound {
+            sample_rate,
+            bits_per_sample: 8,
+            samples: samples.iter().map(|&s| s as i16 - 128).collect(),
+            loop_start: None,
+        }
+    }
+
+    pub fn with_loop(self, loop_start: Option<usize>) -> Sound {
+        Sound { loop_start, ..self }
+    }
+
+    /// The sound as the game's unsigned 8-bit samples, rounded to the
+    /// nearest.
+    pub fn to_u8(&self) -> Vec<u8> {
+        let shift = self.bits_per_sample.saturating_sub(8);
+        let half = (1 << shift) >> 1;
+        self.samples
+            .iter()
+            .map(|&s| (((s as i32 + half) >> shift) + 128).clamp(0, 255) as u8)
+            .collect()
+    }
+
+    /// Converts the sound to 16-bit samples at `sample_rate` with a
+    /// windowed sinc filter, which leaves no aliasing images of the
+    /// original rate.
+    pub fn resample(&self, sample_rate: u32) -> Sound {
+        let (from, to) = (self.sample_rate as u64, sample_rate as u64);
+        let scale = 1 << (16 - self.bits_per_sample);
+        if from == 0 || from == to {
+            return Sound {
+                bits_per_sample: 16,
+                samples: self.samples.iter().map(|&s| s * scale).collect(),
+                ..self.clone()
+            };
+        }
+
+        // Cut off at the lower of the two Nyquist frequencies, in cycles
+        // per input sample.
+        let cutoff = from.min(to) as f64 / from as f64;
+        let radius = RESAMPLE_TAPS as f64 / cutoff;
+
+        let len = (self.samples.len() as u64 * to).div_ceil(from) as usize;
+        let samples = (0..len)
+            .map(|n| {
+                let t = n as f64 * from as f64 / to as f64;
+                let first = (t - radius).ceil().max(0.0) as usize;
+                let last =
+                    ((t + radius).floor() as usize).min(self.samples.len().saturating_sub(1));
+
+                let v: f64 = (first..=last)
+                    .map(|i| {
+                        let x = t - i as f64;
+                        self.samples[i] as f64 * cutoff * sinc(cutoff * x) * sinc(x / radius)
+                    })
+                    .sum();
+                (v * scale as f64)
+                    .round()
+                    .clamp(i16::MIN as f64, i16::MAX as f64) as i16
+            })
+            .collect();
+
+        Sound {
+            sample_rate,
+            bits_per_sample: 16,
+            samples,
+            loop_start: self.loop_start.map(|s| (s as u64 * to / from) as usize),
+        }
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W, format: AudioFormat) -> io::Result<()> {
+        match format {
+            AudioFormat::Wav => self.write_wav(w),
+            AudioFormat::Flac => flac::write_flac(w, self),
+            AudioFormat::Ogg => vorbis::write_vorbis(w, self),
+            AudioFormat::OggFlac => flac::write_ogg_flac(w, self),
+        }
+    }
+
+    /// Writes the sound as a PCM WAV file, with a sampler chunk marking the
+    /// loop if there is one.
+    pub fn write_wav<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let bytes_per_sample = self.bits_per_sample as u32 / 8;
+        let data_len = self.samples.len() as u32 * bytes_per_sample;
+        let pad = data_len & 1;
+        let smpl_len = if self.loop_start.is_some() { 8 + 60 } else { 0 };
+
+        let audio_format = 1;
+        let num_channels = 1;
+        let byte_rate = self.sample_rate * num_channels * bytes_per_sample;
+        let block_align = num_channels * bytes_per_sample;
+
+        w.write_all(b"RIFF")?;
+        w.write_le_u32(data_len + pad + 36 + smpl_len)?;
+        w.write_all(b"WAVE")?;
+
+        w.write_all(b"fmt ")?;
+        w.write_le_u32(16)?;
+        w.write_le_u16(audio_format)?;
+        w.write_le_u16(num_channels as u16)?;
+        w.write_le_u32(self.sample_rate)?;
+        w.write_le_u32(byte_rate)?;
+        w.write_le_u16(block_align as u16)?;
+        w.write_le_u16(self.bits_per_sample as u16)?;
+
+        w.write_all(b"data")?;
+        w.write_le_u32(data_len)?;
+        // 8-bit WAV samples are unsigned, wider ones signed.
+        if self.bits_per_sample == 8 {
+            let samples: Vec<u8> = self.samples.iter().map(|&s| (s + 128) as u8).collect();
+            w.write_all(&samples)?;
+        } else {
+            for &s in &self.samples {
+                w.write_le_u16(s as u16)?;
+            }
+        }
+
+        if pad != 0 {
+            w.write_all(&[0])?;
+        }
+
+        if let Some(loop_start) = self.loop_start {
+            w.write_all(b"smpl")?;
+            w.write_le_u32(60)?;
+            w.write_le_u32(0)?; // manufacturer
+            w.write_le_u32(0)?; // product
+            w.write_le_u32(1_000_000_000 / self.sample_rate.max(1))?; // sample period in ns
+            w.write_le_u32(60)?; // unity note
+            w.write_le_u32(0)?; // pitch fraction
+            w.write_le_u32(0)?; // SMPTE format
+            w.write_le_u32(0)?; // SMPTE offset
+            w.write_le_u32(1)?; // loops
+            w.write_le_u32(0)?; // sampler data
+
+            w.write_le_u32(0)?; // cue point id
+            w.write_le_u32(0)?; // forward loop
+            w.write_le_u32(loop_start as u32)?;
+            w.write_le_u32((self.samples.len() as u32).saturating_sub(1))?; // last sample of the loop
+            w.write_le_u32(0)?; // fraction
+            w.write_le_u32(0)?; // play count, 0 for forever
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads an 8 or 16-bit PCM WAV file, mixing multiple channels down to
+/// one. The first loop of a sampler chunk is kept as the loop start; where
+/// the loop ends isn't, as sounds only loop to their end.
+pub fn read_wav<R: Read>(r: &mut R) -> io::Result<Sound> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+
+    let mut id = [0; 4];
+    r.read_exact(&mut id)?;
+    let _riff_len = r.read_le_u32()?;
+    let mut wave = [0; 4];
+    r.read_exact(&mut wave)?;
+    if &id != b"RIFF" || &wave != b"WAVE" {
+        return Err(invalid("not a WAV file"));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut loop_start = None;
+    loop {
+        // Chunks after the data, such as the sampler chunk, are optional.
+        match r.read_exact(&mut id) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && data.is_some() => break,
+            result => result?,
+        }
+        // The length is only trusted as far as there are bytes to read.
+        let len = r.read_le_u32()? as usize;
+        let mut chunk = Vec::new();
+        r.by_ref().take(len as u64).read_to_end(&mut chunk)?;
+        if chunk.len() < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // Some writers leave out the pad byte after the last chunk.
+        if len & 1 != 0 {
+            match r.read_u8() {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+                result => _ = result?,
+            }
+        }
+
+        match &id {
+            b"fmt " => {
+                let mut c = chunk.as_slice();
+                let audio_format = c.read_le_u16()?;
+                let channels = c.read_le_u16()?;
+                let sample_rate = c.read_le_u32()?;
+                let _byte_rate = c.read_le_u32()?;
+                let _block_align = c.read_le_u16()?;
+                let bits = c.read_le_u16()?;
+
+                // 0xfffe is WAVE_FORMAT_EXTENSIBLE, which is also used
+                // for plain PCM.
+                if !matches!(audio_format, 1 | 0xfffe) || !matches!(bits, 8 | 16) {
+                    return Err(invalid("only 8 and 16-bit PCM WAV files are supported"));
+                }
+                if channels == 0 {
+                    return Err(invalid("WAV file has no channels"));
+                }
+                format = Some((channels as usize, sample_rate, bits as u8));
+            }
+            b"data" => data = Some(chunk),
+            b"smpl" => {
+                // The loops follow 36 bytes of sampler details, the start
+                // being the third field of each.
+                let mut c = chunk.get(28..).unwrap_or_default();
+                let loops = c.read_le_u32().unwrap_or(0);
+                if loops > 0 && chunk.len() >= 60 {
+                    let mut c = &chunk[44..];
+                    loop_start = Some(c.read_le_u32()? as usize);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (channels, sample_rate, bits) = format.ok_or_else(|| invalid("WAV file has no format"))?;
+    let data = data.unwrap_or_default();
+    let frames: Vec<i16> = if bits == 8 {
+        data.iter().map(|&s| s as i16 - 128).collect()
+    } else {
+        data.chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]))
+            .collect()
+    };
+    let samples: Vec<i16> = frames
+        .chunks_exact(channels)
+        .map(|frame| {
+            let sum: i32 = frame.iter().map(|&s| s as i32).sum();
+            (sum / channels as i32) as i16
+        })
+        .collect();
+
+    Ok(Sound {
+        sample_rate,
+        bits_per_sample: bits,
+        loop_start: loop_start.filter(|&s| s < samples.len()),
+        samples,
+    })
+}
+
+/// Writes unsigned 8-bit mono `samples` as a PCM WAV file.
+pub fn write_wav<W: Write>(w: &mut W, samples: &[u8], sample_rate: u32) -> io::Result<()> {
+    Sound::from_u8(samples, sample_rate).write_wav(w)
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
@@ -0,0 +1,87 @@
+//! Reading bits least significant first from 16-bit little endian words.
+//!
+//! This is how RNC method 1 packs its Huffman codes, and the other
+//! Revolution formats that store bit streams follow the same layout. Bits
+//! are taken from the low end of each word, and a new word is read from
+//! the stream only once the bits already queued run short. A stream that
+//! ends in the middle of a word reads as if it were padded with zeros.
+
+use std::io::{self, BufRead, Read};
+
+use crate::bytes_ext::ReadBytesExt;
+
+/// A queue of bits read from a byte stream, which is passed to each call
+/// rather than owned, so the bytes between bit fields can be read from it
+/// directly.
+#[derive(Clone, Debug, Default)]
+pub struct BitQueue {
+    bit_queue: u32,
+    bits_in_queue: u16,
+}
+
+impl BitQueue {
+    pub fn new() -> Self {
+        BitQueue {
+            bit_queue: 0,
+            bits_in_queue: 0,
+        }
+    }
+
+    /// Number of bits read from the stream that haven't been taken yet.
+    pub fn len(&self) -> usize {
+        self.bits_in_queue as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits_in_queue == 0
+    }
+
+    #[inline]
+    fn refill<R: Read + ReadBytesExt>(&mut self, r: &mut R) {
+        // We read two u8's instead of one u16 because read_le_u16 will
+        // fail if only one byte is left in the input stream.
+        let b0 = r.read_u8().unwrap_or(0) as u32;
+        let b1 = r.read_u8().unwrap_or(0) as u32;
+
+        let new_bits = (b1 << 8) | b0;
+
+        self.bit_queue |= new_bits << self.bits_in_queue;
+        self.bits_in_queue += 16;
+    }
+
+    /// Returns the next 16 bits without taking them, the bits still queued
+    /// followed by those of the word waiting in `r`.
+    #[inline]
+    pub fn peek<R: BufRead + ReadBytesExt>(&mut self, r: &mut R) -> io::Result<u16> {
+        let peek = r.fill_buf()?;
+        let p0 = *peek.first().unwrap_or(&0) as u32;
+        let p1 = *peek.get(1).unwrap_or(&0) as u32;
+        let p = (p1 << 8) | p0;
+        let t = ((p << self.bits_in_queue) | self.bit_queue) as u16;
+
+        Ok(t)
+    }
+
+    /// Takes the next `n` bits, the first of them in the lowest bit of the
+    /// result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is more than 16.
+    pub fn read_bits<R: Read + ReadBytesExt>(&mut self, r: &mut R, n: u8) -> io::Result<u16> {
+        assert!(n <= 16);
+        let n = n as u16;
+
+        if n > self.bits_in_queue {
+            self.refill(r);
+        }
+
+        let mask = ((1u32 << n) - 1) as u16;
+        let v = (self.bit_queue as u16) & mask;
+
+        self.bit_queue >>= n;
+        self.bits_in_queue -= n;
+
+        Ok(v)
+    }
+}
@@ -0,0 +1,369 @@
+//! The `browse` subcommand, for looking through the resources of an
+//! archive in the terminal without dumping them first.
+//!
+//! The list on the left has every entry with the type it was classified
+//! as. The pane on the right describes the selected entry and previews it:
+//! screens, palettes, sprites and walk grids as pictures drawn with half
+//! block characters in 24-bit color, or with characters of increasing
+//! density in ASCII mode, and anything else as a hexdump.
+
+use std::io::{self, Cursor};
+
+use beneath_a_steel_sky_extract::{
+    classify::classify,
+    grid::Grid,
+    image::RgbImage,
+    palette::PaletteMap,
+    sprite::{self, Frame},
+    Archive, Entry, Resource, ResourceType,
+};
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph, Widget},
+    DefaultTerminal, Frame as TerminalFrame,
+};
+use rayon::prelude::*;
+
+use crate::{hexdump, screen_palette};
+
+/// Characters of increasing density the ASCII preview shades with.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Number of bytes of data shown for resources without a picture.
+const HEXDUMP_BYTES: usize = 512;
+
+/// Entries moved by Page Up and Page Down.
+const PAGE: usize = 20;
+
+struct Item {
+    entry: Entry,
+    kind: Option<ResourceType>,
+}
+
+/// What the preview pane shows for a resource.
+enum Preview {
+    Image(RgbImage),
+    Frames(Vec<RgbImage>),
+    Text(String),
+}
+
+struct App {
+    archive: Archive<Cursor<Vec<u8>>>,
+    palettes: PaletteMap,
+    items: Vec<Item>,
+    list: ListState,
+    /// The resource shown in the detail pane and its preview, for the
+    /// selected entry.
+    selected: Option<(Resource, Preview)>,
+    frame: usize,
+    ascii: bool,
+}
+
+/// Runs the browser until it is quit.
+pub fn run(archive: Archive<Cursor<Vec<u8>>>) -> io::Result<()> {
+    let mut app = App::new(archive);
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn new(archive: Archive<Cursor<Vec<u8>>>) -> Self {
+        let version = archive.version();
+        let items = archive
+            .entries()
+            .par_iter()
+            .map(|entry| Item {
+                entry: *entry,
+                kind: archive
+                    .get_resource(entry.number)
+                    .and_then(|r| classify(&r, version)),
+            })
+            .collect();
+
+        let mut app = App {
+            palettes: PaletteMap::builtin(version),
+            archive,
+            items,
+            list: ListState::default(),
+            selected: None,
+            frame: 0,
+            ascii: false,
+        };
+        app.select(0);
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|f| self.draw(f))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            let current = self.list.selected().unwrap_or(0);
+            let last = self.items.len().saturating_sub(1);
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.select(current + 1),
+                KeyCode::Up | KeyCode::Char('k') => self.select(current.saturating_sub(1)),
+                KeyCode::PageDown => self.select(current + PAGE),
+                KeyCode::PageUp => self.select(current.saturating_sub(PAGE)),
+                KeyCode::Home => self.select(0),
+                KeyCode::End => self.select(last),
+                KeyCode::Right | KeyCode::Char('l') => self.frame += 1,
+                KeyCode::Left | KeyCode::Char('h') => self.frame = self.frame.saturating_sub(1),
+                KeyCode::Char('a') => self.ascii = !self.ascii,
+                _ => {}
+            }
+            if let Some((_, Preview::Frames(ref frames))) = self.selected {
+                self.frame = self.frame.min(frames.len() - 1);
+            }
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        let Some(last) = self.items.len().checked_sub(1) else {
+            return;
+        };
+        let index = index.min(last);
+        self.list.select(Some(index));
+        self.frame = 0;
+
+        let item = &self.items[index];
+        self.selected = self
+            .archive
+            .get_resource(item.entry.number)
+            .map(|resource| {
+                let preview = self.preview(&resource, item.kind);
+                (resource, preview)
+            });
+    }
+
+    fn preview(&self, resource: &Resource, kind: Option<ResourceType>) -> Preview {
+        let id = resource.entry.number;
+        let image = |frame: &Frame, palette: Option<&Resource>| {
+            let palette = palette.map(|r| r.data.as_slice());
+            RgbImage::from_indexed(frame.width, frame.height, &frame.data, palette)
+        };
+
+        match kind {
+            Some(ResourceType::Screen) => {
+                let palette = screen_palette(&self.archive, &self.palettes, id);
+                let frame = Frame {
+                    width: 320,
+                    height: resource.data.len() / 320,
+                    data: resource.data.clone(),
+                };
+                Preview::Image(image(&frame, palette.as_ref()))
+            }
+            Some(ResourceType::Palette) => {
+                let frame = Frame {
+                    width: 16,
+                    height: 16,
+                    data: (0..=255).collect(),
+                };
+                Preview::Image(image(&frame, Some(resource)))
+            }
+            Some(ResourceType::Sprite | ResourceType::Animation) => {
+                let palette = self
+                    .archive
+                    .nearest_palette(id)
+                    .and_then(|e| self.archive.get_resource(e.number));
+                match sprite::decode_frames(resource) {
+                    Some(frames) if !frames.is_empty() => {
+                        Preview::Frames(frames.iter().map(|f| image(f, palette.as_ref())).collect())
+                    }
+                    _ => Preview::Text(hexdump(
+                        &resource.data[..resource.data.len().min(HEXDUMP_BYTES)],
+                    )),
+                }
+            }
+            Some(ResourceType::Grid) => match Grid::decode(&resource.data) {
+                Some(grid) => Preview::Image(grid.render()),
+                None => Preview::Text(String::new()),
+            },
+            _ => Preview::Text(hexdump(
+                &resource.data[..resource.data.len().min(HEXDUMP_BYTES)],
+            )),
+        }
+    }
+
+    fn draw(&mut self, f: &mut TerminalFrame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(f.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Length(26), Constraint::Min(0)]).areas(main);
+
+        let items: Vec<_> = self
+            .items
+            .iter()
+            .map(|item| {
+                ListItem::new(format!(
+                    "{:05} {}",
+                    item.entry.number,
+                    item.kind.map_or("-", |k| k.name())
+                ))
+            })
+            .collect();
+        let title = format!(" {} resources ", self.items.len());
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().reversed());
+        f.render_stateful_widget(list, list_area, &mut self.list);
+
+        let block = Block::bordered().title(" Resource ");
+        let inner = block.inner(detail_area);
+        f.render_widget(block, detail_area);
+
+        if let Some((ref resource, ref preview)) = self.selected {
+            let details = details(resource, preview, self.frame);
+            let [details_area, preview_area] = Layout::vertical([
+                Constraint::Length(details.len() as u16 + 1),
+                Constraint::Min(0),
+            ])
+            .areas(inner);
+            f.render_widget(Paragraph::new(details), details_area);
+
+            match preview {
+                Preview::Image(image) => {
+                    f.render_widget(Picture::new(image, self.ascii), preview_area)
+                }
+                Preview::Frames(frames) => {
+                    f.render_widget(Picture::new(&frames[self.frame], self.ascii), preview_area)
+                }
+                Preview::Text(text) => f.render_widget(Paragraph::new(text.as_str()), preview_area),
+            }
+        }
+
+        f.render_widget(
+            Line::from(" ↑↓ select  PgUp/PgDn page  ←→ frame  a ASCII  q quit").dim(),
+            help,
+        );
+    }
+}
+
+/// The lines describing `resource`: its entry, its header if it has one,
+/// and what is previewed.
+fn details(resource: &Resource, preview: &Preview, frame: usize) -> Vec<Line<'static>> {
+    let entry = &resource.entry;
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+    let mut lines = vec![
+        Line::from(format!(
+            "{:05}  offset 0x{:08x}  stored size {}  decoded size {}",
+            entry.number,
+            entry.offset,
+            entry.size,
+            resource.data.len()
+        )),
+        Line::from(format!(
+            "file header {}  uses file header {}  compressed {}",
+            yes_no(entry.has_file_header),
+            yes_no(entry.uses_file_header),
+            yes_no(resource.is_compressed())
+        )),
+    ];
+
+    if let Some(header) = &resource.header {
+        lines.push(Line::from(format!(
+            "flags 0x{:04x}  x {}  y {}  width {}  height {}",
+            header.flags, header.x, header.y, header.width, header.height
+        )));
+        lines.push(Line::from(format!(
+            "sp_size {}  tot_size {}  n_sprites {}  offset {},{}  compressed_size {}",
+            header.sp_size,
+            header.tot_size,
+            header.n_sprites,
+            header.offset_x,
+            header.offset_y,
+            header.compressed_size
+        )));
+    }
+
+    if let Preview::Frames(frames) = preview {
+        lines.push(Line::from(format!(
+            "frame {} of {}",
+            frame + 1,
+            frames.len()
+        )));
+    }
+
+    lines
+}
+
+/// An image scaled to fit the area it's drawn in, keeping its aspect.
+/// Each character cell shows two pixels, one above the other.
+struct Picture<'a> {
+    image: &'a RgbImage,
+    ascii: bool,
+}
+
+impl<'a> Picture<'a> {
+    fn new(image: &'a RgbImage, ascii: bool) -> Self {
+        Picture { image, ascii }
+    }
+
+    /// The color of the image at (`x`, `y`) of a `width` by `height`
+    /// scaled copy.
+    fn sample(&self, x: usize, y: usize, width: usize, height: usize) -> [u8; 3] {
+        let ix = x * self.image.width / width;
+        let iy = y * self.image.height / height;
+        let ofs = 3 * (iy * self.image.width + ix);
+        [
+            self.image.data[ofs],
+            self.image.data[ofs + 1],
+            self.image.data[ofs + 2],
+        ]
+    }
+}
+
+impl Widget for Picture<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (image_w, image_h) = (self.image.width, self.image.height);
+        if image_w == 0 || image_h == 0 || area.is_empty() {
+            return;
+        }
+
+        let (area_w, area_h) = (area.width as usize, 2 * area.height as usize);
+        let (width, height) = if area_w * image_h < area_h * image_w {
+            (area_w, (area_w * image_h / image_w).max(1))
+        } else {
+            ((area_h * image_w / image_h).max(1), area_h)
+        };
+
+        for cy in 0..height.div_ceil(2) {
+            for x in 0..width {
+                let top = self.sample(x, 2 * cy, width, height);
+                let bottom = if 2 * cy + 1 < height {
+                    self.sample(x, 2 * cy + 1, width, height)
+                } else {
+                    [0; 3]
+                };
+
+                let cell = &mut buf[(area.x + x as u16, area.y + cy as u16)];
+                if self.ascii {
+                    let luma =
+                        |[r, g, b]: [u8; 3]| 299 * r as usize + 587 * g as usize + 114 * b as usize;
+                    let level = (luma(top) + luma(bottom)) / 2 * ASCII_RAMP.len() / (1000 * 256);
+                    cell.set_char(ASCII_RAMP[level] as char);
+                } else {
+                    let [r, g, b] = top;
+                    let [br, bg, bb] = bottom;
+                    cell.set_char('▀')
+                        .set_fg(Color::Rgb(r, g, b))
+                        .set_bg(Color::Rgb(br, bg, bb));
+                }
+            }
+        }
+    }
+}
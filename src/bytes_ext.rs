@@ -1,3 +1,9 @@
+//! Reading and writing the fixed size integers the data files are made of.
+//!
+//! The traits are implemented for every reader and writer, so bringing
+//! them into scope is enough to use them.
+
+/// Writes integers in little or big endian byte order.
 pub trait WriteBytesExt: std::io::Write {
     #[inline]
     fn write_le_u16(&mut self, v: u16) -> std::io::Result<()> {
@@ -13,6 +19,13 @@ pub trait WriteBytesExt: std::io::Write {
         Ok(())
     }
 
+    #[inline]
+    fn write_be_u16(&mut self, v: u16) -> std::io::Result<()> {
+        let buf = v.to_be_bytes();
+        self.write_all(&buf)?;
+        Ok(())
+    }
+
     #[inline]
     fn write_be_u32(&mut self, v: u32) -> std::io::Result<()> {
         let buf = v.to_be_bytes();
@@ -23,6 +36,9 @@ pub trait WriteBytesExt: std::io::Write {
 
 impl<W: std::io::Write> WriteBytesExt for W {}
 
+/// Reads integers in little or big endian byte order. Each fails with
+/// [`std::io::ErrorKind::UnexpectedEof`] if the reader ends before all of
+/// the integer's bytes are read.
 pub trait ReadBytesExt: std::io::Read {
     #[inline]
     fn read_u8(&mut self) -> std::io::Result<u8> {
@@ -52,6 +68,8 @@ pub trait ReadBytesExt: std::io::Read {
         Ok(u16::from_be_bytes(buf))
     }
 
+    /// Reads a three byte little endian integer, as the offsets and sizes
+    /// of the dinner table are stored.
     #[inline]
     fn read_le_u24(&mut self) -> std::io::Result<u32> {
         let mut buf = [0; 4];
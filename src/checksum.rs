@@ -0,0 +1,234 @@
+//! Checksums of the resources of a release, for telling whether a copy of
+//! the game is complete and undamaged.
+//!
+//! A checksum database is a CSV file with the id, size and SHA-1 hash of
+//! every resource as stored in `sky.dsk`, and can be written from any copy
+//! known to be good with [`write_checksums`]. A [`Fingerprint`] holds the
+//! same along with what the release is told apart by, as JSON. No
+//! checksums are built into the program, as none have been taken from a
+//! copy of a release known to be good yet.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{Cursor, Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive::Entry,
+    error::{Error, Result},
+    resource::Header,
+    rnc_decompress::{decompress_rnc1, DecompressError},
+    version::Version,
+};
+
+/// Revision of the fingerprint format, raised when fields change meaning.
+pub const FINGERPRINT_FORMAT: u32 = 1;
+
+/// The size and SHA-1 hash of a resource as stored in `sky.dsk`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub id: u16,
+    pub size: u32,
+    pub sha1: String,
+}
+
+impl Checksum {
+    pub fn new(id: u16, data: &[u8]) -> Checksum {
+        Checksum {
+            id,
+            size: data.len() as u32,
+            sha1: sha1_smol::Sha1::from(data).digest().to_string(),
+        }
+    }
+}
+
+/// The CRC-32 of `data` as used by zip and PNG, for comparing resources
+/// with tools that don't do SHA-1.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// A copy of the game as it would be added to the tables of known
+/// releases: the size of its dinner table, which [`crate::version`] tells
+/// releases apart by, and the checksums of its resources.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// The [`FINGERPRINT_FORMAT`] it was written in.
+    pub format: u32,
+    /// The version number of the release, if it is a known one.
+    pub version: Option<u16>,
+    /// Number of entries in the dinner table.
+    pub entries: usize,
+    pub checksums: Vec<Checksum>,
+}
+
+impl Fingerprint {
+    pub fn new(version: Option<&Version>, checksums: Vec<Checksum>) -> Fingerprint {
+        Fingerprint {
+            format: FINGERPRINT_FORMAT,
+            version: version.map(|v| v.number),
+            entries: checksums.len(),
+            checksums,
+        }
+    }
+
+    /// Reads a fingerprint, failing on formats newer than this program
+    /// knows.
+    pub fn read<R: Read>(r: R) -> Result<Fingerprint> {
+        let fingerprint: Fingerprint = serde_json::from_reader(r)?;
+        if fingerprint.format > FINGERPRINT_FORMAT {
+            return Err(Error::InvalidData(format!(
+                "fingerprint format {} is newer than {FINGERPRINT_FORMAT}",
+                fingerprint.format
+            )));
+        }
+
+        Ok(fingerprint)
+    }
+
+    pub fn write<W: Write>(&self, w: W) -> Result<()> {
+        serde_json::to_writer_pretty(w, self)?;
+
+        Ok(())
+    }
+}
+
+pub fn read_checksums<R: Read>(r: R) -> Result<Vec<Checksum>> {
+    let mut rdr = csv::Reader::from_reader(r);
+    let checksums = rdr.deserialize().collect::<std::result::Result<_, _>>()?;
+
+    Ok(checksums)
+}
+
+pub fn write_checksums<W: Write>(w: W, checksums: &[Checksum]) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(w);
+    for checksum in checksums {
+        wtr.serialize(checksum)?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+}
+
+/// Something wrong with a copy of the game.
+#[derive(Debug)]
+pub enum Problem {
+    /// A resource of the release that isn't in the archive.
+    Missing(u16),
+    /// A resource in the archive that isn't part of the release.
+    Extra(u16),
+    /// A resource that isn't stored as it is in the release.
+    Mismatch {
+        expected: Checksum,
+        actual: Checksum,
+    },
+    /// A resource that ends before the size the directory gives it.
+    Truncated { id: u16, size: u32, expected: u32 },
+    /// A compressed resource that doesn't decompress.
+    Damaged { id: u16, error: DecompressError },
+}
+
+impl Problem {
+    pub fn id(&self) -> u16 {
+        match *self {
+            Problem::Missing(id) | Problem::Extra(id) => id,
+            Problem::Mismatch { ref actual, .. } => actual.id,
+            Problem::Truncated { id, .. } | Problem::Damaged { id, .. } => id,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::Missing(id) => write!(f, "{:05}: missing", id),
+            Problem::Extra(id) => write!(f, "{:05}: not part of the release", id),
+            Problem::Mismatch {
+                ref expected,
+                ref actual,
+            } if expected.size != actual.size => write!(
+                f,
+                "{:05}: corrupt, {} bytes instead of {}",
+                actual.id, actual.size, expected.size
+            ),
+            Problem::Mismatch {
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "{:05}: corrupt, SHA-1 {} instead of {}",
+                actual.id, actual.sha1, expected.sha1
+            ),
+            Problem::Truncated { id, size, expected } => {
+                write!(f, "{:05}: truncated to {} of {} bytes", id, size, expected)
+            }
+            Problem::Damaged { id, ref error } => write!(f, "{:05}: damaged, {}", id, error),
+        }
+    }
+}
+
+/// Checks what can be checked of a resource without knowing the release:
+/// that all of it is there and that it decompresses if it's compressed.
+pub fn check_resource(entry: &Entry, data: &[u8]) -> Option<Problem> {
+    if data.len() < entry.size as usize {
+        return Some(Problem::Truncated {
+            id: entry.number,
+            size: data.len() as u32,
+            expected: entry.size,
+        });
+    }
+
+    if !entry.has_file_header || data.len() < Header::SIZE {
+        return None;
+    }
+    let flags = u16::from_le_bytes([data[0], data[1]]);
+    if flags & 0x80 == 0 {
+        return None;
+    }
+
+    decompress_rnc1(&mut Cursor::new(&data[Header::SIZE..]))
+        .err()
+        .map(|error| Problem::Damaged {
+            id: entry.number,
+            error,
+        })
+}
+
+/// Compares the checksums of an archive with those expected, in order of
+/// resource id.
+pub fn compare(expected: &[Checksum], actual: &[Checksum]) -> Vec<Problem> {
+    let expected: BTreeMap<_, _> = expected.iter().map(|c| (c.id, c)).collect();
+    let actual: BTreeMap<_, _> = actual.iter().map(|c| (c.id, c)).collect();
+
+    let mut problems = Vec::new();
+    for (&id, &checksum) in &expected {
+        match actual.get(&id) {
+            None => problems.push(Problem::Missing(id)),
+            Some(&found) if found != checksum => problems.push(Problem::Mismatch {
+                expected: checksum.clone(),
+                actual: found.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    problems.extend(
+        actual
+            .keys()
+            .filter(|id| !expected.contains_key(id))
+            .map(|&id| Problem::Extra(id)),
+    );
+    problems.sort_by_key(Problem::id);
+
+    problems
+}
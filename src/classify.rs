@@ -0,0 +1,153 @@
+//! Classification of resources by id, header and content.
+//!
+//! Many kinds of resource live at fixed ids and are recognised by those
+//! alone. The rest are told apart by their size and header: screens and
+//! palettes have fixed sizes, sequences only decode as a whole and sprites
+//! describe their frames in the header. Digitized sounds are found by
+//! [`audio_evidence`], which also says what gave them away.
+
+use crate::{
+    font,
+    resource::{Resource, ResourceType},
+    sequence, speech, sprite, text,
+    version::Version,
+};
+
+/// Walk grids, one per screen.
+pub const GRID_FILE_START: u16 = 60000;
+pub const TOT_NO_GRIDS: u16 = 70;
+/// Size of a walk grid: one bit for each 8 by 8 pixel block of a screen.
+pub const GRID_SIZE: usize = 120;
+
+/// Script modules.
+pub const SCRIPT_FILE_BASE: u16 = 60400;
+pub const MAX_SCRIPT_FILES: u16 = 100;
+
+/// Music and sound effects, four resources for each section of the game:
+/// the music for the different sound cards followed by the sound effect
+/// bank.
+pub const MUSIC_FILE_BASE: u16 = 60200;
+pub const FILES_PER_SECTION: u16 = 4;
+pub const NO_OF_SECTIONS: u16 = 8;
+
+/// Bit of the header's `x` the game sets on digitized sounds.
+const AUDIO_FLAG: u16 = 0x8000;
+
+/// Unflagged sounds shorter than this aren't looked for, as there's too
+/// little data to tell samples from anything else.
+const MIN_AUDIO_SAMPLES: usize = 1024;
+
+pub const PALETTE_SIZE: usize = 768;
+pub const SCREEN_SIZE: usize = 320 * 200;
+
+/// Determines the type of `resource`. Speech is only recognised if the
+/// release is unknown or has speech.
+pub fn classify(resource: &Resource, version: Option<&Version>) -> Option<ResourceType> {
+    let id = resource.entry.number;
+    let data = &resource.data;
+
+    if font::CHAR_SET_FILES.contains(&id) {
+        return Some(ResourceType::Font);
+    }
+    if text::is_text_file(id) {
+        return Some(ResourceType::Text);
+    }
+    if (GRID_FILE_START..GRID_FILE_START + TOT_NO_GRIDS).contains(&id) && data.len() == GRID_SIZE {
+        return Some(ResourceType::Grid);
+    }
+    if (SCRIPT_FILE_BASE..SCRIPT_FILE_BASE + MAX_SCRIPT_FILES).contains(&id) {
+        return Some(ResourceType::Script);
+    }
+    if (MUSIC_FILE_BASE..MUSIC_FILE_BASE + FILES_PER_SECTION * NO_OF_SECTIONS).contains(&id) {
+        return Some(ResourceType::Music);
+    }
+
+    if data.len() == PALETTE_SIZE && resource.header.is_none() && data.iter().all(|&c| c < 64) {
+        return Some(ResourceType::Palette);
+    }
+    if data.len() == SCREEN_SIZE {
+        return Some(ResourceType::Screen);
+    }
+    if sequence::is_sequence(data) {
+        return Some(ResourceType::Sequence);
+    }
+
+    let header = resource.header.as_ref()?;
+    if let Some(evidence) = audio_evidence(resource, version) {
+        Some(evidence.resource_type())
+    } else if header.n_sprites > 0 && header.width > 0 && header.height > 0 {
+        if header.n_sprites > 1 {
+            Some(ResourceType::Animation)
+        } else {
+            Some(ResourceType::Sprite)
+        }
+    } else {
+        None
+    }
+}
+
+/// What identified a resource as digitized sound.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioEvidence {
+    /// Its id is among those of the speech files of the release.
+    SpeechId,
+    /// Its header has the flag the game marks sounds with, and doesn't
+    /// describe sprite frames that fit the data.
+    HeaderFlag,
+    /// Its header describes no sprites, and the data looks like unsigned
+    /// 8-bit samples: mostly near the middle of the range and changing
+    /// smoothly from one to the next.
+    Samples,
+}
+
+impl AudioEvidence {
+    pub fn resource_type(&self) -> ResourceType {
+        match self {
+            AudioEvidence::SpeechId => ResourceType::Speech,
+            AudioEvidence::HeaderFlag | AudioEvidence::Samples => ResourceType::Audio,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            AudioEvidence::SpeechId => "speech file id",
+            AudioEvidence::HeaderFlag => "header flag",
+            AudioEvidence::Samples => "sample data",
+        }
+    }
+}
+
+/// Determines whether `resource`, which must have a header, holds digitized
+/// sound, and how that was found out. Resources at fixed ids of other types
+/// are left to [`classify`].
+pub fn audio_evidence(resource: &Resource, version: Option<&Version>) -> Option<AudioEvidence> {
+    let header = resource.header.as_ref()?;
+    if speech::is_speech_file(resource.entry.number, version) {
+        return Some(AudioEvidence::SpeechId);
+    }
+
+    let sprites = sprite::decode_frames(resource)
+        .is_some_and(|frames| frames.len() == header.n_sprites as usize);
+    if header.x & AUDIO_FLAG != 0 && !sprites {
+        return Some(AudioEvidence::HeaderFlag);
+    }
+    if header.n_sprites == 0 && looks_like_samples(&resource.data) {
+        return Some(AudioEvidence::Samples);
+    }
+
+    None
+}
+
+/// Whether `data` looks like unsigned 8-bit sound: three quarters of it
+/// within a quarter of the range from silence, and neighbouring samples an
+/// average of less than a tenth of the range apart.
+fn looks_like_samples(data: &[u8]) -> bool {
+    if data.len() < MIN_AUDIO_SAMPLES {
+        return false;
+    }
+
+    let quiet = data.iter().filter(|&&s| s.abs_diff(0x80) < 0x40).count();
+    let steps: usize = data.windows(2).map(|w| w[0].abs_diff(w[1]) as usize).sum();
+
+    quiet * 4 >= data.len() * 3 && steps * 10 < (data.len() - 1) * 0x100
+}
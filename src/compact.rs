@@ -0,0 +1,600 @@
+//! Compacts, the structures describing the objects of the game.
+//!
+//! Compacts aren't stored in `sky.dsk` but in the game executable. The file
+//! read here is `sky.cpt` as shipped with ScummVM, which holds them in a
+//! version independent form: a number of lists, each a sequence of
+//! entries, every entry a run of little endian words with a type and a
+//! name. An id holds the list in its top four bits and the index into the
+//! list in the rest.
+//!
+//! Entries of type [`CptType::Compact`] are objects: their words are the
+//! fields listed in [`COMPACT_FIELDS`], followed for characters by four
+//! sets of [`MEGA_SET_FIELDS`]. Shorter compacts only have the leading
+//! fields.
+//!
+//! Entries of type [`CptType::AnimSequence`] are the animations objects
+//! play, decoded by [`AnimSequence::decode`], and entries of type
+//! [`CptType::TurnTable`] pick the frames characters turn around with, read
+//! by [`TurnTable::decode`].
+
+use std::collections::BTreeMap;
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::{
+    classify::SCRIPT_FILE_BASE,
+    error::{Error, Result},
+};
+
+pub const COMPACT_FIELDS: [&str; 55] = [
+    "logic",
+    "status",
+    "sync",
+    "screen",
+    "place",
+    "get_to_table_id",
+    "xcood",
+    "ycood",
+    "frame",
+    "cursor_text",
+    "mouse_on",
+    "mouse_off",
+    "mouse_click",
+    "mouse_rel_x",
+    "mouse_rel_y",
+    "mouse_size_x",
+    "mouse_size_y",
+    "action_script",
+    "up_flag",
+    "down_flag",
+    "get_to_flag",
+    "flag",
+    "mood",
+    "grafix_prog_id",
+    "grafix_prog_pos",
+    "offset",
+    "mode",
+    "base_sub",
+    "base_sub_off",
+    "action_sub",
+    "action_sub_off",
+    "get_to_sub",
+    "get_to_sub_off",
+    "extra_sub",
+    "extra_sub_off",
+    "dir",
+    "stop_script",
+    "mini_bump",
+    "leaving",
+    "at_watch",
+    "at_was",
+    "alt",
+    "request",
+    "sp_width_xx",
+    "sp_color",
+    "sp_text_id",
+    "sp_time",
+    "ar_anim_index",
+    "turn_prog_id",
+    "turn_prog_pos",
+    "waiting_for",
+    "ar_target_x",
+    "ar_target_y",
+    "anim_scratch_id",
+    "mega_set",
+];
+
+pub const MEGA_SET_FIELDS: [&str; 14] = [
+    "grid_width",
+    "col_offset",
+    "col_width",
+    "last_chr",
+    "anim_up_id",
+    "anim_down_id",
+    "anim_left_id",
+    "anim_right_id",
+    "stand_up_id",
+    "stand_down_id",
+    "stand_left_id",
+    "stand_right_id",
+    "stand_talk_id",
+    "turn_table_id",
+];
+
+const MEGA_SETS: usize = 4;
+
+/// The directions characters face, in the order of the engine's `dir`
+/// field and of the rows and columns of a turn table.
+pub const DIRECTIONS: [&str; 5] = ["up", "down", "left", "right", "talk"];
+
+/// First word of an animation step that plays a sound effect.
+const LF_START_FX: u16 = 0xfffe;
+/// First word of an animation step that sets the `sync` field of another
+/// compact.
+const SEND_SYNC: u16 = 0xffff;
+
+/// Bits of the `status` field putting an object on one of the lists the
+/// engine draws sprites from.
+const ST_BACKGROUND: u16 = 0x01;
+const ST_FOREGROUND: u16 = 0x02;
+const ST_SORT: u16 = 0x04;
+
+/// Fields holding script numbers: the module in the top four bits and the
+/// script within the module in the rest.
+const SCRIPT_FIELDS: [&str; 7] = [
+    "mouse_on",
+    "mouse_off",
+    "mouse_click",
+    "base_sub",
+    "action_sub",
+    "get_to_sub",
+    "extra_sub",
+];
+
+/// Fields holding text ids.
+const TEXT_FIELDS: [&str; 2] = ["cursor_text", "sp_text_id"];
+
+/// Fields holding the ids of other entries.
+const COMPACT_ID_FIELDS: [&str; 4] = [
+    "get_to_table_id",
+    "grafix_prog_id",
+    "turn_prog_id",
+    "anim_scratch_id",
+];
+
+/// Fields of mega sets holding the ids of other entries.
+const MEGA_SET_ID_FIELDS: [&str; 10] = [
+    "anim_up_id",
+    "anim_down_id",
+    "anim_left_id",
+    "anim_right_id",
+    "stand_up_id",
+    "stand_down_id",
+    "stand_left_id",
+    "stand_right_id",
+    "stand_talk_id",
+    "turn_table_id",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CptType {
+    Null,
+    Compact,
+    TurnTable,
+    AnimSequence,
+    MiscBinary,
+    GetToTable,
+    RouteBuffer,
+    MainList,
+    Unknown(u16),
+}
+
+impl From<u16> for CptType {
+    fn from(v: u16) -> Self {
+        match v {
+            0 => CptType::Null,
+            1 => CptType::Compact,
+            2 => CptType::TurnTable,
+            3 => CptType::AnimSequence,
+            4 => CptType::MiscBinary,
+            5 => CptType::GetToTable,
+            6 => CptType::RouteBuffer,
+            7 => CptType::MainList,
+            v => CptType::Unknown(v),
+        }
+    }
+}
+
+/// The lists the engine draws the sprites of objects from, in drawing
+/// order: background objects right over the backdrop, then sorted objects
+/// from the top of the screen down, then foreground objects over
+/// everything.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawList {
+    Background,
+    Sort,
+    Foreground,
+}
+
+/// An entry of `sky.cpt`.
+#[derive(Clone, Debug)]
+pub struct CptEntry {
+    pub id: u16,
+    pub name: String,
+    pub kind: CptType,
+    pub data: Vec<u16>,
+}
+
+impl CptEntry {
+    /// The entry as a game object, if it is one.
+    pub fn compact(&self) -> Option<Compact<'_>> {
+        (self.kind == CptType::Compact).then_some(Compact { data: &self.data })
+    }
+}
+
+/// All entries of `sky.cpt`, by id.
+pub struct CptFile {
+    pub entries: BTreeMap<u16, CptEntry>,
+    /// The entries a saved game holds the state of, in the order it holds
+    /// them.
+    pub save_ids: Vec<u16>,
+}
+
+impl CptFile {
+    pub fn read(data: &[u8]) -> Result<CptFile> {
+        let mut r = WordReader { data, pos: 0 };
+
+        if r.u16()? != 0 {
+            return Err(Error::InvalidData("unknown sky.cpt version".to_owned()));
+        }
+
+        let list_count = r.u16()? as usize;
+        let list_lens = (0..list_count)
+            .map(|_| r.u16())
+            .collect::<Result<Vec<_>>>()?;
+
+        let _raw_size = r.u32()?;
+        let src_len = r.u32()? as usize;
+        let src = r.bytes(2 * src_len)?;
+        let ascii_len = r.u32()? as usize;
+        let ascii = r.bytes(ascii_len)?;
+
+        let mut src = WordReader { data: src, pos: 0 };
+        let mut names = ascii.split(|&c| c == 0);
+
+        let mut entries = BTreeMap::new();
+        for (list, &len) in list_lens.iter().enumerate() {
+            for index in 0..len {
+                // Empty slots have a type and a name as well.
+                let size = src.u16()? as usize;
+                let kind = CptType::from(src.u16()?);
+                let name = names.next().unwrap_or_default();
+                if size == 0 {
+                    continue;
+                }
+
+                let data = (0..size).map(|_| src.u16()).collect::<Result<_>>()?;
+
+                let id = ((list as u16) << 12) | index;
+                entries.insert(
+                    id,
+                    CptEntry {
+                        id,
+                        name: String::from_utf8_lossy(name).into_owned(),
+                        kind,
+                        data,
+                    },
+                );
+            }
+        }
+
+        // Entries that only stand for others, then changes made for
+        // v0.0288, then the ids of the entries saved games hold.
+        let dlinc_count = r.u16()? as usize;
+        r.bytes(4 * dlinc_count)?;
+        let _diff_count = r.u16()?;
+        let diff_len = r.u16()? as usize;
+        r.bytes(2 * diff_len)?;
+        let save_id_count = r.u16()? as usize;
+        let save_ids = (0..save_id_count).map(|_| r.u16()).collect::<Result<_>>()?;
+
+        Ok(CptFile { entries, save_ids })
+    }
+
+    pub fn name(&self, id: u16) -> Option<&str> {
+        self.entries.get(&id).map(|e| e.name.as_str())
+    }
+}
+
+struct WordReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WordReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| Error::InvalidData("sky.cpt is truncated".to_owned()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// A game object, viewed through its named fields.
+#[derive(Copy, Clone)]
+pub struct Compact<'a> {
+    data: &'a [u16],
+}
+
+/// A script referred to by a field of a compact.
+#[derive(Debug, Serialize)]
+pub struct ScriptRef {
+    pub field: &'static str,
+    pub script: u16,
+    /// The resource holding the script's module.
+    pub module: u16,
+}
+
+/// Another entry or a line of text referred to by a field of a compact.
+#[derive(Debug, Serialize)]
+pub struct IdRef {
+    pub field: String,
+    pub id: u16,
+}
+
+/// What a compact refers to.
+#[derive(Debug, Default, Serialize)]
+pub struct References {
+    /// The item number of the sprite the object is drawn with, the engine's
+    /// number for a sprite resource, and the frame within it.
+    pub sprite: Option<(u16, u16)>,
+    pub scripts: Vec<ScriptRef>,
+    pub text: Vec<IdRef>,
+    pub compacts: Vec<IdRef>,
+}
+
+impl<'a> Compact<'a> {
+    /// Views the words of an entry of type [`CptType::Compact`], such as
+    /// those held by a saved game, as a compact.
+    pub fn new(data: &'a [u16]) -> Compact<'a> {
+        Compact { data }
+    }
+
+    /// The value of the field `name`, if the compact is long enough to
+    /// have it.
+    pub fn get(&self, name: &str) -> Option<u16> {
+        let n = COMPACT_FIELDS.iter().position(|&f| f == name)?;
+        self.data.get(n).copied()
+    }
+
+    /// The fields of mega set `n`, if the compact has them.
+    pub fn mega_set(&self, n: usize) -> Option<&'a [u16]> {
+        let start = COMPACT_FIELDS.len() + n * MEGA_SET_FIELDS.len();
+        self.data.get(start..start + MEGA_SET_FIELDS.len())
+    }
+
+    /// The list the object is drawn from, if its status puts it on one and
+    /// it has a sprite.
+    pub fn draw_list(&self) -> Option<DrawList> {
+        let status = self.get("status")?;
+        if self.get("frame")? >> 6 == 0 {
+            return None;
+        }
+
+        if status & ST_BACKGROUND != 0 {
+            Some(DrawList::Background)
+        } else if status & ST_SORT != 0 {
+            Some(DrawList::Sort)
+        } else if status & ST_FOREGROUND != 0 {
+            Some(DrawList::Foreground)
+        } else {
+            None
+        }
+    }
+
+    pub fn references(&self) -> References {
+        let mut refs = References::default();
+
+        if let Some(frame) = self.get("frame").filter(|&f| f != 0) {
+            refs.sprite = Some((frame >> 6, frame & 0x3f));
+        }
+
+        for field in SCRIPT_FIELDS {
+            if let Some(script) = self.get(field).filter(|&s| s != 0) {
+                refs.scripts.push(ScriptRef {
+                    field,
+                    script,
+                    module: SCRIPT_FILE_BASE + (script >> 12),
+                });
+            }
+        }
+
+        for field in TEXT_FIELDS {
+            if let Some(id) = self.get(field).filter(|&t| t != 0) {
+                refs.text.push(IdRef {
+                    field: field.to_owned(),
+                    id,
+                });
+            }
+        }
+
+        for field in COMPACT_ID_FIELDS {
+            if let Some(id) = self.get(field).filter(|&c| c != 0) {
+                refs.compacts.push(IdRef {
+                    field: field.to_owned(),
+                    id,
+                });
+            }
+        }
+        for n in 0..MEGA_SETS {
+            let Some(set) = self.mega_set(n) else {
+                break;
+            };
+            for (&field, &id) in MEGA_SET_FIELDS.iter().zip(set) {
+                if id != 0 && MEGA_SET_ID_FIELDS.contains(&field) {
+                    refs.compacts.push(IdRef {
+                        field: format!("mega_set{n}.{field}"),
+                        id,
+                    });
+                }
+            }
+        }
+
+        refs
+    }
+}
+
+/// A step of an animation sequence.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnimStep {
+    /// Shows `frame` of the sequence's sprite, with the object at (`x`,
+    /// `y`) in game coordinates, for `cycles` game cycles.
+    Frame {
+        x: u16,
+        y: u16,
+        frame: u16,
+        cycles: usize,
+    },
+    /// Starts sound effect `sound` at `volume`, taking no time.
+    Sound { sound: u16, volume: u16 },
+    /// Sets the `sync` field of the compact `compact` to `value`, taking no
+    /// time.
+    Sync { compact: u16, value: u16 },
+}
+
+/// An animation an object plays, a frame every game cycle.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AnimSequence {
+    /// The item number of the sprite all frames are taken from.
+    pub sprite: u16,
+    pub steps: Vec<AnimStep>,
+}
+
+impl AnimSequence {
+    /// Decodes the words of an animation sequence: the sprite shifted left
+    /// by six, then steps of three words up to a zero. Cycles showing the
+    /// same frame in the same place one after the other are merged into a
+    /// single step. Returns `None` if the words aren't shaped like that.
+    ///
+    /// Sprites are never flipped: characters have separate frames for
+    /// facing left and right.
+    pub fn decode(data: &[u16]) -> Option<AnimSequence> {
+        let (&offset, mut rest) = data.split_first()?;
+        if offset & 0x3f != 0 {
+            return None;
+        }
+
+        let mut steps = Vec::new();
+        loop {
+            let step = match *rest {
+                [0, ..] => break,
+                [LF_START_FX, sound, volume, ..] => AnimStep::Sound { sound, volume },
+                [SEND_SYNC, compact, value, ..] => AnimStep::Sync { compact, value },
+                [x, y, frame, ..] if frame >> 6 == 0 => AnimStep::Frame {
+                    x,
+                    y,
+                    frame,
+                    cycles: 1,
+                },
+                _ => return None,
+            };
+            rest = &rest[3..];
+
+            match (steps.last_mut(), &step) {
+                (
+                    Some(AnimStep::Frame {
+                        x,
+                        y,
+                        frame,
+                        cycles,
+                    }),
+                    AnimStep::Frame {
+                        x: next_x,
+                        y: next_y,
+                        frame: next_frame,
+                        ..
+                    },
+                ) if (*x, *y, *frame) == (*next_x, *next_y, *next_frame) => *cycles += 1,
+                _ => steps.push(step),
+            }
+        }
+
+        Some(AnimSequence {
+            sprite: offset >> 6,
+            steps,
+        })
+    }
+
+    /// Number of game cycles the sequence takes to play.
+    pub fn cycles(&self) -> usize {
+        self.steps
+            .iter()
+            .map(|step| match step {
+                AnimStep::Frame { cycles, .. } => *cycles,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// The turns of a character: for each direction it faces, from the order
+/// of [`DIRECTIONS`], the id of the entry listing the frames it turns to
+/// each other direction with, or zero for turning without any.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TurnTable {
+    pub turns: [[u16; 5]; 5],
+}
+
+impl TurnTable {
+    /// Decodes the 25 words of a turn table, returning `None` if there are
+    /// fewer.
+    pub fn decode(data: &[u16]) -> Option<TurnTable> {
+        let mut turns = [[0; 5]; 5];
+        for (from, row) in turns.iter_mut().enumerate() {
+            row.copy_from_slice(data.get(5 * from..5 * from + 5)?);
+        }
+        Some(TurnTable { turns })
+    }
+}
+
+/// The frames of a turn, each the item number of a sprite and the frame
+/// within it, shown a game cycle each. The list ends at a zero word.
+pub fn turn_frames(data: &[u16]) -> Vec<(u16, u16)> {
+    data.iter()
+        .take_while(|&&f| f != 0)
+        .map(|&f| (f >> 6, f & 0x3f))
+        .collect()
+}
+
+/// Values with their field names, serialized as a map in field order.
+struct Fields<'a>(&'a [&'static str], &'a [u16]);
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        for (&field, &value) in self.0.iter().zip(self.1) {
+            if field.starts_with("mouse_rel_") {
+                map.serialize_entry(field, &(value as i16))?;
+            } else {
+                map.serialize_entry(field, &value)?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+impl Serialize for Compact<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        let len = self.data.len().min(COMPACT_FIELDS.len());
+        map.serialize_entry("fields", &Fields(&COMPACT_FIELDS, &self.data[..len]))?;
+
+        let sets: Vec<_> = (0..MEGA_SETS)
+            .map_while(|n| self.mega_set(n))
+            .map(|set| Fields(&MEGA_SET_FIELDS, set))
+            .collect();
+        if !sets.is_empty() {
+            map.serialize_entry("mega_sets", &sets)?;
+        }
+
+        map.end()
+    }
+}
@@ -0,0 +1,132 @@
+//! How the directory covers `sky.dsk`.
+//!
+//! Every entry of the dinner table refers to a range of bytes of
+//! `sky.dsk`. In an intact copy the ranges follow each other without
+//! overlapping, but nothing stops two entries sharing bytes, two entries
+//! holding the same data, or bytes that no entry refers to at all. The
+//! last may be padding, or data left behind by the tools that built the
+//! file.
+
+use std::collections::BTreeMap;
+
+use crate::archive::Entry;
+
+/// Two entries whose ranges share bytes without being the same range.
+#[derive(Clone, Debug)]
+pub struct Overlap {
+    pub first: u16,
+    pub second: u16,
+    /// Where the shared bytes start in `sky.dsk`.
+    pub offset: u32,
+    pub len: u32,
+}
+
+/// A range of `sky.dsk` no entry refers to.
+#[derive(Clone, Debug)]
+pub struct Gap {
+    pub offset: u32,
+    pub len: u32,
+    /// Whether every byte of the gap is zero, as padding would be.
+    pub zero: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    pub overlaps: Vec<Overlap>,
+    /// Groups of entries holding identical data, in order of id.
+    pub duplicates: Vec<Vec<u16>>,
+    pub gaps: Vec<Gap>,
+    /// Number of bytes of `sky.dsk` referred to by at least one entry.
+    pub covered: u64,
+    pub len: u64,
+}
+
+/// Checks how the ranges of `entries` cover `dsk`. Entries running past the
+/// end of `dsk` only count with the bytes that are there.
+pub fn check(entries: &[Entry], dsk: &[u8]) -> Coverage {
+    let len = dsk.len() as u64;
+    let range = |e: &Entry| {
+        let start = (e.offset as u64).min(len);
+        (start, (start + e.size as u64).min(len))
+    };
+
+    let mut sorted: Vec<_> = entries
+        .iter()
+        .filter(|e| {
+            let (start, end) = range(e);
+            start < end
+        })
+        .collect();
+    sorted.sort_by_key(|e| (e.offset, e.number));
+
+    // Entries that haven't ended yet, as the sweep goes through them in
+    // order of offset.
+    let mut overlaps = Vec::new();
+    let mut active: Vec<&Entry> = Vec::new();
+    let mut gaps = Vec::new();
+    let mut covered = 0;
+    let mut covered_to = 0;
+    for &entry in &sorted {
+        let (start, end) = range(entry);
+
+        active.retain(|e| range(e).1 > start);
+        for &other in &active {
+            let other_range = range(other);
+            if other_range != (start, end) {
+                overlaps.push(Overlap {
+                    first: other.number,
+                    second: entry.number,
+                    offset: start as u32,
+                    len: (other_range.1.min(end) - start) as u32,
+                });
+            }
+        }
+        active.push(entry);
+
+        if start > covered_to {
+            gaps.push(gap(dsk, covered_to, start));
+        }
+        if end > covered_to {
+            covered += end - start.max(covered_to);
+            covered_to = end;
+        }
+    }
+    if len > covered_to {
+        gaps.push(gap(dsk, covered_to, len));
+    }
+
+    let mut by_data: BTreeMap<_, Vec<u16>> = BTreeMap::new();
+    for entry in &sorted {
+        let (start, end) = range(entry);
+        let data = &dsk[start as usize..end as usize];
+        by_data
+            .entry((data.len(), sha1_smol::Sha1::from(data).digest().bytes()))
+            .or_default()
+            .push(entry.number);
+    }
+    let mut duplicates: Vec<_> = by_data
+        .into_values()
+        .filter(|ids| ids.len() > 1)
+        .map(|mut ids| {
+            ids.sort();
+            ids
+        })
+        .collect();
+    duplicates.sort();
+
+    Coverage {
+        overlaps,
+        duplicates,
+        gaps,
+        covered,
+        len,
+    }
+}
+
+fn gap(dsk: &[u8], start: u64, end: u64) -> Gap {
+    Gap {
+        offset: start as u32,
+        len: (end - start) as u32,
+        zero: dsk[start as usize..end as usize].iter().all(|&b| b == 0),
+    }
+}
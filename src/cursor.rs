@@ -0,0 +1,34 @@
+//! Mouse pointers.
+//!
+//! The pointers are two sprite resources, one with the arrows, crosshair
+//! and disk the game switches between and one with a pointer for each
+//! object that can be picked up. Every frame of a resource has the size
+//! and offsets given in its header; the point the pointer picks is the
+//! one the top left corner is offset from.
+
+use serde::Serialize;
+
+use crate::resource::Header;
+
+pub const MICE_FILE: u16 = 60300;
+pub const OBJECT_MICE_FILE: u16 = 60301;
+
+/// The resources holding mouse pointers.
+pub const MICE_FILES: [u16; 2] = [MICE_FILE, OBJECT_MICE_FILE];
+
+/// The pixel of a pointer frame that picks, from its top left corner.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Hotspot {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Hotspot {
+    /// The hotspot of the frames of a pointer resource with `header`.
+    pub fn from_header(header: &Header) -> Hotspot {
+        Hotspot {
+            x: -(header.offset_x as i32),
+            y: -(header.offset_y as i32),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Palette cycling: animating a screen by rotating a range of its palette,
+//! the way water and lights are often animated in games of the time.
+//!
+//! The PC data files don't record which colors are cycled, so ranges are
+//! read from a CSV file with `screen,first,last` records, or guessed from
+//! the screen and its palette: a cycled range is a run of colors the screen
+//! uses that shade smoothly into each other and from the last back to the
+//! first, so rotating it shows no seam. The guess can find gradients that
+//! aren't animated in the game, so ranges from a file take precedence.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{error::Result, image::palette_to_rgb};
+
+/// Hundredths of a second between the frames of a palette cycle.
+pub const CYCLE_DELAY: u16 = 10;
+
+/// Fewest colors a guessed range has.
+const MIN_RANGE: usize = 4;
+
+/// Most a channel of the 6-bit VGA palette changes between neighbouring
+/// colors of a guessed range.
+const MAX_STEP: u8 = 12;
+
+/// Most frames written for one screen, for ranges whose lengths have no
+/// small common multiple.
+const MAX_FRAMES: usize = 256;
+
+/// The colors `first` to `last` of the palette of `screen`, rotated by one
+/// every frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct CycleRange {
+    pub screen: u16,
+    pub first: u8,
+    pub last: u8,
+}
+
+impl CycleRange {
+    /// Number of colors in the range.
+    pub fn len(&self) -> usize {
+        (self.last as usize + 1).saturating_sub(self.first as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CycleMap {
+    ranges: HashMap<u16, Vec<CycleRange>>,
+}
+
+impl CycleMap {
+    /// Reads ranges from a CSV file with `screen`, `first` and `last`
+    /// columns, a screen having as many ranges as it has records.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut rdr = csv::Reader::from_path(path)?;
+
+        let mut ranges: HashMap<u16, Vec<CycleRange>> = HashMap::new();
+        for range in rdr.deserialize::<CycleRange>() {
+            let range = range?;
+            ranges.entry(range.screen).or_default().push(range);
+        }
+
+        Ok(CycleMap { ranges })
+    }
+
+    /// The ranges cycled on `screen`, if known.
+    pub fn ranges(&self, screen: u16) -> Option<&[CycleRange]> {
+        self.ranges.get(&screen).map(|r| r.as_slice())
+    }
+}
+
+/// Guesses the ranges cycled on `screen`, whose pixels `data` are shown
+/// with the 6-bit VGA `palette`.
+pub fn detect(screen: u16, data: &[u8], palette: &[u8]) -> Vec<CycleRange> {
+    let mut used = [false; 256];
+    for &c in data {
+        used[c as usize] = true;
+    }
+
+    let color = |i: usize| &palette[3 * i..3 * i + 3];
+    let close = |a: usize, b: usize| {
+        color(a)
+            .iter()
+            .zip(color(b))
+            .all(|(&x, &y)| x.abs_diff(y) <= MAX_STEP)
+    };
+
+    let colors = (palette.len() / 3).min(256);
+    let mut ranges = Vec::new();
+    let mut first = 0;
+    while first < colors {
+        let mut last = first;
+        while used[first] && last + 1 < colors && used[last + 1] && close(last, last + 1) {
+            last += 1;
+        }
+
+        let len = last + 1 - first;
+        let varies = (first..=last).any(|i| color(i) != color(first));
+        if len >= MIN_RANGE && varies && close(last, first) {
+            ranges.push(CycleRange {
+                screen,
+                first: first as u8,
+                last: last as u8,
+            });
+        }
+        first = last + 1;
+    }
+
+    ranges
+}
+
+/// The palettes of one full cycle of `ranges`, each rotated one color
+/// further along than in the one before.
+pub fn cycle_palettes(palette: &[u8], ranges: &[CycleRange]) -> Vec<Vec<u8>> {
+    let frames = ranges
+        .iter()
+        .filter(|r| !r.is_empty())
+        .fold(1, |n, r| lcm(n, r.len()).min(MAX_FRAMES));
+
+    let mut palettes = vec![palette.to_vec()];
+    for _ in 1..frames {
+        let mut next = palettes.last().unwrap().clone();
+        for range in ranges {
+            let colors = 3 * range.first as usize..3 * (range.last as usize + 1);
+            if let Some(colors) = next.get_mut(colors) {
+                colors.rotate_right(3);
+            }
+        }
+        palettes.push(next);
+    }
+
+    palettes
+}
+
+/// Writes a 320 by 200 `screen` as a looping animated GIF showing one
+/// full cycle of `ranges` of its 6-bit VGA `palette`.
+pub fn write_cycle_gif<W: Write>(
+    w: W,
+    screen: &[u8],
+    palette: &[u8],
+    ranges: &[CycleRange],
+) -> io::Result<()> {
+    let mut encoder = gif::Encoder::new(w, 320, 200, &[]).map_err(io::Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(io::Error::other)?;
+
+    for palette in cycle_palettes(palette, ranges) {
+        let mut frame =
+            gif::Frame::from_palette_pixels(320, 200, screen, palette_to_rgb(&palette), None);
+        frame.delay = CYCLE_DELAY;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+
+    Ok(())
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let gcd = |mut a: usize, mut b: usize| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    a / gcd(a, b) * b
+}
@@ -0,0 +1,579 @@
+//! Decompilation of the logic scripts into structured pseudo-code.
+//!
+//! The stack machine's pushes and pops are folded back into expressions,
+//! and the skips into the `if`/`else`, `switch` and `do`/`while` blocks
+//! they were compiled from: a conditional skip forward over a block is an
+//! `if`, ending that block with a skip over another makes the other its
+//! `else`, the cases of a `switch` end with a skip to the end of it, and a
+//! skip back while a condition holds closes a loop. Jumps that fit none of
+//! these are kept as `goto`s to labels, and values left on the stack where
+//! control flow meets as `push` statements, so nothing is lost.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::{self, Write},
+};
+
+use crate::script::{label, mcode_name, mcode_references, Instruction, Module, Op, Reference};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Less,
+    More,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+    Plus,
+    Minus,
+}
+
+impl BinOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Less => "<",
+            BinOp::More => ">",
+            BinOp::Equal => "==",
+            BinOp::NotEqual => "!=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+            BinOp::Plus => "+",
+            BinOp::Minus => "-",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(u16),
+    Variable(u16),
+    /// A field of the compact running the script, by the offset
+    /// `push_offset` gives.
+    Field(u16),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// A value pushed by code that wasn't followed, such as before a
+    /// label.
+    Stack,
+}
+
+impl Expr {
+    fn binary(op: BinOp, a: Expr, b: Expr) -> Expr {
+        Expr::Binary(op, Box::new(a), Box::new(b))
+    }
+
+    fn not(self) -> Expr {
+        match self {
+            Expr::Not(e) => *e,
+            Expr::Binary(BinOp::Equal, a, b) => Expr::Binary(BinOp::NotEqual, a, b),
+            Expr::Binary(BinOp::NotEqual, a, b) => Expr::Binary(BinOp::Equal, a, b),
+            e => Expr::Not(Box::new(e)),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Operands that are operations themselves are parenthesized, as the
+        // order of evaluation is all the scripts have.
+        let operand = |f: &mut fmt::Formatter<'_>, e: &Expr| match e {
+            Expr::Binary(..) => write!(f, "({e})"),
+            e => write!(f, "{e}"),
+        };
+
+        match self {
+            Expr::Number(n) => write!(f, "{n}"),
+            Expr::Variable(v) => write!(f, "var[{v}]"),
+            Expr::Field(o) => write!(f, "field[0x{o:04x}]"),
+            Expr::Binary(op, a, b) => {
+                operand(f, a)?;
+                write!(f, " {} ", op.symbol())?;
+                operand(f, b)
+            }
+            Expr::Not(e) => {
+                write!(f, "!")?;
+                operand(f, e)
+            }
+            Expr::Stack => write!(f, "pop()"),
+        }
+    }
+}
+
+/// A case of a `switch`: the values leading to it, whether it is also the
+/// default, and its statements. `breaks` is false where the case falls
+/// through into the next.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Case {
+    pub values: Vec<u16>,
+    pub default: bool,
+    pub body: Vec<Stmt>,
+    pub breaks: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stmt {
+    SetVariable(u16, Expr),
+    SetField(u16, Expr),
+    Call {
+        mcode: u16,
+        args: Vec<Expr>,
+    },
+    /// A value left on the stack.
+    Push(Expr),
+    If {
+        cond: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    DoWhile {
+        body: Vec<Stmt>,
+        cond: Expr,
+    },
+    Switch {
+        value: Expr,
+        cases: Vec<Case>,
+    },
+    /// A jump to the label at a word offset, if `cond` holds.
+    Goto {
+        cond: Option<Expr>,
+        target: usize,
+    },
+    Label(usize),
+    Return,
+    Restart,
+    Unknown(u16),
+}
+
+/// Decompiles the script starting at word `start` of `module`.
+pub fn decompile(module: &Module, start: usize) -> Vec<Stmt> {
+    let code = module.disassemble(start);
+
+    let mut loops = BTreeMap::new();
+    for instruction in &code {
+        if let Op::SkipNonZero(target) = instruction.op {
+            if target <= instruction.offset {
+                let back = loops.entry(target).or_insert(instruction.offset);
+                *back = (*back).max(instruction.offset);
+            }
+        }
+    }
+
+    let end = code.last().map_or(start, |i| i.offset + i.len);
+    let mut decompiler = Decompiler {
+        code: &code,
+        loops,
+        open_loops: BTreeSet::new(),
+        labels: BTreeSet::new(),
+        gotos: BTreeSet::new(),
+    };
+
+    // Labels are only known once the gotos are, so the first pass finds
+    // them and the second puts them in.
+    decompiler.block_flushed(start, end);
+    decompiler.labels = std::mem::take(&mut decompiler.gotos);
+    decompiler.block_flushed(start, end)
+}
+
+struct Decompiler<'a> {
+    code: &'a [Instruction],
+    /// The offsets of loop heads and of the last skip back to each.
+    loops: BTreeMap<usize, usize>,
+    /// The loops whose bodies are being decompiled.
+    open_loops: BTreeSet<usize>,
+    labels: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+}
+
+impl Decompiler<'_> {
+    /// The index of the first instruction at or after word `offset`.
+    fn index(&self, offset: usize) -> usize {
+        self.code.partition_point(|i| i.offset < offset)
+    }
+
+    /// The instruction right before word `offset`.
+    fn before(&self, offset: usize) -> Option<&Instruction> {
+        self.index(offset).checked_sub(1).map(|n| &self.code[n])
+    }
+
+    fn goto(&mut self, cond: Option<Expr>, target: usize) -> Stmt {
+        self.gotos.insert(target);
+        Stmt::Goto { cond, target }
+    }
+
+    /// Decompiles the code in `start..end`, leaving no values on the stack.
+    fn block_flushed(&mut self, start: usize, end: usize) -> Vec<Stmt> {
+        let mut stack = Vec::new();
+        let mut stmts = self.block(start, end, &mut stack);
+        flush(&mut stmts, &mut stack);
+        stmts
+    }
+
+    /// Decompiles the code in `start..end` with the values on `stack`.
+    fn block(&mut self, start: usize, end: usize, stack: &mut Vec<Expr>) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        let pop = |stack: &mut Vec<Expr>| stack.pop().unwrap_or(Expr::Stack);
+
+        let mut n = self.index(start);
+        while let Some(instruction) = self.code.get(n).filter(|i| i.offset < end) {
+            let offset = instruction.offset;
+            let next = offset + instruction.len;
+
+            if self.labels.contains(&offset) {
+                flush(&mut stmts, stack);
+                stmts.push(Stmt::Label(offset));
+            }
+
+            if let Some(&back) = self.loops.get(&offset) {
+                if back < end && self.open_loops.insert(offset) {
+                    flush(&mut stmts, stack);
+                    let mut body_stack = Vec::new();
+                    let mut body = self.block(offset, back, &mut body_stack);
+                    let cond = pop(&mut body_stack);
+                    flush(&mut body, &mut body_stack);
+                    self.open_loops.remove(&offset);
+
+                    stmts.push(Stmt::DoWhile { body, cond });
+                    n = self.index(back + 1);
+                    continue;
+                }
+            }
+
+            match instruction.op {
+                Op::PushVariable(v) => stack.push(Expr::Variable(v)),
+                Op::PushNumber(v) => stack.push(Expr::Number(v)),
+                Op::PushOffset(o) => stack.push(Expr::Field(o)),
+                Op::LessThan => binary(stack, BinOp::Less),
+                Op::MoreThan => binary(stack, BinOp::More),
+                Op::IsEqual => binary(stack, BinOp::Equal),
+                Op::NotEqual => binary(stack, BinOp::NotEqual),
+                Op::IfAnd => binary(stack, BinOp::And),
+                Op::IfOr => binary(stack, BinOp::Or),
+                Op::Plus => binary(stack, BinOp::Plus),
+                Op::Minus => binary(stack, BinOp::Minus),
+                Op::PopVariable(v) => {
+                    let value = pop(stack);
+                    stmts.push(Stmt::SetVariable(v, value));
+                }
+                Op::PopOffset(o) => {
+                    let value = pop(stack);
+                    stmts.push(Stmt::SetField(o, value));
+                }
+                Op::CallMcode { args, mcode } => {
+                    let mut popped: Vec<_> = (0..args).map(|_| pop(stack)).collect();
+                    popped.reverse();
+                    stmts.push(Stmt::Call {
+                        mcode,
+                        args: popped,
+                    });
+                }
+                Op::SkipZero(target) => {
+                    let cond = pop(stack);
+                    flush(&mut stmts, stack);
+                    if target > offset && target <= end {
+                        let (stmt, resume) = self.if_else(cond, next, target, end);
+                        stmts.push(stmt);
+                        n = self.index(resume);
+                        continue;
+                    }
+                    let stmt = self.goto(Some(cond.not()), target);
+                    stmts.push(stmt);
+                }
+                Op::SkipNonZero(target) => {
+                    let cond = pop(stack);
+                    flush(&mut stmts, stack);
+                    if target > offset && target <= end {
+                        let then = self.block_flushed(next, target);
+                        stmts.push(Stmt::If {
+                            cond: cond.not(),
+                            then,
+                            otherwise: Vec::new(),
+                        });
+                        n = self.index(target);
+                        continue;
+                    }
+                    let stmt = self.goto(Some(cond), target);
+                    stmts.push(stmt);
+                }
+                Op::SkipAlways(target) => {
+                    flush(&mut stmts, stack);
+                    let stmt = self.goto(None, target);
+                    stmts.push(stmt);
+                }
+                Op::Switch { ref cases, default } => {
+                    let value = pop(stack);
+                    flush(&mut stmts, stack);
+                    let (stmt, resume) = self.switch(value, cases, default, offset, end);
+                    stmts.push(stmt);
+                    n = self.index(resume);
+                    continue;
+                }
+                Op::Exit => {
+                    flush(&mut stmts, stack);
+                    stmts.push(Stmt::Return);
+                }
+                Op::Restart => {
+                    flush(&mut stmts, stack);
+                    stmts.push(Stmt::Restart);
+                }
+                Op::Unknown(op) => stmts.push(Stmt::Unknown(op)),
+            }
+
+            n += 1;
+        }
+
+        stmts
+    }
+
+    /// An `if` whose block runs from `next` to `target`, with an `else` if
+    /// the block ends by skipping the code after it. Returns the statement
+    /// and where to carry on.
+    fn if_else(&mut self, cond: Expr, next: usize, target: usize, end: usize) -> (Stmt, usize) {
+        let skip_else = self.before(target).and_then(|i| match i.op {
+            Op::SkipAlways(e) if i.offset >= next && e > target && e <= end => Some((i.offset, e)),
+            _ => None,
+        });
+
+        match skip_else {
+            Some((skip, else_end)) => {
+                let then = self.block_flushed(next, skip);
+                let otherwise = self.block_flushed(target, else_end);
+                (
+                    Stmt::If {
+                        cond,
+                        then,
+                        otherwise,
+                    },
+                    else_end,
+                )
+            }
+            None => {
+                let then = self.block_flushed(next, target);
+                (
+                    Stmt::If {
+                        cond,
+                        then,
+                        otherwise: Vec::new(),
+                    },
+                    target,
+                )
+            }
+        }
+    }
+
+    /// A `switch` at word `offset` on `value`. The cases run from one
+    /// target to the next, and the switch ends where they skip to. Returns
+    /// the statement and where to carry on.
+    fn switch(
+        &mut self,
+        value: Expr,
+        cases: &[(u16, usize)],
+        default: usize,
+        offset: usize,
+        end: usize,
+    ) -> (Stmt, usize) {
+        let targets: BTreeSet<usize> = cases.iter().map(|&(_, t)| t).chain([default]).collect();
+        let targets: Vec<usize> = targets.into_iter().collect();
+
+        if targets.iter().any(|&t| t <= offset || t > end) {
+            // Cases outside the code around become jumps.
+            let cases = targets
+                .iter()
+                .map(|&t| Case {
+                    values: values_for(cases, t),
+                    default: t == default,
+                    body: vec![self.goto(None, t)],
+                    breaks: false,
+                })
+                .collect();
+            return (Stmt::Switch { value, cases }, offset + 1);
+        }
+
+        let last = *targets.last().expect("a switch has a default");
+        let switch_end = targets
+            .iter()
+            .skip(1)
+            .filter_map(|&t| match self.before(t)?.op {
+                Op::SkipAlways(e) if e >= last && e <= end => Some(e),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(last);
+
+        let mut out = Vec::new();
+        for (n, &start) in targets.iter().enumerate() {
+            if start == switch_end {
+                break;
+            }
+            let case_end = targets.get(n + 1).copied().unwrap_or(switch_end);
+            let (body_end, breaks) = match self.before(case_end).map(|i| (i.offset, &i.op)) {
+                Some((skip, &Op::SkipAlways(e))) if e == switch_end && skip >= start => {
+                    (skip, true)
+                }
+                _ => (case_end, case_end == switch_end),
+            };
+
+            out.push(Case {
+                values: values_for(cases, start),
+                default: start == default,
+                body: self.block_flushed(start, body_end),
+                breaks,
+            });
+        }
+
+        (Stmt::Switch { value, cases: out }, switch_end)
+    }
+}
+
+fn values_for(cases: &[(u16, usize)], target: usize) -> Vec<u16> {
+    cases
+        .iter()
+        .filter(|&&(_, t)| t == target)
+        .map(|&(v, _)| v)
+        .collect()
+}
+
+fn binary(stack: &mut Vec<Expr>, op: BinOp) {
+    let b = stack.pop().unwrap_or(Expr::Stack);
+    let a = stack.pop().unwrap_or(Expr::Stack);
+    stack.push(Expr::binary(op, a, b));
+}
+
+/// Moves the values left on `stack` into `push` statements.
+fn flush(stmts: &mut Vec<Stmt>, stack: &mut Vec<Expr>) {
+    stmts.extend(stack.drain(..).map(Stmt::Push));
+}
+
+/// Writes every script of `module` as pseudo-code. `text` looks up the text
+/// ids passed to engine functions.
+pub fn write_module<W: Write>(
+    w: &mut W,
+    module: &Module,
+    text: impl Fn(u16) -> Option<String>,
+) -> io::Result<()> {
+    writeln!(w, "// module {}", module.number)?;
+
+    for (n, start) in module.scripts().into_iter().enumerate() {
+        let Some(start) = start else {
+            continue;
+        };
+
+        let script = (module.number << 12) | n as u16;
+        writeln!(w)?;
+        writeln!(w, "script_{script:04x} {{")?;
+        write_block(w, &decompile(module, start), 1, &text)?;
+        writeln!(w, "}}")?;
+    }
+
+    Ok(())
+}
+
+fn write_block<W: Write>(
+    w: &mut W,
+    stmts: &[Stmt],
+    depth: usize,
+    text: &impl Fn(u16) -> Option<String>,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+
+    for stmt in stmts {
+        match stmt {
+            Stmt::SetVariable(v, e) => writeln!(w, "{indent}var[{v}] = {e};")?,
+            Stmt::SetField(o, e) => writeln!(w, "{indent}field[0x{o:04x}] = {e};")?,
+            Stmt::Call { mcode, args } => {
+                let name = mcode_name(*mcode).map_or(format!("mcode_{mcode}"), str::to_owned);
+                let args: Vec<_> = args
+                    .iter()
+                    .enumerate()
+                    .map(|(n, arg)| format_arg(*mcode, n, arg))
+                    .collect();
+                let comment = args_comment(*mcode, stmt, text);
+                writeln!(w, "{indent}{name}({});{comment}", args.join(", "))?;
+            }
+            Stmt::Push(e) => writeln!(w, "{indent}push {e};")?,
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                writeln!(w, "{indent}if ({cond}) {{")?;
+                write_block(w, then, depth + 1, text)?;
+                if !otherwise.is_empty() {
+                    writeln!(w, "{indent}}} else {{")?;
+                    write_block(w, otherwise, depth + 1, text)?;
+                }
+                writeln!(w, "{indent}}}")?;
+            }
+            Stmt::DoWhile { body, cond } => {
+                writeln!(w, "{indent}do {{")?;
+                write_block(w, body, depth + 1, text)?;
+                writeln!(w, "{indent}}} while ({cond});")?;
+            }
+            Stmt::Switch { value, cases } => {
+                writeln!(w, "{indent}switch ({value}) {{")?;
+                for case in cases {
+                    for value in &case.values {
+                        writeln!(w, "{indent}case 0x{value:04x}:")?;
+                    }
+                    if case.default {
+                        writeln!(w, "{indent}default:")?;
+                    }
+                    write_block(w, &case.body, depth + 1, text)?;
+                    if case.breaks {
+                        writeln!(w, "{indent}    break;")?;
+                    }
+                }
+                writeln!(w, "{indent}}}")?;
+            }
+            Stmt::Goto {
+                cond: Some(cond),
+                target,
+            } => writeln!(w, "{indent}if ({cond}) goto {};", label(*target))?,
+            Stmt::Goto { cond: None, target } => writeln!(w, "{indent}goto {};", label(*target))?,
+            Stmt::Label(offset) => writeln!(w, "{}:", label(*offset))?,
+            Stmt::Return => writeln!(w, "{indent}return;")?,
+            Stmt::Restart => writeln!(w, "{indent}restart;")?,
+            Stmt::Unknown(op) => writeln!(w, "{indent}// unknown opcode 0x{op:04x}")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats argument `n` of a call to `mcode`, ids in hex.
+fn format_arg(mcode: u16, n: usize, arg: &Expr) -> String {
+    let is_id = mcode_references(mcode).iter().any(|&(m, r)| {
+        m == n
+            && matches!(
+                r,
+                Reference::Compact | Reference::Text | Reference::Animation
+            )
+    });
+
+    match arg {
+        Expr::Number(v) if is_id => format!("0x{v:04x}"),
+        arg => arg.to_string(),
+    }
+}
+
+/// A comment quoting the lines of text a call is passed.
+fn args_comment(mcode: u16, stmt: &Stmt, text: &impl Fn(u16) -> Option<String>) -> String {
+    let Stmt::Call { args, .. } = stmt else {
+        return String::new();
+    };
+
+    let lines: Vec<_> = mcode_references(mcode)
+        .iter()
+        .filter(|&&(_, r)| r == Reference::Text)
+        .filter_map(|&(n, _)| match args.get(n) {
+            Some(Expr::Number(id)) => text(*id).map(|line| format!("{line:?}")),
+            _ => None,
+        })
+        .collect();
+
+    if lines.is_empty() {
+        String::new()
+    } else {
+        format!(" // {}", lines.join(", "))
+    }
+}
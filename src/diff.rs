@@ -0,0 +1,59 @@
+//! Differences between two copies of the game, such as two releases, or
+//! an original and a modified copy.
+//!
+//! Resources are compared as stored in `sky.dsk`, by their size and SHA-1
+//! hash, so a resource that was only compressed differently shows up as
+//! changed; [`Change::Changed`] says whether the decoded data still
+//! matches.
+
+use std::collections::BTreeMap;
+
+use crate::checksum::Checksum;
+
+/// A resource that differs between two copies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A resource only the second copy has.
+    Added(Checksum),
+    /// A resource only the first copy has.
+    Removed(Checksum),
+    /// A resource stored differently in the two copies.
+    Changed { before: Checksum, after: Checksum },
+}
+
+impl Change {
+    pub fn id(&self) -> u16 {
+        match self {
+            Change::Added(checksum) | Change::Removed(checksum) => checksum.id,
+            Change::Changed { after, .. } => after.id,
+        }
+    }
+}
+
+/// The resources that differ between `before` and `after`, in order of
+/// resource id.
+pub fn diff(before: &[Checksum], after: &[Checksum]) -> Vec<Change> {
+    let before: BTreeMap<_, _> = before.iter().map(|c| (c.id, c)).collect();
+    let after: BTreeMap<_, _> = after.iter().map(|c| (c.id, c)).collect();
+
+    let mut changes = Vec::new();
+    for (id, &old) in &before {
+        match after.get(id) {
+            None => changes.push(Change::Removed(old.clone())),
+            Some(&new) if new != old => changes.push(Change::Changed {
+                before: old.clone(),
+                after: new.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(
+        after
+            .iter()
+            .filter(|(id, _)| !before.contains_key(id))
+            .map(|(_, &new)| Change::Added(new.clone())),
+    );
+    changes.sort_by_key(Change::id);
+
+    changes
+}
@@ -0,0 +1,102 @@
+//! The error type shared by the library and the command line tool.
+
+use std::{fmt, io, path::PathBuf};
+
+use crate::rnc_decompress::DecompressError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Decompress(DecompressError),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    /// The archive has no resource with this id.
+    NoSuchResource(u16),
+    /// Data that isn't in the expected format.
+    InvalidData(String),
+    /// An error while handling the resource `id`.
+    Resource {
+        id: u16,
+        source: Box<Error>,
+    },
+    /// An error while reading or writing `path`.
+    File {
+        path: PathBuf,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Attributes `err` to the resource `id`.
+    pub fn resource<E: Into<Error>>(id: u16, err: E) -> Error {
+        Error::Resource {
+            id,
+            source: Box::new(err.into()),
+        }
+    }
+
+    /// Attributes `err` to the file at `path`.
+    pub fn file<P: Into<PathBuf>, E: Into<Error>>(path: P, err: E) -> Error {
+        Error::File {
+            path: path.into(),
+            source: Box::new(err.into()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Decompress(ref err) => write!(f, "Decompression failed: {}", err),
+            Error::Csv(ref err) => write!(f, "{}", err),
+            Error::Json(ref err) => write!(f, "{}", err),
+            Error::NoSuchResource(id) => write!(f, "No resource with id {}", id),
+            Error::InvalidData(ref msg) => write!(f, "{}", msg),
+            Error::Resource { id, ref source } => write!(f, "Resource {:05}: {}", id, source),
+            Error::File {
+                ref path,
+                ref source,
+            } => write!(f, "`{}`: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Decompress(ref err) => Some(err),
+            Error::Csv(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+            Error::Resource { ref source, .. } | Error::File { ref source, .. } => Some(source),
+            Error::NoSuchResource(_) | Error::InvalidData(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<DecompressError> for Error {
+    fn from(err: DecompressError) -> Error {
+        Error::Decompress(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Error {
+        Error::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
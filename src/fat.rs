@@ -0,0 +1,219 @@
+//! Reading files from images of DOS floppy disks.
+//!
+//! The game came on 3.5" disks formatted with the FAT12 filesystem, and
+//! images of them are how it is usually preserved. The layout of an image
+//! is described by the BIOS parameter block in its boot sector: a number
+//! of reserved sectors, one or more copies of the file allocation table,
+//! the root directory and the clusters files and subdirectories are kept
+//! in. The file allocation table links the clusters of each file, twelve
+//! bits to an entry. Only short 8.3 names are read; long file names are
+//! skipped.
+
+use std::io;
+
+/// Size of a directory entry.
+const DIR_ENTRY_SIZE: usize = 32;
+
+const ATTR_VOLUME_LABEL: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+/// The attributes marking a long file name entry.
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// The first byte of the name of an entry that was deleted.
+const DELETED: u8 = 0xe5;
+
+/// Cluster numbers from here on end a chain.
+const END_OF_CHAIN: u16 = 0xff8;
+
+/// How deep subdirectories are searched, as a guard against directories
+/// that contain themselves in damaged images.
+const MAX_DEPTH: usize = 8;
+
+/// A file or directory in a directory.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    /// The name as `NAME.EXT`, without the padding.
+    pub name: String,
+    pub is_dir: bool,
+    pub first_cluster: u16,
+    pub size: u32,
+}
+
+/// An image of a FAT12 floppy disk.
+pub struct FatImage<'a> {
+    data: &'a [u8],
+    bytes_per_cluster: usize,
+    fat: &'a [u8],
+    root_dir: &'a [u8],
+    data_start: usize,
+}
+
+impl<'a> FatImage<'a> {
+    /// Reads the layout of the image `data` from its boot sector. Fails if
+    /// the boot sector doesn't describe a FAT filesystem that fits in the
+    /// image.
+    pub fn new(data: &'a [u8]) -> io::Result<Self> {
+        let invalid = || invalid_data("not a FAT12 disk image");
+
+        let bytes_per_sector = u16_at(data, 11).ok_or_else(invalid)? as usize;
+        let sectors_per_cluster = *data.get(13).ok_or_else(invalid)? as usize;
+        let reserved_sectors = u16_at(data, 14).ok_or_else(invalid)? as usize;
+        let fat_count = *data.get(16).ok_or_else(invalid)? as usize;
+        let root_entries = u16_at(data, 17).ok_or_else(invalid)? as usize;
+        let sectors_per_fat = u16_at(data, 22).ok_or_else(invalid)? as usize;
+
+        if ![512, 1024, 2048, 4096].contains(&bytes_per_sector)
+            || !sectors_per_cluster.is_power_of_two()
+            || fat_count == 0
+            || sectors_per_fat == 0
+        {
+            return Err(invalid());
+        }
+
+        let fat_start = reserved_sectors * bytes_per_sector;
+        let fat_size = sectors_per_fat * bytes_per_sector;
+        let root_start = fat_start + fat_count * fat_size;
+        let root_size = root_entries * DIR_ENTRY_SIZE;
+        let data_start = root_start + root_size.next_multiple_of(bytes_per_sector);
+        if data_start > data.len() {
+            return Err(invalid());
+        }
+
+        Ok(FatImage {
+            data,
+            bytes_per_cluster: sectors_per_cluster * bytes_per_sector,
+            fat: &data[fat_start..fat_start + fat_size],
+            root_dir: &data[root_start..root_start + root_size],
+            data_start,
+        })
+    }
+
+    /// The entries of the root directory.
+    pub fn root(&self) -> Vec<DirEntry> {
+        dir_entries(self.root_dir)
+    }
+
+    /// The entries of the subdirectory `dir`.
+    pub fn read_dir(&self, dir: &DirEntry) -> io::Result<Vec<DirEntry>> {
+        Ok(dir_entries(&self.read_chain(dir.first_cluster, None)?))
+    }
+
+    /// The contents of the file `file`.
+    pub fn read(&self, file: &DirEntry) -> io::Result<Vec<u8>> {
+        self.read_chain(file.first_cluster, Some(file.size as usize))
+    }
+
+    /// Looks for the file `name` in the root directory and then in the
+    /// subdirectories, matching names without regard to case.
+    pub fn find(&self, name: &str) -> io::Result<Option<DirEntry>> {
+        self.find_in(self.root(), name, 0)
+    }
+
+    fn find_in(
+        &self,
+        entries: Vec<DirEntry>,
+        name: &str,
+        depth: usize,
+    ) -> io::Result<Option<DirEntry>> {
+        if let Some(found) = entries
+            .iter()
+            .find(|e| !e.is_dir && e.name.eq_ignore_ascii_case(name))
+        {
+            return Ok(Some(found.clone()));
+        }
+        if depth == MAX_DEPTH {
+            return Ok(None);
+        }
+
+        for dir in entries.iter().filter(|e| e.is_dir) {
+            if let Some(found) = self.find_in(self.read_dir(dir)?, name, depth + 1)? {
+                return Ok(Some(found));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The entry of the file allocation table for `cluster`: the next
+    /// cluster of the chain.
+    fn next_cluster(&self, cluster: u16) -> Option<u16> {
+        let offset = cluster as usize * 3 / 2;
+        let pair = u16_at(self.fat, offset)?;
+        Some(if cluster & 1 == 0 {
+            pair & 0xfff
+        } else {
+            pair >> 4
+        })
+    }
+
+    /// Reads the clusters of the chain starting at `first`, `len` bytes of
+    /// them or, for directories, all of them.
+    fn read_chain(&self, first: u16, len: Option<usize>) -> io::Result<Vec<u8>> {
+        let broken = || invalid_data("broken cluster chain in disk image");
+
+        let max_clusters = (self.data.len() - self.data_start) / self.bytes_per_cluster;
+        let mut out = Vec::new();
+        let mut cluster = first;
+        while len.is_none_or(|len| out.len() < len) {
+            if cluster >= END_OF_CHAIN {
+                break;
+            }
+            // Cluster numbering starts at 2, and a chain can't be longer
+            // than the image has clusters.
+            if cluster < 2 || out.len() >= max_clusters * self.bytes_per_cluster {
+                return Err(broken());
+            }
+            let start = self.data_start + (cluster as usize - 2) * self.bytes_per_cluster;
+            let bytes = self
+                .data
+                .get(start..start + self.bytes_per_cluster)
+                .ok_or_else(broken)?;
+            out.extend_from_slice(bytes);
+            cluster = self.next_cluster(cluster).ok_or_else(broken)?;
+        }
+
+        match len {
+            Some(len) if out.len() < len => Err(broken()),
+            Some(len) => {
+                out.truncate(len);
+                Ok(out)
+            }
+            None => Ok(out),
+        }
+    }
+}
+
+/// Lists the files and subdirectories of a directory, leaving out deleted
+/// entries, volume labels, long file names and the `.` and `..` entries.
+fn dir_entries(dir: &[u8]) -> Vec<DirEntry> {
+    dir.chunks_exact(DIR_ENTRY_SIZE)
+        .take_while(|entry| entry[0] != 0)
+        .filter(|entry| entry[0] != DELETED && entry[0] != b'.')
+        .filter(|entry| entry[11] & ATTR_LONG_NAME != ATTR_LONG_NAME)
+        .filter(|entry| entry[11] & ATTR_VOLUME_LABEL == 0)
+        .map(|entry| {
+            let base = String::from_utf8_lossy(&entry[..8]).trim_end().to_owned();
+            let ext = String::from_utf8_lossy(&entry[8..11]).trim_end().to_owned();
+            let name = if ext.is_empty() {
+                base
+            } else {
+                format!("{base}.{ext}")
+            };
+            DirEntry {
+                name,
+                is_dir: entry[11] & ATTR_DIRECTORY != 0,
+                first_cluster: u16::from_le_bytes([entry[26], entry[27]]),
+                size: u32::from_le_bytes([entry[28], entry[29], entry[30], entry[31]]),
+            }
+        })
+        .collect()
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
@@ -0,0 +1,238 @@
+//! A C interface to the archive reader and the RNC decompressor, for
+//! engines and tools written in C or C++. Only built with the `ffi`
+//! feature; `include/sky_extract.h` declares the functions.
+//!
+//! Data handed out is owned by the caller and given back with
+//! [`sky_free`]. Functions returning `int` return [`SKY_OK`] or one of the
+//! negative error codes.
+
+use std::{
+    ffi::{c_char, c_int, CStr},
+    io::Cursor,
+    ptr, slice,
+};
+
+use crate::{archive::Archive, rnc_decompress::decompress_rnc1};
+
+pub const SKY_OK: c_int = 0;
+/// A pointer that may not be null was.
+pub const SKY_ERROR_NULL: c_int = -1;
+/// The archive has no resource with the id.
+pub const SKY_ERROR_NOT_FOUND: c_int = -2;
+/// The resource is stored without a file header.
+pub const SKY_ERROR_NO_HEADER: c_int = -3;
+/// The data isn't a valid RNC stream.
+pub const SKY_ERROR_DECOMPRESS: c_int = -4;
+
+/// An archive opened by [`sky_open_archive`], opaque to C.
+pub struct SkyArchive(Archive<Cursor<Vec<u8>>>);
+
+/// The file header of a resource, as [`crate::Header`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SkyHeader {
+    pub flags: u16,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub sp_size: u16,
+    pub tot_size: u16,
+    pub n_sprites: u16,
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub compressed_size: u16,
+}
+
+/// Hands `data` over to C, to be given back with [`sky_free`].
+///
+/// # Safety
+///
+/// `data` and `len` must be valid for writes.
+unsafe fn hand_over(data: Vec<u8>, out: *mut *mut u8, len: *mut usize) {
+    let data = data.into_boxed_slice();
+    *len = data.len();
+    *out = Box::into_raw(data) as *mut u8;
+}
+
+/// Opens the game data files in the directory `path`, a NUL-terminated
+/// UTF-8 string, and reads `sky.dsk` into memory. Returns null if they
+/// can't be found or read.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sky_open_archive(path: *const c_char) -> *mut SkyArchive {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return ptr::null_mut();
+    };
+
+    match Archive::load(path) {
+        Ok(archive) => Box::into_raw(Box::new(SkyArchive(archive))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Closes an archive returned by [`sky_open_archive`].
+///
+/// # Safety
+///
+/// `archive` must be null or returned by [`sky_open_archive`] and not yet
+/// closed.
+#[no_mangle]
+pub unsafe extern "C" fn sky_close_archive(archive: *mut SkyArchive) {
+    if !archive.is_null() {
+        drop(Box::from_raw(archive));
+    }
+}
+
+/// The number of entries in the directory of `archive`, 0 if it is null.
+///
+/// # Safety
+///
+/// `archive` must be null or an open archive.
+#[no_mangle]
+pub unsafe extern "C" fn sky_resource_count(archive: *const SkyArchive) -> usize {
+    archive.as_ref().map_or(0, |a| a.0.entries().len())
+}
+
+/// The id of the entry `index` in the directory of `archive`, for going
+/// through all resources. Returns [`SKY_ERROR_NOT_FOUND`] past the end.
+///
+/// # Safety
+///
+/// `archive` must be null or an open archive, and `id` null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn sky_resource_id(
+    archive: *const SkyArchive,
+    index: usize,
+    id: *mut u16,
+) -> c_int {
+    let (Some(archive), false) = (archive.as_ref(), id.is_null()) else {
+        return SKY_ERROR_NULL;
+    };
+
+    match archive.0.entries().get(index) {
+        Some(entry) => {
+            *id = entry.number;
+            SKY_OK
+        }
+        None => SKY_ERROR_NOT_FOUND,
+    }
+}
+
+/// Decodes the resource `id` of `archive`, decompressing it and leaving
+/// out its file header, and stores its data and length in `data` and
+/// `len`.
+///
+/// # Safety
+///
+/// `archive` must be null or an open archive, and `data` and `len` null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn sky_get_resource(
+    archive: *const SkyArchive,
+    id: u16,
+    data: *mut *mut u8,
+    len: *mut usize,
+) -> c_int {
+    let Some(archive) = archive.as_ref() else {
+        return SKY_ERROR_NULL;
+    };
+    if data.is_null() || len.is_null() {
+        return SKY_ERROR_NULL;
+    }
+
+    match archive.0.get_resource(id) {
+        Some(resource) => {
+            hand_over(resource.data, data, len);
+            SKY_OK
+        }
+        None => SKY_ERROR_NOT_FOUND,
+    }
+}
+
+/// Stores the file header of the resource `id` of `archive` in `header`.
+///
+/// # Safety
+///
+/// `archive` must be null or an open archive, and `header` null or valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn sky_get_header(
+    archive: *const SkyArchive,
+    id: u16,
+    header: *mut SkyHeader,
+) -> c_int {
+    let (Some(archive), false) = (archive.as_ref(), header.is_null()) else {
+        return SKY_ERROR_NULL;
+    };
+
+    let Some(resource) = archive.0.get_resource(id) else {
+        return SKY_ERROR_NOT_FOUND;
+    };
+    let Some(h) = resource.header else {
+        return SKY_ERROR_NO_HEADER;
+    };
+
+    *header = SkyHeader {
+        flags: h.flags,
+        x: h.x,
+        y: h.y,
+        width: h.width,
+        height: h.height,
+        sp_size: h.sp_size,
+        tot_size: h.tot_size,
+        n_sprites: h.n_sprites,
+        offset_x: h.offset_x,
+        offset_y: h.offset_y,
+        compressed_size: h.compressed_size,
+    };
+    SKY_OK
+}
+
+/// Decompresses the RNC stream of `src_len` bytes at `src`, header
+/// included, and stores the data and its length in `data` and `len`.
+///
+/// # Safety
+///
+/// `src` must be valid for reads of `src_len` bytes, and `data` and `len`
+/// null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn sky_decompress_rnc1(
+    src: *const u8,
+    src_len: usize,
+    data: *mut *mut u8,
+    len: *mut usize,
+) -> c_int {
+    if src.is_null() || data.is_null() || len.is_null() {
+        return SKY_ERROR_NULL;
+    }
+
+    let mut src = slice::from_raw_parts(src, src_len);
+    match decompress_rnc1(&mut src) {
+        Ok(decompressed) => {
+            hand_over(decompressed, data, len);
+            SKY_OK
+        }
+        Err(_) => SKY_ERROR_DECOMPRESS,
+    }
+}
+
+/// Frees data returned by [`sky_get_resource`] or [`sky_decompress_rnc1`].
+///
+/// # Safety
+///
+/// `data` must be null or returned by one of those functions with the
+/// length `len`, and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn sky_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
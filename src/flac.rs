@@ -0,0 +1,337 @@
+//! A small FLAC encoder, for writing the game's sounds losslessly.
+//!
+//! Every block is coded with whichever of the fixed polynomial predictors
+//! leaves the smallest residual, Rice coded in a single partition, or is
+//! stored verbatim if that's smaller. That falls short of what a full
+//! encoder achieves but is plenty for 8-bit samples. Loops are recorded as
+//! `LOOPSTART` and `LOOPLENGTH` comments, which is how games and players
+//! that loop FLAC files commonly expect them.
+//!
+//! For Ogg the same stream is wrapped according to the Ogg FLAC mapping,
+//! one FLAC frame to a page.
+
+use std::io::{self, Write};
+
+use crate::{audio::Sound, ogg::OggWriter};
+
+/// Samples in a block; the last block of a stream may be shorter.
+const BLOCK_SIZE: usize = 4096;
+
+const MAX_FIXED_ORDER: usize = 4;
+/// The largest Rice parameter of the 4-bit parameter coding method, below
+/// the escape code.
+const MAX_RICE_PARAMETER: u32 = 14;
+
+const VENDOR: &str = concat!("beneath-a-steel-sky-extract ", env!("CARGO_PKG_VERSION"));
+
+const METADATA_STREAMINFO: u8 = 0;
+const METADATA_VORBIS_COMMENT: u8 = 4;
+
+/// Writes `sound` as a native FLAC file.
+pub fn write_flac<W: Write>(w: &mut W, sound: &Sound) -> io::Result<()> {
+    w.write_all(b"fLaC")?;
+
+    let blocks = metadata_blocks(sound);
+    for (n, (kind, body)) in blocks.iter().enumerate() {
+        w.write_all(&metadata_header(*kind, body.len(), n == blocks.len() - 1))?;
+        w.write_all(body)?;
+    }
+
+    for frame in frames(sound) {
+        w.write_all(&frame.data)?;
+    }
+
+    Ok(())
+}
+
+/// Writes `sound` as FLAC in an Ogg container.
+pub fn write_ogg_flac<W: Write>(w: &mut W, sound: &Sound) -> io::Result<()> {
+    let mut ogg = OggWriter::new(w);
+
+    let blocks = metadata_blocks(sound);
+    let (streaminfo, rest) = blocks.split_first().unwrap();
+
+    // The first packet identifies the mapping and counts the header packets
+    // following it.
+    let mut first = vec![0x7f];
+    first.extend_from_slice(b"FLAC");
+    first.extend_from_slice(&[1, 0]);
+    first.extend_from_slice(&(rest.len() as u16).to_be_bytes());
+    first.extend_from_slice(b"fLaC");
+    first.extend_from_slice(&metadata_header(
+        streaminfo.0,
+        streaminfo.1.len(),
+        rest.is_empty(),
+    ));
+    first.extend_from_slice(&streaminfo.1);
+    ogg.page(&first, 0, false)?;
+
+    for (n, (kind, body)) in rest.iter().enumerate() {
+        let mut packet = metadata_header(*kind, body.len(), n == rest.len() - 1).to_vec();
+        packet.extend_from_slice(body);
+        ogg.page(&packet, 0, false)?;
+    }
+
+    let frames = frames(sound);
+    if frames.is_empty() {
+        return ogg.page(&[], 0, true);
+    }
+    for (n, frame) in frames.iter().enumerate() {
+        ogg.page(&frame.data, frame.end as u64, n == frames.len() - 1)?;
+    }
+
+    Ok(())
+}
+
+/// The metadata blocks of a stream, STREAMINFO first.
+fn metadata_blocks(sound: &Sound) -> Vec<(u8, Vec<u8>)> {
+    let mut streaminfo = BitWriter::new();
+    let block_size = BLOCK_SIZE.min(sound.samples.len()).max(16) as u64;
+    streaminfo.write(block_size, 16); // minimum block size
+    streaminfo.write(block_size, 16); // maximum block size
+    streaminfo.write(0, 24); // minimum frame size, unknown
+    streaminfo.write(0, 24); // maximum frame size, unknown
+    streaminfo.write(sound.sample_rate as u64, 20);
+    streaminfo.write(0, 3); // channels - 1
+    streaminfo.write(sound.bits_per_sample as u64 - 1, 5);
+    streaminfo.write(sound.samples.len() as u64, 36);
+    streaminfo.write(0, 64); // MD5 of the samples, left out
+    streaminfo.write(0, 64);
+
+    let mut comments = Vec::new();
+    if let Some(loop_start) = sound.loop_start {
+        comments.push(format!("LOOPSTART={loop_start}"));
+        comments.push(format!("LOOPLENGTH={}", sound.samples.len() - loop_start));
+    }
+
+    let mut vorbis_comment = Vec::new();
+    vorbis_comment.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    vorbis_comment.extend_from_slice(VENDOR.as_bytes());
+    vorbis_comment.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        vorbis_comment.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        vorbis_comment.extend_from_slice(comment.as_bytes());
+    }
+
+    vec![
+        (METADATA_STREAMINFO, streaminfo.finish()),
+        (METADATA_VORBIS_COMMENT, vorbis_comment),
+    ]
+}
+
+fn metadata_header(kind: u8, len: usize, last: bool) -> [u8; 4] {
+    let len = (len as u32).to_be_bytes();
+    [kind | (last as u8) << 7, len[1], len[2], len[3]]
+}
+
+struct Frame {
+    data: Vec<u8>,
+    /// The number of samples up to the end of the frame.
+    end: usize,
+}
+
+fn frames(sound: &Sound) -> Vec<Frame> {
+    let bps = sound.bits_per_sample as u32;
+
+    sound
+        .samples
+        .chunks(BLOCK_SIZE)
+        .enumerate()
+        .map(|(n, block)| {
+            let mut frame = BitWriter::new();
+            frame.write(0b11_1111_1111_1110, 14); // sync code
+            frame.write(0, 1);
+            frame.write(0, 1); // fixed block size
+            frame.write(0b0111, 4); // block size in 16 bits after the header
+            let rate_in_header = sound.sample_rate <= 0xffff;
+            if rate_in_header {
+                frame.write(0b1101, 4); // sample rate in 16 bits after the header
+            } else {
+                frame.write(0, 4); // sample rate from STREAMINFO
+            }
+            frame.write(0, 4); // mono
+            frame.write(if bps == 8 { 0b001 } else { 0b100 }, 3);
+            frame.write(0, 1);
+            frame.write_coded_number(n as u64);
+            frame.write(block.len() as u64 - 1, 16);
+            if rate_in_header {
+                frame.write(sound.sample_rate as u64, 16);
+            }
+            let crc = crc8(&frame.bytes);
+            frame.write(crc as u64, 8);
+
+            write_subframe(&mut frame, block, bps);
+
+            let mut data = frame.finish();
+            let crc = crc16(&data);
+            data.extend_from_slice(&crc.to_be_bytes());
+
+            Frame {
+                data,
+                end: n * BLOCK_SIZE + block.len(),
+            }
+        })
+        .collect()
+}
+
+fn write_subframe(w: &mut BitWriter, block: &[i16], bps: u32) {
+    let samples: Vec<i32> = block.iter().map(|&s| s as i32).collect();
+    let verbatim_bits = samples.len() as u64 * bps as u64;
+
+    let best = (0..=MAX_FIXED_ORDER.min(samples.len().saturating_sub(1)))
+        .map(|order| {
+            let residual = fixed_residual(&samples, order);
+            let (parameter, bits) = rice_parameter(&residual);
+            let total = order as u64 * bps as u64 + 6 + bits;
+            (total, order, residual, parameter)
+        })
+        .min_by_key(|&(total, ..)| total);
+
+    match best {
+        Some((total, order, residual, parameter)) if total < verbatim_bits => {
+            w.write(0, 1);
+            w.write(0b001000 | order as u64, 6);
+            w.write(0, 1); // no wasted bits
+            for &s in &samples[..order] {
+                w.write_signed(s, bps);
+            }
+
+            w.write(0b00, 2); // Rice coding with 4-bit parameters
+            w.write(0, 4); // one partition
+            w.write(parameter as u64, 4);
+            for r in residual {
+                let u = ((r << 1) ^ (r >> 31)) as u32;
+                let q = u >> parameter;
+                w.write_unary(q);
+                w.write((u & ((1 << parameter) - 1)) as u64, parameter);
+            }
+        }
+        _ => {
+            w.write(0, 1);
+            w.write(0b000001, 6);
+            w.write(0, 1);
+            for &s in &samples {
+                w.write_signed(s, bps);
+            }
+        }
+    }
+}
+
+/// What's left of `samples` after predicting each from the `order`
+/// before it with the fixed polynomial predictor of that order.
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    let mut residual = samples.to_vec();
+    for _ in 0..order {
+        for i in (1..residual.len()).rev() {
+            residual[i] -= residual[i - 1];
+        }
+    }
+    residual.split_off(order)
+}
+
+/// The Rice parameter coding `residual` in the fewest bits, and how many
+/// that is.
+fn rice_parameter(residual: &[i32]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|k| {
+            let bits = residual
+                .iter()
+                .map(|&r| (((r << 1) ^ (r >> 31)) as u32 >> k) as u64 + 1 + k as u64)
+                .sum();
+            (k, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap()
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    /// Writes the low `n` bits of `v`, most significant first.
+    fn write(&mut self, v: u64, n: u32) {
+        for i in (0..n).rev() {
+            self.acc = (self.acc << 1) | ((v >> i) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn write_signed(&mut self, v: i32, n: u32) {
+        self.write(v as u32 as u64, n);
+    }
+
+    fn write_unary(&mut self, q: u32) {
+        for _ in 0..q {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+    }
+
+    /// Writes a number the way UTF-8 codes characters, as frame headers
+    /// hold frame numbers.
+    fn write_coded_number(&mut self, v: u64) {
+        if v < 0x80 {
+            self.write(v, 8);
+            return;
+        }
+
+        let mut continuation = 1;
+        while v >= 1 << (6 * continuation + 6 - continuation) {
+            continuation += 1;
+        }
+        let lead = (0xff00u64 >> (continuation + 1)) & 0xff;
+        self.write(lead | (v >> (6 * continuation)), 8);
+        for i in (0..continuation).rev() {
+            self.write(0x80 | ((v >> (6 * i)) & 0x3f), 8);
+        }
+    }
+
+    /// Pads the last byte with zero bits.
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            let n = 8 - self.bits;
+            self.write(0, n);
+        }
+        self.bytes
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ b, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ (b as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
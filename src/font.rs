@@ -0,0 +1,291 @@
+//! Bitmap fonts used for subtitles and the control panel.
+//!
+//! A character set starts with a table of [`CHAR_SET_HEADER`] bytes holding
+//! the width of each glyph less one, followed by the glyphs themselves.
+//! Every row of a glyph is a big endian data word and a big endian mask
+//! word, most significant bit leftmost. Pixels outside the mask are
+//! transparent, masked pixels are drawn in the text color where the data
+//! bit is set and as outline where it's clear.
+//!
+//! Glyphs start at the space character, and there is room in the width
+//! table for 128 of them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{sprite::Frame, text::to_utf8};
+
+pub const CHAR_SET_FILE: u16 = 60150;
+pub const CONTROL_CHAR_SET_FILE: u16 = 60520;
+pub const LINK_CHAR_SET_FILE: u16 = 60521;
+
+/// The resources holding character sets.
+pub const CHAR_SET_FILES: [u16; 3] = [CHAR_SET_FILE, CONTROL_CHAR_SET_FILE, LINK_CHAR_SET_FILE];
+
+pub const CHAR_SET_HEADER: usize = 128;
+/// The width in pixels the game wraps speech at.
+pub const SPEECH_WIDTH: usize = 128;
+pub const CHAR_HEIGHT: usize = 12;
+pub const FIRST_CHAR: u8 = 0x20;
+
+/// Pixel values of decoded glyphs.
+pub const TRANSPARENT: u8 = 0;
+pub const INK: u8 = 1;
+pub const OUTLINE: u8 = 2;
+
+/// A 6-bit palette for previewing decoded glyphs: black background, white
+/// text and a gray outline.
+pub const PREVIEW_PALETTE: [u8; 9] = [0, 0, 0, 63, 63, 63, 24, 24, 24];
+
+/// Glyphs are at most 16 pixels wide, one bit per pixel of a row word.
+pub const MAX_CHAR_WIDTH: usize = 16;
+
+/// The number of glyphs per row of a sheet as dumped.
+pub const SHEET_COLUMNS: usize = 16;
+
+pub struct Font {
+    pub height: usize,
+    pub glyphs: Vec<Frame>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GlyphInfo {
+    pub code: u8,
+    /// The character in Unicode, only written for reference.
+    #[serde(default)]
+    pub char: String,
+    pub width: usize,
+}
+
+impl Font {
+    /// Decodes a character set with glyphs `height` rows tall. Returns
+    /// `None` if the data is too short to hold the width table.
+    pub fn decode(data: &[u8], height: usize) -> Option<Font> {
+        let widths = data.get(..CHAR_SET_HEADER)?;
+        let glyph_size = 4 * height;
+
+        let glyphs = widths
+            .iter()
+            .enumerate()
+            .map_while(|(n, &width)| {
+                let ofs = CHAR_SET_HEADER + n * glyph_size;
+                let rows = data.get(ofs..ofs + glyph_size)?;
+                Some(decode_glyph(rows, (width as usize + 1).min(MAX_CHAR_WIDTH)))
+            })
+            .collect();
+
+        Some(Font { height, glyphs })
+    }
+
+    /// Cuts the glyphs out of a sheet laid out as by
+    /// [`Font::render_sheet`], one of each width in `widths` starting with
+    /// the space character. Returns `None` if there are more glyphs than
+    /// the width table has room for, a width is out of range or the sheet
+    /// is too small.
+    pub fn from_sheet(
+        sheet: &Frame,
+        columns: usize,
+        widths: &[usize],
+        height: usize,
+    ) -> Option<Font> {
+        if widths.len() > CHAR_SET_HEADER {
+            return None;
+        }
+        let cell_width = MAX_CHAR_WIDTH + 1;
+        let cell_height = height + 1;
+        if sheet.width < columns * cell_width
+            || sheet.height < widths.len().div_ceil(columns) * cell_height
+        {
+            return None;
+        }
+
+        let glyphs = widths
+            .iter()
+            .enumerate()
+            .map(|(n, &width)| {
+                if !(1..=MAX_CHAR_WIDTH).contains(&width) {
+                    return None;
+                }
+                let (column, row) = (n % columns, n / columns);
+                let data = (0..height)
+                    .flat_map(|y| {
+                        let ofs = (row * cell_height + y) * sheet.width + column * cell_width;
+                        &sheet.data[ofs..ofs + width]
+                    })
+                    .copied()
+                    .collect();
+
+                Some(Frame {
+                    width,
+                    height,
+                    data,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Font { height, glyphs })
+    }
+
+    /// Encodes the font as a character set, the inverse of
+    /// [`Font::decode`]. Entries of the width table without a glyph are
+    /// zero.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![0; CHAR_SET_HEADER];
+        for (width, glyph) in data.iter_mut().zip(&self.glyphs) {
+            *width = (glyph.width.clamp(1, MAX_CHAR_WIDTH) - 1) as u8;
+        }
+
+        for glyph in &self.glyphs {
+            for y in 0..self.height {
+                let (mut bits, mut mask) = (0u16, 0u16);
+                for x in 0..glyph.width.min(MAX_CHAR_WIDTH) {
+                    let bit = 0x8000 >> x;
+                    match glyph.data.get(y * glyph.width + x) {
+                        Some(&INK) => (bits, mask) = (bits | bit, mask | bit),
+                        Some(&OUTLINE) => mask |= bit,
+                        _ => {}
+                    }
+                }
+                data.extend(bits.to_be_bytes());
+                data.extend(mask.to_be_bytes());
+            }
+        }
+
+        data
+    }
+
+    /// The character code and width of every glyph.
+    pub fn glyph_info(&self) -> Vec<GlyphInfo> {
+        self.glyphs
+            .iter()
+            .zip(FIRST_CHAR..)
+            .map(|(glyph, code)| GlyphInfo {
+                code,
+                char: to_utf8(&[code]),
+                width: glyph.width,
+            })
+            .collect()
+    }
+
+    /// The glyph of the character `c`, if the font has one.
+    pub fn glyph(&self, c: u8) -> Option<&Frame> {
+        self.glyphs.get(c.checked_sub(FIRST_CHAR)? as usize)
+    }
+
+    /// The width of `text` in pixels. Characters without a glyph take up no
+    /// space.
+    pub fn text_width(&self, text: &[u8]) -> usize {
+        text.iter()
+            .filter_map(|&c| self.glyph(c))
+            .map(|glyph| glyph.width)
+            .sum()
+    }
+
+    /// Breaks `text` into lines at spaces, each as long as fits in
+    /// `max_width` pixels. A word wider than that gets a line of its own.
+    pub fn wrap<'a>(&self, text: &'a [u8], max_width: usize) -> Vec<&'a [u8]> {
+        let mut lines = Vec::new();
+        let mut line: Option<(usize, usize)> = None;
+
+        let mut start = 0;
+        for word in text.split(|&c| c == b' ') {
+            let end = start + word.len();
+            line = match line {
+                Some((first, _)) if self.text_width(&text[first..end]) <= max_width => {
+                    Some((first, end))
+                }
+                Some((first, last)) => {
+                    lines.push(&text[first..last]);
+                    Some((start, end))
+                }
+                None if word.is_empty() => None,
+                None => Some((start, end)),
+            };
+            start = end + 1;
+        }
+        lines.extend(line.map(|(first, last)| &text[first..last]));
+
+        lines
+    }
+
+    /// Renders `lines` of text one below the other, each centered on the
+    /// widest.
+    pub fn render_lines(&self, lines: &[&[u8]]) -> Frame {
+        let width = lines
+            .iter()
+            .map(|line| self.text_width(line))
+            .max()
+            .unwrap_or(0);
+        let height = lines.len() * self.height;
+        let mut frame = Frame {
+            width,
+            height,
+            data: vec![TRANSPARENT; width * height],
+        };
+
+        for (n, line) in lines.iter().enumerate() {
+            let mut x = (width - self.text_width(line)) / 2;
+            for glyph in line.iter().filter_map(|&c| self.glyph(c)) {
+                for y in 0..glyph.height.min(self.height) {
+                    let out_ofs = (n * self.height + y) * width + x;
+                    frame.data[out_ofs..out_ofs + glyph.width]
+                        .copy_from_slice(&glyph.data[y * glyph.width..(y + 1) * glyph.width]);
+                }
+                x += glyph.width;
+            }
+        }
+
+        frame
+    }
+
+    /// Renders all glyphs into a grid of `columns` cells per row, each
+    /// glyph in the top left corner of a cell one pixel wider and taller
+    /// than the largest glyph.
+    pub fn render_sheet(&self, columns: usize) -> Frame {
+        let cell_width = MAX_CHAR_WIDTH + 1;
+        let cell_height = self.height + 1;
+        let rows = self.glyphs.len().div_ceil(columns);
+
+        let width = columns * cell_width;
+        let mut sheet = Frame {
+            width,
+            height: rows * cell_height,
+            data: vec![TRANSPARENT; width * rows * cell_height],
+        };
+
+        for (n, glyph) in self.glyphs.iter().enumerate() {
+            let (column, row) = (n % columns, n / columns);
+            for y in 0..glyph.height {
+                let out_ofs = (row * cell_height + y) * width + column * cell_width;
+                sheet.data[out_ofs..out_ofs + glyph.width]
+                    .copy_from_slice(&glyph.data[y * glyph.width..(y + 1) * glyph.width]);
+            }
+        }
+
+        sheet
+    }
+}
+
+fn decode_glyph(rows: &[u8], width: usize) -> Frame {
+    let height = rows.len() / 4;
+    let mut data = Vec::with_capacity(width * height);
+
+    for row in rows.chunks_exact(4) {
+        let bits = u16::from_be_bytes([row[0], row[1]]);
+        let mask = u16::from_be_bytes([row[2], row[3]]);
+
+        data.extend((0..width).map(|x| {
+            let bit = 0x8000 >> x;
+            match (mask & bit != 0, bits & bit != 0) {
+                (false, _) => TRANSPARENT,
+                (true, true) => INK,
+                (true, false) => OUTLINE,
+            }
+        }));
+    }
+
+    Frame {
+        width,
+        height,
+        data,
+    }
+}
@@ -0,0 +1,146 @@
+//! Walk grids marking where characters can't go.
+//!
+//! A grid has one bit for each 8 by 8 pixel block of the 320 by 192 pixel
+//! game area at the top of a screen, [`GRID_WIDTH`] blocks to a row. Set
+//! bits are blocked. The game reads the grid as little endian 32-bit words
+//! holding the blocks from the most significant bit down.
+//!
+//! Grids are numbered in their own sequence rather than by screen, so
+//! which screen a grid belongs to is read from a CSV file with
+//! `screen,grid` records.
+
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+
+use crate::{classify::GRID_SIZE, error::Result, image::RgbImage};
+
+pub const GRID_WIDTH: usize = 40;
+pub const GRID_HEIGHT: usize = 24;
+/// Width and height in pixels of the block of a screen a bit stands for.
+pub const GRID_BLOCK: usize = 8;
+
+/// Color blocked cells are drawn in, and its opacity out of 256 when drawn
+/// over a screen.
+const BLOCKED_COLOR: [u8; 3] = [255, 0, 0];
+const BLOCKED_ALPHA: u16 = 128;
+
+pub struct Grid {
+    blocked: Vec<bool>,
+}
+
+impl Grid {
+    /// Decodes a walk grid. Returns `None` if `data` isn't the size of one.
+    pub fn decode(data: &[u8]) -> Option<Grid> {
+        if data.len() != GRID_SIZE {
+            return None;
+        }
+
+        let blocked = data
+            .chunks_exact(4)
+            .flat_map(|word| {
+                let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+                (0..32).map(move |n| word & (0x8000_0000 >> n) != 0)
+            })
+            .collect();
+
+        Some(Grid { blocked })
+    }
+
+    /// Whether the block in column `x` and row `y` is blocked.
+    pub fn is_blocked(&self, x: usize, y: usize) -> bool {
+        self.blocked[y * GRID_WIDTH + x]
+    }
+
+    /// The rows of the grid from the top, with 1 for each blocked cell and
+    /// 0 for the others.
+    pub fn rows(&self) -> Vec<Vec<u8>> {
+        self.blocked
+            .chunks_exact(GRID_WIDTH)
+            .map(|row| row.iter().map(|&b| b as u8).collect())
+            .collect()
+    }
+
+    /// Renders the grid on its own, blocked cells in red on black.
+    pub fn render(&self) -> RgbImage {
+        let mut image = RgbImage::new(GRID_WIDTH * GRID_BLOCK, GRID_HEIGHT * GRID_BLOCK);
+        self.draw(&mut image, 256);
+        image
+    }
+
+    /// Shades the blocked cells of an image of the screen the grid belongs
+    /// to in semi-transparent red.
+    pub fn overlay(&self, image: &mut RgbImage) {
+        self.draw(image, BLOCKED_ALPHA);
+    }
+
+    fn draw(&self, image: &mut RgbImage, alpha: u16) {
+        let height = image.height.min(GRID_HEIGHT * GRID_BLOCK);
+        let width = image.width.min(GRID_WIDTH * GRID_BLOCK);
+
+        for y in 0..height {
+            for x in 0..width {
+                if !self.is_blocked(x / GRID_BLOCK, y / GRID_BLOCK) {
+                    continue;
+                }
+
+                let ofs = 3 * (y * image.width + x);
+                for (c, &blocked) in image.data[ofs..ofs + 3].iter_mut().zip(&BLOCKED_COLOR) {
+                    *c = ((*c as u16 * (256 - alpha) + blocked as u16 * alpha) >> 8) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// A single blocked cell, as drawn by [`Grid::render`].
+pub fn blocked_cell() -> RgbImage {
+    let mut image = RgbImage::new(GRID_BLOCK, GRID_BLOCK);
+    for pixel in image.data.chunks_exact_mut(3) {
+        pixel.copy_from_slice(&BLOCKED_COLOR);
+    }
+    image
+}
+
+/// A screen and the walk grid used with it.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct GridPair {
+    pub screen: u16,
+    pub grid: u16,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GridMap {
+    grids: HashMap<u16, u16>,
+}
+
+impl GridMap {
+    /// Reads pairs from a CSV file with `screen` and `grid` columns, the
+    /// grid given by its resource id.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut rdr = csv::Reader::from_path(path)?;
+        let grids = rdr
+            .deserialize::<GridPair>()
+            .map(|pair| pair.map(|pair| (pair.screen, pair.grid)))
+            .collect::<std::result::Result<_, _>>()?;
+
+        Ok(GridMap { grids })
+    }
+
+    /// The grid for `screen`, if known.
+    pub fn grid(&self, screen: u16) -> Option<u16> {
+        self.grids.get(&screen).copied()
+    }
+
+    /// The screens `grid` is used with, in order.
+    pub fn screens(&self, grid: u16) -> Vec<u16> {
+        let mut screens: Vec<_> = self
+            .grids
+            .iter()
+            .filter(|&(_, &g)| g == grid)
+            .map(|(&screen, _)| screen)
+            .collect();
+        screens.sort();
+        screens
+    }
+}
@@ -0,0 +1,343 @@
+use std::io::{self, BufRead, Seek, Write};
+
+use crate::bytes_ext::WriteBytesExt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Bmp,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 3] = [ImageFormat::Ppm, ImageFormat::Png, ImageFormat::Bmp];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+            ImageFormat::Bmp => "bmp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.name()
+    }
+}
+
+#[inline]
+pub fn rescale_6_bit_color_to_8_bit(c: u8) -> u8 {
+    ((255 * c as u16) / 63) as u8
+}
+
+/// Converts a 6-bit VGA palette as stored by the game to 8-bit RGB.
+pub fn palette_to_rgb(palette: &[u8]) -> Vec<u8> {
+    palette
+        .iter()
+        .map(|&c| rescale_6_bit_color_to_8_bit(c))
+        .collect()
+}
+
+/// Writes 8-bit color indices as an indexed PNG with the 6-bit VGA
+/// `palette` embedded, or a grayscale ramp when no palette is given, so the
+/// original indices are preserved. Color index `transparent` is marked as
+/// see-through if given.
+pub fn write_indexed_png<W: Write>(
+    w: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    palette: Option<&[u8]>,
+    transparent: Option<u8>,
+) -> io::Result<()> {
+    let palette = match palette {
+        Some(palette) => palette_to_rgb(palette),
+        None => (0..=255).flat_map(|c| [c, c, c]).collect(),
+    };
+
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette);
+    if let Some(transparent) = transparent {
+        // Entries past the end of the table are opaque.
+        let mut trns = vec![255; transparent as usize + 1];
+        trns[transparent as usize] = 0;
+        encoder.set_trns(trns);
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels[..width * height])?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes 8-bit color indices as an uncompressed 8-bit BMP with the 6-bit
+/// VGA `palette` as its color table, or a grayscale ramp when no palette
+/// is given, so the original indices are preserved.
+pub fn write_indexed_bmp<W: Write>(
+    w: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    palette: Option<&[u8]>,
+) -> io::Result<()> {
+    let palette = match palette {
+        Some(palette) => palette_to_rgb(palette),
+        None => (0..=255).flat_map(|c| [c, c, c]).collect(),
+    };
+
+    // Rows are stored bottom-up, each padded to a multiple of four bytes.
+    let stride = (width + 3) & !3;
+    let image_size = (stride * height) as u32;
+    let data_offset = 14 + 40 + 4 * 256;
+
+    w.write_all(b"BM")?;
+    w.write_le_u32(data_offset + image_size)?;
+    w.write_le_u32(0)?;
+    w.write_le_u32(data_offset)?;
+
+    w.write_le_u32(40)?;
+    w.write_le_u32(width as u32)?;
+    w.write_le_u32(height as u32)?;
+    w.write_le_u16(1)?;
+    w.write_le_u16(8)?;
+    w.write_le_u32(0)?;
+    w.write_le_u32(image_size)?;
+    w.write_le_u32(2835)?;
+    w.write_le_u32(2835)?;
+    w.write_le_u32(256)?;
+    w.write_le_u32(0)?;
+
+    // The color table is in BGR order with a reserved byte, and always
+    // has all 256 entries.
+    for c in 0..256 {
+        let rgb = palette.get(3 * c..3 * c + 3).unwrap_or(&[0; 3]);
+        w.write_all(&[rgb[2], rgb[1], rgb[0], 0])?;
+    }
+
+    let mut row = vec![0u8; stride];
+    for y in (0..height).rev() {
+        row[..width].copy_from_slice(&pixels[width * y..width * (y + 1)]);
+        w.write_all(&row)?;
+    }
+
+    Ok(())
+}
+
+/// An image of 8-bit color indices with an 8-bit RGB palette, as read
+/// from an indexed PNG.
+pub struct IndexedImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+    pub palette: Vec<u8>,
+}
+
+/// Reads an 8-bit indexed PNG. Other kinds of PNG are rejected since their
+/// colors would have to be mapped back to indices.
+pub fn read_indexed_png<R: BufRead + Seek>(r: R) -> io::Result<IndexedImage> {
+    let decoder = png::Decoder::new(r);
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+
+    let info = reader.info();
+    if info.color_type != png::ColorType::Indexed || info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an 8-bit indexed PNG",
+        ));
+    }
+    let palette = info.palette.as_deref().unwrap_or_default().to_vec();
+
+    let mut data = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let frame = reader.next_frame(&mut data).map_err(io::Error::other)?;
+    data.truncate(frame.buffer_size());
+
+    Ok(IndexedImage {
+        width: frame.width as usize,
+        height: frame.height as usize,
+        data,
+        palette,
+    })
+}
+
+/// Reads a PNG of any color type as 8-bit RGB, keeping the alpha channel
+/// if it has one, for images edited outside the game's palettes.
+pub fn read_png<R: BufRead + Seek>(r: R) -> io::Result<RgbImage> {
+    let mut decoder = png::Decoder::new(r);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+
+    let mut buf = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let frame = reader.next_frame(&mut buf).map_err(io::Error::other)?;
+    buf.truncate(frame.buffer_size());
+
+    let channels = frame.color_type.samples();
+    let pixels = buf.chunks_exact(channels);
+    let data = pixels
+        .clone()
+        .flat_map(|p| match p.len() {
+            1 | 2 => [p[0]; 3],
+            _ => [p[0], p[1], p[2]],
+        })
+        .collect();
+    let alpha = matches!(
+        frame.color_type,
+        png::ColorType::GrayscaleAlpha | png::ColorType::Rgba
+    )
+    .then(|| pixels.map(|p| p[channels - 1]).collect());
+
+    Ok(RgbImage {
+        width: frame.width as usize,
+        height: frame.height as usize,
+        data,
+        alpha,
+    })
+}
+
+/// An 8-bit per channel RGB image, optionally with an alpha channel. Only
+/// PNG keeps the alpha channel, other formats are written opaque.
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+    /// One byte of opacity per pixel.
+    pub alpha: Option<Vec<u8>>,
+}
+
+impl RgbImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        RgbImage {
+            width,
+            height,
+            data: vec![0; 3 * width * height],
+            alpha: None,
+        }
+    }
+
+    /// Converts 8-bit color indices to RGB using a 6-bit VGA `palette` as
+    /// stored by the game, or as grayscale when no palette is given.
+    pub fn from_indexed(
+        width: usize,
+        height: usize,
+        pixels: &[u8],
+        palette: Option<&[u8]>,
+    ) -> Self {
+        let mut image = RgbImage::new(width, height);
+        for (out, &c) in image.data.chunks_exact_mut(3).zip(pixels) {
+            let c = c as usize;
+            match palette {
+                Some(palette) => {
+                    for n in 0..3 {
+                        out[n] = rescale_6_bit_color_to_8_bit(palette[3 * c + n]);
+                    }
+                }
+                None => out.fill(c as u8),
+            }
+        }
+        image
+    }
+
+    /// Like [`RgbImage::from_indexed`], with pixels of color index
+    /// `transparent` made fully transparent.
+    pub fn from_indexed_with_transparency(
+        width: usize,
+        height: usize,
+        pixels: &[u8],
+        palette: Option<&[u8]>,
+        transparent: u8,
+    ) -> Self {
+        let mut image = RgbImage::from_indexed(width, height, pixels, palette);
+        image.alpha = Some(
+            pixels[..width * height]
+                .iter()
+                .map(|&c| if c == transparent { 0 } else { 255 })
+                .collect(),
+        );
+        image
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(w),
+            ImageFormat::Png => self.write_png(w),
+            ImageFormat::Bmp => self.write_bmp(w),
+        }
+    }
+
+    pub fn write_ppm<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "P6 {} {} 255", self.width, self.height)?;
+        w.write_all(&self.data)
+    }
+
+    pub fn write_png<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        match self.alpha {
+            Some(ref alpha) => {
+                encoder.set_color(png::ColorType::Rgba);
+                let rgba: Vec<u8> = self
+                    .data
+                    .chunks_exact(3)
+                    .zip(alpha)
+                    .flat_map(|(rgb, &a)| [rgb[0], rgb[1], rgb[2], a])
+                    .collect();
+
+                let mut writer = encoder.write_header()?;
+                writer.write_image_data(&rgba)?;
+                writer.finish()?;
+            }
+            None => {
+                encoder.set_color(png::ColorType::Rgb);
+
+                let mut writer = encoder.write_header()?;
+                writer.write_image_data(&self.data)?;
+                writer.finish()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_bmp<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // Rows are stored bottom-up in BGR order, each padded to a multiple
+        // of four bytes.
+        let stride = (3 * self.width + 3) & !3;
+        let image_size = (stride * self.height) as u32;
+        let data_offset = 14 + 40;
+
+        w.write_all(b"BM")?;
+        w.write_le_u32(data_offset + image_size)?;
+        w.write_le_u32(0)?;
+        w.write_le_u32(data_offset)?;
+
+        w.write_le_u32(40)?;
+        w.write_le_u32(self.width as u32)?;
+        w.write_le_u32(self.height as u32)?;
+        w.write_le_u16(1)?;
+        w.write_le_u16(24)?;
+        w.write_le_u32(0)?;
+        w.write_le_u32(image_size)?;
+        w.write_le_u32(2835)?;
+        w.write_le_u32(2835)?;
+        w.write_le_u32(0)?;
+        w.write_le_u32(0)?;
+
+        let mut row = vec![0u8; stride];
+        for y in (0..self.height).rev() {
+            let line = &self.data[3 * self.width * y..3 * self.width * (y + 1)];
+            for (out, rgb) in row.chunks_exact_mut(3).zip(line.chunks_exact(3)) {
+                out.copy_from_slice(&[rgb[2], rgb[1], rgb[0]]);
+            }
+            w.write_all(&row)?;
+        }
+
+        Ok(())
+    }
+}
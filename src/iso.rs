@@ -0,0 +1,252 @@
+//! Reading files from images of CDs.
+//!
+//! The CD release keeps `sky.dnr` and `sky.dsk` on an ISO 9660 data
+//! track, with the speech stored as resources of `sky.dsk`. Images are
+//! either of the 2048 bytes of user data of each sector, as `.iso` files
+//! are, or of whole raw sectors of 2352 bytes, as the `.bin` of a
+//! `.bin`/`.cue` pair is. The layout is told from the volume descriptor
+//! at sector 16, so the `.cue` sheet is only read for the name of the
+//! `.bin`.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+const SECTOR_SIZE: usize = 2048;
+const RAW_SECTOR_SIZE: usize = 2352;
+
+/// The sector of the primary volume descriptor.
+const VOLUME_DESCRIPTOR_SECTOR: u64 = 16;
+const PRIMARY_VOLUME_DESCRIPTOR: u8 = 1;
+const STANDARD_IDENTIFIER: &[u8] = b"CD001";
+/// Offset of the directory record of the root directory in the primary
+/// volume descriptor.
+const ROOT_RECORD: usize = 156;
+
+/// The bytes every raw sector starts with.
+const SYNC: [u8; 12] = [
+    0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0,
+];
+
+const FLAG_DIRECTORY: u8 = 0x02;
+
+/// The most memory reserved up front from the size in a directory record,
+/// which a damaged image could put at gigabytes.
+const MAX_RESERVE: usize = 0x10_0000;
+
+/// How deep subdirectories are searched, as a guard against directories
+/// that contain themselves in damaged images.
+const MAX_DEPTH: usize = 8;
+
+/// A file or directory in a directory.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    /// The name without its version number.
+    pub name: String,
+    pub is_dir: bool,
+    /// The first sector of the file.
+    pub extent: u32,
+    pub size: u32,
+}
+
+/// An image of a CD with an ISO 9660 filesystem.
+pub struct IsoImage<R> {
+    reader: R,
+    /// The size of a sector in the image, and where its user data starts.
+    sector_size: usize,
+    data_offset: usize,
+    root: DirEntry,
+}
+
+impl<R: Read + Seek> IsoImage<R> {
+    /// Reads the primary volume descriptor of the image, working out how
+    /// its sectors are stored. Fails if there is none.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let layouts = [
+            (SECTOR_SIZE, 0),
+            // Mode 1 sectors have a sync pattern and header of 16 bytes
+            // before the data, mode 2 sectors a subheader of 8 more.
+            (RAW_SECTOR_SIZE, 16),
+            (RAW_SECTOR_SIZE, 24),
+        ];
+        for (sector_size, data_offset) in layouts {
+            let mut image = IsoImage {
+                reader,
+                sector_size,
+                data_offset,
+                root: DirEntry {
+                    name: String::new(),
+                    is_dir: true,
+                    extent: 0,
+                    size: 0,
+                },
+            };
+            if sector_size == RAW_SECTOR_SIZE && !image.has_sync(VOLUME_DESCRIPTOR_SECTOR)? {
+                reader = image.reader;
+                continue;
+            }
+            let descriptor = image.read_sector(VOLUME_DESCRIPTOR_SECTOR)?;
+            if descriptor[0] == PRIMARY_VOLUME_DESCRIPTOR
+                && &descriptor[1..6] == STANDARD_IDENTIFIER
+            {
+                image.root = dir_record(&descriptor[ROOT_RECORD..])
+                    .map(|(entry, _)| entry)
+                    .ok_or_else(|| invalid_data("bad root directory in CD image"))?;
+                return Ok(image);
+            }
+            reader = image.reader;
+        }
+
+        Err(invalid_data("not an ISO 9660 CD image"))
+    }
+
+    /// The entries of the root directory.
+    pub fn root(&mut self) -> io::Result<Vec<DirEntry>> {
+        let root = self.root.clone();
+        self.read_dir(&root)
+    }
+
+    /// The entries of the directory `dir`, leaving out `.` and `..`.
+    pub fn read_dir(&mut self, dir: &DirEntry) -> io::Result<Vec<DirEntry>> {
+        let data = self.read(dir)?;
+
+        // Records don't cross sector boundaries; the rest of a sector after
+        // the last record of it is zeroed.
+        let mut entries = Vec::new();
+        for sector in data.chunks(SECTOR_SIZE) {
+            let mut records = sector;
+            while let Some((entry, len)) = dir_record(records) {
+                if !entry.name.is_empty() {
+                    entries.push(entry);
+                }
+                records = &records[len..];
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// The contents of the file `file`.
+    pub fn read(&mut self, file: &DirEntry) -> io::Result<Vec<u8>> {
+        let size = file.size as usize;
+        let mut out = Vec::with_capacity(size.min(MAX_RESERVE));
+        let mut sector = file.extent as u64;
+        while out.len() < size {
+            let data = self.read_sector(sector)?;
+            let len = (size - out.len()).min(SECTOR_SIZE);
+            out.extend_from_slice(&data[..len]);
+            sector += 1;
+        }
+
+        Ok(out)
+    }
+
+    /// Looks for the file `name` in the root directory and then in the
+    /// subdirectories, matching names without regard to case.
+    pub fn find(&mut self, name: &str) -> io::Result<Option<DirEntry>> {
+        let root = self.root()?;
+        self.find_in(root, name, 0)
+    }
+
+    fn find_in(
+        &mut self,
+        entries: Vec<DirEntry>,
+        name: &str,
+        depth: usize,
+    ) -> io::Result<Option<DirEntry>> {
+        if let Some(found) = entries
+            .iter()
+            .find(|e| !e.is_dir && e.name.eq_ignore_ascii_case(name))
+        {
+            return Ok(Some(found.clone()));
+        }
+        if depth == MAX_DEPTH {
+            return Ok(None);
+        }
+
+        for dir in entries.iter().filter(|e| e.is_dir) {
+            let sub = self.read_dir(dir)?;
+            if let Some(found) = self.find_in(sub, name, depth + 1)? {
+                return Ok(Some(found));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether the raw sector `sector` starts with the sync pattern.
+    fn has_sync(&mut self, sector: u64) -> io::Result<bool> {
+        let mut sync = [0; SYNC.len()];
+        self.reader
+            .seek(SeekFrom::Start(sector * self.sector_size as u64))?;
+        match self.reader.read_exact(&mut sync) {
+            Ok(()) => Ok(sync == SYNC),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The user data of `sector`.
+    fn read_sector(&mut self, sector: u64) -> io::Result<[u8; SECTOR_SIZE]> {
+        let mut data = [0; SECTOR_SIZE];
+        let start = sector * self.sector_size as u64 + self.data_offset as u64;
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Reads the directory record at the start of `data`, returning it and its
+/// length, or `None` at the end of the records of a sector. The `.` and
+/// `..` records are returned with empty names.
+fn dir_record(data: &[u8]) -> Option<(DirEntry, usize)> {
+    let len = *data.first()? as usize;
+    if len < 34 || len > data.len() {
+        return None;
+    }
+    let record = &data[..len];
+    let name_len = record[32] as usize;
+    let name = record.get(33..33 + name_len)?;
+
+    // The `.` and `..` records are named with the bytes 0 and 1, and files
+    // have a version number after a `;`.
+    let name = match name {
+        [0] | [1] => String::new(),
+        _ => {
+            let name = String::from_utf8_lossy(name);
+            let name = name.split(';').next().unwrap_or_default();
+            name.strip_suffix('.').unwrap_or(name).to_owned()
+        }
+    };
+
+    let entry = DirEntry {
+        name,
+        is_dir: record[25] & FLAG_DIRECTORY != 0,
+        extent: u32::from_le_bytes([record[2], record[3], record[4], record[5]]),
+        size: u32::from_le_bytes([record[10], record[11], record[12], record[13]]),
+    };
+    Some((entry, len))
+}
+
+/// The image file a `.cue` sheet refers to with its first `FILE` command,
+/// relative to the directory of the sheet.
+pub fn cue_image<P: AsRef<Path>>(cue: P) -> io::Result<PathBuf> {
+    let cue = cue.as_ref();
+    let sheet = std::fs::read_to_string(cue)?;
+    let name = sheet
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("FILE "))
+        .map(|rest| match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+            None => rest.split_whitespace().next().unwrap_or_default(),
+        })
+        .next()
+        .ok_or_else(|| invalid_data("no FILE in cue sheet"))?;
+
+    Ok(cue.parent().unwrap_or(Path::new(".")).join(name))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
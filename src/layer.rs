@@ -0,0 +1,154 @@
+//! Foreground layers drawn over room screens.
+//!
+//! A room's 64000 byte screen is only its backdrop. The parts of a room
+//! that characters walk behind, such as railings, pillars and door frames,
+//! are sprites drawn in front of everything else once the backdrop and the
+//! background objects are in place. Where they are drawn is set by the
+//! object data compiled into the game rather than by anything in `sky.dsk`,
+//! so placements are read from a CSV file with `screen,layer,x,y` records
+//! and an optional `frame` column. Coordinates are in game units, which
+//! place the top left corner of the screen at ([`TOP_LEFT_X`],
+//! [`TOP_LEFT_Y`]).
+
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    error::Result,
+    image::RgbImage,
+    resource::Resource,
+    sprite::{self, Frame},
+};
+
+pub const TOP_LEFT_X: i32 = 128;
+pub const TOP_LEFT_Y: i32 = 136;
+
+/// Colors the outlines of successive layers cycle through in previews.
+const OUTLINE_COLORS: [[u8; 3]; 4] = [[255, 0, 255], [0, 255, 255], [255, 255, 0], [0, 255, 0]];
+
+/// A frame of a sprite resource drawn over a screen.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct LayerPlacement {
+    pub screen: u16,
+    pub layer: u16,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub frame: usize,
+}
+
+/// A layer frame positioned in screen pixels.
+pub struct PlacedLayer {
+    pub id: u16,
+    pub x: i32,
+    pub y: i32,
+    pub frame: Frame,
+}
+
+impl PlacedLayer {
+    /// Positions frame `placement.frame` of `resource` the way the game does,
+    /// shifted by the offsets in the sprite header. Returns `None` if the
+    /// resource has no such frame.
+    pub fn new(placement: &LayerPlacement, resource: &Resource) -> Option<Self> {
+        let header = resource.header.as_ref()?;
+        let frame = sprite::decode_frames(resource)?
+            .into_iter()
+            .nth(placement.frame)?;
+
+        Some(PlacedLayer {
+            id: placement.layer,
+            x: placement.x - TOP_LEFT_X + header.offset_x as i32,
+            y: placement.y - TOP_LEFT_Y + header.offset_y as i32,
+            frame,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LayerMap {
+    layers: HashMap<u16, Vec<LayerPlacement>>,
+}
+
+impl LayerMap {
+    /// Reads placements from a CSV file with `screen`, `layer`, `x`, `y`
+    /// and optionally `frame` columns, the first frame being used without
+    /// one. Layers are drawn in file order.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut rdr = csv::Reader::from_path(path)?;
+
+        let mut layers: HashMap<u16, Vec<LayerPlacement>> = HashMap::new();
+        for placement in rdr.deserialize::<LayerPlacement>() {
+            let placement = placement?;
+            layers.entry(placement.screen).or_default().push(placement);
+        }
+
+        Ok(LayerMap { layers })
+    }
+
+    /// The layers placed on `screen`, in drawing order.
+    pub fn layers(&self, screen: u16) -> &[LayerPlacement] {
+        self.layers.get(&screen).map_or(&[], |l| l.as_slice())
+    }
+}
+
+/// Draws `layers` in order over a copy of `screen`, leaving the screen
+/// showing through their transparent pixels. Layers are clipped to the
+/// screen.
+pub fn composite(screen: &Frame, layers: &[PlacedLayer]) -> Frame {
+    let mut out = Frame {
+        width: screen.width,
+        height: screen.height,
+        data: screen.data.clone(),
+    };
+
+    for layer in layers {
+        let frame = &layer.frame;
+        for y in 0..frame.height {
+            let Some(out_y) = clip(layer.y + y as i32, out.height) else {
+                continue;
+            };
+            for x in 0..frame.width {
+                let Some(out_x) = clip(layer.x + x as i32, out.width) else {
+                    continue;
+                };
+                let c = frame.data[y * frame.width + x];
+                if c != 0 {
+                    out.data[out_y * out.width + out_x] = c;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Outlines the bounds of each of `layers` on `image`, so the extent of
+/// every layer shows in a preview.
+pub fn outline_layers(image: &mut RgbImage, layers: &[PlacedLayer]) {
+    for (layer, color) in layers.iter().zip(OUTLINE_COLORS.iter().cycle()) {
+        let (left, top) = (layer.x, layer.y);
+        let right = left + layer.frame.width as i32 - 1;
+        let bottom = top + layer.frame.height as i32 - 1;
+
+        for x in left..=right {
+            set_pixel(image, x, top, color);
+            set_pixel(image, x, bottom, color);
+        }
+        for y in top..=bottom {
+            set_pixel(image, left, y, color);
+            set_pixel(image, right, y, color);
+        }
+    }
+}
+
+fn clip(v: i32, size: usize) -> Option<usize> {
+    usize::try_from(v).ok().filter(|&v| v < size)
+}
+
+fn set_pixel(image: &mut RgbImage, x: i32, y: i32, color: &[u8; 3]) {
+    if let (Some(x), Some(y)) = (clip(x, image.width), clip(y, image.height)) {
+        let ofs = 3 * (y * image.width + x);
+        image.data[ofs..ofs + 3].copy_from_slice(color);
+    }
+}
@@ -0,0 +1,66 @@
+//! Reading and decoding of the data files of Revolution Software's 1994
+//! game Beneath a Steel Sky.
+//!
+//! The game keeps its resources in two files: `sky.dnr`, the "dinner
+//! table" directory, and `sky.dsk`, which holds the resource data itself.
+//! [`Archive`] opens both and hands out decoded [`Resource`] values.
+
+#![allow(dead_code)]
+
+pub mod archive;
+pub mod atlas;
+pub mod audio;
+pub mod bit_queue;
+pub mod bytes_ext;
+pub mod checksum;
+pub mod classify;
+pub mod compact;
+pub mod coverage;
+pub mod cursor;
+pub mod cycle;
+pub mod decompile;
+pub mod diff;
+pub mod error;
+pub mod fat;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod flac;
+pub mod font;
+pub mod grid;
+pub mod image;
+pub mod iso;
+pub mod layer;
+pub mod midi;
+pub mod music;
+pub mod names;
+pub mod ogg;
+pub mod output;
+pub mod palette;
+#[cfg(feature = "pyo3")]
+pub mod python;
+pub mod repack;
+pub mod resource;
+pub mod rnc_compress;
+pub mod rnc_decompress;
+
+pub mod savegame;
+pub mod script;
+pub mod scummvm;
+pub mod sequence;
+pub mod sfx;
+pub mod speech;
+pub mod sprite;
+pub mod text;
+pub mod tiled;
+pub mod ui;
+pub mod version;
+pub mod vorbis;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zip;
+
+pub use archive::{Archive, Entry};
+pub use bit_queue::BitQueue;
+pub use bytes_ext::{ReadBytesExt, WriteBytesExt};
+pub use error::{Error, Result};
+pub use resource::{Header, Resource, ResourceType};
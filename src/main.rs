@@ -1,439 +1,5067 @@
-#![allow(dead_code)]
+mod browse;
 
-mod bytes_ext;
-mod rnc_decompress;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{self, BufReader, BufWriter, Cursor, Read, Seek, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Args, Parser, Subcommand,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use beneath_a_steel_sky_extract::{
+    archive::{self, Source},
+    atlas,
+    audio::{self, AudioFormat, Sound},
+    checksum::{self, Checksum, Fingerprint},
+    classify::{self, classify, GRID_FILE_START, NO_OF_SECTIONS, SCREEN_SIZE},
+    compact::{
+        self, AnimSequence, AnimStep, Compact, CptEntry, CptFile, CptType, DrawList, ScriptRef,
+        TurnTable,
+    },
+    coverage::Gap,
+    cursor::{self, Hotspot},
+    cycle::{self, CycleMap},
+    decompile,
+    diff::{self, Change},
+    font::{self, Font},
+    grid::{self, Grid, GridMap},
+    image::{
+        palette_to_rgb, read_indexed_png, read_png, rescale_6_bit_color_to_8_bit,
+        write_indexed_bmp, write_indexed_png, ImageFormat, RgbImage,
+    },
+    layer::{self, LayerMap, LayerPlacement, PlacedLayer},
+    midi, music,
+    names::ResourceNames,
+    output::{self, Output},
+    palette::{write_palette, PaletteFormat, PaletteMap},
+    repack,
+    resource::{decode_resource, read_resource, read_resource_strict, write_resource},
+    rnc_decompress::{self, decompress_rnc1},
+    savegame::{SaveFormat, SaveGame},
+    script::{self, Reference},
+    scummvm, sequence, sfx, speech,
+    sprite::{self, Frame},
+    text::{self, HuffmanTree, TextLine},
+    tiled,
+    ui::{self, UiElement, UiKind},
+    version::Version,
+    Archive, Entry, Error, Header, Resource, ResourceType, Result,
+};
+
+/// Where the files the command writes go, the output directory unless
+/// [`dump`] chose otherwise.
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+fn output() -> &'static Output {
+    OUTPUT.get_or_init(Output::directory)
+}
+
+/// Whether `path` given for a file stands for stdout.
+fn is_stdout(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Prints a message about the command to stdout, or to stderr when the
+/// files it writes go to stdout.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if STATUS_TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Set when stdout is taken by the files written, see [`status`].
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Extracts and decodes data files from Beneath a Steel Sky
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    /// Path to game data files
+    #[arg(required = true)]
+    path: Option<PathBuf>,
+
+    #[command(flatten)]
+    options: DumpArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Options of the dump, shared with `extract-all`.
+#[derive(Args, Clone)]
+struct DumpArgs {
+    /// Dump the resource list with headers, sizes and checksums to
+    /// `resources.csv`
+    #[arg(short, long, default_value_t = false)]
+    dump_csv: bool,
+
+    /// Dump the directory with headers, types and SHA-1 hashes to
+    /// `resources.json`
+    #[arg(long)]
+    dump_json: bool,
+
+    /// Write everything to a single ZIP file instead of the output
+    /// directory, with a manifest of the files in it, or to stdout for `-`
+    #[arg(long, value_name = "FILE", conflicts_with = "incremental")]
+    dump_zip: Option<PathBuf>,
+
+    /// Directory to extract the resources to
+    #[arg(short, long, default_value = "dump")]
+    output: PathBuf,
+
+    /// A further volume of a release whose data is split over several,
+    /// with a `sky.dnr` and `sky.dsk` of its own, merged with the game
+    /// into one directory. Can be repeated
+    #[arg(long = "volume", value_name = "PATH")]
+    volumes: Vec<PathBuf>,
+
+    /// Leave files in the output directory alone if they already hold what
+    /// would be written to them
+    #[arg(long)]
+    incremental: bool,
+
+    /// Decode everything as usual but only list the files that would be
+    /// written, with their sizes, without writing any
+    #[arg(long, conflicts_with = "dump_zip")]
+    dry_run: bool,
+
+    /// Report compressed resources that don't decompress as failed, rather
+    /// than dumping their data as stored
+    #[arg(long)]
+    strict: bool,
+
+    /// Image format for screens, palettes and sprites
+    #[arg(long, default_value = "png", value_parser = image_format_parser())]
+    format: ImageFormat,
+
+    /// Audio format for sounds, sound effects and speech. `ogg` is Vorbis,
+    /// `oga` holds FLAC in an Ogg container
+    #[arg(long, default_value = "wav", value_parser = audio_format_parser())]
+    audio_format: AudioFormat,
+
+    /// Resample sounds, sound effects and speech to 16-bit at 44100 Hz
+    #[arg(long)]
+    resample: bool,
+
+    /// Write screens and sprites as 8-bit indexed images with their palette
+    /// embedded: BMPs with `--format bmp`, PNGs otherwise
+    #[arg(long)]
+    indexed: bool,
+
+    /// Also pack the frames of each sprite into a single sheet image
+    #[arg(long)]
+    sprite_sheets: bool,
+
+    /// Also pack the frames of each sprite, trimmed of their transparent
+    /// edges, into texture atlases of up to 2048x2048 pixels, described by
+    /// `<id>-atlas.json`
+    #[arg(long)]
+    sprite_atlases: bool,
+
+    /// Color index left transparent in sprite PNGs and GIFs
+    #[arg(long, default_value_t = sprite::TRANSPARENT_INDEX)]
+    transparent_index: u8,
+
+    /// Id of the palette to render sprites with, grayscale if not given
+    #[arg(long)]
+    sprite_palette: Option<u16>,
+
+    /// CSV file of `screen,palette` pairs, overriding the built-in pairs
+    /// and the palette guessed from neighbouring ids
+    #[arg(long)]
+    palette_map: Option<PathBuf>,
+
+    /// CSV file of `id,name` records naming resources, added to the file
+    /// names of screens, palettes and sprites and to the resource list
+    #[arg(long, alias = "room-names")]
+    names: Option<PathBuf>,
+
+    /// CSV file of `screen,grid` pairs, for writing previews of screens
+    /// with the blocked cells of their walk grid shaded
+    #[arg(long)]
+    grid_map: Option<PathBuf>,
+
+    /// CSV file of `screen,layer,x,y[,frame]` records placing foreground
+    /// layers, for writing previews of screens with their layers drawn in
+    #[arg(long)]
+    layer_map: Option<PathBuf>,
+
+    /// Write screens with palette cycling as animated GIFs of one full
+    /// cycle, using the ranges of `--cycle-map` or else guessing them
+    #[arg(long)]
+    palette_cycles: bool,
+
+    /// CSV file of `screen,first,last` records giving the ranges of colors
+    /// cycled on screens, for `--palette-cycles`
+    #[arg(long)]
+    cycle_map: Option<PathBuf>,
+
+    /// Also write each screen as a map for the Tiled editor, with its
+    /// foreground layers and walk grid where they are known
+    #[arg(long)]
+    tiled: bool,
+
+    /// File holding the Huffman tree to decode text with. If not given, the
+    /// tree is searched for in `sky.exe` or the other executables in the
+    /// game directory
+    #[arg(long)]
+    huffman_tree: Option<PathBuf>,
+
+    /// ScummVM's `sky.cpt` to read the compacts describing game objects
+    /// from. If not given, it is searched for in the game directory
+    #[arg(long)]
+    compacts: Option<PathBuf>,
+
+    /// Also write sprites with more than one frame as animated GIFs, using
+    /// the sprite palette or else the palette with the nearest id, and
+    /// cutscenes with the palette of their screen
+    #[arg(long)]
+    gif: bool,
+
+    /// Also render every line of text with the subtitle font, wrapped the
+    /// way the game wraps speech, for checking whether translations fit
+    #[arg(long)]
+    subtitles: bool,
+
+    /// Width in pixels to wrap subtitles at
+    #[arg(long, default_value_t = font::SPEECH_WIDTH)]
+    subtitle_width: usize,
+
+    /// Only write these kinds of output: `raw` for the resources as stored,
+    /// `decoded` for everything else, or just the `images` or `audio` of
+    /// it. Can be repeated or comma separated, everything is written if not
+    /// given
+    #[arg(long, value_delimiter = ',', value_parser = dump_class_parser())]
+    dump: Vec<DumpClass>,
+
+    /// Write the raw dump as a single image of `sky.dsk` with every entry
+    /// at its offset and the bytes no entry refers to zeroed, listed in
+    /// `raw/sky.dsk.csv`, instead of a file per entry
+    #[arg(long)]
+    raw_image: bool,
+
+    /// The types of resource to dump, all if empty. Set by `extract-all`.
+    #[arg(skip)]
+    types: Vec<ResourceType>,
+
+    /// Whether to describe the layout of the dump in `pack.json`. Set by
+    /// `export-pack`.
+    #[arg(skip)]
+    pack: bool,
+}
+
+impl DumpArgs {
+    /// Whether output of `class` is to be written.
+    fn dumps(&self, class: DumpClass) -> bool {
+        self.dump.is_empty()
+            || self.dump.contains(&class)
+            || (class != DumpClass::Raw && self.dump.contains(&DumpClass::Decoded))
+    }
+
+    /// Whether resources of type `kind` are to be dumped.
+    fn extracts(&self, kind: Option<ResourceType>) -> bool {
+        self.types.is_empty() || kind.is_some_and(|kind| self.types.contains(&kind))
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the resources in the archive
+    List(ListArgs),
+    /// Extract a single resource
+    Extract(ExtractArgs),
+    /// Write a single resource to stdout, decompressed unless `--raw`
+    Cat(CatArgs),
+    /// Dump every resource of the given types, decoded as in a full dump
+    ExtractAll(Box<ExtractAllArgs>),
+    /// Dump everything decoded as an asset pack for remakes and game
+    /// engines: PNGs, sprite sheets and atlases with JSON descriptions, Ogg
+    /// Vorbis audio at 44100 Hz and UTF-8 text, described by `pack.json`
+    ExportPack(Box<ExportPackArgs>),
+    /// Describe a single resource and hexdump the start of its data
+    Info(InfoArgs),
+    /// Print counts, sizes and compression ratios of the resources by type,
+    /// the largest resources and how large sprite frames are
+    Stats(StatsArgs),
+    /// Browse the resources in the terminal, with previews of pictures
+    Browse(BrowseArgs),
+    /// Check that every resource is stored whole and decompresses, and
+    /// compare their checksums with those written from a good copy
+    Verify(VerifyArgs),
+    /// Report overlapping and duplicate entries and the bytes of `sky.dsk`
+    /// no entry refers to
+    Coverage(CoverageArgs),
+    /// List the resources added, removed and changed between two copies of
+    /// the game
+    Diff(DiffArgs),
+    /// Describe the data files the way ScummVM's detection tables do
+    ScummvmManifest(ScummvmManifestArgs),
+    /// Write the size of the dinner table and the checksums of every
+    /// resource as JSON, for adding the release to the known ones
+    Fingerprint(FingerprintArgs),
+    /// Rebuild `sky.dnr` and `sky.dsk` from a raw dump
+    Repack(RepackArgs),
+    /// Replace a single resource in `sky.dsk`, moving the resources after
+    /// it along if the new one doesn't fit
+    Patch(PatchArgs),
+    /// Convert an indexed PNG into a screen resource for `repack`
+    ImportScreen(ImportScreenArgs),
+    /// Convert an edited sheet of glyphs into a character set for `repack`
+    ImportFont(ImportFontArgs),
+    /// Convert a directory of WAV files into speech resources for `repack`
+    PackSpeech(PackSpeechArgs),
+    /// Encode edited or translated text into text resources for `repack`
+    PackText(PackTextArgs),
+    /// Draw a screen with the objects placed on it when the game starts
+    RenderRoom(RenderRoomArgs),
+    /// Gather everything a room is made of into one directory
+    ExtractRoom(ExtractRoomArgs),
+    /// Print the state held by a saved game of ScummVM or the DOS releases
+    /// as JSON
+    Savegame(SavegameArgs),
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Only list resources matching `key=value`, e.g. `type=screen`
+    #[arg(long, value_parser = parse_filter)]
+    filter: Vec<Filter>,
+
+    /// Only list resources of at least this many bytes
+    #[arg(long)]
+    min_size: Option<u32>,
+
+    /// Only list resources of at most this many bytes
+    #[arg(long)]
+    max_size: Option<u32>,
+}
+
+#[derive(Args)]
+struct ExtractArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Id of the resource to extract
+    id: u16,
+
+    /// Write the resource as stored in `sky.dsk`
+    #[arg(long, conflicts_with = "decoded")]
+    raw: bool,
+
+    /// Write the decompressed resource data without its header (default)
+    #[arg(long)]
+    decoded: bool,
+
+    /// Output file, defaults to `<id>.dmp` for raw and `<id>.bin` for
+    /// decoded resources
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CatArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Id of the resource to write
+    id: u16,
+
+    /// Write the resource as stored in `sky.dsk`
+    #[arg(long)]
+    raw: bool,
+}
+
+#[derive(Args)]
+struct ExtractAllArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Type of resource to dump. Can be repeated or comma separated
+    #[arg(
+        long = "type",
+        required = true,
+        value_delimiter = ',',
+        value_parser = resource_type_parser()
+    )]
+    types: Vec<ResourceType>,
+
+    #[command(flatten)]
+    options: DumpArgs,
+}
+
+#[derive(Args)]
+struct ExportPackArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Options as for a dump; the formats of the pack take precedence
+    #[command(flatten)]
+    options: DumpArgs,
+}
+
+#[derive(Args)]
+struct StatsArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Number of the largest resources to list
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Id of the resource to describe
+    id: u16,
+
+    /// Number of bytes of the decoded data to hexdump
+    #[arg(short = 'n', long, default_value_t = 256)]
+    bytes: usize,
+}
+
+#[derive(Args)]
+struct BrowseArgs {
+    /// Path to game data files
+    path: PathBuf,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// CSV file of `id,size,sha1` records to check against, as written by
+    /// `--write` from a copy known to be good
+    #[arg(long, conflicts_with = "write")]
+    checksums: Option<PathBuf>,
+
+    /// Write the checksums of the archive to this file instead of checking
+    /// them, for checking other copies against later
+    #[arg(long)]
+    write: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    /// Path to the game data files to compare from
+    before: PathBuf,
+
+    /// Path to the game data files to compare with
+    after: PathBuf,
+}
+
+#[derive(Args)]
+struct CoverageArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Directory to write the unreferenced ranges holding anything but
+    /// zeros to, as `<offset>.bin` with a list of all ranges in
+    /// `index.csv`
+    #[arg(long)]
+    gaps: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ScummvmManifestArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Output file, defaults to standard output
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct FingerprintArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Output file, defaults to standard output
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct RepackArgs {
+    /// Directory of `<id>.dmp` files as written to `raw` in the output
+    /// directory
+    dir: PathBuf,
+
+    /// Directory to write `sky.dnr` and `sky.dsk` to
+    out: PathBuf,
+
+    /// Keep resources at the offsets they were dumped from where they still
+    /// fit, and put back the bytes from `slack`, so the result only differs
+    /// from the original where resources changed
+    #[arg(long)]
+    preserve_layout: bool,
+}
+
+#[derive(Args)]
+struct PatchArgs {
+    /// Path to game data files, which are changed in place
+    path: PathBuf,
+
+    /// Id of the resource to replace
+    #[arg(long)]
+    id: u16,
+
+    /// The new resource as stored in `sky.dsk`, such as a `<id>.dmp` file
+    /// from the raw dump or `import-screen`
+    #[arg(long)]
+    file: PathBuf,
+}
+
+#[derive(Args)]
+struct ImportScreenArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Id of the screen to replace
+    id: u16,
+
+    /// 320x200 8-bit indexed PNG holding the new screen
+    image: PathBuf,
+
+    /// Id of the palette to check the image's colors against, by default
+    /// the one the screen is dumped with
+    #[arg(long)]
+    palette: Option<u16>,
+
+    /// RNC1 compress the screen data
+    #[arg(long)]
+    compress: bool,
+
+    /// Output file, defaults to `<id>.dmp`
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ImportFontArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Id of the character set to replace
+    id: u16,
+
+    /// PNG of the glyphs laid out as in the dumped font sheets, in black,
+    /// white and gray or transparent, white and gray
+    image: PathBuf,
+
+    /// JSON list of the code and width of every glyph, as dumped next to
+    /// the sheet. Defaults to the image's path with a `.json` extension
+    #[arg(long)]
+    widths: Option<PathBuf>,
+
+    /// RNC1 compress the character set
+    #[arg(long)]
+    compress: bool,
+
+    /// Output file, defaults to `<id>.dmp`
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PackSpeechArgs {
+    /// Directory of WAV files, each named after the id of the speech
+    /// resource it replaces, like `50001.wav`, or after the line of text it
+    /// voices, like `0x0001.wav`
+    dir: PathBuf,
+
+    /// Directory to write the `<id>.dmp` files to, usually a raw dump. Its
+    /// `index.csv` is updated to store them with a file header
+    out: PathBuf,
+}
+
+#[derive(Args)]
+struct PackTextArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Directory of `<section>.txt` files as dumped to `text/<language>`,
+    /// with a line of text id and text separated by a tab for each line
+    dir: PathBuf,
+
+    /// Directory to write the `<id>.dmp` files to, usually a raw dump. Its
+    /// `index.csv` is updated with the flags of the resources replaced
+    out: PathBuf,
+
+    /// Language the text replaces, by default the name of the directory
+    #[arg(long, value_parser = PossibleValuesParser::new(text::Language::ALL.map(|l| l.name())))]
+    language: Option<String>,
+
+    /// File holding the Huffman tree to encode text with. If not given, the
+    /// tree is searched for in `sky.exe` or the other executables in the
+    /// game directory
+    #[arg(long, conflicts_with = "new_tree")]
+    huffman_tree: Option<PathBuf>,
+
+    /// Build a new Huffman tree for the text and write it to
+    /// `huffman-tree.bin` in the output directory. The game can only read
+    /// the text once its executable has the tree
+    #[arg(long)]
+    new_tree: bool,
+
+    /// RNC1 compress the text resources
+    #[arg(long)]
+    compress: bool,
+}
+
+#[derive(Args)]
+struct RenderRoomArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Id of the screen to draw the objects over
+    id: u16,
+
+    /// Number the objects of the room give as their screen
+    #[arg(long)]
+    screen: u16,
+
+    /// Path to `sky.cpt`, searched for in the game directory if not given
+    #[arg(long)]
+    compacts: Option<PathBuf>,
+
+    /// Id of the palette to use, by default the one the screen is dumped
+    /// with
+    #[arg(long)]
+    palette: Option<u16>,
+
+    /// Image format to write
+    #[arg(long, default_value = "png", value_parser = image_format_parser())]
+    format: ImageFormat,
+
+    /// Output file, defaults to `<id>-room.png`
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ExtractRoomArgs {
+    /// Path to game data files
+    path: PathBuf,
+
+    /// Id of the room's screen
+    id: u16,
+
+    /// Number the objects of the room give as their screen
+    #[arg(long)]
+    screen: u16,
+
+    /// Path to `sky.cpt`, searched for in the game directory if not given
+    #[arg(long)]
+    compacts: Option<PathBuf>,
+
+    /// Id of the palette of the room, by default the one the screen is
+    /// dumped with
+    #[arg(long)]
+    palette: Option<u16>,
+
+    /// CSV file of `screen,grid` pairs giving the room's walk grid
+    #[arg(long)]
+    grid_map: Option<PathBuf>,
+
+    /// CSV file of `screen,layer,x,y[,frame]` records placing the room's
+    /// foreground layers
+    #[arg(long)]
+    layer_map: Option<PathBuf>,
+
+    /// File holding the Huffman tree to decode text with. If not given, the
+    /// tree is searched for in `sky.exe` or the other executables in the
+    /// game directory
+    #[arg(long)]
+    huffman_tree: Option<PathBuf>,
+
+    /// Image format to write
+    #[arg(long, default_value = "png", value_parser = image_format_parser())]
+    format: ImageFormat,
+
+    /// Directory to write to, defaults to `room-<id>`
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SavegameArgs {
+    /// Saved game to read
+    file: PathBuf,
+
+    /// ScummVM's `sky.cpt` of the same release, for telling the saved
+    /// compacts apart. If not given, it is searched for next to the saved
+    /// game
+    #[arg(long)]
+    compacts: Option<PathBuf>,
+
+    /// First script variable holding the inventory: the offset the scripts
+    /// give `fn_start_menu` for the game menu, divided by four
+    #[arg(long, value_name = "VARIABLE")]
+    inventory: Option<usize>,
+
+    /// File to write the JSON to instead of standard output
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug)]
+enum Filter {
+    Type(ResourceType),
+}
+
+fn parse_filter(s: &str) -> std::result::Result<Filter, String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `key=value`, got `{s}`"))?;
+
+    match key {
+        "type" => ResourceType::from_name(value)
+            .map(Filter::Type)
+            .ok_or_else(|| {
+                let names: Vec<_> = ResourceType::ALL.iter().map(|t| t.name()).collect();
+                format!(
+                    "unknown type `{value}`, expected one of {}",
+                    names.join(", ")
+                )
+            }),
+        _ => Err(format!("unknown filter `{key}`")),
+    }
+}
+
+/// The kinds of output the dump can be narrowed down to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum DumpClass {
+    Raw,
+    /// Decoded output that is neither images nor audio, such as text and
+    /// scripts. Asking for it asks for images and audio as well.
+    Decoded,
+    Images,
+    /// Sounds, speech and music.
+    Audio,
+}
+
+impl DumpClass {
+    const ALL: [DumpClass; 4] = [
+        DumpClass::Raw,
+        DumpClass::Decoded,
+        DumpClass::Images,
+        DumpClass::Audio,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            DumpClass::Raw => "raw",
+            DumpClass::Decoded => "decoded",
+            DumpClass::Images => "images",
+            DumpClass::Audio => "audio",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<DumpClass> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// What the decoded output of a resource of type `kind` counts as.
+    fn of(kind: ResourceType) -> DumpClass {
+        match kind {
+            ResourceType::Screen
+            | ResourceType::Palette
+            | ResourceType::Sprite
+            | ResourceType::Animation
+            | ResourceType::Font
+            | ResourceType::Grid
+            | ResourceType::Sequence => DumpClass::Images,
+            ResourceType::Audio | ResourceType::Speech | ResourceType::Music => DumpClass::Audio,
+            ResourceType::Text | ResourceType::Script => DumpClass::Decoded,
+        }
+    }
+}
+
+fn dump_class_parser() -> impl TypedValueParser<Value = DumpClass> {
+    PossibleValuesParser::new(DumpClass::ALL.map(|c| c.name()))
+        .map(|s| DumpClass::from_name(&s).unwrap())
+}
+
+fn resource_type_parser() -> impl TypedValueParser<Value = ResourceType> {
+    PossibleValuesParser::new(ResourceType::ALL.map(|t| t.name()))
+        .map(|s| ResourceType::from_name(&s).unwrap())
+}
+
+fn image_format_parser() -> impl TypedValueParser<Value = ImageFormat> {
+    PossibleValuesParser::new(ImageFormat::ALL.map(|f| f.name()))
+        .map(|s| ImageFormat::from_name(&s).unwrap())
+}
+
+fn audio_format_parser() -> impl TypedValueParser<Value = AudioFormat> {
+    PossibleValuesParser::new(AudioFormat::ALL.map(|f| f.name()))
+        .map(|s| AudioFormat::from_name(&s).unwrap())
+}
+
+fn open_archive(path: &Path) -> Result<Archive<Cursor<Vec<u8>>>> {
+    Archive::load(path).map_err(|e| Error::file(path, e))
+}
+
+fn dump_entry(out: &Path, entry: &Entry, buf: &[u8]) -> Result<()> {
+    let dump_name = output::resource_path(out, "raw", entry.number).with_extension("dmp");
+    output().write(dump_name, buf)?;
+
+    Ok(())
+}
+
+fn save_image(image: &RgbImage, name: &Path, format: ImageFormat) -> Result<()> {
+    let dump_name = name.with_extension(format.extension());
+    let mut dump_file = output().create(dump_name);
+    image.write(&mut dump_file, format)?;
+    dump_file.finish()?;
+
+    Ok(())
+}
+
+/// Saves `sound` in the audio format of `args`, resampled first if asked
+/// to.
+fn save_sound(sound: Sound, name: &Path, args: &DumpArgs) -> Result<()> {
+    let sound = if args.resample {
+        sound.resample(audio::RESAMPLE_RATE)
+    } else {
+        sound
+    };
+
+    let dump_name = name.with_extension(args.audio_format.extension());
+    let mut dump_file = output().create(dump_name);
+    sound.write(&mut dump_file, args.audio_format)?;
+    dump_file.finish()?;
+
+    Ok(())
+}
+
+/// Saves 8-bit color indices either converted to RGB in `format`, or with
+/// `indexed` as an indexed PNG, or BMP for `ImageFormat::Bmp`, keeping the
+/// indices.
+fn save_indexed_image(
+    name: &Path,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    palette: Option<&[u8]>,
+    format: ImageFormat,
+    indexed: bool,
+) -> Result<()> {
+    if !indexed {
+        let image = RgbImage::from_indexed(width, height, pixels, palette);
+        return save_image(&image, name, format);
+    }
+
+    if format == ImageFormat::Bmp {
+        let mut dump_file = output().create(name.with_extension("bmp"));
+        write_indexed_bmp(&mut dump_file, width, height, pixels, palette)?;
+        dump_file.finish()?;
+        return Ok(());
+    }
+
+    let mut dump_file = output().create(name.with_extension("png"));
+    write_indexed_png(&mut dump_file, width, height, pixels, palette, None)?;
+    dump_file.finish()?;
+
+    Ok(())
+}
+
+/// Saves a sprite frame like [`save_indexed_image`], with the transparent
+/// color index of `args` left see-through in PNGs.
+fn save_sprite_image(
+    name: &Path,
+    frame: &Frame,
+    palette: Option<&[u8]>,
+    args: &DumpArgs,
+) -> Result<()> {
+    let transparent = args.transparent_index;
+    if args.indexed && args.format == ImageFormat::Bmp {
+        // BMP has no transparency, the transparent color shows as it is.
+        return save_indexed_image(
+            name,
+            frame.width,
+            frame.height,
+            &frame.data,
+            palette,
+            args.format,
+            true,
+        );
+    }
+    if !args.indexed {
+        let image = RgbImage::from_indexed_with_transparency(
+            frame.width,
+            frame.height,
+            &frame.data,
+            palette,
+            transparent,
+        );
+        return save_image(&image, name, args.format);
+    }
+
+    let mut dump_file = output().create(name.with_extension("png"));
+    write_indexed_png(
+        &mut dump_file,
+        frame.width,
+        frame.height,
+        &frame.data,
+        palette,
+        Some(transparent),
+    )?;
+    dump_file.finish()?;
+
+    Ok(())
+}
+
+fn dump_resource_as_pal(
+    out: &Path,
+    resource: &Resource,
+    name: &str,
+    format: ImageFormat,
+) -> Result<()> {
+    let data: &Vec<u8> = &resource.data;
+
+    const SCALE: usize = 16;
+
+    let mut image = RgbImage::new(16 * SCALE, 16 * SCALE);
+    for y in 0..16 * SCALE {
+        for x in 0..16 * SCALE {
+            let out_ofs = 16 * SCALE * y + x;
+            let in_ofs = 16 * (y / 16) + (x / 16);
+
+            for j in 0..3 {
+                image.data[3 * out_ofs + j] = rescale_6_bit_color_to_8_bit(data[3 * in_ofs + j])
+            }
+        }
+    }
+
+    let dump_name = output::dump_path(out, "palette", name);
+    save_image(&image, &dump_name, format)?;
+
+    for palette_format in PaletteFormat::ALL {
+        let path = dump_name.with_extension(palette_format.extension());
+        let mut dump_file = output().create(path);
+        write_palette(
+            &mut dump_file,
+            data,
+            &format!("{:05}", resource.entry.number),
+            palette_format,
+        )?;
+        dump_file.finish()?;
+    }
+
+    Ok(())
+}
+
+fn dump_screen_in_grayscale(
+    out: &Path,
+    screen_res: &Resource,
+    name: &str,
+    format: ImageFormat,
+    indexed: bool,
+) -> Result<()> {
+    let dump_name = output::dump_path(out, "screen", &format!("{name}-grayscale"));
+    save_indexed_image(
+        &dump_name,
+        320,
+        200,
+        &screen_res.data,
+        None,
+        format,
+        indexed,
+    )
+}
+
+fn dump_screen_with_pal(
+    out: &Path,
+    screen_res: &Resource,
+    name: &str,
+    pal_res: &Resource,
+    format: ImageFormat,
+    indexed: bool,
+) -> Result<()> {
+    let dump_name = output::dump_path(out, "screen", name);
+    save_indexed_image(
+        &dump_name,
+        320,
+        200,
+        &screen_res.data,
+        Some(&pal_res.data),
+        format,
+        indexed,
+    )
+}
+
+/// Writes a screen as an animated GIF showing one full cycle of the
+/// palette `ranges`.
+fn dump_screen_cycle(
+    out: &Path,
+    screen_res: &Resource,
+    name: &str,
+    pal_res: &Resource,
+    ranges: &[cycle::CycleRange],
+) -> Result<()> {
+    let path = output::dump_path(out, "screen", &format!("{name}.gif"));
+    let mut dump_file = output().create(&path);
+    cycle::write_cycle_gif(&mut dump_file, &screen_res.data, &pal_res.data, ranges)
+        .map_err(|e| Error::file(&path, e))?;
+    dump_file.finish()?;
+
+    Ok(())
+}
+
+/// Writes a preview of a screen with its foreground layers drawn in and
+/// their bounds outlined.
+fn dump_screen_layers(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    screen_res: &Resource,
+    name: &str,
+    layers: &[LayerPlacement],
+    pal_res: Option<&Resource>,
+    format: ImageFormat,
+) -> Result<()> {
+    let placed = place_layers(archive, layers)?;
+
+    let screen = Frame {
+        width: 320,
+        height: 200,
+        data: screen_res.data.clone(),
+    };
+    let composited = layer::composite(&screen, &placed);
+
+    let palette = pal_res.map(|r| r.data.as_slice());
+    let mut image = RgbImage::from_indexed(320, 200, &composited.data, palette);
+    layer::outline_layers(&mut image, &placed);
+
+    let dump_name = output::dump_path(out, "screen", &format!("{name}-layers"));
+    save_image(&image, &dump_name, format)
+}
+
+fn place_layers(
+    archive: &Archive<Cursor<Vec<u8>>>,
+    layers: &[LayerPlacement],
+) -> Result<Vec<PlacedLayer>> {
+    layers
+        .iter()
+        .map(|placement| {
+            archive
+                .get_resource(placement.layer)
+                .and_then(|r| PlacedLayer::new(placement, &r))
+                .ok_or_else(|| {
+                    Error::InvalidData(format!(
+                        "layer {} has no frame {}",
+                        placement.layer, placement.frame
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// The palette with id `id`, failing if that resource isn't one.
+fn read_palette(archive: &Archive<Cursor<Vec<u8>>>, id: u16) -> Result<Resource> {
+    archive
+        .get_resource(id)
+        .filter(|r| r.data.len() == 768)
+        .ok_or_else(|| Error::InvalidData(format!("no palette with id {id}")))
+}
+
+/// The colors of `pal`, failing if it isn't a palette.
+fn palette_colors(pal: Option<&Resource>) -> Result<Option<&[u8]>> {
+    match pal {
+        Some(r) if r.data.len() != 768 => Err(Error::InvalidData(format!(
+            "resource {} isn't a palette",
+            r.entry.number
+        ))),
+        _ => Ok(pal.map(|r| r.data.as_slice())),
+    }
+}
+
+fn read_grid(archive: &Archive<Cursor<Vec<u8>>>, grid_id: u16) -> Result<Grid> {
+    archive
+        .get_resource(grid_id)
+        .and_then(|r| Grid::decode(&r.data))
+        .ok_or_else(|| Error::InvalidData(format!("no walk grid with id {grid_id}")))
+}
+
+/// Writes a preview of a screen with the blocked cells of its walk grid
+/// shaded.
+fn dump_screen_grid(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    screen_res: &Resource,
+    name: &str,
+    grid_id: u16,
+    pal_res: Option<&Resource>,
+    format: ImageFormat,
+) -> Result<()> {
+    let grid = read_grid(archive, grid_id)?;
+
+    let palette = palette_colors(pal_res)?;
+    let mut image = RgbImage::from_indexed(320, 200, &screen_res.data, palette);
+    grid.overlay(&mut image);
+
+    let dump_name = output::dump_path(out, "screen", &format!("{name}-grid"));
+    save_image(&image, &dump_name, format)
+}
+
+/// Writes a screen as a map for the Tiled editor to `tiled/<name>/`, along
+/// with the images it refers to. Images are always PNGs, which keep the
+/// transparency of the foreground layers.
+fn dump_tiled_map(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    screen_res: &Resource,
+    name: &str,
+    pal_res: Option<&Resource>,
+    maps: &ScreenMaps,
+    args: &DumpArgs,
+) -> Result<()> {
+    let screen_id = screen_res.entry.number;
+    let dir = output::dump_path(out, "tiled", name);
+    output()
+        .create_dir_all(&dir)
+        .map_err(|e| Error::file(&dir, e))?;
+
+    let palette = pal_res.map(|r| r.data.as_slice());
+    let image = RgbImage::from_indexed(320, 200, &screen_res.data, palette);
+    save_image(&image, &dir.join("screen"), ImageFormat::Png)?;
+
+    let mut map = tiled::Map::new("screen.png");
+    map.add_resource("screen", screen_id);
+    if let Some(pal_res) = pal_res {
+        map.add_resource("palette", pal_res.entry.number);
+    }
+
+    let placed = place_layers(archive, maps.layers.layers(screen_id))?;
+    for (n, layer) in placed.iter().enumerate() {
+        let layer_name = format!("layer-{n:02}-{:05}", layer.id);
+        let frame = &layer.frame;
+        let image = RgbImage::from_indexed_with_transparency(
+            frame.width,
+            frame.height,
+            &frame.data,
+            palette,
+            args.transparent_index,
+        );
+        save_image(&image, &dir.join(&layer_name), ImageFormat::Png)?;
+        map.add_image_layer(&layer_name, &format!("{layer_name}.png"), layer.x, layer.y);
+    }
+
+    if let Some(grid_id) = maps.grids.grid(screen_id) {
+        let grid = read_grid(archive, grid_id)?;
+        save_image(
+            &grid::blocked_cell(),
+            &dir.join("walk-grid"),
+            ImageFormat::Png,
+        )?;
+        map.add_grid(&grid, "walk-grid.png");
+        map.add_resource("grid", grid_id);
+    }
+
+    let map_path = dir.join("room.tmj");
+    output()
+        .write_json(&map_path, &map)
+        .map_err(|e| Error::file(&map_path, e))?;
+
+    Ok(())
+}
+
+/// A walk grid as written to `<id>.json` next to its image, for tools
+/// finding paths through the rooms.
+#[derive(Serialize)]
+struct JsonGrid {
+    id: u16,
+    /// The screens the grid is known to be used with.
+    screens: Vec<u16>,
+    width: usize,
+    height: usize,
+    /// Size in pixels of the square of the screen a cell stands for.
+    cell_size: usize,
+    /// Rows from the top, 1 for a blocked cell and 0 for a free one.
+    blocked: Vec<Vec<u8>>,
+}
+
+fn dump_grid(
+    out: &Path,
+    resource: &Resource,
+    format: ImageFormat,
+    maps: &ScreenMaps,
+) -> Result<()> {
+    let Some(grid) = Grid::decode(&resource.data) else {
+        return Ok(());
+    };
+
+    let id = resource.entry.number;
+    let dump_name = output::resource_path(out, "grids", id);
+    save_image(&grid.render(), &dump_name, format)?;
+    write_grid_json(&dump_name, id, &grid, maps.grids.screens(id))
+}
+
+fn write_grid_json(name: &Path, id: u16, grid: &Grid, screens: Vec<u16>) -> Result<()> {
+    let json = JsonGrid {
+        id,
+        screens,
+        width: grid::GRID_WIDTH,
+        height: grid::GRID_HEIGHT,
+        cell_size: grid::GRID_BLOCK,
+        blocked: grid.rows(),
+    };
+    let path = name.with_extension("json");
+    output()
+        .write_json(&path, &json)
+        .map_err(|e| Error::file(&path, e))?;
+
+    Ok(())
+}
+
+/// The extension of sprite images: indexed images are PNGs unless BMPs
+/// were asked for.
+fn sprite_extension(args: &DumpArgs) -> &'static str {
+    if args.indexed && args.format != ImageFormat::Bmp {
+        "png"
+    } else {
+        args.format.extension()
+    }
+}
+
+/// The frames of a sprite resource and where they are drawn, written next
+/// to them as `<id>.json`.
+#[derive(Serialize)]
+struct SpriteRecord {
+    id: u16,
+    width: usize,
+    height: usize,
+    /// Offset of the top left corner of every frame from the position of
+    /// the object showing the sprite.
+    offset_x: i16,
+    offset_y: i16,
+    frames: Vec<String>,
+    sheet: Option<String>,
+    sheet_columns: Option<usize>,
+}
+
+/// The texture atlases of a sprite resource, written as `<id>-atlas.json`.
+#[derive(Serialize)]
+struct AtlasRecord {
+    id: u16,
+    images: Vec<String>,
+    frames: Vec<AtlasFrameRecord>,
+}
+
+/// A frame of a sprite in its atlas.
+#[derive(Serialize)]
+struct AtlasFrameRecord {
+    /// Index into the images of the atlas.
+    image: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    /// Position of the trimmed frame in the untrimmed one.
+    trim_x: usize,
+    trim_y: usize,
+    source_width: usize,
+    source_height: usize,
+    /// Offset of the top left corner of the trimmed frame from the position
+    /// of the object showing the sprite.
+    anchor_x: i32,
+    anchor_y: i32,
+}
+
+/// Writes each frame of a sprite resource, the sheet of them all with
+/// `--sprite-sheets`, its atlases with `--sprite-atlases`, and a record of
+/// their size and anchor offsets.
+fn dump_sprites(
+    out: &Path,
+    resource: &Resource,
+    base: &str,
+    pal_res: Option<&Resource>,
+    args: &DumpArgs,
+) -> Result<()> {
+    let (Some(header), Some(frames)) = (&resource.header, sprite::decode_frames(resource)) else {
+        return Ok(());
+    };
+    let palette = pal_res.map(|r| r.data.as_slice());
+    let id = resource.entry.number;
+    let extension = sprite_extension(args);
+
+    let mut names = Vec::new();
+    for (n, frame) in frames.iter().enumerate() {
+        let name = format!("{base}-{n:03}");
+        save_sprite_image(
+            &output::dump_path(out, "sprites", &name),
+            frame,
+            palette,
+            args,
+        )?;
+        names.push(format!("{name}.{extension}"));
+    }
+
+    let mut sheet_name = None;
+    if args.sprite_sheets {
+        if let Some(sheet) = sprite::pack_sheet(&frames) {
+            let name = format!("{base}-sheet");
+            save_sprite_image(
+                &output::dump_path(out, "sprites", &name),
+                &sheet,
+                palette,
+                args,
+            )?;
+            sheet_name = Some(format!("{name}.{extension}"));
+        }
+    }
+
+    let record = SpriteRecord {
+        id,
+        width: header.width as usize,
+        height: header.height as usize,
+        offset_x: header.offset_x,
+        offset_y: header.offset_y,
+        frames: names,
+        sheet_columns: sheet_name
+            .as_ref()
+            .map(|_| sprite::sheet_columns(frames.len())),
+        sheet: sheet_name,
+    };
+    let path = output::dump_path(out, "sprites", &format!("{base}.json"));
+    output()
+        .write_json(&path, &record)
+        .map_err(|e| Error::file(&path, e))?;
+
+    if args.sprite_atlases {
+        dump_sprite_atlases(out, id, header, &frames, base, palette, args)?;
+    }
+
+    Ok(())
+}
+
+/// Packs the trimmed frames of a sprite into `<id>-atlas-<n>` and writes
+/// where they went to `<id>-atlas.json`. Sprites without a visible pixel
+/// get no atlas.
+fn dump_sprite_atlases(
+    out: &Path,
+    id: u16,
+    header: &Header,
+    frames: &[Frame],
+    base: &str,
+    palette: Option<&[u8]>,
+    args: &DumpArgs,
+) -> Result<()> {
+    let (trimmed, trims): (Vec<_>, Vec<_>) = frames
+        .iter()
+        .map(|frame| atlas::trim(frame, args.transparent_index))
+        .map(|t| (t.frame, (t.x, t.y)))
+        .unzip();
+    if trimmed.iter().all(|frame| frame.data.is_empty()) {
+        return Ok(());
+    }
+    let Some((atlases, placements)) =
+        atlas::pack(&trimmed, atlas::MAX_ATLAS_SIZE, args.transparent_index)
+    else {
+        return Err(Error::InvalidData(
+            "sprite frames are too large for an atlas".to_owned(),
+        ));
+    };
+
+    let extension = sprite_extension(args);
+    let mut images = Vec::new();
+    for (n, image) in atlases.iter().enumerate() {
+        let name = format!("{base}-atlas-{n}");
+        save_sprite_image(
+            &output::dump_path(out, "sprites", &name),
+            image,
+            palette,
+            args,
+        )?;
+        images.push(format!("{name}.{extension}"));
+    }
+
+    let frames = frames
+        .iter()
+        .zip(&trimmed)
+        .zip(trims)
+        .zip(placements)
+        .map(
+            |(((frame, trimmed), (trim_x, trim_y)), placement)| AtlasFrameRecord {
+                image: placement.atlas,
+                x: placement.x,
+                y: placement.y,
+                width: trimmed.width,
+                height: trimmed.height,
+                trim_x,
+                trim_y,
+                source_width: frame.width,
+                source_height: frame.height,
+                anchor_x: header.offset_x as i32 + trim_x as i32,
+                anchor_y: header.offset_y as i32 + trim_y as i32,
+            },
+        )
+        .collect();
+    let record = AtlasRecord { id, images, frames };
+    let path = output::dump_path(out, "sprites", &format!("{base}-atlas.json"));
+    output()
+        .write_json(&path, &record)
+        .map_err(|e| Error::file(&path, e))?;
+
+    Ok(())
+}
+
+fn dump_sprite_animation(
+    out: &Path,
+    resource: &Resource,
+    base: &str,
+    pal_res: &Resource,
+    transparent: u8,
+) -> Result<()> {
+    let Some(frames) = sprite::decode_frames(resource) else {
+        return Ok(());
+    };
+    if frames.len() < 2 {
+        return Ok(());
+    }
+
+    let dump_name = output::dump_path(out, "sprites", &format!("{base}.gif"));
+    let mut dump_file = output().create(dump_name);
+    sprite::write_gif(&mut dump_file, &frames, &pal_res.data, transparent)?;
+    dump_file.finish()?;
+
+    Ok(())
+}
+
+fn dump_audio(out: &Path, resource: &Resource, args: &DumpArgs) -> Result<()> {
+    let dump_name = output::resource_path(out, "audio", resource.entry.number);
+    save_sound(
+        Sound::from_u8(&resource.data, audio::SAMPLE_RATE),
+        &dump_name,
+        args,
+    )
+}
+
+/// Writes a music resource as is, driver data and all.
+fn dump_music(out: &Path, resource: &Resource) -> Result<()> {
+    let dump_name =
+        output::resource_path(out, "music", resource.entry.number).with_extension("bin");
+    output().write(dump_name, &resource.data)?;
+
+    Ok(())
+}
+
+/// Converts the tracks of the Roland and AdLib music of every section to
+/// MIDI as `music/<id>-<track>.mid` and lists them in `music/manifest.csv`.
+/// The messages setting up the MT-32 for the Roland music are written to
+/// `music/<id>.syx`.
+fn dump_music_tracks(out: &Path, archive: &Archive<Cursor<Vec<u8>>>) -> Result<()> {
+    let mut records = Vec::new();
+    for section in 0..NO_OF_SECTIONS {
+        for device in music::Device::ALL {
+            let id = device.file(section);
+            let Some(resource) = archive.get_resource(id) else {
+                continue;
+            };
+
+            let tracks = device.tracks(&resource.data, archive.version());
+            if tracks.is_empty() {
+                continue;
+            }
+
+            if device == music::Device::Roland {
+                match music::roland_sysex(&resource.data, archive.version()) {
+                    Some(sysex) => {
+                        let sysex_path =
+                            output::resource_path(out, "music", id).with_extension("syx");
+                        output()
+                            .write(&sysex_path, &sysex.concat())
+                            .map_err(|e| Error::file(&sysex_path, e))?;
+                    }
+                    None => eprintln!(
+                        "{}",
+                        Error::resource(
+                            id,
+                            Error::InvalidData("MT-32 setup doesn't fit in the data".to_owned())
+                        )
+                    ),
+                }
+            }
+
+            for track in tracks {
+                let midi_path =
+                    output::dump_path(out, "music", &format!("{id:05}-{:02}.mid", track.number));
+                let midi_tracks = device.to_midi(&resource.data, &track, archive.version());
+                let mut midi_file = output().create(&midi_path);
+                midi::write_smf(&mut midi_file, &midi_tracks)
+                    .map_err(|e| Error::file(&midi_path, e))?;
+                midi_file.finish()?;
+
+                records.push(MusicRecord {
+                    id,
+                    section,
+                    device,
+                    track: track.number,
+                    offset: format!("0x{:04x}", track.offset),
+                });
+            }
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let manifest_path = output::dump_path(out, "music", "manifest.csv");
+    let mut wtr = output().csv_writer(&manifest_path);
+    for record in records {
+        wtr.serialize(record)
+            .map_err(|e| Error::file(&manifest_path, e))?;
+    }
+    output::finish_csv(wtr).map_err(|e| Error::file(&manifest_path, e))?;
+
+    Ok(())
+}
+
+/// Writes the sound effects of every section's bank to
+/// `audio/<id>-<effect>` at their own sample rates, with their loops, and
+/// lists them in `audio/sfx.json`.
+fn dump_sound_effects(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    args: &DumpArgs,
+) -> Result<()> {
+    let mut records = Vec::new();
+    for section in 0..NO_OF_SECTIONS {
+        let id = sfx::sfx_file(section);
+        let Some(resource) = archive.get_resource(id) else {
+            continue;
+        };
+
+        let effects = match sfx::read_bank(&resource.data, section, archive.version()) {
+            Ok(effects) => effects,
+            Err(e) => {
+                eprintln!("{}", Error::resource(id, e));
+                continue;
+            }
+        };
+
+        for effect in effects {
+            let file = format!(
+                "{:05}-{:03}.{}",
+                id,
+                effect.number,
+                args.audio_format.extension()
+            );
+            let sound = Sound::from_u8(effect.samples(&resource.data), effect.sample_rate)
+                .with_loop(effect.loop_start);
+            save_sound(sound, &output::dump_path(out, "audio", &file), args)?;
+
+            records.push(SfxRecord {
+                bank: id,
+                section,
+                file,
+                effect,
+            });
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    output().write_json(output::dump_path(out, "audio", "sfx.json"), &records)?;
+
+    Ok(())
+}
+
+fn dump_speech(out: &Path, resource: &Resource, args: &DumpArgs) -> Result<()> {
+    let dump_name = output::resource_path(out, "speech", resource.entry.number);
+    save_sound(
+        Sound::from_u8(speech::samples(resource), audio::SAMPLE_RATE),
+        &dump_name,
+        args,
+    )
+}
+
+fn dump_font(out: &Path, resource: &Resource, format: ImageFormat) -> Result<()> {
+    let Some(font) = Font::decode(&resource.data, font::CHAR_HEIGHT) else {
+        return Ok(());
+    };
+
+    let dump_name = output::resource_path(out, "fonts", resource.entry.number);
+    save_font(&font, &dump_name, format)
+}
+
+/// Saves the sheet of all glyphs of `font` and a list of their widths next
+/// to it.
+fn save_font(font: &Font, dump_name: &Path, format: ImageFormat) -> Result<()> {
+    let sheet = font.render_sheet(font::SHEET_COLUMNS);
+    let image = RgbImage::from_indexed(
+        sheet.width,
+        sheet.height,
+        &sheet.data,
+        Some(&font::PREVIEW_PALETTE),
+    );
+    save_image(&image, dump_name, format)?;
+
+    output().write_json(dump_name.with_extension("json"), &font.glyph_info())?;
+
+    Ok(())
+}
+
+/// A frame of a mouse pointer, listed in `cursors/cursors.json`.
+#[derive(Serialize)]
+struct CursorRecord {
+    id: u16,
+    frame: usize,
+    file: String,
+    width: usize,
+    height: usize,
+    hotspot: Hotspot,
+}
+
+/// Writes every frame of the mouse pointers to `cursors/<id>-<frame>`, drawn
+/// with the palette of the control panel, and lists them with the hotspots
+/// from their headers in `cursors/cursors.json`.
+fn dump_cursors(out: &Path, archive: &Archive<Cursor<Vec<u8>>>, args: &DumpArgs) -> Result<()> {
+    let palette = archive
+        .get_resource(ui::palette(archive.version()))
+        .filter(|r| r.data.len() == 768);
+    let palette = palette.as_ref().map(|r| r.data.as_slice());
+    let extension = sprite_extension(args);
+
+    let mut records = Vec::new();
+    for id in cursor::MICE_FILES {
+        let Some(resource) = archive.get_resource(id) else {
+            continue;
+        };
+        let (Some(header), Some(frames)) = (&resource.header, sprite::decode_frames(&resource))
+        else {
+            continue;
+        };
+
+        for (n, frame) in frames.iter().enumerate() {
+            let name = format!("{id:05}-{n:03}");
+            save_sprite_image(
+                &output::dump_path(out, "cursors", &name),
+                frame,
+                palette,
+                args,
+            )?;
+            records.push(CursorRecord {
+                id,
+                frame: n,
+                file: format!("{name}.{extension}"),
+                width: frame.width,
+                height: frame.height,
+                hotspot: Hotspot::from_header(header),
+            });
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    output().write_json(output::dump_path(out, "cursors", "cursors.json"), &records)?;
+
+    Ok(())
+}
+
+/// The graphics of the user interface, written to `ui/ui.json`.
+#[derive(Serialize)]
+struct UiRecord {
+    /// The palette the sprites are drawn with, if the archive has it.
+    palette: Option<u16>,
+    elements: Vec<UiElementRecord>,
+}
+
+#[derive(Serialize)]
+struct UiElementRecord {
+    #[serde(flatten)]
+    element: UiElement,
+    files: Vec<String>,
+}
+
+/// Writes the graphics of the control panel, the mouse pointers and the
+/// fonts to `ui`, named after what they are, for reskinning the interface.
+/// Sprites are drawn with the palette of the control panel, a file per
+/// frame, and fonts as sheets of their glyphs.
+fn dump_ui(out: &Path, archive: &Archive<Cursor<Vec<u8>>>, args: &DumpArgs) -> Result<()> {
+    let version = archive.version();
+    let palette_id = ui::palette(version);
+    let palette = archive
+        .get_resource(palette_id)
+        .filter(|r| r.data.len() == 768);
+    let extension = sprite_extension(args);
+
+    let mut elements = Vec::new();
+    for element in ui::elements(version) {
+        let Some(resource) = archive.get_resource(element.id) else {
+            continue;
+        };
+        let name = format!("{:05}-{}", element.id, element.name);
+
+        let files = match element.kind {
+            UiKind::Sprite => {
+                let Some(frames) = sprite::decode_frames(&resource) else {
+                    continue;
+                };
+                let mut files = Vec::new();
+                for (n, frame) in frames.iter().enumerate() {
+                    let frame_name = format!("{name}-{n:03}");
+                    let pal = palette.as_ref().map(|r| r.data.as_slice());
+                    save_sprite_image(
+                        &output::dump_path(out, "ui", &frame_name),
+                        frame,
+                        pal,
+                        args,
+                    )?;
+                    files.push(format!("{frame_name}.{extension}"));
+                }
+                files
+            }
+            UiKind::Font => {
+                let Some(font) = Font::decode(&resource.data, font::CHAR_HEIGHT) else {
+                    continue;
+                };
+                save_font(&font, &output::dump_path(out, "ui", &name), args.format)?;
+                vec![
+                    format!("{name}.{}", args.format.extension()),
+                    format!("{name}.json"),
+                ]
+            }
+        };
+        elements.push(UiElementRecord {
+            element: *element,
+            files,
+        });
+    }
+
+    if elements.is_empty() {
+        return Ok(());
+    }
+
+    let record = UiRecord {
+        palette: palette.map(|_| palette_id),
+        elements,
+    };
+    output().write_json(output::dump_path(out, "ui", "ui.json"), &record)?;
+
+    Ok(())
+}
+
+/// Reads the Huffman tree from `tree` if given, otherwise from an
+/// executable in the game directory `game_path` if one has it.
+fn load_huffman_tree(game_path: &Path, tree: Option<&Path>) -> Result<Option<HuffmanTree>> {
+    let Some(path) = tree else {
+        // The demos' executables aren't all called `sky.exe`, so the
+        // others next to the data files are searched too.
+        let sky_exe = archive::find_file(game_path, "sky.exe").ok().flatten();
+        let others = archive::find_files_with_extension(game_path, "exe").unwrap_or_default();
+        let exes = sky_exe
+            .iter()
+            .chain(others.iter().filter(|&p| Some(p) != sky_exe.as_ref()));
+
+        return Ok(exes
+            .filter_map(|path| std::fs::read(path).ok())
+            .find_map(|exe| HuffmanTree::find_in_executable(&exe)));
+    };
+
+    let data = std::fs::read(path).map_err(|e| Error::file(path, e))?;
+    let tree = HuffmanTree::from_bytes(&data)
+        .ok_or_else(|| Error::file(path, Error::InvalidData("invalid Huffman tree".to_owned())))?;
+
+    Ok(Some(tree))
+}
+
+/// Reads `sky.cpt` from `compacts` if given, otherwise from the game
+/// directory `path` if it's there.
+fn load_compacts(path: &Path, compacts: Option<&Path>) -> Result<Option<CptFile>> {
+    let path = match compacts {
+        Some(path) => path.to_owned(),
+        None => match archive::find_file(path, "sky.cpt") {
+            Ok(Some(path)) => path,
+            _ => return Ok(None),
+        },
+    };
+
+    let data = std::fs::read(&path).map_err(|e| Error::file(&path, e))?;
+    let cpt = CptFile::read(&data).map_err(|e| Error::file(&path, e))?;
+
+    Ok(Some(cpt))
+}
+
+/// Writes every compact with the sprites, scripts, text and other compacts
+/// it refers to, both all together and grouped by the screen the objects
+/// are on.
+fn dump_compacts(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    cpt: &CptFile,
+    texts: &BTreeMap<u16, String>,
+) -> Result<()> {
+    let mut records = Vec::new();
+    let mut screens: BTreeMap<u16, Vec<usize>> = BTreeMap::new();
+    for entry in cpt.entries.values() {
+        if let Some(screen) = entry.compact().and_then(|c| c.get("screen")) {
+            screens.entry(screen).or_default().push(records.len());
+        }
+        records.push(json_compact(archive, cpt, entry, texts));
+    }
+
+    output().write_json(
+        output::dump_path(out, "compacts", "compacts.json"),
+        &records,
+    )?;
+
+    for (screen, indices) in screens {
+        let room: Vec<_> = indices.into_iter().map(|n| &records[n]).collect();
+        output().write_json(
+            output::dump_path(out, "compacts", &format!("screen-{screen}.json")),
+            &room,
+        )?;
+    }
+
+    dump_animations(out, archive, cpt)
+}
+
+/// An entry of `sky.cpt` with what it refers to, as listed in
+/// `compacts/compacts.json`.
+fn json_compact<'a, R>(
+    archive: &Archive<R>,
+    cpt: &CptFile,
+    entry: &'a CptEntry,
+    texts: &BTreeMap<u16, String>,
+) -> JsonCompact<'a> {
+    let named = |field: String, id: u16| JsonCompactRef {
+        field,
+        id: format!("0x{id:04x}"),
+        name: cpt.name(id).map(str::to_owned),
+    };
+
+    let compact = entry.compact();
+    let references = compact.map(|compact| {
+        let refs = compact.references();
+        JsonCompactRefs {
+            sprite_resources: refs
+                .sprite
+                .map_or_else(Vec::new, |(item, _)| sprite_resources(archive, item)),
+            sprite_frame: refs.sprite.map(|(_, frame)| frame),
+            scripts: refs.scripts,
+            text: refs
+                .text
+                .into_iter()
+                .map(|t| JsonTextRef {
+                    field: t.field,
+                    id: format!("0x{:04x}", t.id),
+                    text: texts.get(&t.id).cloned(),
+                })
+                .collect(),
+            compacts: refs
+                .compacts
+                .into_iter()
+                .map(|c| named(c.field, c.id))
+                .collect(),
+        }
+    });
+
+    JsonCompact {
+        id: format!("0x{:04x}", entry.id),
+        name: &entry.name,
+        r#type: entry.kind,
+        compact,
+        data: compact.is_none().then_some(&entry.data[..]),
+        references,
+    }
+}
+
+/// Writes the animation sequences and turn tables of `sky.cpt` with their
+/// frames and timing to `compacts/animations.json` and
+/// `compacts/turn-tables.json`.
+fn dump_animations(out: &Path, archive: &Archive<Cursor<Vec<u8>>>, cpt: &CptFile) -> Result<()> {
+    let entries = |kind| cpt.entries.values().filter(move |e| e.kind == kind);
+
+    let animations: Vec<_> = entries(CptType::AnimSequence)
+        .filter_map(|entry| {
+            let anim = AnimSequence::decode(&entry.data)?;
+            Some(JsonAnimation {
+                id: format!("0x{:04x}", entry.id),
+                name: &entry.name,
+                sprite: anim.sprite,
+                sprite_resources: sprite_resources(archive, anim.sprite),
+                cycles: anim.cycles(),
+                steps: anim.steps,
+            })
+        })
+        .collect();
+
+    output().write_json(
+        output::dump_path(out, "compacts", "animations.json"),
+        &animations,
+    )?;
+
+    let turn_tables: Vec<_> = entries(CptType::TurnTable)
+        .filter_map(|entry| {
+            let table = TurnTable::decode(&entry.data)?;
+            let turns = (0..compact::DIRECTIONS.len())
+                .flat_map(|from| (0..compact::DIRECTIONS.len()).map(move |to| (from, to)))
+                .filter(|&(from, to)| table.turns[from][to] != 0)
+                .map(|(from, to)| {
+                    let id = table.turns[from][to];
+                    let frames = cpt
+                        .entries
+                        .get(&id)
+                        .map_or_else(Vec::new, |e| compact::turn_frames(&e.data));
+                    JsonTurn {
+                        from: compact::DIRECTIONS[from],
+                        to: compact::DIRECTIONS[to],
+                        id: format!("0x{id:04x}"),
+                        name: cpt.name(id),
+                        frames: frames
+                            .into_iter()
+                            .map(|(sprite, frame)| JsonFrame { sprite, frame })
+                            .collect(),
+                    }
+                })
+                .collect();
+
+            Some(JsonTurnTable {
+                id: format!("0x{:04x}", entry.id),
+                name: &entry.name,
+                turns,
+            })
+        })
+        .collect();
+
+    output().write_json(
+        output::dump_path(out, "compacts", "turn-tables.json"),
+        &turn_tables,
+    )?;
+
+    Ok(())
+}
+
+/// Decodes the text of every section of each language there is text for.
+fn decode_text<R: Read + Seek>(
+    archive: &mut Archive<R>,
+    tree: &HuffmanTree,
+) -> BTreeMap<text::Language, Vec<TextLine>> {
+    let mut languages = BTreeMap::new();
+
+    for language in text::Language::ALL {
+        let mut all_lines = None;
+        for section in 0..text::NO_OF_TEXT_SECTIONS {
+            let Some(resource) = archive.resource(text::text_file(language.number(), section))
+            else {
+                continue;
+            };
+
+            let lines = text::decode_section(section, &resource.data, tree);
+            all_lines.get_or_insert_with(Vec::new).extend(lines);
+        }
+
+        if let Some(all_lines) = all_lines {
+            languages.insert(language, all_lines);
+        }
+    }
+
+    languages
+}
+
+/// Writes the text of each language by section and all together, and the
+/// lines of all languages side by side if there's more than one.
+fn dump_text(out: &Path, languages: &BTreeMap<text::Language, Vec<TextLine>>) -> Result<()> {
+    for (language, lines) in languages {
+        let dir = output::dump_path(out, "text", language.name());
+        output().create_dir_all(&dir)?;
+
+        for section in 0..text::NO_OF_TEXT_SECTIONS {
+            let mut section_lines = lines.iter().filter(|l| l.id >> 12 == section).peekable();
+            if section_lines.peek().is_none() {
+                continue;
+            }
+
+            let mut dump_file = output().create(dir.join(format!("{section}.txt")));
+            for line in section_lines {
+                writeln!(dump_file, "0x{:04x}\t{}", line.id, line.text)?;
+            }
+            dump_file.finish()?;
+        }
+
+        let json_lines: BTreeMap<_, _> = lines
+            .iter()
+            .map(|l| (format!("0x{:04x}", l.id), &l.text))
+            .collect();
+        output().write_json(dir.join("text.json"), &json_lines)?;
+    }
+
+    if languages.len() > 1 {
+        write_translations(
+            &output::dump_path(out, "text", "translations.csv"),
+            languages,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Renders every line of text with the subtitle font to
+/// `subtitles/<language>/<id>`, listing how many lines each wraps to and
+/// whether it fits in `subtitles/<language>/manifest.csv`.
+fn dump_subtitles(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    languages: &BTreeMap<text::Language, Vec<TextLine>>,
+    args: &DumpArgs,
+) -> Result<()> {
+    let Some(font) = archive
+        .get_resource(font::CHAR_SET_FILE)
+        .and_then(|r| Font::decode(&r.data, font::CHAR_HEIGHT))
+    else {
+        status!("No subtitle font found, skipping subtitles");
+        return Ok(());
+    };
+
+    for (language, lines) in languages {
+        let dir = output::dump_path(out, "subtitles", language.name());
+        output()
+            .create_dir_all(&dir)
+            .map_err(|e| Error::file(&dir, e))?;
+
+        let records = lines
+            .par_iter()
+            .map(|line| {
+                let wrapped = font.wrap(&line.bytes, args.subtitle_width);
+                let frame = font.render_lines(&wrapped);
+                if frame.width > 0 {
+                    let image = RgbImage::from_indexed(
+                        frame.width,
+                        frame.height,
+                        &frame.data,
+                        Some(&font::PREVIEW_PALETTE),
+                    );
+                    save_image(&image, &dir.join(format!("0x{:04x}", line.id)), args.format)?;
+                }
+
+                Ok(SubtitleRecord {
+                    id: format!("0x{:04x}", line.id),
+                    lines: wrapped.len(),
+                    width: frame.width,
+                    fits: frame.width <= args.subtitle_width,
+                    text: line.text.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let manifest_path = dir.join("manifest.csv");
+        let mut wtr = output().csv_writer(&manifest_path);
+        for record in records {
+            wtr.serialize(record)
+                .map_err(|e| Error::file(&manifest_path, e))?;
+        }
+        output::finish_csv(wtr).map_err(|e| Error::file(&manifest_path, e))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the lines of all languages side by side, one row per text id.
+fn write_translations(
+    path: &Path,
+    languages: &BTreeMap<text::Language, Vec<TextLine>>,
+) -> Result<()> {
+    let by_id: Vec<BTreeMap<u16, &str>> = languages
+        .values()
+        .map(|lines| lines.iter().map(|l| (l.id, l.text.as_str())).collect())
+        .collect();
+
+    let mut ids: Vec<u16> = by_id
+        .iter()
+        .flat_map(|lines| lines.keys())
+        .copied()
+        .collect();
+    ids.sort();
+    ids.dedup();
+
+    let mut wtr = output().csv_writer(path);
+    let header = ["id"].into_iter().chain(languages.keys().map(|l| l.name()));
+    wtr.write_record(header).map_err(|e| Error::file(path, e))?;
+    for id in ids {
+        let texts = by_id
+            .iter()
+            .map(|lines| lines.get(&id).copied().unwrap_or(""));
+        let record = [format!("0x{id:04x}")]
+            .into_iter()
+            .chain(texts.map(str::to_owned));
+        wtr.write_record(record).map_err(|e| Error::file(path, e))?;
+    }
+    output::finish_csv(wtr).map_err(|e| Error::file(path, e))?;
+
+    Ok(())
+}
+
+/// Writes a listing of a script module and its decompiled pseudo-code, with
+/// the text ids passed to engine functions looked up in `texts`.
+fn dump_script(out: &Path, resource: &Resource, texts: &BTreeMap<u16, String>) -> Result<()> {
+    let Some(module) = script::Module::new(resource.entry.number, &resource.data) else {
+        return Ok(());
+    };
+
+    let dump_name =
+        output::resource_path(out, "scripts", resource.entry.number).with_extension("asm");
+    let mut dump_file = output().create(dump_name);
+    module.write_asm(&mut dump_file, |id| texts.get(&id).cloned())?;
+    dump_file.finish()?;
+
+    let dump_name =
+        output::resource_path(out, "scripts", resource.entry.number).with_extension("txt");
+    let mut dump_file = output().create(dump_name);
+    decompile::write_module(&mut dump_file, &module, |id| texts.get(&id).cloned())?;
+    dump_file.finish()?;
+
+    Ok(())
+}
+
+/// A reference of a script, listed in `scripts/references.json` and
+/// `scripts/references.csv`.
+#[derive(Serialize)]
+struct ScriptReferenceRecord {
+    script: String,
+    module: u16,
+    kind: Reference,
+    id: u16,
+    /// The line of text, or the name of the compact in `sky.cpt`.
+    name: Option<String>,
+    /// The sprite item an animation shows, the engine's number for a
+    /// sprite resource.
+    sprite: Option<u16>,
+}
+
+/// Lists what the constant arguments of the engine functions each script
+/// calls refer to: compacts, text, animations and the sprites they show,
+/// sound effects, music and rooms.
+fn dump_script_references(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    texts: &BTreeMap<u16, String>,
+    cpt: Option<&CptFile>,
+) -> Result<()> {
+    let name = |id: u16| cpt.and_then(|cpt| cpt.name(id)).map(str::to_owned);
+    let sprite = |id: u16| {
+        let entry = cpt?.entries.get(&id)?;
+        (entry.kind == CptType::AnimSequence)
+            .then(|| AnimSequence::decode(&entry.data))
+            .flatten()
+            .map(|anim| anim.sprite)
+    };
+
+    let mut records = Vec::new();
+    for entry in archive.entries() {
+        let Some(module) = archive
+            .get_resource(entry.number)
+            .and_then(|r| script::Module::new(entry.number, &r.data))
+        else {
+            continue;
+        };
+
+        for (script, refs) in module.references() {
+            for (kind, id) in refs {
+                let (name, sprite) = match kind {
+                    Reference::Text => (texts.get(&id).cloned(), None),
+                    Reference::Compact => (name(id), None),
+                    Reference::Animation => (name(id), sprite(id)),
+                    Reference::Sfx | Reference::Music | Reference::Room => (None, None),
+                };
+                records.push(ScriptReferenceRecord {
+                    script: format!("0x{script:04x}"),
+                    module: entry.number,
+                    kind,
+                    id,
+                    name,
+                    sprite,
+                });
+            }
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    output().write_json(
+        output::dump_path(out, "scripts", "references.json"),
+        &records,
+    )?;
+
+    let csv_path = output::dump_path(out, "scripts", "references.csv");
+    let mut wtr = output().csv_writer(&csv_path);
+    for record in &records {
+        wtr.serialize(record)
+            .map_err(|e| Error::file(&csv_path, e))?;
+    }
+    output::finish_csv(wtr).map_err(|e| Error::file(&csv_path, e))?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct SfxRecord {
+    bank: u16,
+    section: u16,
+    file: String,
+    #[serde(flatten)]
+    effect: sfx::Effect,
+}
+
+#[derive(Debug, Serialize)]
+struct SubtitleRecord {
+    id: String,
+    lines: usize,
+    width: usize,
+    /// Whether every line fits in the wrapping width, which fails only
+    /// when a single word doesn't.
+    fits: bool,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CutsceneInfo {
+    screen: u16,
+    palette: Option<u16>,
+    frame_delay_ms: u32,
+    frames: usize,
+    sequences: Vec<CutsceneSequence>,
+}
+
+#[derive(Debug, Serialize)]
+struct CutsceneSequence {
+    id: u16,
+    first_frame: usize,
+    frames: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct MusicRecord {
+    id: u16,
+    section: u16,
+    device: music::Device,
+    track: u16,
+    offset: String,
+}
+
+/// What a resource was dumped as.
+struct Dumped {
+    kind: Option<ResourceType>,
+    speech: Option<SpeechRecord>,
+}
+
+/// A row of `speech/manifest.csv`, lining a speech file up with the line
+/// of text it voices.
+#[derive(Debug, Serialize)]
+struct SpeechRecord {
+    id: u16,
+    /// The file name in `speech/`.
+    file: String,
+    /// Length in seconds.
+    duration: String,
+    text_id: Option<String>,
+    text_section: Option<u16>,
+    text_line: Option<u16>,
+    /// The line in the first language there is text for.
+    text: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonCompact<'a> {
+    id: String,
+    name: &'a str,
+    r#type: CptType,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    compact: Option<Compact<'a>>,
+    /// The raw words of entries that aren't compacts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<&'a [u16]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    references: Option<JsonCompactRefs>,
+}
+
+#[derive(Serialize)]
+struct JsonCompactRefs {
+    /// Resources that may hold the object's sprite. The engine only keeps
+    /// the low eleven bits of the resource id.
+    sprite_resources: Vec<u16>,
+    sprite_frame: Option<u16>,
+    scripts: Vec<ScriptRef>,
+    text: Vec<JsonTextRef>,
+    compacts: Vec<JsonCompactRef>,
+}
+
+#[derive(Serialize)]
+struct JsonTextRef {
+    field: String,
+    id: String,
+    text: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonCompactRef {
+    field: String,
+    id: String,
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonAnimation<'a> {
+    id: String,
+    name: &'a str,
+    sprite: u16,
+    /// Resources that may hold the sprite, as for compacts.
+    sprite_resources: Vec<u16>,
+    cycles: usize,
+    steps: Vec<AnimStep>,
+}
+
+#[derive(Serialize)]
+struct JsonTurnTable<'a> {
+    id: String,
+    name: &'a str,
+    turns: Vec<JsonTurn<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonTurn<'a> {
+    from: &'static str,
+    to: &'static str,
+    id: String,
+    name: Option<&'a str>,
+    frames: Vec<JsonFrame>,
+}
+
+#[derive(Serialize)]
+struct JsonFrame {
+    sprite: u16,
+    frame: u16,
+}
+
+#[derive(Debug, Serialize)]
+struct CsvRecord {
+    r#type: String,
+    id: i32,
+    palette: Option<i32>,
+    comment: String,
+    size: usize,
+    flags: Option<u16>,
+    x: Option<u16>,
+    y: Option<u16>,
+    width: Option<u16>,
+    height: Option<u16>,
+    sp_size: Option<u16>,
+    tot_size: Option<u16>,
+    n_sprites: Option<u16>,
+    offset_x: Option<i16>,
+    offset_y: Option<i16>,
+    compressed_size: Option<u16>,
+    /// Size as stored in `sky.dsk`, header included, where `size` is that
+    /// of the decompressed data.
+    stored_size: u32,
+    compressed: bool,
+    /// SHA-1 of the decompressed data.
+    sha1: String,
+    /// CRC-32 of the data as stored.
+    crc32: String,
+    /// Why the data of a compressed resource is given as stored rather
+    /// than decompressed, if it is.
+    decompress_error: Option<String>,
+    /// The volume the resource is stored in, for an archive merged from
+    /// several.
+    volume: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDirectory {
+    version: Option<String>,
+    resources: Vec<JsonRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRecord {
+    id: u16,
+    offset: u32,
+    size: u32,
+    has_file_header: bool,
+    uses_file_header: bool,
+    r#type: Option<ResourceType>,
+    header: Option<Header>,
+    /// Length of the data after decompression, without its header.
+    data_size: usize,
+    /// Hash of the resource as stored in `sky.dsk`.
+    sha1: String,
+    /// The volume the resource is stored in, for an archive merged from
+    /// several.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume: Option<usize>,
+    /// What identified a sound as one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identified_by: Option<&'static str>,
+}
+
+fn write_json_directory<R: Source + Sync>(archive: &Archive<R>, path: &str) -> Result<()> {
+    let version = archive.version();
+
+    let resources = archive
+        .entries()
+        .par_iter()
+        .map_init(
+            || archive.reader(),
+            |reader, entry| {
+                let data = match reader {
+                    Ok(reader) => reader.read_entry(entry),
+                    Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+                };
+                let data = match data {
+                    Ok(data) => data,
+                    Err(e) => {
+                        eprintln!("{}", Error::resource(entry.number, e));
+                        return Ok(None);
+                    }
+                };
+                let sha1 = sha1_smol::Sha1::from(&data).digest().to_string();
+                let resource = read_resource(entry, data)?;
+                let r#type = classify(&resource, version);
+
+                Ok(Some(JsonRecord {
+                    id: entry.number,
+                    offset: entry.offset,
+                    size: entry.size,
+                    has_file_header: entry.has_file_header,
+                    uses_file_header: entry.uses_file_header,
+                    r#type,
+                    identified_by: identified_by(&resource, version, r#type),
+                    data_size: resource.data.len(),
+                    header: resource.header,
+                    sha1,
+                    volume: (archive.volume_count() > 1).then(|| archive.volume(entry)),
+                }))
+            },
+        )
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let json = JsonDirectory {
+        version: version.map(|v| v.to_string()),
+        resources,
+    };
+
+    output().write_json(path, &json)?;
+
+    Ok(())
+}
+
+/// How `resource` was found to be a sound, if it was classified as one as
+/// `r#type`.
+fn identified_by(
+    resource: &Resource,
+    version: Option<&Version>,
+    r#type: Option<ResourceType>,
+) -> Option<&'static str> {
+    classify::audio_evidence(resource, version)
+        .filter(|evidence| Some(evidence.resource_type()) == r#type)
+        .map(|evidence| evidence.description())
+}
+
+fn list(args: &ListArgs) -> Result<()> {
+    let mut archive = open_archive(&args.path)?;
+
+    let directory = archive.entries().to_vec();
+    let version = archive.version();
+
+    println!("   id   offset     size  flags  type");
+    for entry in &directory {
+        if args.min_size.is_some_and(|min| entry.size < min)
+            || args.max_size.is_some_and(|max| entry.size > max)
+        {
+            continue;
+        }
+
+        let resource = match archive.read_resource(entry) {
+            Ok(resource) => resource,
+            Err(e) => {
+                eprintln!("{}", Error::resource(entry.number, e));
+                continue;
+            }
+        };
+        let resource_type = classify(&resource, version);
+
+        let matches = args.filter.iter().all(|filter| match filter {
+            Filter::Type(t) => resource_type == Some(*t),
+        });
+        if !matches {
+            continue;
+        }
+
+        let flags = resource
+            .header
+            .as_ref()
+            .map_or("-".to_owned(), |h| format!("0x{:04x}", h.flags));
+        let resource_type = resource_type.map_or("-", |t| t.name());
+
+        println!(
+            "{:05} {:08x} {:8} {:>6}  {}",
+            entry.number, entry.offset, entry.size, flags, resource_type
+        );
+    }
+
+    Ok(())
+}
+
+fn extract(args: &ExtractArgs) -> Result<()> {
+    let mut archive = open_archive(&args.path)?;
+
+    let entry = *archive
+        .entry(args.id)
+        .ok_or(Error::NoSuchResource(args.id))?;
+
+    let data = archive
+        .read_entry(&entry)
+        .map_err(|e| Error::resource(entry.number, e))?;
+
+    let (data, extension) = if args.raw {
+        (data, "dmp")
+    } else {
+        let resource = read_resource(&entry, data).map_err(|e| Error::resource(entry.number, e))?;
+        (resource.data, "bin")
+    };
+
+    let out = args
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{:05}.{extension}", entry.number)));
+
+    std::fs::write(&out, data).map_err(|e| Error::file(out, e))
+}
+
+fn cat(args: &CatArgs) -> Result<()> {
+    let mut archive = open_archive(&args.path)?;
+
+    let entry = *archive
+        .entry(args.id)
+        .ok_or(Error::NoSuchResource(args.id))?;
+
+    let data = archive
+        .read_entry(&entry)
+        .map_err(|e| Error::resource(entry.number, e))?;
+
+    let data = if args.raw {
+        data
+    } else {
+        read_resource(&entry, data)
+            .map_err(|e| Error::resource(entry.number, e))?
+            .data
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(&data).and_then(|()| stdout.flush()) {
+        // Whatever reads the output may stop early, like `head` does.
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn extract_all(args: &ExtractAllArgs) -> Result<()> {
+    let options = DumpArgs {
+        types: args.types.clone(),
+        ..args.options.clone()
+    };
+
+    dump(&args.path, &options)
+}
+
+fn export_pack(args: &ExportPackArgs) -> Result<()> {
+    let options = DumpArgs {
+        format: ImageFormat::Png,
+        audio_format: AudioFormat::Ogg,
+        resample: true,
+        indexed: false,
+        sprite_sheets: true,
+        sprite_atlases: true,
+        dump: vec![DumpClass::Decoded],
+        pack: true,
+        ..args.options.clone()
+    };
+
+    dump(&args.path, &options)
+}
+
+/// The largest side of sprite frames each row of the histogram of `stats`
+/// counts up to, the last row counting the rest.
+const FRAME_SIZE_BUCKETS: [usize; 7] = [8, 16, 32, 64, 128, 256, 320];
+
+/// Width of the longest bar of the histogram of `stats`.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// The counts and sizes of resources of one type, for `stats`.
+#[derive(Default)]
+struct TypeStats {
+    count: usize,
+    compressed: usize,
+    /// Bytes as stored in `sky.dsk`.
+    stored: u64,
+    /// Bytes of the data once decompressed, without headers.
+    decoded: u64,
+}
+
+impl TypeStats {
+    fn add(&mut self, other: &TypeStats) {
+        self.count += other.count;
+        self.compressed += other.compressed;
+        self.stored += other.stored;
+        self.decoded += other.decoded;
+    }
+
+    fn print(&self, name: &str) {
+        let ratio = if self.decoded == 0 {
+            "-".to_owned()
+        } else {
+            format!("{:.1}%", 100.0 * self.stored as f64 / self.decoded as f64)
+        };
+        println!(
+            "  {name:<10} {:>6} {:>6} {:>6} {:>11} {:>11} {ratio:>7}",
+            self.count,
+            self.compressed,
+            self.count - self.compressed,
+            self.stored,
+            self.decoded,
+        );
+    }
+}
+
+fn stats(args: &StatsArgs) -> Result<()> {
+    let archive = open_archive(&args.path)?;
+    let version = archive.version();
+
+    let resources: Vec<_> = archive
+        .entries()
+        .par_iter()
+        .filter_map(|entry| {
+            let resource = archive.get_resource(entry.number);
+            if resource.is_none() {
+                eprintln!(
+                    "{}",
+                    Error::resource(
+                        entry.number,
+                        Error::InvalidData("doesn't decode".to_owned())
+                    )
+                );
+            }
+            resource
+        })
+        .map(|resource| (classify(&resource, version), resource))
+        .collect();
+
+    let mut by_type: BTreeMap<Option<ResourceType>, TypeStats> = BTreeMap::new();
+    for (kind, resource) in &resources {
+        let stats = by_type.entry(*kind).or_default();
+        stats.count += 1;
+        stats.compressed += resource.is_compressed() as usize;
+        stats.stored += resource.entry.size as u64;
+        stats.decoded += resource.data.len() as u64;
+    }
+
+    match version {
+        Some(version) => println!("Version: {version}"),
+        None => println!("Unknown game version"),
+    }
+    println!();
+    println!("  type        count    rnc    raw      stored     decoded   ratio");
+    let rows = ResourceType::ALL
+        .iter()
+        .map(|kind| (kind.name(), by_type.get(&Some(*kind))))
+        .chain([("unknown", by_type.get(&None))]);
+    let mut total = TypeStats::default();
+    for (name, stats) in rows {
+        if let Some(stats) = stats {
+            stats.print(name);
+            total.add(stats);
+        }
+    }
+    total.print("total");
+
+    let mut largest: Vec<_> = resources.iter().collect();
+    largest.sort_by_key(|(_, r)| (std::cmp::Reverse(r.data.len()), r.entry.number));
+    println!();
+    println!("Largest resources:");
+    for (kind, resource) in largest.into_iter().take(args.top) {
+        println!(
+            "  {:05} {:>9} bytes  {}",
+            resource.entry.number,
+            resource.data.len(),
+            kind.map_or("unknown", |k| k.name())
+        );
+    }
+
+    let mut histogram = [0usize; FRAME_SIZE_BUCKETS.len() + 1];
+    for (kind, resource) in &resources {
+        if !matches!(kind, Some(ResourceType::Sprite | ResourceType::Animation)) {
+            continue;
+        }
+        let Some(header) = &resource.header else {
+            continue;
+        };
+        let side = header.width.max(header.height) as usize;
+        let bucket = FRAME_SIZE_BUCKETS
+            .iter()
+            .position(|&max| side <= max)
+            .unwrap_or(FRAME_SIZE_BUCKETS.len());
+        histogram[bucket] += header.n_sprites as usize;
+    }
+    let most = histogram.iter().copied().max().unwrap_or(0);
+    if most > 0 {
+        println!();
+        println!("Sprite frames by largest side:");
+        for (bucket, &count) in histogram.iter().enumerate() {
+            let label = match FRAME_SIZE_BUCKETS.get(bucket) {
+                Some(max) => format!("<= {max}"),
+                None => format!("> {}", FRAME_SIZE_BUCKETS[FRAME_SIZE_BUCKETS.len() - 1]),
+            };
+            let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(most));
+            let line = format!("  {label:>6} {count:>6} {bar}");
+            println!("{}", line.trim_end());
+        }
+    }
+
+    Ok(())
+}
+
+fn info(args: &InfoArgs) -> Result<()> {
+    let mut archive = open_archive(&args.path)?;
+    let version = archive.version();
+
+    let entry = *archive
+        .entry(args.id)
+        .ok_or(Error::NoSuchResource(args.id))?;
+    let raw = archive
+        .read_entry(&entry)
+        .map_err(|e| Error::resource(entry.number, e))?;
+    let resource =
+        read_resource(&entry, raw.clone()).map_err(|e| Error::resource(entry.number, e))?;
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+    println!("Resource {:05}", entry.number);
+    println!("  offset             0x{:08x}", entry.offset);
+    println!("  stored size        {}", entry.size);
+    println!("  has file header    {}", yes_no(entry.has_file_header));
+    println!("  uses file header   {}", yes_no(entry.uses_file_header));
+    let r#type = classify(&resource, version);
+    println!(
+        "  type               {}",
+        r#type.map_or("unknown", |t| t.name())
+    );
+    if let Some(evidence) = identified_by(&resource, version, r#type) {
+        println!("  identified by      {evidence}");
+    }
+
+    if let Some(header) = &resource.header {
+        println!("Header");
+        println!("  flags              0x{:04x}", header.flags);
+        println!("  x                  {}", header.x);
+        println!("  y                  {}", header.y);
+        println!("  width              {}", header.width);
+        println!("  height             {}", header.height);
+        println!("  sp_size            {}", header.sp_size);
+        println!("  tot_size           {}", header.tot_size);
+        println!("  n_sprites          {}", header.n_sprites);
+        println!("  offset_x           {}", header.offset_x);
+        println!("  offset_y           {}", header.offset_y);
+        println!("  compressed_size    {}", header.compressed_size);
+    }
+
+    let compression = if !resource.is_compressed() {
+        "no".to_owned()
+    } else {
+        let stream = &raw[Header::SIZE..];
+        let method = rnc_decompress::method(stream)
+            .map_or_else(|| "RNC".to_owned(), |method| format!("RNC method {method}"));
+        match decompress_rnc1(&mut Cursor::new(stream)) {
+            Ok(_) => method,
+            Err(e) => format!("{method}, but fails to decompress: {e}"),
+        }
+    };
+    println!("Data");
+    println!("  compressed         {compression}");
+    println!("  size               {}", resource.data.len());
+    if let Some(format) = sprite::pixel_format(&resource) {
+        println!("  pixel format       {}", format.name());
+    }
+
+    let shown = &resource.data[..args.bytes.min(resource.data.len())];
+    if !shown.is_empty() {
+        println!();
+        print!("{}", hexdump(shown));
+    }
+
+    Ok(())
+}
+
+fn browse(args: &BrowseArgs) -> Result<()> {
+    let archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    browse::run(archive)?;
+
+    Ok(())
+}
+
+/// Formats `data` sixteen bytes to a line, with offsets and the printable
+/// characters alongside.
+fn hexdump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (n, line) in data.chunks(16).enumerate() {
+        let hex: Vec<_> = line.iter().map(|b| format!("{b:02x}")).collect();
+        let text: String = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:08x}  {:<47}  |{text}|\n",
+            n * 16,
+            hex.join(" ")
+        ));
+    }
+    out
+}
+
+fn repack(args: &RepackArgs) -> Result<()> {
+    let directory = repack::repack(&args.dir, &args.out, args.preserve_layout)
+        .map_err(|e| Error::file(&args.dir, e))?;
+
+    println!(
+        "Wrote {} resources to `{}`",
+        directory.len(),
+        args.out.display()
+    );
+
+    Ok(())
+}
+
+fn patch(args: &PatchArgs) -> Result<()> {
+    let (dnr_path, dsk_path) =
+        archive::find_data_files(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let mut directory = archive::read_dinner_table(&mut BufReader::new(
+        File::open(&dnr_path).map_err(|e| Error::file(&dnr_path, e))?,
+    ))
+    .map_err(|e| Error::file(&dnr_path, e))?;
+    let mut dsk = std::fs::read(&dsk_path).map_err(|e| Error::file(&dsk_path, e))?;
+    let data = std::fs::read(&args.file).map_err(|e| Error::file(&args.file, e))?;
+
+    if !directory.iter().any(|e| e.number == args.id) {
+        return Err(Error::NoSuchResource(args.id));
+    }
+    let patched = repack::patch_resource(&mut directory, &mut dsk, args.id, &data)
+        .map_err(|e| Error::resource(args.id, e))?;
+
+    // The directory is built before anything is written, as it fails on
+    // offsets out of range, so nothing is changed then. Both files are
+    // written next to the originals before either replaces its original,
+    // so a failed write doesn't leave a directory for other data.
+    let mut dnr = Vec::new();
+    archive::write_dinner_table(&mut dnr, &directory).map_err(|e| Error::file(&dnr_path, e))?;
+    let dsk_tmp = dsk_path.with_extension("dsk.tmp");
+    let dnr_tmp = dnr_path.with_extension("dnr.tmp");
+    let written = std::fs::write(&dsk_tmp, dsk)
+        .map_err(|e| Error::file(&dsk_tmp, e))
+        .and_then(|()| std::fs::write(&dnr_tmp, dnr).map_err(|e| Error::file(&dnr_tmp, e)));
+    if let Err(e) = written {
+        _ = std::fs::remove_file(&dsk_tmp);
+        _ = std::fs::remove_file(&dnr_tmp);
+        return Err(e);
+    }
+    std::fs::rename(&dsk_tmp, &dsk_path).map_err(|e| Error::file(&dsk_path, e))?;
+    std::fs::rename(&dnr_tmp, &dnr_path).map_err(|e| Error::file(&dnr_path, e))?;
+
+    match patched {
+        repack::Patched::InPlace { padding } => println!(
+            "Replaced {:05} in place, {} bytes of padding left",
+            args.id, padding
+        ),
+        repack::Patched::Moved { shift, moved } => println!(
+            "Replaced {:05}, moving {} resources after it along by {} bytes",
+            args.id, moved, shift
+        ),
+    }
+
+    Ok(())
+}
+
+/// The sprite resource an item number stands for on the screen
+/// `screen_id`. Sprites are loaded for the section being played, which is
+/// most likely the one the screen is stored in.
+fn room_sprite<R>(archive: &Archive<R>, item: u16, screen_id: u16) -> Option<u16> {
+    let candidates = sprite_resources(archive, item);
+    match candidates[..] {
+        [id] => Some(id),
+        _ => candidates
+            .iter()
+            .copied()
+            .find(|id| id >> 11 == screen_id >> 11),
+    }
+}
+
+/// The sprite resources an item number can stand for. The engine numbers
+/// sprites by their id within a section, so there is one candidate for
+/// every section that has a resource with that number.
+fn sprite_resources<R>(archive: &Archive<R>, item: u16) -> Vec<u16> {
+    archive
+        .entries()
+        .iter()
+        .filter(|e| e.number & 0x7ff == item && e.number < GRID_FILE_START)
+        .map(|e| e.number)
+        .collect()
+}
+
+fn render_room(args: &RenderRoomArgs) -> Result<()> {
+    let archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let screen_res = archive
+        .get_resource(args.id)
+        .filter(|r| r.data.len() == SCREEN_SIZE)
+        .ok_or_else(|| Error::InvalidData(format!("no screen with id {}", args.id)))?;
+
+    let cpt = load_compacts(&args.path, args.compacts.as_deref())?.ok_or_else(|| {
+        Error::InvalidData("no sky.cpt found, give one with --compacts".to_owned())
+    })?;
+
+    let pal = match args.palette {
+        Some(id) => Some(read_palette(&archive, id)?),
+        None => screen_palette(&archive, &PaletteMap::builtin(archive.version()), args.id),
+    };
+
+    let mut objects = Vec::new();
+    for entry in cpt.entries.values() {
+        let Some(compact) = entry.compact() else {
+            continue;
+        };
+        if compact.get("screen") != Some(args.screen) {
+            continue;
+        }
+        let Some(list) = compact.draw_list() else {
+            continue;
+        };
+
+        let frame = compact.get("frame").unwrap_or_default();
+        let placed = room_sprite(&archive, frame >> 6, args.id).and_then(|id| {
+            let placement = LayerPlacement {
+                screen: args.id,
+                layer: id,
+                x: compact.get("xcood").unwrap_or_default() as i32,
+                y: compact.get("ycood").unwrap_or_default() as i32,
+                frame: (frame & 0x3f) as usize,
+            };
+            PlacedLayer::new(&placement, &archive.get_resource(id)?)
+        });
+
+        match placed {
+            Some(placed) => objects.push((list, entry, placed)),
+            None => eprintln!(
+                "Skipping {} (0x{:04x}): no sprite {} with frame {}",
+                entry.name,
+                entry.id,
+                frame >> 6,
+                frame & 0x3f
+            ),
+        }
+    }
+
+    // Sorted objects are drawn from the top of the screen down, by where
+    // their sprites end.
+    objects.sort_by_key(|(list, _, placed)| match list {
+        DrawList::Sort => (*list, placed.y + placed.frame.height as i32),
+        _ => (*list, 0),
+    });
+    for (list, entry, placed) in &objects {
+        println!(
+            "{:<10} {} (0x{:04x}), sprite {:05} at {},{}",
+            format!("{list:?}").to_lowercase(),
+            entry.name,
+            entry.id,
+            placed.id,
+            placed.x,
+            placed.y
+        );
+    }
+
+    let screen = Frame {
+        width: 320,
+        height: 200,
+        data: screen_res.data,
+    };
+    let placed: Vec<_> = objects.into_iter().map(|(_, _, placed)| placed).collect();
+    let composited = layer::composite(&screen, &placed);
+
+    let palette = pal.as_ref().map(|r| r.data.as_slice());
+    let image = RgbImage::from_indexed(320, 200, &composited.data, palette);
+
+    let out = args.out.clone().unwrap_or_else(|| {
+        PathBuf::from(format!("{:05}-room.{}", args.id, args.format.extension()))
+    });
+    let mut out_file = BufWriter::new(File::create(&out).map_err(|e| Error::file(&out, e))?);
+    image
+        .write(&mut out_file, args.format)
+        .and_then(|_| out_file.flush())
+        .map_err(|e| Error::file(&out, e))?;
+
+    Ok(())
+}
+
+/// What `extract-room` gathered for a room, written to `manifest.json`.
+/// Files are relative to the manifest.
+#[derive(Serialize)]
+struct RoomManifest<'a> {
+    screen: u16,
+    /// The number the objects of the room give as their screen.
+    room: u16,
+    files: Vec<String>,
+    palette: Option<u16>,
+    grid: Option<u16>,
+    layers: Vec<RoomLayer>,
+    compacts: Vec<JsonNamedId<'a>>,
+    sprites: Vec<RoomSprite>,
+    scripts: Vec<String>,
+    /// The text ids the compacts and their scripts refer to.
+    text: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RoomLayer {
+    id: u16,
+    x: i32,
+    y: i32,
+    frame: usize,
+    file: String,
+}
+
+#[derive(Serialize)]
+struct RoomSprite {
+    id: u16,
+    files: Vec<String>,
+}
+
+/// Writes the screen of a room with its palette, walk grid and foreground
+/// layers, and the compacts placed in it with their sprites, scripts and
+/// text, to one directory listed in `manifest.json`.
+fn extract_room(args: &ExtractRoomArgs) -> Result<()> {
+    let mut archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let screen_res = archive
+        .get_resource(args.id)
+        .filter(|r| r.data.len() == SCREEN_SIZE)
+        .ok_or_else(|| Error::InvalidData(format!("no screen with id {}", args.id)))?;
+
+    let cpt = load_compacts(&args.path, args.compacts.as_deref())?.ok_or_else(|| {
+        Error::InvalidData("no sky.cpt found, give one with --compacts".to_owned())
+    })?;
+
+    let pal = match args.palette {
+        Some(id) => Some(read_palette(&archive, id)?),
+        None => screen_palette(&archive, &PaletteMap::builtin(archive.version()), args.id),
+    };
+    let palette = pal.as_ref().map(|r| r.data.as_slice());
+
+    let out = args
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("room-{:05}", args.id)));
+    for dir in ["screen", "palette", "grids", "layers", "sprites", "scripts"] {
+        let dir = out.join(dir);
+        output()
+            .create_dir_all(&dir)
+            .map_err(|e| Error::file(&dir, e))?;
+    }
+    let ext = args.format.extension();
+    let mut files = Vec::new();
+
+    let name = format!("{:05}", args.id);
+    save_indexed_image(
+        &output::dump_path(&out, "screen", &name),
+        320,
+        200,
+        &screen_res.data,
+        palette,
+        args.format,
+        false,
+    )?;
+    files.push(format!("screen/{name}.{ext}"));
+
+    if let Some(ref pal) = pal {
+        let name = format!("{:05}", pal.entry.number);
+        dump_resource_as_pal(&out, pal, &name, args.format)?;
+        files.push(format!("palette/{name}.{ext}"));
+        files.extend(PaletteFormat::ALL.map(|f| format!("palette/{name}.{}", f.extension())));
+    }
+
+    let grid = match args.grid_map {
+        Some(ref path) => GridMap::read(path)
+            .map_err(|e| Error::file(path, e))?
+            .grid(args.id),
+        None => None,
+    };
+    if let Some(grid_id) = grid {
+        let grid = read_grid(&archive, grid_id)?;
+        let name = output::resource_path(&out, "grids", grid_id);
+        save_image(&grid.render(), &name, args.format)?;
+        write_grid_json(&name, grid_id, &grid, vec![args.id])?;
+        files.push(format!("grids/{grid_id:05}.{ext}"));
+        files.push(format!("grids/{grid_id:05}.json"));
+    }
+
+    let layer_map = match args.layer_map {
+        Some(ref path) => LayerMap::read(path).map_err(|e| Error::file(path, e))?,
+        None => LayerMap::default(),
+    };
+    let placements = layer_map.layers(args.id);
+    let mut layers = Vec::new();
+    for (n, (placement, layer)) in placements
+        .iter()
+        .zip(place_layers(&archive, placements)?)
+        .enumerate()
+    {
+        let name = format!("{n:02}-{:05}", layer.id);
+        let frame = &layer.frame;
+        let image = RgbImage::from_indexed_with_transparency(
+            frame.width,
+            frame.height,
+            &frame.data,
+            palette,
+            sprite::TRANSPARENT_INDEX,
+        );
+        save_image(
+            &image,
+            &output::dump_path(&out, "layers", &name),
+            ImageFormat::Png,
+        )?;
+        layers.push(RoomLayer {
+            id: layer.id,
+            x: placement.x,
+            y: placement.y,
+            frame: placement.frame,
+            file: format!("layers/{name}.png"),
+        });
+    }
+
+    let entries: Vec<_> = cpt
+        .entries
+        .values()
+        .filter(|e| e.compact().and_then(|c| c.get("screen")) == Some(args.screen))
+        .collect();
+
+    let mut sprite_ids = BTreeSet::new();
+    let mut modules = BTreeSet::new();
+    let mut scripts = BTreeSet::new();
+    let mut text_ids = BTreeSet::new();
+    for compact in entries.iter().filter_map(|e| e.compact()) {
+        let refs = compact.references();
+        if let Some((item, _)) = refs.sprite {
+            sprite_ids.extend(room_sprite(&archive, item, args.id));
+        }
+        for script in refs.scripts {
+            modules.insert(script.module);
+            scripts.insert(script.script);
+        }
+        text_ids.extend(refs.text.iter().map(|t| t.id));
+    }
+
+    let mut sprites = Vec::new();
+    for id in sprite_ids {
+        let Some(frames) = archive
+            .get_resource(id)
+            .and_then(|r| sprite::decode_frames(&r))
+        else {
+            continue;
+        };
+
+        let mut sprite_files = Vec::new();
+        for (n, frame) in frames.iter().enumerate() {
+            let name = format!("{id:05}-{n:03}");
+            let image = RgbImage::from_indexed_with_transparency(
+                frame.width,
+                frame.height,
+                &frame.data,
+                palette,
+                sprite::TRANSPARENT_INDEX,
+            );
+            save_image(
+                &image,
+                &output::dump_path(&out, "sprites", &name),
+                ImageFormat::Png,
+            )?;
+            sprite_files.push(format!("sprites/{name}.png"));
+        }
+        sprites.push(RoomSprite {
+            id,
+            files: sprite_files,
+        });
+    }
+
+    for &module_id in &modules {
+        let Some(resource) = archive.get_resource(module_id) else {
+            continue;
+        };
+        let Some(module) = script::Module::new(module_id, &resource.data) else {
+            continue;
+        };
+        for (script, refs) in module.references() {
+            if scripts.contains(&script) {
+                text_ids.extend(
+                    refs.iter()
+                        .filter(|&&(kind, _)| kind == Reference::Text)
+                        .map(|&(_, id)| id),
+                );
+            }
+        }
+    }
+
+    let texts: BTreeMap<u16, String> =
+        match load_huffman_tree(&args.path, args.huffman_tree.as_deref())? {
+            Some(tree) => decode_text(&mut archive, &tree)
+                .into_values()
+                .next()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|line| (line.id, line.text))
+                .collect(),
+            None => {
+                println!("No Huffman tree found, skipping text");
+                BTreeMap::new()
+            }
+        };
+
+    for &module_id in &modules {
+        if let Some(resource) = archive.get_resource(module_id) {
+            dump_script(&out, &resource, &texts)?;
+            files.push(format!("scripts/{module_id:05}.asm"));
+            files.push(format!("scripts/{module_id:05}.txt"));
+        }
+    }
+
+    let records: Vec<_> = entries
+        .iter()
+        .map(|entry| json_compact(&archive, &cpt, entry, &texts))
+        .collect();
+    output().write_json(out.join("compacts.json"), &records)?;
+    files.push("compacts.json".to_owned());
+
+    let room_text: BTreeMap<_, _> = text_ids
+        .iter()
+        .map(|&id| (format!("0x{id:04x}"), texts.get(&id)))
+        .collect();
+    output().write_json(out.join("text.json"), &room_text)?;
+    files.push("text.json".to_owned());
+
+    let manifest = RoomManifest {
+        screen: args.id,
+        room: args.screen,
+        files,
+        palette: pal.as_ref().map(|r| r.entry.number),
+        grid,
+        layers,
+        compacts: entries
+            .iter()
+            .map(|e| JsonNamedId {
+                id: format!("0x{:04x}", e.id),
+                name: Some(&e.name),
+            })
+            .collect(),
+        sprites,
+        scripts: scripts.iter().map(|s| format!("0x{s:04x}")).collect(),
+        text: text_ids.iter().map(|id| format!("0x{id:04x}")).collect(),
+    };
+    output().write_json(out.join("manifest.json"), &manifest)?;
+
+    println!(
+        "Extracted room {} with {} compacts to `{}`",
+        args.id,
+        manifest.compacts.len(),
+        out.display()
+    );
+
+    Ok(())
+}
+
+fn import_screen(args: &ImportScreenArgs) -> Result<()> {
+    let archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let entry = *archive
+        .entry(args.id)
+        .ok_or(Error::NoSuchResource(args.id))?;
+
+    let file = File::open(&args.image).map_err(|e| Error::file(&args.image, e))?;
+    let image = read_indexed_png(BufReader::new(file)).map_err(|e| Error::file(&args.image, e))?;
+    if (image.width, image.height) != (320, 200) {
+        return Err(Error::InvalidData(format!(
+            "`{}` is {}x{}, screens are 320x200",
+            args.image.display(),
+            image.width,
+            image.height
+        )));
+    }
+
+    let pal = match args.palette {
+        Some(id) => Some(archive.get_resource(id).ok_or(Error::NoSuchResource(id))?),
+        None => screen_palette(&archive, &PaletteMap::builtin(archive.version()), args.id),
+    };
+
+    // The indices are what ends up in the game, so the image's own colors
+    // only matter as a check that it was drawn with the right palette.
+    match pal {
+        Some(pal) => {
+            let expected = palette_to_rgb(&pal.data);
+            for &c in &image.data {
+                let c = c as usize;
+                let color = image.palette.get(3 * c..3 * c + 3);
+                if color != expected.get(3 * c..3 * c + 3) {
+                    return Err(Error::InvalidData(format!(
+                        "color {c} of `{}` does not match palette {}",
+                        args.image.display(),
+                        pal.entry.number
+                    )));
+                }
+            }
+        }
+        None => println!(
+            "No palette found for screen {}, colors not checked",
+            args.id
+        ),
+    }
+
+    let original = archive.get_resource(args.id);
+    let header = match original.and_then(|r| r.header) {
+        Some(header) => header,
+        None if args.compress && !entry.has_file_header => {
+            return Err(Error::InvalidData(format!(
+                "resource {} has no file header and can't be compressed",
+                args.id
+            )));
+        }
+        None => Header {
+            flags: 0,
+            x: 0,
+            y: 0,
+            width: 320,
+            height: 200,
+            sp_size: 0,
+            tot_size: 0,
+            n_sprites: 0,
+            offset_x: 0,
+            offset_y: 0,
+            compressed_size: 0,
+        },
+    };
+
+    let data = write_resource(&entry, &header, &image.data, args.compress);
+
+    let out = args
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{:05}.dmp", entry.number)));
+    std::fs::write(&out, data).map_err(|e| Error::file(out, e))
+}
+
+/// Maps the pixels of an edited font sheet to glyph pixels by the nearest
+/// color of the preview palette, with transparent pixels transparent.
+fn font_sheet_pixels(image: &RgbImage) -> Frame {
+    let colors: Vec<_> = font::PREVIEW_PALETTE
+        .chunks_exact(3)
+        .map(|c| c.iter().map(|&v| rescale_6_bit_color_to_8_bit(v) as i32))
+        .map(|mut c| [c.next().unwrap(), c.next().unwrap(), c.next().unwrap()])
+        .collect();
+
+    let data = image
+        .data
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(n, rgb)| {
+            if image.alpha.as_ref().is_some_and(|alpha| alpha[n] < 128) {
+                return font::TRANSPARENT;
+            }
+            let distance =
+                |c: &[i32; 3]| (0..3).map(|i| (c[i] - rgb[i] as i32).pow(2)).sum::<i32>();
+            (0..colors.len())
+                .min_by_key(|&i| distance(&colors[i]))
+                .unwrap_or(0) as u8
+        })
+        .collect();
+
+    Frame {
+        width: image.width,
+        height: image.height,
+        data,
+    }
+}
+
+fn import_font(args: &ImportFontArgs) -> Result<()> {
+    let archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let entry = *archive
+        .entry(args.id)
+        .ok_or(Error::NoSuchResource(args.id))?;
+
+    let file = File::open(&args.image).map_err(|e| Error::file(&args.image, e))?;
+    let image = read_png(BufReader::new(file)).map_err(|e| Error::file(&args.image, e))?;
+
+    let widths_path = args
+        .widths
+        .clone()
+        .unwrap_or_else(|| args.image.with_extension("json"));
+    let file = File::open(&widths_path).map_err(|e| Error::file(&widths_path, e))?;
+    let glyphs: Vec<font::GlyphInfo> =
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| Error::file(&widths_path, e))?;
+
+    // Glyphs are stored in order of their codes, so none may be missing.
+    let mut widths = vec![None; glyphs.len()];
+    for glyph in &glyphs {
+        let n = glyph.code.wrapping_sub(font::FIRST_CHAR) as usize;
+        match widths.get_mut(n) {
+            Some(width) => *width = Some(glyph.width),
+            None => {
+                return Err(Error::file(
+                    &widths_path,
+                    Error::InvalidData(format!(
+                        "glyph 0x{:02x} is out of order or not in 0x20 to 0x9f",
+                        glyph.code
+                    )),
+                ));
+            }
+        }
+    }
+    let widths: Vec<usize> = widths.into_iter().collect::<Option<_>>().ok_or_else(|| {
+        Error::file(
+            &widths_path,
+            Error::InvalidData("a glyph is listed twice".to_owned()),
+        )
+    })?;
+
+    let sheet = font_sheet_pixels(&image);
+    let font = Font::from_sheet(&sheet, font::SHEET_COLUMNS, &widths, font::CHAR_HEIGHT)
+        .ok_or_else(|| {
+            Error::InvalidData(format!(
+                "`{}` doesn't hold {} glyphs of 1 to {} pixels wide",
+                args.image.display(),
+                widths.len(),
+                font::MAX_CHAR_WIDTH
+            ))
+        })?;
+
+    let data = font.encode();
+    let data = match archive.get_resource(args.id).and_then(|r| r.header) {
+        Some(header) => write_resource(&entry, &header, &data, args.compress),
+        None if args.compress => {
+            return Err(Error::InvalidData(format!(
+                "resource {} has no file header and can't be compressed",
+                args.id
+            )));
+        }
+        None => data,
+    };
+
+    let out = args
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{:05}.dmp", entry.number)));
+    std::fs::write(&out, data).map_err(|e| Error::file(out, e))
+}
+
+/// The id of the speech resource a WAV file is for, from its name: either
+/// the resource id or a text id in hex.
+fn speech_id(path: &Path) -> Option<u16> {
+    let stem = path.file_stem()?.to_str()?;
+    match stem.strip_prefix("0x") {
+        Some(text_id) => speech::speech_file(u16::from_str_radix(text_id, 16).ok()?),
+        None => stem.parse().ok(),
+    }
+}
+
+fn pack_speech(args: &PackSpeechArgs) -> Result<()> {
+    let mut files = Vec::new();
+    for dir_entry in args.dir.read_dir().map_err(|e| Error::file(&args.dir, e))? {
+        let path = dir_entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    std::fs::create_dir_all(&args.out).map_err(|e| Error::file(&args.out, e))?;
+
+    let mut records = Vec::new();
+    for path in files {
+        let Some(id) = speech_id(&path) else {
+            println!("Skipping `{}`, not named after speech", path.display());
+            continue;
+        };
+
+        let file = File::open(&path).map_err(|e| Error::file(&path, e))?;
+        let sound =
+            audio::read_wav(&mut BufReader::new(file)).map_err(|e| Error::file(&path, e))?;
+        let samples = if sound.sample_rate == audio::SAMPLE_RATE && sound.bits_per_sample == 8 {
+            sound.to_u8()
+        } else {
+            sound.resample(audio::SAMPLE_RATE).to_u8()
+        };
+
+        let out = args.out.join(format!("{id:05}.dmp"));
+        std::fs::write(&out, speech::write_speech(id, &samples))
+            .map_err(|e| Error::file(&out, e))?;
+        records.push(repack::IndexRecord {
+            id,
+            has_file_header: true,
+            uses_file_header: true,
+            offset: None,
+            size: None,
+        });
+    }
+
+    let index_path = args.out.join(repack::INDEX_FILE_NAME);
+    repack::update_index(&index_path, &records).map_err(|e| Error::file(&index_path, e))?;
+    println!("Packed {} speech files", records.len());
+
+    Ok(())
+}
+
+/// Reads a section of text as [`dump_text`] writes it, one line of text id
+/// and text separated by a tab for each line.
+fn read_text_section(contents: &str) -> Result<Vec<TextLine>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(n, line)| {
+            let invalid = |what: &str| Error::InvalidData(format!("line {}: {what}", n + 1));
+            let (id, text) = line.split_once('\t').ok_or_else(|| invalid("no tab"))?;
+            let id = id
+                .strip_prefix("0x")
+                .and_then(|id| u16::from_str_radix(id, 16).ok())
+                .ok_or_else(|| invalid("invalid text id"))?;
+            let bytes = text::from_utf8(text)
+                .ok_or_else(|| invalid("character not in the game's code page"))?;
+
+            Ok(TextLine {
+                id,
+                text: text.to_owned(),
+                bytes,
+            })
+        })
+        .collect()
+}
+
+fn pack_text(args: &PackTextArgs) -> Result<()> {
+    let name = match args.language {
+        Some(ref name) => name.clone(),
+        None => args
+            .dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    let language = text::Language::ALL
+        .into_iter()
+        .find(|l| l.name() == name)
+        .ok_or_else(|| Error::InvalidData(format!("unknown language `{name}`, use --language")))?;
+
+    let mut sections = Vec::new();
+    for section in 0..text::NO_OF_TEXT_SECTIONS {
+        let path = args.dir.join(format!("{section}.txt"));
+        if !path.exists() {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path).map_err(|e| Error::file(&path, e))?;
+        let lines = read_text_section(&contents).map_err(|e| Error::file(&path, e))?;
+        sections.push((section, path, lines));
+    }
+    if sections.is_empty() {
+        return Err(Error::file(
+            &args.dir,
+            Error::InvalidData("no `<section>.txt` files".to_owned()),
+        ));
+    }
+
+    std::fs::create_dir_all(&args.out).map_err(|e| Error::file(&args.out, e))?;
+
+    let tree = if args.new_tree {
+        let lines = sections.iter().flat_map(|(_, _, lines)| lines);
+        let tree = HuffmanTree::for_lines(lines.map(|l| &l.bytes[..])).ok_or_else(|| {
+            Error::InvalidData("the text has too many characters for a Huffman tree".to_owned())
+        })?;
+        let tree_path = args.out.join("huffman-tree.bin");
+        std::fs::write(&tree_path, tree.to_bytes()).map_err(|e| Error::file(&tree_path, e))?;
+        println!("Wrote the new Huffman tree to `{}`", tree_path.display());
+        tree
+    } else {
+        load_huffman_tree(&args.path, args.huffman_tree.as_deref())?
+            .ok_or_else(|| Error::InvalidData("no Huffman tree found".to_owned()))?
+    };
+
+    let archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let mut records = Vec::new();
+    for (section, path, lines) in &sections {
+        let id = text::text_file(language.number(), *section);
+        let entry = *archive.entry(id).ok_or(Error::NoSuchResource(id))?;
+
+        let data = text::encode_section(lines, &tree).map_err(|e| Error::file(path, e))?;
+        let data = match archive.get_resource(id).and_then(|r| r.header) {
+            Some(header) => write_resource(&entry, &header, &data, args.compress),
+            None if args.compress => {
+                return Err(Error::InvalidData(format!(
+                    "resource {id} has no file header and can't be compressed"
+                )));
+            }
+            None => data,
+        };
+
+        let out = args.out.join(format!("{id:05}.dmp"));
+        std::fs::write(&out, data).map_err(|e| Error::file(&out, e))?;
+        records.push(repack::IndexRecord {
+            id,
+            has_file_header: entry.has_file_header,
+            uses_file_header: entry.uses_file_header,
+            offset: None,
+            size: None,
+        });
+    }
+
+    let index_path = args.out.join(repack::INDEX_FILE_NAME);
+    repack::update_index(&index_path, &records).map_err(|e| Error::file(&index_path, e))?;
+    println!("Packed {} text sections", records.len());
+
+    Ok(())
+}
+
+/// A saved game with the names of the objects it refers to.
+#[derive(Serialize)]
+struct JsonSaveGame<'a> {
+    screen: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inventory: Option<Vec<JsonNamedId<'a>>>,
+    #[serde(flatten)]
+    save: &'a SaveGame,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compacts: Option<Vec<JsonCompact<'a>>>,
+}
+
+#[derive(Serialize)]
+struct JsonNamedId<'a> {
+    id: String,
+    name: Option<&'a str>,
+}
+
+fn savegame(args: &SavegameArgs) -> Result<()> {
+    let data = std::fs::read(&args.file).map_err(|e| Error::file(&args.file, e))?;
+    let save = SaveGame::read(&data).map_err(|e| Error::file(&args.file, e))?;
+    if save.format == SaveFormat::Dos {
+        eprintln!("Not a saved game of ScummVM, reading it as one of the DOS releases");
+    }
+
+    let dir = args.file.parent().unwrap_or(Path::new("."));
+    let cpt = load_compacts(dir, args.compacts.as_deref())?;
+    let name = |id: u16| cpt.as_ref().and_then(|cpt| cpt.name(id));
+
+    let inventory = args.inventory.map(|first| {
+        save.menu_objects(first)
+            .into_iter()
+            .map(|id| JsonNamedId {
+                id: format!("0x{id:04x}"),
+                name: name(id as u16),
+            })
+            .collect()
+    });
+
+    let compacts = match cpt {
+        Some(ref cpt) => {
+            let saved = save.compacts(cpt).map_err(|e| Error::file(&args.file, e))?;
+            Some(
+                saved
+                    .into_iter()
+                    .map(|(id, words)| {
+                        let entry = &cpt.entries[&id];
+                        let compact = (entry.kind == CptType::Compact).then(|| Compact::new(words));
+                        JsonCompact {
+                            id: format!("0x{id:04x}"),
+                            name: &entry.name,
+                            r#type: entry.kind,
+                            compact,
+                            data: compact.is_none().then_some(words),
+                            references: None,
+                        }
+                    })
+                    .collect(),
+            )
+        }
+        None => {
+            eprintln!("No sky.cpt found, leaving out the compacts");
+            None
+        }
+    };
+
+    let json = JsonSaveGame {
+        screen: save.screen(),
+        inventory,
+        save: &save,
+        compacts,
+    };
+    match args.output {
+        Some(ref path) => {
+            let file = File::create(path).map_err(|e| Error::file(path, e))?;
+            serde_json::to_writer_pretty(BufWriter::new(file), &json)
+                .map_err(|e| Error::file(path, e))?;
+        }
+        None => println!("{}", serde_json::to_string_pretty(&json)?),
+    }
+
+    Ok(())
+}
+
+/// The pairings of screens with the palette, foreground layers and walk
+/// grid that go with them, the ranges of their palettes that are cycled,
+/// and the names of resources.
+struct ScreenMaps {
+    palettes: PaletteMap,
+    layers: LayerMap,
+    grids: GridMap,
+    cycles: CycleMap,
+    names: ResourceNames,
+}
+
+/// The most palettes a screen is written with, each named by a letter.
+const MAX_PALETTE_VARIANTS: usize = 26;
+
+/// The palette `screen` is shown with: the first `palettes` pairs it with,
+/// or else a neighbouring one.
+fn screen_palette(
+    archive: &Archive<Cursor<Vec<u8>>>,
+    palettes: &PaletteMap,
+    screen: u16,
+) -> Option<Resource> {
+    screen_palettes(archive, palettes, screen)
+        .into_iter()
+        .next()
+}
+
+/// The palettes `screen` is shown with: those `palettes` pairs it with, or
+/// else the neighbouring ones. Resources paired with it that aren't
+/// palettes are passed over.
+fn screen_palettes(
+    archive: &Archive<Cursor<Vec<u8>>>,
+    palettes: &PaletteMap,
+    screen: u16,
+) -> Vec<Resource> {
+    let known: Vec<_> = palettes
+        .palettes(screen)
+        .iter()
+        .filter_map(|&id| archive.get_resource(id))
+        .filter(|r| r.data.len() == 768)
+        .take(MAX_PALETTE_VARIANTS)
+        .collect();
+    if !known.is_empty() {
+        return known;
+    }
+
+    neighbouring_palettes(archive, screen)
+}
+
+/// Guesses the palettes of a screen without a known pairing: the run of
+/// palettes right after it, of which there are several for screens with
+/// variants, otherwise the resource before it if that is a palette.
+fn neighbouring_palettes(archive: &Archive<Cursor<Vec<u8>>>, screen: u16) -> Vec<Resource> {
+    let after: Vec<_> = (1..=MAX_PALETTE_VARIANTS as u16)
+        .map_while(|n| screen.checked_add(n))
+        .map_while(|id| archive.get_resource(id).filter(|r| r.data.len() == 768))
+        .collect();
+    if !after.is_empty() {
+        return after;
+    }
+
+    screen
+        .checked_sub(1)
+        .and_then(|id| archive.get_resource(id))
+        .filter(|r| r.data.len() == 768)
+        .into_iter()
+        .collect()
+}
+
+/// Dumps a single resource, returning its record for the speech manifest if
+/// it holds speech.
+fn dump_resource(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    entry: &Entry,
+    args: &DumpArgs,
+    sprite_palette: Option<&Resource>,
+    maps: &ScreenMaps,
+    texts: &BTreeMap<u16, String>,
+) -> Result<Dumped> {
+    let format = args.format;
+
+    let data = archive.checked_entry_bytes(entry)?;
+    let resource = if args.strict {
+        read_resource_strict(entry, data.to_vec())?
+    } else {
+        read_resource(entry, data.to_vec())?
+    };
+    let kind = classify(&resource, archive.version());
+    if !args.extracts(kind) {
+        return Ok(Dumped { kind, speech: None });
+    }
 
-use std::{
-    fmt,
-    fs::File,
-    io::{BufReader, Cursor, Read, Seek, Write},
-};
+    if args.dumps(DumpClass::Raw) && !args.raw_image {
+        dump_entry(out, entry, data)?;
+    }
+    if kind.is_some_and(|kind| !args.dumps(DumpClass::of(kind))) {
+        return Ok(Dumped { kind, speech: None });
+    }
 
-use clap::Parser;
-use csv::Writer;
-use serde::Serialize;
+    match kind {
+        Some(ResourceType::Font) => dump_font(out, &resource, format)?,
+        Some(ResourceType::Palette) => {
+            let name = maps.names.file_name(entry.number);
+            dump_resource_as_pal(out, &resource, &name, format)?
+        }
+        Some(ResourceType::Screen) => {
+            let name = maps.names.file_name(entry.number);
+            let variants = screen_palettes(archive, &maps.palettes, entry.number);
+            let pal = variants.first();
 
-use bytes_ext::{ReadBytesExt, WriteBytesExt};
-use rnc_decompress::decompress_rnc1;
+            let cycles = match pal {
+                Some(pal) if args.palette_cycles => maps
+                    .cycles
+                    .ranges(entry.number)
+                    .map(<[_]>::to_vec)
+                    .unwrap_or_else(|| cycle::detect(entry.number, &resource.data, &pal.data)),
+                _ => Vec::new(),
+            };
 
-/// Extracts and decodes data files from Beneath a Steel Sky
-#[derive(Parser)]
-struct Cli {
-    /// Path to game data files
-    path: std::path::PathBuf,
+            match pal {
+                Some(pal) if !cycles.is_empty() => {
+                    dump_screen_cycle(out, &resource, &name, pal, &cycles)?
+                }
+                Some(_) if variants.len() > 1 => {
+                    for (letter, pal) in ('a'..='z').zip(&variants) {
+                        let name = format!("{name}-{letter}");
+                        dump_screen_with_pal(out, &resource, &name, pal, format, args.indexed)?;
+                    }
+                }
+                Some(pal) => {
+                    dump_screen_with_pal(out, &resource, &name, pal, format, args.indexed)?
+                }
+                None => dump_screen_in_grayscale(out, &resource, &name, format, args.indexed)?,
+            }
 
-    /// Dump the resource list to `resource.csv`
-    #[arg(short, long, default_value_t = false)]
-    dump_csv: bool,
-}
+            let layers = maps.layers.layers(entry.number);
+            if !layers.is_empty() {
+                dump_screen_layers(out, archive, &resource, &name, layers, pal, format)?;
+            }
 
-#[derive(Copy, Clone, Debug)]
-struct Entry {
-    number: u16,
-    offset: u32,
-    size: u32,
-    has_file_header: bool,
-    uses_file_header: bool,
-}
+            if let Some(id) = maps.grids.grid(entry.number) {
+                dump_screen_grid(out, archive, &resource, &name, id, pal, format)?;
+            }
 
-#[derive(Debug, Serialize)]
-struct Header {
-    flags: u16,
-    x: u16,
-    y: u16,
-    width: u16,
-    height: u16,
-    sp_size: u16,
-    tot_size: u16,
-    n_sprites: u16,
-    offset_x: i16,
-    offset_y: i16,
-    compressed_size: u16,
-}
+            if args.tiled {
+                dump_tiled_map(out, archive, &resource, &name, pal, maps, args)?;
+            }
+        }
+        Some(ResourceType::Speech) => {
+            dump_speech(out, &resource, args)?;
+
+            let text_id = speech::text_id(entry.number);
+            let samples = speech::samples(&resource).len();
+            return Ok(Dumped {
+                kind,
+                speech: Some(SpeechRecord {
+                    id: entry.number,
+                    file: format!("{:05}.{}", entry.number, args.audio_format.extension()),
+                    duration: format!("{:.3}", samples as f64 / audio::SAMPLE_RATE as f64),
+                    text_id: text_id.map(|t| format!("0x{t:04x}")),
+                    text_section: text_id.map(|t| t >> 12),
+                    text_line: text_id.map(|t| t & 0xfff),
+                    text: text_id.and_then(|t| texts.get(&t).cloned()),
+                }),
+            });
+        }
+        Some(ResourceType::Audio) => dump_audio(out, &resource, args)?,
+        Some(ResourceType::Grid) => dump_grid(out, &resource, format, maps)?,
+        Some(ResourceType::Music) => dump_music(out, &resource)?,
+        Some(ResourceType::Script) => dump_script(out, &resource, texts)?,
+        Some(ResourceType::Sprite | ResourceType::Animation) => {
+            let name = maps.names.file_name(entry.number);
+            dump_sprites(out, &resource, &name, sprite_palette, args)?;
 
-impl Header {
-    fn is_compressed(&self) -> bool {
-        self.flags & 0x80 != 0
+            if args.gif && resource.header.as_ref().is_some_and(|h| h.n_sprites > 1) {
+                let pal = args
+                    .sprite_palette
+                    .or_else(|| archive.nearest_palette(entry.number).map(|e| e.number))
+                    .and_then(|id| archive.get_resource(id));
+                if let Some(ref pal) = pal {
+                    dump_sprite_animation(out, &resource, &name, pal, args.transparent_index)?;
+                }
+            }
+        }
+        _ => {}
     }
+
+    Ok(Dumped { kind, speech: None })
 }
 
-struct Resource {
-    entry: Entry,
-    header: Option<Header>,
-    data: Vec<u8>,
+/// Plays the sequences following each screen over it in order of id,
+/// writing every frame of the cutscene to `cutscenes/<screen>/<frame>`
+/// with its timing in `cutscene.json`, and with `--gif` the whole cutscene
+/// as an animated GIF.
+fn dump_cutscenes(
+    out: &Path,
+    archive: &Archive<Cursor<Vec<u8>>>,
+    kinds: &BTreeMap<u16, ResourceType>,
+    maps: &ScreenMaps,
+    args: &DumpArgs,
+) -> Result<()> {
+    let mut cutscenes: Vec<(u16, Vec<u16>)> = Vec::new();
+    for (&id, &kind) in kinds {
+        match kind {
+            ResourceType::Screen => cutscenes.push((id, Vec::new())),
+            ResourceType::Sequence => match cutscenes.last_mut() {
+                Some((_, sequences)) => sequences.push(id),
+                None => eprintln!(
+                    "{}",
+                    Error::resource(
+                        id,
+                        Error::InvalidData("no screen to play the sequence over".to_owned())
+                    )
+                ),
+            },
+            _ => {}
+        }
+    }
+
+    for (screen_id, sequence_ids) in cutscenes {
+        if sequence_ids.is_empty() {
+            continue;
+        }
+        let Some(screen_res) = archive.get_resource(screen_id) else {
+            continue;
+        };
+        let pal = screen_palette(archive, &maps.palettes, screen_id);
+        let palette = match palette_colors(pal.as_ref()) {
+            Ok(palette) => palette,
+            Err(e) => {
+                eprintln!("{}", Error::resource(screen_id, e));
+                continue;
+            }
+        };
+
+        let mut screen = screen_res.data.clone();
+        let mut frames = vec![Frame {
+            width: 320,
+            height: 200,
+            data: screen.clone(),
+        }];
+        let mut sequences = Vec::new();
+        for id in sequence_ids {
+            let Some(decoded) = archive
+                .get_resource(id)
+                .and_then(|r| sequence::decode(&r.data, &mut screen))
+            else {
+                continue;
+            };
+
+            sequences.push(CutsceneSequence {
+                id,
+                first_frame: frames.len(),
+                frames: decoded.len(),
+            });
+            frames.extend(decoded);
+        }
+
+        let name = maps.names.file_name(screen_id);
+        let dir = output::dump_path(out, "cutscenes", &name);
+        output()
+            .create_dir_all(&dir)
+            .map_err(|e| Error::file(&dir, e))?;
+
+        frames.par_iter().enumerate().try_for_each(|(n, frame)| {
+            save_indexed_image(
+                &dir.join(format!("{n:04}")),
+                frame.width,
+                frame.height,
+                &frame.data,
+                palette,
+                args.format,
+                args.indexed,
+            )
+        })?;
+
+        let info = CutsceneInfo {
+            screen: screen_id,
+            palette: pal.as_ref().map(|r| r.entry.number),
+            frame_delay_ms: sequence::FRAME_DELAY_MS,
+            frames: frames.len(),
+            sequences,
+        };
+        let info_path = dir.join("cutscene.json");
+        output()
+            .write_json(&info_path, &info)
+            .map_err(|e| Error::file(&info_path, e))?;
+
+        if let (true, Some(palette)) = (args.gif, palette) {
+            let gif_path = output::dump_path(out, "cutscenes", &format!("{name}.gif"));
+            let mut gif_file = output().create(&gif_path);
+            sprite::write_gif_with(
+                &mut gif_file,
+                &frames,
+                palette,
+                (sequence::FRAME_DELAY_MS / 10) as u16,
+                None,
+            )
+            .map_err(|e| Error::file(&gif_path, e))?;
+            gif_file.finish()?;
+        }
+    }
+
+    Ok(())
 }
 
-impl fmt::Debug for Resource {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Resource")
-            .field("entry", &self.entry)
-            .field("header", &self.header)
-            .finish()
+/// Lists the files a dry run would have written, with their size and
+/// format, and how many of them there are in all.
+fn print_planned(files: &[output::PlannedFile]) {
+    let mut total = 0;
+    for file in files {
+        let format = file
+            .path
+            .extension()
+            .map_or("-".into(), |ext| ext.to_string_lossy());
+        let unchanged = if file.unchanged { "  (up to date)" } else { "" };
+        println!(
+            "{:>10}  {format:<5} {}{unchanged}",
+            file.size,
+            file.path.display()
+        );
+        total += file.size;
     }
+    println!("Would write {} files, {total} bytes", files.len());
 }
 
-impl Resource {
-    fn is_compressed(&self) -> bool {
-        self.header
-            .as_ref()
-            .map(|h| h.is_compressed())
-            .unwrap_or(false)
+/// Prints how many resources of each type were dumped, and how many
+/// failed.
+fn print_summary(
+    counts: &BTreeMap<Option<ResourceType>, usize>,
+    failed: usize,
+    total: usize,
+    start: Instant,
+) {
+    status!(
+        "Dumped {} of {total} resources in {:.1}s",
+        total - failed,
+        start.elapsed().as_secs_f32()
+    );
+
+    let rows = ResourceType::ALL
+        .iter()
+        .map(|kind| (kind.name(), counts.get(&Some(*kind))))
+        .chain([("unknown", counts.get(&None))]);
+    for (name, count) in rows {
+        if let Some(count) = count {
+            status!("  {name:<10} {count:>6}");
+        }
+    }
+    if failed > 0 {
+        status!("  {:<10} {failed:>6}", "failed");
     }
 }
 
-fn read_dinner_table<R: Read + ReadBytesExt>(file: &mut R) -> std::io::Result<Vec<Entry>> {
-    let entry_count = file.read_le_u32()?;
+fn write_csv_directory<R: Source>(
+    archive: &Archive<R>,
+    names: &ResourceNames,
+    path: &str,
+) -> Result<()> {
+    let version = archive.version();
+    let mut reader = archive.reader()?;
+    let mut wtr = output().csv_writer(path);
+
+    for entry in archive.entries() {
+        let read = reader
+            .read_entry(entry)
+            .and_then(|stored| Ok((decode_resource(entry, stored.clone())?, stored)));
+        let ((resource, decompress_error), stored) = match read {
+            Ok(read) => read,
+            Err(e) => {
+                eprintln!("{}", Error::resource(entry.number, e));
+                continue;
+            }
+        };
 
-    let mut directory = Vec::with_capacity(entry_count as usize);
-    for _ in 0..entry_count {
-        let number = file.read_le_u16()?;
-        let offset = file.read_le_u24()?;
-        let size = file.read_le_u24()?;
+        let resource_type =
+            classify(&resource, version).map_or("".to_owned(), |t| t.name().to_owned());
 
-        let has_file_header = size >> 23 == 0;
-        let uses_file_header = size >> 22 == 0;
-        let size = size & 0x3f_ff_ff;
+        let header = resource.header;
 
-        directory.push(Entry {
-            number,
-            offset,
-            size,
-            has_file_header,
-            uses_file_header,
-        });
+        let csv_line = CsvRecord {
+            r#type: resource_type,
+            id: resource.entry.number.into(),
+            palette: None,
+            comment: names
+                .name(resource.entry.number)
+                .unwrap_or_default()
+                .to_owned(),
+            size: resource.data.len(),
+            flags: header.as_ref().map(|h| h.flags),
+            x: header.as_ref().map(|h| h.x),
+            y: header.as_ref().map(|h| h.y),
+            width: header.as_ref().map(|h| h.width),
+            height: header.as_ref().map(|h| h.height),
+            sp_size: header.as_ref().map(|h| h.sp_size),
+            tot_size: header.as_ref().map(|h| h.tot_size),
+            n_sprites: header.as_ref().map(|h| h.n_sprites),
+            offset_x: header.as_ref().map(|h| h.offset_x),
+            offset_y: header.as_ref().map(|h| h.offset_y),
+            compressed_size: header.as_ref().map(|h| h.compressed_size),
+            stored_size: stored.len() as u32,
+            compressed: header.as_ref().is_some_and(|h| h.is_compressed()),
+            sha1: sha1_smol::Sha1::from(&resource.data).digest().to_string(),
+            crc32: format!("{:08x}", checksum::crc32(&stored)),
+            decompress_error: decompress_error.map(|e| e.to_string()),
+            volume: (archive.volume_count() > 1).then(|| archive.volume(entry)),
+        };
+        wtr.serialize(csv_line)?;
     }
+    output::finish_csv(wtr)?;
 
-    Ok(directory)
+    Ok(())
 }
 
-fn read_entry<R: Read + Seek + ReadBytesExt>(
-    entry: &Entry,
-    file: &mut R,
-) -> std::io::Result<Vec<u8>> {
-    file.seek(std::io::SeekFrom::Start(entry.offset as u64))?;
+/// Names the sprites objects are drawn with after the first object drawn
+/// with each, where the item number stands for only one sprite resource.
+fn name_sprites<R>(names: &mut ResourceNames, archive: &Archive<R>, cpt: &CptFile) {
+    for entry in cpt.entries.values() {
+        let Some((item, _)) = entry.compact().and_then(|c| c.references().sprite) else {
+            continue;
+        };
+        if let [id] = sprite_resources(archive, item)[..] {
+            names.add(id, &entry.name);
+        }
+    }
+}
 
-    let mut buf = Vec::<u8>::new();
-    file.take(entry.size as u64).read_to_end(&mut buf)?;
+/// Loads the archive in `path`, merged with those of `volumes` if given,
+/// see [`Archive::merge`].
+fn load_volumes(path: &Path, volumes: &[PathBuf]) -> Result<Archive<Cursor<Vec<u8>>>> {
+    let archive = Archive::load(path).map_err(|e| Error::file(path, e))?;
+    if volumes.is_empty() {
+        return Ok(archive);
+    }
 
-    Ok(buf)
+    let mut archives = vec![archive];
+    for volume in volumes {
+        archives.push(Archive::load(volume).map_err(|e| Error::file(volume, e))?);
+    }
+    Archive::merge(archives).map_err(|e| Error::file(path, e))
 }
 
-fn read_resource(entry: &Entry, data: Vec<u8>) -> std::io::Result<Resource> {
-    if !entry.has_file_header {
-        return Ok(Resource {
-            entry: *entry,
-            header: None,
-            data,
-        });
+fn dump(path: &Path, args: &DumpArgs) -> Result<()> {
+    let sink = match args.dump_zip {
+        Some(ref zip_path) if is_stdout(zip_path) => {
+            STATUS_TO_STDERR.store(true, Ordering::Relaxed);
+            Output::zip(BufWriter::new(io::stdout()), &args.output)
+        }
+        Some(ref zip_path) => {
+            Output::create_zip(zip_path, &args.output).map_err(|e| Error::file(zip_path, e))?
+        }
+        None if args.dry_run => Output::dry_run(),
+        None => Output::directory(),
+    };
+    assert!(
+        OUTPUT.set(sink.incremental(args.incremental)).is_ok(),
+        "the output of a dump is chosen before anything is written"
+    );
+    let mut archive = load_volumes(path, &args.volumes)?;
+
+    let directory = archive.entries().to_vec();
+    let version = archive.version();
+
+    let mut names = ResourceNames::builtin(version);
+    if let Some(ref path) = args.names {
+        names.extend(ResourceNames::read(path).map_err(|e| Error::file(path, e))?);
     }
 
-    let mut r = Cursor::new(data);
-    let header = Header {
-        flags: r.read_le_u16()?,
-        x: r.read_le_u16()?,
-        y: r.read_le_u16()?,
-        width: r.read_le_u16()?,
-        height: r.read_le_u16()?,
-        sp_size: r.read_le_u16()?,
-        tot_size: r.read_le_u16()?,
-        n_sprites: r.read_le_u16()?,
-        offset_x: r.read_le_i16()?,
-        offset_y: r.read_le_i16()?,
-        compressed_size: r.read_le_u16()?,
-    };
-
-    let data = if header.is_compressed() {
-        let uncompressed_data = decompress_rnc1(&mut r).ok();
-        uncompressed_data.unwrap_or_else(|| {
-            let mut data = Vec::new();
-            r.read_to_end(&mut data).unwrap();
-            data
-        })
+    let compacts = args.dumps(DumpClass::Decoded) && args.types.is_empty();
+    let scripts = args.dumps(DumpClass::Decoded) && args.extracts(Some(ResourceType::Script));
+    let sprites = args.dumps(DumpClass::Images) && args.extracts(Some(ResourceType::Sprite));
+    let cpt = if compacts || scripts || sprites || args.dump_csv {
+        load_compacts(path, args.compacts.as_deref())?
     } else {
-        let mut data = Vec::new();
-        r.read_to_end(&mut data)?;
-        data
+        None
     };
+    if let Some(ref cpt) = cpt {
+        name_sprites(&mut names, &archive, cpt);
+    }
 
-    Ok(Resource {
-        entry: *entry,
-        header: Some(header),
-        data,
-    })
-}
+    if args.dump_csv {
+        write_csv_directory(&archive, &names, "resources.csv")
+            .map_err(|e| Error::file("resources.csv", e))?;
+    }
 
-fn dump_entry<R: Read + Seek + ReadBytesExt>(file: &mut R, entry: &Entry) -> std::io::Result<()> {
-    let buf = read_entry(entry, file)?;
+    if args.dump_json {
+        write_json_directory(&archive, "resources.json")
+            .map_err(|e| Error::file("resources.json", e))?;
+    }
 
-    let dump_name = format!("dump/raw/{:05}.dmp", entry.number);
-    let mut dump_file = File::create(dump_name)?;
-    dump_file.write_all(&buf)?;
+    match version {
+        Some(version) => status!("Detected version: {version}"),
+        None => status!(
+            "Unknown game version ({} directory entries)",
+            directory.len()
+        ),
+    }
 
-    Ok(())
-}
+    let out = args.output.as_path();
+    let target = args.dump_zip.as_deref().unwrap_or(out);
+    if args.dry_run {
+        status!("Listing what would be dumped to `{}`", target.display());
+    } else {
+        status!("Dumping resources to `{}`", target.display());
+    }
 
-#[inline]
-fn rescale_6_bit_color_to_8_bit(c: u8) -> u8 {
-    ((255 * c as u16) / 63) as u8
-}
+    let sprite_palette = match args.sprite_palette {
+        Some(id) => Some(read_palette(&archive, id)?),
+        None => None,
+    };
 
-fn dump_resource_as_pal(resource: &Resource) -> std::io::Result<()> {
-    let data: &Vec<u8> = &resource.data;
+    let mut palette_map = PaletteMap::builtin(version);
+    if let Some(ref path) = args.palette_map {
+        palette_map.extend(PaletteMap::read(path).map_err(|e| Error::file(path, e))?);
+    }
 
-    const SCALE: usize = 16;
+    let layer_map = match args.layer_map {
+        Some(ref path) => LayerMap::read(path).map_err(|e| Error::file(path, e))?,
+        None => LayerMap::default(),
+    };
 
-    let mut image_buffer = vec![0u8; 16 * 16 * SCALE * SCALE * 3];
-    for y in 0..16 * SCALE {
-        for x in 0..16 * SCALE {
-            let out_ofs = 16 * SCALE * y + x;
-            let in_ofs = 16 * (y / 16) + (x / 16);
+    let grid_map = match args.grid_map {
+        Some(ref path) => GridMap::read(path).map_err(|e| Error::file(path, e))?,
+        None => GridMap::default(),
+    };
 
-            for j in 0..3 {
-                image_buffer[3 * out_ofs + j] = rescale_6_bit_color_to_8_bit(data[3 * in_ofs + j])
+    let cycle_map = match args.cycle_map {
+        Some(ref path) => CycleMap::read(path).map_err(|e| Error::file(path, e))?,
+        None => CycleMap::default(),
+    };
+
+    let maps = ScreenMaps {
+        palettes: palette_map,
+        layers: layer_map,
+        grids: grid_map,
+        cycles: cycle_map,
+        names,
+    };
+
+    // The version is written even when nothing else is.
+    output()
+        .create_dir_all(out)
+        .map_err(|e| Error::file(out, e))?;
+    output()
+        .check_writable(out)
+        .map_err(|e| Error::file(out, e))?;
+    check_free_space(out, &archive, args);
+    use ResourceType as T;
+    let dirs: [(_, _, &[_]); 15] = [
+        ("raw", DumpClass::Raw, &T::ALL),
+        ("screen", DumpClass::Images, &[T::Screen]),
+        ("palette", DumpClass::Images, &[T::Palette]),
+        ("sprites", DumpClass::Images, &[T::Sprite, T::Animation]),
+        ("fonts", DumpClass::Images, &[T::Font]),
+        ("grids", DumpClass::Images, &[T::Grid]),
+        ("cutscenes", DumpClass::Images, &[T::Sequence]),
+        ("ui", DumpClass::Images, &[T::Sprite, T::Animation, T::Font]),
+        ("cursors", DumpClass::Images, &[T::Sprite, T::Animation]),
+        ("audio", DumpClass::Audio, &[T::Audio, T::Music]),
+        ("speech", DumpClass::Audio, &[T::Speech]),
+        ("music", DumpClass::Audio, &[T::Music]),
+        ("text", DumpClass::Decoded, &[T::Text]),
+        ("scripts", DumpClass::Decoded, &[T::Script]),
+        // Compacts aren't resources, so they're only dumped with
+        // everything else.
+        ("compacts", DumpClass::Decoded, &[]),
+    ];
+    for (dir, class, kinds) in dirs {
+        let wanted = args.types.is_empty() || kinds.iter().any(|&k| args.extracts(Some(k)));
+        if args.dumps(class) && wanted {
+            let dir = out.join(dir);
+            output()
+                .create_dir_all(&dir)
+                .map_err(|e| Error::file(&dir, e))?;
+        }
+    }
+
+    let version_name = version.map_or("unknown".to_owned(), |v| v.to_string());
+    let version_path = out.join("version.txt");
+    output()
+        .write(&version_path, format!("{version_name}\n").as_bytes())
+        .map_err(|e| Error::file(version_path, e))?;
+
+    if args.dumps(DumpClass::Raw) && args.raw_image {
+        write_raw_image(&out.join("raw"), &archive)?;
+    }
+
+    // The index is only of use for repacking a complete dump, and the
+    // bytes between resources only belong with one.
+    if args.dumps(DumpClass::Raw) && args.types.is_empty() && !args.raw_image {
+        let index_path = output::dump_path(out, "raw", repack::INDEX_FILE_NAME);
+        let mut wtr = output().csv_writer(&index_path);
+        for entry in &directory {
+            wtr.serialize(repack::IndexRecord::from(entry))
+                .map_err(|e| Error::file(&index_path, e))?;
+        }
+        output::finish_csv(wtr).map_err(|e| Error::file(&index_path, e))?;
+
+        let gaps = archive.coverage().gaps;
+        if !gaps.is_empty() {
+            let written = write_gaps(&out.join("slack"), &archive, &gaps)?;
+            status!(
+                "{} ranges of sky.dsk aren't referred to, {written} with data written to `slack`",
+                gaps.len()
+            );
+        }
+    }
+
+    // Text is decoded first so scripts can quote the lines they refer to.
+    let text = args.dumps(DumpClass::Decoded) && args.extracts(Some(ResourceType::Text));
+    let subtitles =
+        args.subtitles && args.dumps(DumpClass::Images) && args.extracts(Some(ResourceType::Text));
+    let languages = if !text && !subtitles && !scripts {
+        BTreeMap::new()
+    } else {
+        match load_huffman_tree(path, args.huffman_tree.as_deref())? {
+            Some(tree) => decode_text(&mut archive, &tree),
+            None => {
+                status!("No Huffman tree found, skipping text");
+                BTreeMap::new()
             }
         }
+    };
+    if text {
+        dump_text(out, &languages)?;
+    }
+    if subtitles {
+        dump_subtitles(out, &archive, &languages, args)?;
     }
 
-    let dump_name = format!("dump/palette/{:05}.ppm", resource.entry.number);
-    let mut dump_file = File::create(dump_name)?;
-    writeln!(dump_file, "P6 256 256 255")?;
-    dump_file.write_all(&image_buffer)?;
+    // Scripts and compacts quote the English text, or that of the first
+    // language there is.
+    let texts: BTreeMap<u16, String> = languages
+        .values()
+        .next()
+        .map(|lines| lines.iter().map(|l| (l.id, l.text.clone())).collect())
+        .unwrap_or_default();
 
-    Ok(())
-}
+    let start = Instant::now();
+    let progress = ProgressBar::new(directory.len() as u64).with_style(
+        ProgressStyle::with_template("{wide_bar} {pos}/{len} resources, {elapsed}")
+            .expect("progress template is valid"),
+    );
 
-fn dump_screen_in_grayscale<R: Read + ReadBytesExt + Seek>(
-    screen: &Entry,
-    mut file: &mut R,
-) -> std::io::Result<()> {
-    let data = read_entry(screen, &mut file).expect("failed to read resource entry");
-    let screen_res = read_resource(screen, data)?;
+    let results: Vec<_> = directory
+        .par_iter()
+        .map(|entry| {
+            let result = dump_resource(
+                out,
+                &archive,
+                entry,
+                args,
+                sprite_palette.as_ref(),
+                &maps,
+                &texts,
+            )
+            .map_err(|e| Error::resource(entry.number, e));
+            progress.inc(1);
+            result
+        })
+        .collect();
+    progress.finish_and_clear();
 
-    let mut image_buffer = vec![0; 3 * 320 * 200];
-    for y in 0..200 {
-        for x in 0..320 {
-            let c = screen_res.data[320 * y + x];
-            for n in 0..3 {
-                image_buffer[3 * (320 * y + x) + n] = c;
+    let mut speech_manifest = Vec::new();
+    let mut counts: BTreeMap<Option<ResourceType>, usize> = BTreeMap::new();
+    let mut kinds = BTreeMap::new();
+    let mut failed = 0;
+    for (entry, result) in directory.iter().zip(results) {
+        match result {
+            Ok(dumped) => {
+                *counts.entry(dumped.kind).or_default() += 1;
+                kinds.extend(dumped.kind.map(|kind| (entry.number, kind)));
+                speech_manifest.extend(dumped.speech);
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                failed += 1;
             }
         }
     }
 
-    let dump_name = format!("dump/screen/{:05}-grayscale.ppm", screen_res.entry.number);
-    let mut dump_file = File::create(dump_name)?;
-    writeln!(dump_file, "P6 320 200 255")?;
-    dump_file.write_all(&image_buffer)?;
+    if args.dumps(DumpClass::Images) && args.extracts(Some(ResourceType::Sequence)) {
+        dump_cutscenes(out, &archive, &kinds, &maps, args)?;
+    }
 
-    Ok(())
-}
+    let ui_types = [
+        ResourceType::Sprite,
+        ResourceType::Animation,
+        ResourceType::Font,
+    ];
+    if args.dumps(DumpClass::Images) && ui_types.iter().any(|&k| args.extracts(Some(k))) {
+        dump_ui(out, &archive, args)?;
+    }
+    if args.dumps(DumpClass::Images)
+        && (args.extracts(Some(ResourceType::Sprite))
+            || args.extracts(Some(ResourceType::Animation)))
+    {
+        dump_cursors(out, &archive, args)?;
+    }
 
-fn dump_screen_with_pal<R: Read + ReadBytesExt + Seek>(
-    screen: &Entry,
-    pal: &Entry,
-    mut file: &mut R,
-) -> std::io::Result<()> {
-    let data = read_entry(screen, &mut file).expect("failed to read resource entry");
-    let screen_res = read_resource(screen, data)?;
+    if args.dumps(DumpClass::Audio) && args.extracts(Some(ResourceType::Music)) {
+        dump_music_tracks(out, &archive)?;
+        dump_sound_effects(out, &archive, args)?;
+    }
 
-    let data = read_entry(pal, &mut file).expect("failed to read resource entry");
-    let pal_res = read_resource(pal, data)?;
+    if compacts {
+        match cpt {
+            Some(ref cpt) => dump_compacts(out, &archive, cpt, &texts)?,
+            None => status!("No sky.cpt found, skipping compacts"),
+        }
+    }
+    if scripts {
+        dump_script_references(out, &archive, &texts, cpt.as_ref())?;
+    }
 
-    let mut image_buffer = vec![0; 3 * 320 * 200];
-    for y in 0..200 {
-        for x in 0..320 {
-            for n in 0..3 {
-                let c = screen_res.data[320 * y + x] as usize;
-                image_buffer[3 * (320 * y + x) + n] =
-                    rescale_6_bit_color_to_8_bit(pal_res.data[3 * c + n]);
-            }
+    if !speech_manifest.is_empty() {
+        let manifest_path = output::dump_path(out, "speech", "manifest.csv");
+        let mut wtr = output().csv_writer(&manifest_path);
+        for record in speech_manifest {
+            wtr.serialize(record)
+                .map_err(|e| Error::file(&manifest_path, e))?;
         }
+        output::finish_csv(wtr).map_err(|e| Error::file(&manifest_path, e))?;
     }
 
-    let dump_name = format!("dump/screen/{:05}.ppm", screen_res.entry.number);
-    let mut dump_file = File::create(dump_name)?;
-    writeln!(dump_file, "P6 320 200 255")?;
-    dump_file.write_all(&image_buffer)?;
+    if args.pack {
+        let path = out.join(PACK_MANIFEST_NAME);
+        write_pack_manifest(&path, version, args).map_err(|e| Error::file(&path, e))?;
+    }
+
+    if args.dry_run {
+        print_planned(&output().planned());
+    }
+    print_summary(&counts, failed, directory.len(), start);
+    if args.incremental && !args.dry_run {
+        status!("{} files were already up to date", output().unchanged());
+    }
+    if let Some(ref zip_path) = args.dump_zip {
+        let version = version.map(|v| v.to_string());
+        let files = output()
+            .finish_zip(version.as_deref())
+            .map_err(|e| Error::file(zip_path, e))?;
+        status!("Wrote {files} files to `{}`", zip_path.display());
+    }
 
     Ok(())
 }
 
-fn get_resource_by_id<R: Read + ReadBytesExt + Seek>(
-    id: u16,
-    directory: &[Entry],
-    file: &mut R,
-) -> Option<Resource> {
-    let entry = directory.iter().find(|&e| e.number == id)?;
-    let data = read_entry(entry, file).ok()?;
-    read_resource(entry, data).ok()
+/// Warns if the disk holding the output directory `out` has less space free
+/// than the dump is estimated to need, see [`estimate_dump_size`]. A disk
+/// whose free space can't be told is dumped to without a warning.
+fn check_free_space(out: &Path, archive: &Archive<Cursor<Vec<u8>>>, args: &DumpArgs) {
+    if !output().writes_to(out) {
+        return;
+    }
+    let Ok(available) = fs4::available_space(out) else {
+        return;
+    };
+
+    let needed = estimate_dump_size(archive, args);
+    if needed > available {
+        eprintln!(
+            "warning: the dump may need about {} MB, but only {} MB are free on the disk of `{}`",
+            needed.div_ceil(1_000_000),
+            available / 1_000_000,
+            out.display()
+        );
+    }
 }
 
-fn dump_audio<R: Read + ReadBytesExt + Seek>(
-    entry: &Entry,
-    mut file: &mut R,
-) -> std::io::Result<()> {
-    let data = read_entry(entry, &mut file).expect("failed to read entry");
-
-    let resource = read_resource(entry, data).expect("failed to read resource");
-    let data = resource.data;
-    let data_len = data.len() as u32;
-
-    let audio_format = 1;
-    let sample_rate = 11025;
-    let num_channels = 1;
-    let bytes_per_sample = 1;
-    let byte_rate = sample_rate * num_channels * bytes_per_sample;
-    let block_align = num_channels * bytes_per_sample;
-    let bits_per_sample = bytes_per_sample * 8;
-
-    let dump_name = format!("dump/audio/{:05}.wav", entry.number);
-    let mut dump_file = File::create(dump_name)?;
-    dump_file.write_all(&[b'R', b'I', b'F', b'F'])?;
-    dump_file.write_le_u32(data_len + 36)?;
-    dump_file.write_all(&[b'W', b'A', b'V', b'E'])?;
-
-    dump_file.write_all(&[b'f', b'm', b't', b' '])?;
-    dump_file.write_le_u32(16)?;
-    dump_file.write_le_u16(audio_format)?;
-    dump_file.write_le_u16(num_channels as u16)?;
-    dump_file.write_le_u32(sample_rate)?;
-    dump_file.write_le_u32(byte_rate)?;
-    dump_file.write_le_u16(block_align as u16)?;
-    dump_file.write_le_u16(bits_per_sample as u16)?;
-
-    dump_file.write_all(&[b'd', b'a', b't', b'a'])?;
-    dump_file.write_le_u32(data_len)?;
-    dump_file.write_all(&data)
+/// A rough estimate of the bytes a dump writes: the stored bytes of each
+/// resource if dumped raw, and the size its header gives for its decoded
+/// data if anything is dumped decoded. Nothing is decompressed or
+/// classified, so `--types` is not taken into account; images compress and
+/// audio may be resampled too, so it is only a guide.
+fn estimate_dump_size(archive: &Archive<Cursor<Vec<u8>>>, args: &DumpArgs) -> u64 {
+    let raw = args.dumps(DumpClass::Raw);
+    let decoded = DumpClass::ALL
+        .iter()
+        .any(|&class| class != DumpClass::Raw && args.dumps(class));
+
+    archive
+        .entries()
+        .iter()
+        .map(|entry| {
+            let stored = entry.size as u64;
+            let header = entry
+                .has_file_header
+                .then(|| Header::read(&mut archive.entry_bytes(entry)).ok())
+                .flatten();
+            let size = match header {
+                Some(header) if header.is_compressed() => header.total_size() as u64,
+                _ => stored,
+            };
+            (if raw { stored } else { 0 }) + (if decoded { size } else { 0 })
+        })
+        .sum()
 }
 
-#[derive(Debug, Serialize)]
-struct CsvRecord {
-    r#type: String,
-    id: i32,
-    palette: Option<i32>,
-    comment: String,
-    size: usize,
-    flags: Option<u16>,
-    x: Option<u16>,
-    y: Option<u16>,
-    width: Option<u16>,
-    height: Option<u16>,
-    sp_size: Option<u16>,
-    tot_size: Option<u16>,
-    n_sprites: Option<u16>,
-    offset_x: Option<i16>,
-    offset_y: Option<i16>,
-    compressed_size: Option<u16>,
+/// Name of the description of an asset pack at its top.
+const PACK_MANIFEST_NAME: &str = "pack.json";
+
+/// Revision of the layout of asset packs, raised when it changes in ways
+/// importers have to know about.
+const PACK_LAYOUT_VERSION: u32 = 2;
+
+/// The directories of an asset pack and what is in them.
+const PACK_DIRECTORIES: &[(&str, &str)] = &[
+    ("screen", "screens as 320x200 PNGs"),
+    (
+        "palette",
+        "palettes as swatches and as .pal, .gpl and .act files",
+    ),
+    (
+        "sprites",
+        "sprite frames, sheets of them with the frame size, offset and columns \
+         of the sheet in <id>.json, and texture atlases of the trimmed frames \
+         described by <id>-atlas.json",
+    ),
+    (
+        "fonts",
+        "character sets as glyph sheets with JSON glyph widths",
+    ),
+    (
+        "grids",
+        "walk grids as PNGs and JSON lists of blocked cells",
+    ),
+    ("cutscenes", "cutscenes as the frames of their sequences"),
+    ("ui", "sprites and fonts of the control panel, with ui.json"),
+    (
+        "cursors",
+        "mouse cursors, with their hotspots in cursors.json",
+    ),
+    (
+        "audio",
+        "sound effects as Ogg Vorbis files, listed in sfx.json",
+    ),
+    (
+        "speech",
+        "lines of speech as Ogg Vorbis files, listed in manifest.csv",
+    ),
+    (
+        "music",
+        "music as stored and as MIDI files, listed in manifest.csv",
+    ),
+    (
+        "text",
+        "text per language as UTF-8 text and JSON, keyed by text id",
+    ),
+    ("scripts", "scripts disassembled and decompiled"),
+    ("compacts", "game objects as JSON"),
+];
+
+#[derive(Serialize)]
+struct PackManifest<'a> {
+    layout_version: u32,
+    game_version: Option<String>,
+    image_format: &'static str,
+    audio_format: &'static str,
+    /// How the audio files encode their samples.
+    audio_codec: &'static str,
+    sample_rate: u32,
+    text_encoding: &'static str,
+    directories: BTreeMap<&'a str, &'a str>,
 }
 
-fn main() {
-    let args = Cli::parse();
+/// Writes the description of the asset pack dumped with `args` to `path`.
+fn write_pack_manifest(path: &Path, version: Option<&Version>, args: &DumpArgs) -> Result<()> {
+    let manifest = PackManifest {
+        layout_version: PACK_LAYOUT_VERSION,
+        game_version: version.map(|v| v.to_string()),
+        image_format: args.format.name(),
+        audio_format: args.audio_format.name(),
+        audio_codec: match args.audio_format {
+            AudioFormat::Wav => "pcm_s16le",
+            AudioFormat::Flac | AudioFormat::OggFlac => "flac",
+            AudioFormat::Ogg => "vorbis",
+        },
+        sample_rate: audio::RESAMPLE_RATE,
+        text_encoding: "utf-8",
+        directories: PACK_DIRECTORIES.iter().copied().collect(),
+    };
+    output().write_json(path, &manifest)?;
 
-    let path = if args.path.is_dir() {
-        args.path
-    } else {
-        args.path
-            .parent()
-            .unwrap_or_else(|| panic!("Invalid path `{}`", args.path.display()))
-            .to_path_buf()
+    Ok(())
+}
+
+fn diff(args: &DiffArgs) -> Result<()> {
+    let before = Archive::load(&args.before).map_err(|e| Error::file(&args.before, e))?;
+    let after = Archive::load(&args.after).map_err(|e| Error::file(&args.after, e))?;
+
+    for (path, archive) in [(&args.before, &before), (&args.after, &after)] {
+        println!(
+            "{}: {}, {} resources",
+            path.display(),
+            archive
+                .version()
+                .map_or("unknown version".to_owned(), |v| v.to_string()),
+            archive.entries().len()
+        );
+    }
+
+    let checksums = |archive: &Archive<Cursor<Vec<u8>>>| -> Vec<_> {
+        archive
+            .entries()
+            .par_iter()
+            .map(|entry| Checksum::new(entry.number, archive.entry_bytes(entry)))
+            .collect()
     };
+    let changes = diff::diff(&checksums(&before), &checksums(&after));
 
-    let mut sky_dnr_path = None;
-    let mut sky_dsk_path = None;
+    let kind = |archive: &Archive<Cursor<Vec<u8>>>, id| {
+        archive
+            .get_resource(id)
+            .and_then(|r| classify(&r, archive.version()))
+            .map_or("unknown", |t| t.name())
+    };
+    let short = |sha1: &str| sha1[..10.min(sha1.len())].to_owned();
 
-    for entry in path.read_dir().expect("read_dir call failed").flatten() {
-        if entry.file_name().eq_ignore_ascii_case("sky.dnr") {
-            sky_dnr_path = Some(entry.path());
-        }
-        if entry.file_name().eq_ignore_ascii_case("sky.dsk") {
-            sky_dsk_path = Some(entry.path());
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &changes {
+        match change {
+            Change::Added(new) => {
+                added += 1;
+                println!(
+                    "{:05}  added    {:<9} {} bytes",
+                    new.id,
+                    kind(&after, new.id),
+                    new.size
+                );
+            }
+            Change::Removed(old) => {
+                removed += 1;
+                println!(
+                    "{:05}  removed  {:<9} {} bytes",
+                    old.id,
+                    kind(&before, old.id),
+                    old.size
+                );
+            }
+            Change::Changed {
+                before: old,
+                after: new,
+            } => {
+                changed += 1;
+                let decoded_same = before
+                    .get_resource(old.id)
+                    .zip(after.get_resource(new.id))
+                    .is_some_and(|(a, b)| {
+                        a.header.is_some() == b.header.is_some() && a.data == b.data
+                    });
+                let delta = new.size as i64 - old.size as i64;
+                println!(
+                    "{:05}  changed  {:<9} {} -> {} bytes ({delta:+}), SHA-1 {} -> {}{}",
+                    new.id,
+                    kind(&after, new.id),
+                    old.size,
+                    new.size,
+                    short(&old.sha1),
+                    short(&new.sha1),
+                    if decoded_same {
+                        ", same data once decoded"
+                    } else {
+                        ""
+                    }
+                );
+            }
         }
     }
 
-    let sky_dnr_path = sky_dnr_path.expect("sky.dnr not found");
-    let sky_dsk_path = sky_dsk_path.expect("sky.dsk not found");
+    let unchanged = after.entries().len() - added - changed;
+    println!("{added} added, {removed} removed, {changed} changed, {unchanged} unchanged");
+
+    Ok(())
+}
+
+fn coverage(args: &CoverageArgs) -> Result<()> {
+    let archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let coverage = archive.coverage();
+
+    for overlap in &coverage.overlaps {
+        println!(
+            "{:05} and {:05} overlap: {} bytes at 0x{:06x}",
+            overlap.first, overlap.second, overlap.len, overlap.offset
+        );
+    }
+    for ids in &coverage.duplicates {
+        let ids: Vec<_> = ids.iter().map(|id| format!("{id:05}")).collect();
+        println!("Identical data: {}", ids.join(", "));
+    }
+    for gap in &coverage.gaps {
+        println!(
+            "Unreferenced: {} bytes at 0x{:06x}{}",
+            gap.len,
+            gap.offset,
+            if gap.zero { ", all zero" } else { "" }
+        );
+    }
 
-    let mut sky_dnr_file = BufReader::new(
-        File::open(sky_dnr_path.clone())
-            .unwrap_or_else(|_| panic!("unable to open `{}`", sky_dnr_path.display())),
+    let unreferenced: u64 = coverage.gaps.iter().map(|g| g.len as u64).sum();
+    println!(
+        "{} entries cover {} of {} bytes ({:.2}%), {} unreferenced in {} gaps",
+        archive.entries().len(),
+        coverage.covered,
+        coverage.len,
+        100.0 * coverage.covered as f64 / coverage.len.max(1) as f64,
+        unreferenced,
+        coverage.gaps.len()
     );
-    let mut sky_dsk_file = BufReader::new(
-        File::open(sky_dsk_path.clone())
-            .unwrap_or_else(|_| panic!("unable to open `{}`", sky_dsk_path.display())),
+    println!(
+        "{} overlaps, {} groups of identical entries",
+        coverage.overlaps.len(),
+        coverage.duplicates.len()
     );
 
-    let directory = read_dinner_table(&mut sky_dnr_file).expect("error reading dnr file");
+    if let Some(ref dir) = args.gaps {
+        write_gaps(dir, &archive, &coverage.gaps)?;
+    }
 
-    if args.dump_csv {
-        let mut wtr =
-            Writer::from_path("resources.csv").expect("unable to open resources.csv for output");
+    Ok(())
+}
 
-        for entry in &directory {
-            let data = read_entry(entry, &mut sky_dsk_file).expect("failed to read resource entry");
-            let resource = read_resource(entry, data).expect("failed to read resource");
-
-            let guessed_type = if resource.data.len() == 768 {
-                "palette".to_owned()
-            } else if resource.data.len() == 64000 {
-                "screen".to_owned()
-            } else {
-                "".to_owned()
-            };
+fn scummvm_manifest(args: &ScummvmManifestArgs) -> Result<()> {
+    let archive = open_archive(&args.path)?;
+    let (dnr_path, dsk_path) =
+        archive::find_data_files(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    // ScummVM knows the files by their lowercase names whatever they are
+    // called here.
+    let dnr =
+        scummvm::FileInfo::read("sky.dnr", &dnr_path).map_err(|e| Error::file(&dnr_path, e))?;
+    let dsk =
+        scummvm::FileInfo::read("sky.dsk", &dsk_path).map_err(|e| Error::file(&dsk_path, e))?;
 
-            let header = resource.header;
-
-            let csv_line = CsvRecord {
-                r#type: guessed_type,
-                id: entry.number.into(),
-                palette: None,
-                comment: "".to_owned(),
-                size: resource.data.len(),
-                flags: header.as_ref().map(|h| h.flags),
-                x: header.as_ref().map(|h| h.x),
-                y: header.as_ref().map(|h| h.y),
-                width: header.as_ref().map(|h| h.width),
-                height: header.as_ref().map(|h| h.height),
-                sp_size: header.as_ref().map(|h| h.sp_size),
-                tot_size: header.as_ref().map(|h| h.tot_size),
-                n_sprites: header.as_ref().map(|h| h.n_sprites),
-                offset_x: header.as_ref().map(|h| h.offset_x),
-                offset_y: header.as_ref().map(|h| h.offset_y),
-                compressed_size: header.as_ref().map(|h| h.compressed_size),
-            };
-            wtr.serialize(csv_line).expect("unable to serialize record");
+    let languages = text::Language::ALL
+        .into_iter()
+        .filter(|l| archive.entry(text::text_file(l.number(), 0)).is_some())
+        .collect();
+
+    let manifest = scummvm::Manifest {
+        entries: archive.entries().len(),
+        dnr,
+        dsk,
+        version: archive.version(),
+        languages,
+    };
+
+    match args.out {
+        Some(ref out) => std::fs::write(out, manifest.to_string()).map_err(|e| Error::file(out, e)),
+        None => {
+            print!("{manifest}");
+            Ok(())
+        }
+    }
+}
+
+fn fingerprint(args: &FingerprintArgs) -> Result<()> {
+    let archive = open_archive(&args.path)?;
+
+    let checked: Vec<_> = archive
+        .entries()
+        .par_iter()
+        .map(|entry| {
+            let data = archive.entry_bytes(entry);
+            (
+                Checksum::new(entry.number, data),
+                checksum::check_resource(entry, data),
+            )
+        })
+        .collect();
+    let (checksums, damaged): (Vec<_>, Vec<_>) = checked.into_iter().unzip();
+
+    // Only a copy known to be good is worth adding to the known releases.
+    let problems: Vec<_> = damaged.into_iter().flatten().collect();
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("{problem}");
+        }
+        return Err(Error::InvalidData(format!(
+            "{} resources are damaged",
+            problems.len()
+        )));
+    }
+
+    let fingerprint = Fingerprint::new(archive.version(), checksums);
+    match args.out {
+        Some(ref out) => {
+            let file = BufWriter::new(File::create(out).map_err(|e| Error::file(out, e))?);
+            fingerprint.write(file).map_err(|e| Error::file(out, e))
+        }
+        None => {
+            fingerprint.write(std::io::stdout().lock())?;
+            println!();
+            Ok(())
         }
     }
+}
 
-    println!("Dumping resources to `dump/`");
+/// Name of the image of `sky.dsk` written by `--raw-image`.
+const RAW_IMAGE_NAME: &str = "sky.dsk";
 
-    for dir in ["dump/audio", "dump/raw", "dump/screen", "dump/palette"] {
-        _ = std::fs::create_dir_all(dir);
+/// A range of the image of `sky.dsk`: an entry, or bytes no entry refers
+/// to, which have no id.
+#[derive(Serialize)]
+struct RawImageRecord {
+    offset: String,
+    end: String,
+    len: u32,
+    id: Option<u16>,
+    r#type: Option<&'static str>,
+    has_file_header: Option<bool>,
+    compressed: Option<bool>,
+    /// Whether the bytes no entry refers to were all zero in `sky.dsk`.
+    zero: Option<bool>,
+}
+
+/// Writes an image of `sky.dsk` to `dir` with the data of every entry at
+/// its offset and zeros where no entry refers to, and a list of the
+/// entries and the ranges between them in order of offset to
+/// `sky.dsk.csv`.
+fn write_raw_image(dir: &Path, archive: &Archive<Cursor<Vec<u8>>>) -> Result<()> {
+    let len = archive.dsk_bytes(0, u32::MAX).len();
+    let mut image = vec![0; len];
+    let mut records = Vec::new();
+    let hex = |offset: u32| format!("0x{offset:06x}");
+
+    for entry in archive.entries() {
+        let data = archive.entry_bytes(entry);
+        let start = (entry.offset as usize).min(len);
+        image[start..start + data.len()].copy_from_slice(data);
+
+        let resource = archive.get_resource(entry.number);
+        records.push((
+            entry.offset,
+            RawImageRecord {
+                offset: hex(entry.offset),
+                end: hex(entry.offset + entry.size),
+                len: entry.size,
+                id: Some(entry.number),
+                r#type: resource
+                    .as_ref()
+                    .and_then(|r| classify(r, archive.version()))
+                    .map(|t| t.name()),
+                has_file_header: Some(entry.has_file_header),
+                compressed: resource.as_ref().map(|r| r.is_compressed()),
+                zero: None,
+            },
+        ));
     }
+    for gap in archive.coverage().gaps {
+        records.push((
+            gap.offset,
+            RawImageRecord {
+                offset: hex(gap.offset),
+                end: hex(gap.offset + gap.len),
+                len: gap.len,
+                id: None,
+                r#type: None,
+                has_file_header: None,
+                compressed: None,
+                zero: Some(gap.zero),
+            },
+        ));
+    }
+    records.sort_by_key(|(offset, r)| (*offset, r.id));
 
-    for entry in &directory {
-        let data = read_entry(entry, &mut sky_dsk_file).expect("failed to read resource entry");
-        dump_entry(&mut sky_dsk_file, entry).expect("failed to dump entry");
+    let image_path = dir.join(RAW_IMAGE_NAME);
+    output()
+        .write(&image_path, &image)
+        .map_err(|e| Error::file(&image_path, e))?;
+
+    let index_path = image_path.with_extension("dsk.csv");
+    let mut wtr = output().csv_writer(&index_path);
+    for (_, record) in records {
+        wtr.serialize(record)
+            .map_err(|e| Error::file(&index_path, e))?;
+    }
+    output::finish_csv(wtr).map_err(|e| Error::file(&index_path, e))?;
 
-        let resource = read_resource(entry, data).expect("failed to read resource");
-        if !entry.has_file_header && entry.size == 768 {
-            dump_resource_as_pal(&resource).expect("failed to dump entry");
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct GapRecord {
+    offset: String,
+    len: u32,
+    zero: bool,
+    file: Option<String>,
+}
+
+/// Writes the ranges of `gaps` holding anything but zeros to `dir` as
+/// `<offset>.bin`, and a list of all of them to `index.csv`. Returns the
+/// number of files written.
+fn write_gaps(dir: &Path, archive: &Archive<Cursor<Vec<u8>>>, gaps: &[Gap]) -> Result<usize> {
+    output()
+        .create_dir_all(dir)
+        .map_err(|e| Error::file(dir, e))?;
+
+    let index_path = dir.join("index.csv");
+    let mut wtr = output().csv_writer(&index_path);
+    let mut written = 0;
+    for gap in gaps {
+        let file = (!gap.zero).then(|| format!("{:06x}.bin", gap.offset));
+        if let Some(ref file) = file {
+            let path = dir.join(file);
+            output()
+                .write(&path, archive.dsk_bytes(gap.offset, gap.len))
+                .map_err(|e| Error::file(&path, e))?;
+            written += 1;
         }
 
-        if resource.data.len() == 64000 {
-            let mut pal = get_resource_by_id(entry.number + 1, &directory, &mut sky_dsk_file);
-            if pal.as_ref().map_or(false, |r| r.data.len() != 768) {
-                pal = get_resource_by_id(entry.number - 1, &directory, &mut sky_dsk_file);
-            }
-            if pal.is_some() && pal.as_ref().unwrap().data.len() != 768 {
-                pal = None;
-            }
+        wtr.serialize(GapRecord {
+            offset: format!("0x{:06x}", gap.offset),
+            len: gap.len,
+            zero: gap.zero,
+            file,
+        })
+        .map_err(|e| Error::file(&index_path, e))?;
+    }
+    output::finish_csv(wtr).map_err(|e| Error::file(&index_path, e))?;
+
+    Ok(written)
+}
+
+fn verify(args: &VerifyArgs) -> Result<()> {
+    let archive = Archive::load(&args.path).map_err(|e| Error::file(&args.path, e))?;
+    let version = archive.version();
+
+    let checked: Vec<_> = archive
+        .entries()
+        .par_iter()
+        .map(|entry| {
+            let data = archive.entry_bytes(entry);
+            (
+                Checksum::new(entry.number, data),
+                checksum::check_resource(entry, data),
+            )
+        })
+        .collect();
+    let (checksums, damaged): (Vec<_>, Vec<_>) = checked.into_iter().unzip();
+    let mut problems: Vec<_> = damaged.into_iter().flatten().collect();
 
-            if let Some(ref pal) = pal {
-                dump_screen_with_pal(entry, &pal.entry, &mut sky_dsk_file).ok();
-            } else {
-                dump_screen_in_grayscale(entry, &mut sky_dsk_file).ok();
+    if let Some(ref path) = args.write {
+        if problems.is_empty() {
+            let file = BufWriter::new(File::create(path).map_err(|e| Error::file(path, e))?);
+            checksum::write_checksums(file, &checksums).map_err(|e| Error::file(path, e))?;
+            println!("Wrote checksums of {} resources", checksums.len());
+            return Ok(());
+        }
+    } else {
+        match args.checksums {
+            Some(ref path) => {
+                let file = File::open(path).map_err(|e| Error::file(path, e))?;
+                let expected = checksum::read_checksums(file).map_err(|e| Error::file(path, e))?;
+                problems.extend(checksum::compare(&expected, &checksums));
             }
-        } else if resource.header.map_or(false, |h| h.x & 0x8000 != 0) {
-            dump_audio(entry, &mut sky_dsk_file).ok();
+            None => println!(
+                "No checksums given for {}, only checking that resources are whole and decompress",
+                version.map_or("this release".to_owned(), |v| v.to_string())
+            ),
+        }
+        problems.sort_by_key(checksum::Problem::id);
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+
+    if !problems.is_empty() {
+        let skipped = if args.write.is_some() {
+            ", not writing checksums"
+        } else {
+            ""
+        };
+        let plural = if problems.len() == 1 { "" } else { "s" };
+        return Err(Error::InvalidData(format!(
+            "Found {} problem{plural}{skipped}",
+            problems.len()
+        )));
+    }
+    println!("All {} resources are intact", checksums.len());
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+
+    let result = match args.command {
+        Some(Command::List(ref list_args)) => list(list_args),
+        Some(Command::Extract(ref extract_args)) => extract(extract_args),
+        Some(Command::Cat(ref cat_args)) => cat(cat_args),
+        Some(Command::Info(ref info_args)) => info(info_args),
+        Some(Command::Stats(ref stats_args)) => stats(stats_args),
+        Some(Command::Browse(ref browse_args)) => browse(browse_args),
+        Some(Command::Verify(ref verify_args)) => verify(verify_args),
+        Some(Command::Coverage(ref coverage_args)) => coverage(coverage_args),
+        Some(Command::Diff(ref diff_args)) => diff(diff_args),
+        Some(Command::ScummvmManifest(ref manifest_args)) => scummvm_manifest(manifest_args),
+        Some(Command::Fingerprint(ref fingerprint_args)) => fingerprint(fingerprint_args),
+        Some(Command::Repack(ref repack_args)) => repack(repack_args),
+        Some(Command::Patch(ref patch_args)) => patch(patch_args),
+        Some(Command::ImportScreen(ref import_args)) => import_screen(import_args),
+        Some(Command::ImportFont(ref import_args)) => import_font(import_args),
+        Some(Command::PackSpeech(ref pack_args)) => pack_speech(pack_args),
+        Some(Command::PackText(ref pack_args)) => pack_text(pack_args),
+        Some(Command::RenderRoom(ref render_args)) => render_room(render_args),
+        Some(Command::ExtractRoom(ref room_args)) => extract_room(room_args),
+        Some(Command::Savegame(ref savegame_args)) => savegame(savegame_args),
+        Some(Command::ExtractAll(ref extract_args)) => extract_all(extract_args),
+        Some(Command::ExportPack(ref pack_args)) => export_pack(pack_args),
+        None => dump(args.path.as_deref().unwrap(), &args.options),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
         }
     }
 }
@@ -0,0 +1,107 @@
+//! Writing of standard MIDI files.
+
+use std::io::{self, Write};
+
+use crate::bytes_ext::WriteBytesExt;
+
+/// Ticks per quarter note of the files written.
+pub const DIVISION: u16 = 480;
+
+/// The events of one track, each at an absolute tick.
+#[derive(Clone, Debug, Default)]
+pub struct MidiTrack {
+    events: Vec<(u32, Vec<u8>)>,
+}
+
+impl MidiTrack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Adds a channel message. Events at the same tick are kept in the
+    /// order they are added.
+    pub fn event(&mut self, tick: u32, message: &[u8]) {
+        self.events.push((tick, message.to_vec()));
+    }
+
+    pub fn note_on(&mut self, tick: u32, channel: u8, note: u8, velocity: u8) {
+        self.event(tick, &[0x90 | channel, note, velocity]);
+    }
+
+    pub fn note_off(&mut self, tick: u32, channel: u8, note: u8) {
+        self.event(tick, &[0x80 | channel, note, 0]);
+    }
+
+    pub fn program_change(&mut self, tick: u32, channel: u8, program: u8) {
+        self.event(tick, &[0xc0 | channel, program]);
+    }
+
+    pub fn meta(&mut self, tick: u32, kind: u8, data: &[u8]) {
+        let mut message = vec![0xff, kind];
+        push_var_len(&mut message, data.len() as u32);
+        message.extend_from_slice(data);
+        self.events.push((tick, message));
+    }
+
+    pub fn name(&mut self, name: &str) {
+        self.meta(0, 0x03, name.as_bytes());
+    }
+
+    pub fn marker(&mut self, tick: u32, text: &str) {
+        self.meta(tick, 0x06, text.as_bytes());
+    }
+
+    /// Sets the length of a quarter note in microseconds from `tick` on.
+    pub fn tempo(&mut self, tick: u32, us_per_quarter: u32) {
+        let us = us_per_quarter.min(0xff_ffff).to_be_bytes();
+        self.meta(tick, 0x51, &us[1..]);
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut events = self.events.clone();
+        events.sort_by_key(|&(tick, _)| tick);
+
+        let mut body = Vec::new();
+        let mut last = 0;
+        for (tick, message) in &events {
+            push_var_len(&mut body, tick - last);
+            body.extend_from_slice(message);
+            last = *tick;
+        }
+        body.extend_from_slice(&[0, 0xff, 0x2f, 0]);
+
+        w.write_all(b"MTrk")?;
+        w.write_be_u32(body.len() as u32)?;
+        w.write_all(&body)
+    }
+}
+
+/// Writes `tracks` as a format 1 standard MIDI file with [`DIVISION`]
+/// ticks per quarter note.
+pub fn write_smf<W: Write>(w: &mut W, tracks: &[MidiTrack]) -> io::Result<()> {
+    w.write_all(b"MThd")?;
+    w.write_be_u32(6)?;
+    w.write_be_u16(1)?;
+    w.write_be_u16(tracks.len() as u16)?;
+    w.write_be_u16(DIVISION)?;
+
+    for track in tracks {
+        track.write(w)?;
+    }
+
+    Ok(())
+}
+
+fn push_var_len(out: &mut Vec<u8>, v: u32) {
+    let mut groups = vec![(v & 0x7f) as u8];
+    let mut v = v >> 7;
+    while v != 0 {
+        groups.push((v & 0x7f) as u8 | 0x80);
+        v >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
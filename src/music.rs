@@ -0,0 +1,633 @@
+//! Music of the floppy and CD releases.
+//!
+//! Every section of the game has [`FILES_PER_SECTION`] resources from
+//! [`MUSIC_FILE_BASE`] on. The first holds the music for Roland MT-32
+//! devices, which the game also plays on General MIDI ones, and the third
+//! the AdLib music, both with the driver the original game played them
+//! with. The last is the sound effect bank.
+//!
+//! Each driver stores where its music data starts. The data begins with
+//! the number of tracks and the offset of a table with the offset of each
+//! track, all offsets counted from the start of the music data. A track
+//! holds two tempo bytes, the number of channels and the offset of each
+//! channel's event data. Track 0 means no music, the table starts with
+//! track 1.
+//!
+//! A channel's events are each preceded by the number of ticks to wait
+//! for, and are either a note to play, a switch to another channel of the
+//! device or a command such as a change of instrument or tempo.
+//! [`Device::to_midi`] plays them the way the driver does and records the
+//! notes as MIDI.
+//!
+//! The Roland driver also holds the timbres and patches it sets the MT-32
+//! up with, which [`roland_sysex`] turns back into the system exclusive
+//! messages the driver sends.
+
+use serde::Serialize;
+
+use crate::{
+    classify::{FILES_PER_SECTION, MUSIC_FILE_BASE, NO_OF_SECTIONS},
+    midi::{MidiTrack, DIVISION},
+    version::Version,
+};
+
+/// The devices the game has music for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    Roland,
+    Adlib,
+}
+
+impl Device {
+    pub const ALL: [Device; 2] = [Device::Roland, Device::Adlib];
+
+    /// Index of the device's music among the resources of a section.
+    fn file_index(self) -> u16 {
+        match self {
+            Device::Roland => 0,
+            Device::Adlib => 2,
+        }
+    }
+
+    /// The id of the device's music resource of `section`.
+    pub fn file(self, section: u16) -> u16 {
+        MUSIC_FILE_BASE + section * FILES_PER_SECTION + self.file_index()
+    }
+
+    /// The device music resource `id` is for, if it is one.
+    pub fn of_file(id: u16) -> Option<Device> {
+        music_section(id)?;
+        Device::ALL
+            .into_iter()
+            .find(|d| (id - MUSIC_FILE_BASE) % FILES_PER_SECTION == d.file_index())
+    }
+
+    /// Where the driver stores the offset of the music data. The drivers of
+    /// the PC Gamer demo are laid out differently from the others.
+    fn music_data_loc(self, version: Option<&Version>) -> usize {
+        match self {
+            Device::Roland if version.is_some_and(|v| v.number == 109) => 0x79b,
+            Device::Roland => 0x7dc,
+            Device::Adlib => AdlibLayout::new(version).music_data_loc,
+        }
+    }
+
+    /// Lists the tracks of a music resource for the device, leaving out any
+    /// that don't fit in the data.
+    pub fn tracks(self, data: &[u8], version: Option<&Version>) -> Vec<Track> {
+        read_le_u16(data, self.music_data_loc(version))
+            .map_or_else(Vec::new, |base| read_tracks(data, base as usize))
+    }
+
+    /// Converts `track` of a music resource for the device to MIDI, one
+    /// MIDI track for each channel after one for the tempo. A MIDI tick is
+    /// a tick of the driver. Channels are played up to where they stop or
+    /// jump back to their loop point, which are marked `loopStart` and
+    /// `loopEnd`.
+    ///
+    /// The Roland music is MIDI already and is kept as it is sent. AdLib
+    /// instruments become program changes to the program of the same
+    /// number, so those tracks play with the right rhythm and pitch but not
+    /// the sound of the original.
+    pub fn to_midi(self, data: &[u8], track: &Track, version: Option<&Version>) -> Vec<MidiTrack> {
+        let mut tempo = MidiTrack::new();
+        tempo.name(&format!("Track {}", track.number));
+        tempo.tempo(0, us_per_quarter(track.tempo.0, track.tempo.1));
+        let mut tracks = vec![tempo];
+
+        let layout = AdlibLayout::new(version);
+        let driver = AdlibDriver {
+            data,
+            instrument_map: read_le_u16(data, layout.instrument_loc).map(usize::from),
+            base_note: base_note(data, layout.frequency_table),
+        };
+
+        let midi_channels = (0..16).filter(|&c| c != 9);
+        for (n, (&start, midi_channel)) in track.channels.iter().zip(midi_channels).enumerate() {
+            let mut seq = Sequence::new(data, start as usize);
+            match self {
+                Device::Roland => {
+                    seq.midi.name(&format!("Roland channel {n}"));
+                    RolandChannel::default().play(&mut seq);
+                }
+                Device::Adlib => {
+                    seq.midi.name(&format!("AdLib channel {n}"));
+                    AdlibChannel::new(midi_channel).play(&mut seq, &driver);
+                }
+            }
+
+            for &(tick, tempo1) in &seq.tempo_changes {
+                tracks[0].tempo(tick, us_per_quarter(track.tempo.0, tempo1));
+            }
+            tracks.push(seq.midi);
+        }
+
+        tracks
+    }
+}
+
+/// The section the music resource `id` belongs to, if it is one.
+pub fn music_section(id: u16) -> Option<u16> {
+    (MUSIC_FILE_BASE..MUSIC_FILE_BASE + FILES_PER_SECTION * NO_OF_SECTIONS)
+        .contains(&id)
+        .then(|| (id - MUSIC_FILE_BASE) / FILES_PER_SECTION)
+}
+
+/// How often a second the game has the driver play on.
+const POLL_RATE: u64 = 50;
+
+/// Size of an instrument of the AdLib driver, and where in it the note
+/// its notes are relative to is.
+const INSTRUMENT_SIZE: usize = 16;
+const INSTRUMENT_BINDED_EFFECT: usize = 8;
+
+/// Steps of the frequency table to a semitone.
+const FREQ_STEPS: i32 = 64;
+
+/// The manufacturer, device and model ids and the command of the data set
+/// messages the Roland driver sends.
+const ROLAND_SYSEX_HEADER: [u8; 4] = [0x41, 0x10, 0x16, 0x12];
+
+/// Where the AdLib driver keeps the tables the music is played with.
+#[derive(Copy, Clone, Debug)]
+pub struct AdlibLayout {
+    /// Holds the offset of the music data.
+    pub music_data_loc: usize,
+    /// Holds the offset of the instrument map, which is followed by the
+    /// instruments.
+    pub instrument_loc: usize,
+    /// Frequency numbers and blocks of the notes, 64 steps per semitone.
+    pub frequency_table: usize,
+}
+
+impl AdlibLayout {
+    pub fn new(version: Option<&Version>) -> Self {
+        if version.is_some_and(|v| v.number == 109) {
+            AdlibLayout {
+                music_data_loc: 0x1200,
+                instrument_loc: 0x1204,
+                frequency_table: 0x868,
+            }
+        } else {
+            AdlibLayout {
+                music_data_loc: 0x1201,
+                instrument_loc: 0x1205,
+                frequency_table: 0x7f4,
+            }
+        }
+    }
+}
+
+/// A track of a music resource.
+#[derive(Clone, Debug, Serialize)]
+pub struct Track {
+    pub number: u16,
+    /// Offset of the track from the start of the resource.
+    pub offset: u16,
+    pub tempo: (u8, u8),
+    /// Offsets of the event data of each channel from the start of the
+    /// resource.
+    pub channels: Vec<u16>,
+}
+
+pub(crate) fn read_le_u16(data: &[u8], ofs: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(ofs..ofs + 2)?.try_into().ok()?))
+}
+
+fn read_tracks(data: &[u8], base: usize) -> Vec<Track> {
+    let (Some(&count), Some(table)) = (data.get(base), read_le_u16(data, base + 1)) else {
+        return Vec::new();
+    };
+    let table = base + table as usize;
+
+    (1..=count as u16)
+        .filter_map(|number| {
+            let offset = read_le_u16(data, table + 2 * (number as usize - 1))?;
+            let pos = base + offset as usize;
+
+            let tempo = (*data.get(pos)?, *data.get(pos + 1)?);
+            let channel_count = *data.get(pos + 2)? as usize;
+            let channels = (0..channel_count)
+                .map(|n| u16::try_from(base + read_le_u16(data, pos + 3 + 2 * n)? as usize).ok())
+                .collect::<Option<_>>()?;
+
+            Some(Track {
+                number,
+                offset: u16::try_from(pos).ok()?,
+                tempo,
+                channels,
+            })
+        })
+        .collect()
+}
+
+/// The system exclusive messages, framed by `F0` and `F7`, the Roland
+/// driver sets up the MT-32 with before playing: first the timbres, then
+/// the patches using them. Returns `None` if they don't fit in the data.
+///
+/// Timbres are stored run length encoded: a byte with the top bit set is
+/// followed by the number of times to repeat its low seven bits. Patches
+/// are packed into five bytes each, and end with a byte with the top bit
+/// set.
+pub fn roland_sysex(data: &[u8], version: Option<&Version>) -> Option<Vec<Vec<u8>>> {
+    let start = if version.is_some_and(|v| v.number == 109) {
+        0x1ef2
+    } else {
+        read_le_u16(data, 0x7e0)? as usize
+    };
+    let mut r = data.get(start..)?.iter().copied();
+    let mut messages = Vec::new();
+
+    let timbre_count = r.next()?;
+    for _ in 0..timbre_count {
+        let timbre = r.next()?;
+        let mut body = vec![0x08 | (timbre >> 6), (timbre & 0x3f) << 1, 0x0a];
+
+        let mut len = r.next()?;
+        loop {
+            let mut code = r.next()?;
+            let mut run = 1;
+            if code & 0x80 != 0 {
+                code &= 0x7f;
+                run = r.next()?;
+                len = len.wrapping_sub(1);
+            }
+            body.extend(std::iter::repeat_n(code, run as usize));
+            if body.len() > 0x100 {
+                return None;
+            }
+
+            len = len.wrapping_sub(1);
+            if len == 0 {
+                break;
+            }
+        }
+
+        messages.push(roland_message(&body));
+    }
+
+    while let Some(b) = r.next().filter(|&b| b & 0x80 == 0) {
+        let p = [b, r.next()?, r.next()?, r.next()?, r.next()?];
+        messages.push(roland_message(&[
+            0x05,
+            p[0] >> 4,
+            (p[0] & 0x0f) << 3,
+            p[1] >> 6,
+            p[1] & 0x3f,
+            p[2] & 0x3f,
+            p[3] & 0x7f,
+            p[4] & 0x7f,
+            p[2] >> 6,
+            p[3] >> 7,
+        ]));
+    }
+
+    Some(messages)
+}
+
+/// Frames the address and data of a data set message with the header and
+/// the checksum.
+fn roland_message(body: &[u8]) -> Vec<u8> {
+    let sum = body.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+
+    let mut message = vec![0xf0];
+    message.extend_from_slice(&ROLAND_SYSEX_HEADER);
+    message.extend_from_slice(body);
+    message.push(sum.wrapping_neg() & 0x7f);
+    message.push(0xf7);
+    message
+}
+
+/// Length of a quarter note, [`DIVISION`] driver ticks, at the tempo set by
+/// the two tempo bytes. The driver adds `tempo0 * tempo1 * 2999 * 8` to a
+/// 16.16 fixed point tick count every poll.
+fn us_per_quarter(tempo0: u8, tempo1: u8) -> u32 {
+    let tempo = ((tempo0 as u16).wrapping_mul(tempo1 as u16) as u64 * 2999) << 3;
+    if tempo == 0 {
+        return 500_000;
+    }
+
+    let us = DIVISION as u64 * 1_000_000 * 0x1_0000 / (POLL_RATE * tempo);
+    us.min(0xff_ffff) as u32
+}
+
+/// The MIDI note of the first entry of the frequency table, played in the
+/// lowest block, from which all the others are counted.
+fn base_note(data: &[u8], table: usize) -> f64 {
+    let fnum = read_le_u16(data, table).unwrap_or(0) & 0x3ff;
+    if fnum == 0 {
+        return 12.0;
+    }
+
+    let freq = fnum as f64 * 49716.0 / (1 << 20) as f64;
+    69.0 + 12.0 * (freq / 440.0).log2()
+}
+
+struct AdlibDriver<'a> {
+    data: &'a [u8],
+    instrument_map: Option<usize>,
+    base_note: f64,
+}
+
+impl AdlibDriver<'_> {
+    fn mapped_instrument(&self, note: u8) -> Option<u8> {
+        self.data.get(self.instrument_map? + note as usize).copied()
+    }
+
+    fn binded_effect(&self, instrument: u8) -> u8 {
+        self.instrument_map
+            .and_then(|map| {
+                let ofs = map + 0x80 + INSTRUMENT_SIZE * instrument as usize;
+                self.data.get(ofs + INSTRUMENT_BINDED_EFFECT).copied()
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// The event data of a channel being played, and the MIDI recorded from it.
+struct Sequence<'a> {
+    data: &'a [u8],
+    pos: usize,
+    tick: u32,
+    midi: MidiTrack,
+    tempo_changes: Vec<(u32, u8)>,
+}
+
+/// What to do after an event.
+enum Step {
+    Continue,
+    Stop,
+}
+
+impl<'a> Sequence<'a> {
+    fn new(data: &'a [u8], start: usize) -> Self {
+        Sequence {
+            data,
+            pos: start,
+            tick: 0,
+            midi: MidiTrack::new(),
+            tempo_changes: Vec::new(),
+        }
+    }
+
+    /// Hands each event's opcode to `event` at the tick it's due, until it
+    /// stops the channel or the data runs out, which stops it just the same.
+    fn run(&mut self, event: impl FnMut(&mut Self, u8) -> Option<Step>) {
+        let _ = self.run_events(event);
+    }
+
+    fn run_events(&mut self, mut event: impl FnMut(&mut Self, u8) -> Option<Step>) -> Option<()> {
+        self.tick = self.delta_time()?;
+        loop {
+            let opcode = self.byte()?;
+            if let Step::Stop = event(self, opcode)? {
+                return Some(());
+            }
+            self.tick = self.tick.saturating_add(self.delta_time()?);
+        }
+    }
+
+    /// Handles the commands both drivers share. Returns `None` for any
+    /// other.
+    fn common_command(&mut self, opcode: u8) -> Option<Option<Step>> {
+        let step = match opcode & 0xf {
+            1 => Some(Step::Stop),
+            3 => self.byte().map(|tempo1| {
+                self.tempo_changes.push((self.tick, tempo1));
+                Step::Continue
+            }),
+            8 => {
+                self.midi.marker(self.tick, "loopEnd");
+                Some(Step::Stop)
+            }
+            12 => {
+                self.midi.marker(self.tick, "loopStart");
+                Some(Step::Continue)
+            }
+            _ => return None,
+        };
+        Some(step)
+    }
+
+    /// Records a MIDI message at the current tick.
+    fn send(&mut self, message: &[u8]) {
+        self.midi.event(self.tick, message);
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    /// Reads a time of up to four bytes of seven bits, most significant
+    /// first, with the top bit set on all but the last.
+    fn delta_time(&mut self) -> Option<u32> {
+        let mut time = 0;
+        for _ in 0..4 {
+            let b = self.byte()?;
+            time = (time << 7) | (b & 0x7f) as u32;
+            if b & 0x80 == 0 {
+                return Some(time);
+            }
+        }
+
+        None
+    }
+}
+
+struct AdlibChannel {
+    midi_channel: u8,
+    instrument: Option<u8>,
+    program: Option<u8>,
+    tremo_vibro: u8,
+    freq_offset: u8,
+    volume: u8,
+    last_command: u8,
+    note: Option<u8>,
+}
+
+impl AdlibChannel {
+    fn new(midi_channel: u8) -> Self {
+        AdlibChannel {
+            midi_channel,
+            instrument: None,
+            program: None,
+            tremo_vibro: 0,
+            freq_offset: 0x40,
+            volume: 0x7f,
+            last_command: 0,
+            note: None,
+        }
+    }
+
+    fn play(&mut self, seq: &mut Sequence, driver: &AdlibDriver) {
+        seq.run(|seq, opcode| self.event(seq, driver, opcode));
+        self.stop_note(seq);
+    }
+
+    fn event(&mut self, seq: &mut Sequence, driver: &AdlibDriver, opcode: u8) -> Option<Step> {
+        match opcode {
+            0xff => {}
+            0x90.. => {
+                if let Some(step) = seq.common_command(opcode) {
+                    return step;
+                }
+                match opcode & 0xf {
+                    0 => {
+                        let note = seq.byte()?;
+                        if note == self.last_command {
+                            self.stop_note(seq);
+                        }
+                    }
+                    2 => {
+                        self.instrument = Some(seq.byte()?);
+                        self.freq_offset = 0x40;
+                        self.volume = 0x7f;
+                    }
+                    5 => self.freq_offset = seq.byte()?,
+                    6 => self.volume = seq.byte()?,
+                    7 => self.tremo_vibro = seq.byte()?,
+                    9 => self.stop_note(seq),
+                    // The driver treats these as errors and stops the
+                    // channel.
+                    4 | 10 | 11 | 13 => return Some(Step::Stop),
+                    _ => {}
+                }
+            }
+            // Switches the AdLib channel the notes are played on.
+            0x80.. => {}
+            _ => {
+                self.last_command = opcode;
+                self.stop_note(seq);
+
+                let volume = seq.byte()?;
+                if self.instrument.is_some() || self.tremo_vibro != 0 {
+                    self.start_note(seq, driver, opcode, volume);
+                }
+            }
+        }
+
+        Some(Step::Continue)
+    }
+
+    fn start_note(&mut self, seq: &mut Sequence, driver: &AdlibDriver, opcode: u8, volume: u8) {
+        let param = if self.tremo_vibro != 0 {
+            self.instrument = driver.mapped_instrument(opcode).or(self.instrument);
+            let param = self.instrument.map_or(0, |i| driver.binded_effect(i));
+            self.last_command = param;
+            param
+        } else {
+            let effect = self.instrument.map_or(0, |i| driver.binded_effect(i));
+            opcode.wrapping_sub(0x18).wrapping_add(effect)
+        };
+
+        let mut freq_index = self.freq_offset as i32 - 0x40;
+        if freq_index >= 0x3f {
+            freq_index += 1;
+        }
+        freq_index = freq_index * 2 + ((param as i32) << 6);
+
+        let note = driver.base_note + freq_index as f64 / FREQ_STEPS as f64;
+        let note = note.round().clamp(0.0, 127.0) as u8;
+        let velocity = ((volume & 0x7f) as u32 * self.volume.min(0x7f) as u32 / 0x7f).max(1);
+
+        if let Some(instrument) = self.instrument.filter(|&i| Some(i) != self.program) {
+            seq.midi
+                .program_change(seq.tick, self.midi_channel, instrument & 0x7f);
+            self.program = Some(instrument);
+        }
+        seq.midi
+            .note_on(seq.tick, self.midi_channel, note, velocity as u8);
+        self.note = Some(note);
+    }
+
+    fn stop_note(&mut self, seq: &mut Sequence) {
+        if let Some(note) = self.note.take() {
+            seq.midi.note_off(seq.tick, self.midi_channel, note);
+        }
+    }
+}
+
+/// A channel of the Roland driver, which passes notes and controllers on
+/// to the MIDI channel it was last switched to.
+#[derive(Default)]
+struct RolandChannel {
+    midi_channel: u8,
+    note: Option<u8>,
+}
+
+impl RolandChannel {
+    fn play(&mut self, seq: &mut Sequence) {
+        seq.run(|seq, opcode| self.event(seq, opcode));
+        self.key_off(seq);
+    }
+
+    fn event(&mut self, seq: &mut Sequence, opcode: u8) -> Option<Step> {
+        let ch = self.midi_channel;
+        match opcode {
+            0xff => {}
+            0x90.. => {
+                if let Some(step) = seq.common_command(opcode) {
+                    if let Some(Step::Stop) = step {
+                        self.key_off(seq);
+                    }
+                    return step;
+                }
+                match opcode & 0xf {
+                    0 => {
+                        let note = seq.byte()? & 0x7f;
+                        seq.send(&[0x80 | ch, note, 0]);
+                        if self.note == Some(note) {
+                            self.note = None;
+                        }
+                    }
+                    2 => {
+                        let program = seq.byte()? & 0x7f;
+                        seq.send(&[0xc0 | ch, program]);
+                    }
+                    5 => {
+                        let pitch = seq.byte()? & 0x7f;
+                        seq.send(&[0xe0 | ch, 0, pitch]);
+                    }
+                    6 => {
+                        let volume = seq.byte()? & 0x7f;
+                        seq.send(&[0xb0 | ch, 7, volume]);
+                    }
+                    9 => self.key_off(seq),
+                    11 => {
+                        let pan = seq.byte()? & 0x7f;
+                        seq.send(&[0xb0 | ch, 10, pan]);
+                    }
+                    13 => {
+                        let controller = seq.byte()? & 0x7f;
+                        let value = seq.byte()? & 0x7f;
+                        seq.send(&[0xb0 | ch, controller, value]);
+                    }
+                    // The driver treats these as errors and stops the
+                    // channel.
+                    4 | 7 | 10 => {
+                        self.key_off(seq);
+                        return Some(Step::Stop);
+                    }
+                    _ => {}
+                }
+            }
+            0x80.. => self.midi_channel = opcode & 0xf,
+            _ => {
+                let velocity = seq.byte()? & 0x7f;
+                seq.send(&[0x90 | ch, opcode, velocity]);
+                self.note = Some(opcode);
+            }
+        }
+
+        Some(Step::Continue)
+    }
+
+    fn key_off(&mut self, seq: &mut Sequence) {
+        if let Some(note) = self.note.take() {
+            seq.midi.note_off(seq.tick, self.midi_channel, note);
+        }
+    }
+}
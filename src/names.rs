@@ -0,0 +1,150 @@
+//! Names of resources, for the files they are dumped to.
+//!
+//! Resources are only numbered in the data files, so dumped files and the
+//! resource list are named after what a resource is where that is known.
+//! Built in are the logo and intro screens and their palettes, the user
+//! interface and the music, sound effect and text banks of each section.
+//! Rooms, characters, items and single sound effects aren't: the resources
+//! of the rooms are only known by id, characters and items are named after
+//! the objects of `sky.cpt` they are drawn for, and effects are only
+//! numbered within their bank. Further names can be read from a CSV file
+//! with `id,name` records.
+
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    classify::NO_OF_SECTIONS,
+    error::Result,
+    music::Device,
+    sfx,
+    text::{self, Language, NO_OF_TEXT_SECTIONS},
+    ui,
+    version::Version,
+};
+
+/// A resource and its name.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ResourceName {
+    pub id: u16,
+    pub name: String,
+}
+
+/// Known names for the releases with the given version numbers, or for all
+/// releases if no versions are given.
+struct KnownNames {
+    versions: &'static [u16],
+    names: &'static [(u16, &'static str)],
+}
+
+/// The logo and intro screens and their palettes.
+const KNOWN_NAMES: &[KnownNames] = &[
+    KnownNames {
+        versions: &[],
+        names: &[
+            (60110, "virgin-logo"),
+            (60111, "virgin-logo"),
+            (60112, "revolution-logo"),
+            (60113, "revolution-logo"),
+            (60114, "gibbons-logo"),
+            (60115, "gibbons-logo"),
+        ],
+    },
+    KnownNames {
+        versions: &[288, 303, 331],
+        names: &[(60080, "intro"), (60081, "intro")],
+    },
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct ResourceNames {
+    names: HashMap<u16, String>,
+}
+
+impl ResourceNames {
+    /// The names known for `version`. If the version is unknown only the
+    /// names shared by all releases are included.
+    pub fn builtin(version: Option<&Version>) -> Self {
+        let rooms = KNOWN_NAMES
+            .iter()
+            .filter(|known| {
+                known.versions.is_empty()
+                    || version.is_some_and(|v| known.versions.contains(&v.number))
+            })
+            .flat_map(|known| known.names.iter())
+            .map(|&(id, name)| (id, name.to_owned()));
+
+        let ui = ui::elements(version).map(|e| (e.id, e.name.to_owned()));
+
+        let banks = (0..NO_OF_SECTIONS).flat_map(|section| {
+            let music = Device::ALL.into_iter().map(move |device| {
+                let name = match device {
+                    Device::Roland => "roland-music",
+                    Device::Adlib => "adlib-music",
+                };
+                (device.file(section), name.to_owned())
+            });
+            music.chain([(sfx::sfx_file(section), "sound-effects".to_owned())])
+        });
+
+        let text = Language::ALL.into_iter().flat_map(|language| {
+            (0..NO_OF_TEXT_SECTIONS).map(move |section| {
+                (
+                    text::text_file(language.number(), section),
+                    format!("{}-text", language.name()),
+                )
+            })
+        });
+
+        let names = banks.chain(text).chain(ui).chain(rooms).collect();
+        ResourceNames { names }
+    }
+
+    /// Reads names from a CSV file with `id` and `name` columns. Names are
+    /// made safe for use in file names.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut rdr = csv::Reader::from_path(path)?;
+        let names = rdr
+            .deserialize::<ResourceName>()
+            .map(|res| res.map(|res| (res.id, slug(&res.name))))
+            .collect::<std::result::Result<_, _>>()?;
+
+        Ok(ResourceNames { names })
+    }
+
+    /// Adds the names of `other`, replacing those for the same ids.
+    pub fn extend(&mut self, other: ResourceNames) {
+        self.names.extend(other.names);
+    }
+
+    /// Names `id` unless it already has a name. The name is made safe for
+    /// use in file names.
+    pub fn add(&mut self, id: u16, name: &str) {
+        self.names.entry(id).or_insert_with(|| slug(name));
+    }
+
+    /// The name of the resource `id`, if known.
+    pub fn name(&self, id: u16) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// The base name of the files `id` is dumped to: its number, followed
+    /// by its name if known.
+    pub fn file_name(&self, id: u16) -> String {
+        match self.name(id) {
+            Some(name) => format!("{id:05}-{name}"),
+            None => format!("{id:05}"),
+        }
+    }
+}
+
+/// Lowercases `name` and replaces everything but letters and digits with
+/// single dashes.
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
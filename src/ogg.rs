@@ -0,0 +1,69 @@
+//! Ogg pages, the container of the Ogg FLAC and Ogg Vorbis files written.
+
+use std::io::{self, Write};
+
+/// Writes one packet to a page, which is all the codecs written need.
+pub struct OggWriter<'a, W: Write> {
+    w: &'a mut W,
+    sequence: u32,
+}
+
+impl<'a, W: Write> OggWriter<'a, W> {
+    /// Serial number of the one logical stream in the files written.
+    const SERIAL: u32 = 0x5ba5_5ba5;
+
+    pub fn new(w: &'a mut W) -> Self {
+        OggWriter { w, sequence: 0 }
+    }
+
+    /// Writes `packet` as a page of its own, `granule` being the position
+    /// of the stream at its end and `last` marking the end of the stream.
+    pub fn page(&mut self, packet: &[u8], granule: u64, last: bool) -> io::Result<()> {
+        let mut lacing = vec![255; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+        if lacing.len() > 255 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet too large for an Ogg page",
+            ));
+        }
+
+        let mut flags = 0;
+        if self.sequence == 0 {
+            flags |= 0x02;
+        }
+        if last {
+            flags |= 0x04;
+        }
+
+        let mut page = Vec::with_capacity(27 + lacing.len() + packet.len());
+        page.extend_from_slice(b"OggS");
+        page.push(0); // version
+        page.push(flags);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&Self::SERIAL.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]); // checksum, filled in below
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        page.extend_from_slice(packet);
+
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+
+        self.sequence += 1;
+        self.w.write_all(&page)
+    }
+}
+
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ (b as u32) << 24, |crc, _| {
+            if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
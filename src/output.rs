@@ -0,0 +1,355 @@
+//! Writing the files of a dump. An [`Output`] sends every file to one
+//! sink: the output directory, a ZIP file or a ZIP stream on stdout with a
+//! manifest listing the files, nowhere at all in a dry run, where the files
+//! are only listed, or memory, where they can be looked at in tests. With
+//! [`Output::incremental`], files in the output directory that already hold
+//! what would be written to them are left alone, so dumping again after a
+//! partial dump or a small change only touches what changed.
+//!
+//! Files are collected in memory as [`OutputFile`]s and written in one go.
+//! The paths of the files of a dump are made by [`dump_path`] and
+//! [`resource_path`].
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use csv::Writer;
+use serde::Serialize;
+
+use crate::zip::{ZipEntry, ZipWriter};
+
+/// Name of the manifest at the top of a ZIP file of the dump.
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// Name of the file written and removed again by
+/// [`Output::check_writable`].
+const PROBE_NAME: &str = ".write-test";
+
+/// Where the files of a dump go.
+pub struct Output {
+    sink: Sink,
+    incremental: bool,
+    /// The number of files kept as they were.
+    unchanged: AtomicUsize,
+}
+
+enum Sink {
+    Directory,
+    /// Taken when the ZIP file is finished.
+    Zip(Mutex<Option<ZipOutput>>),
+    DryRun(Mutex<Vec<PlannedFile>>),
+    Memory(Mutex<BTreeMap<PathBuf, Vec<u8>>>),
+}
+
+struct ZipOutput {
+    /// The output directory, which the names in the ZIP file are relative
+    /// to.
+    root: PathBuf,
+    writer: ZipWriter<Box<dyn Write + Send>>,
+    /// The files compressed so far, written in order of name when the ZIP
+    /// file is finished, so it doesn't depend on which thread was first.
+    entries: Vec<ZipEntry>,
+    files: Vec<ManifestFile>,
+}
+
+/// A file a dry run would have written.
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub size: usize,
+    /// Whether in incremental mode the file already holds what would be
+    /// written to it.
+    pub unchanged: bool,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    version: Option<&'a str>,
+    files: &'a [ManifestFile],
+}
+
+#[derive(Serialize)]
+struct ManifestFile {
+    path: String,
+    size: u32,
+    crc32: String,
+}
+
+impl Output {
+    fn new(sink: Sink) -> Output {
+        Output {
+            sink,
+            incremental: false,
+            unchanged: AtomicUsize::new(0),
+        }
+    }
+
+    /// Writes files where their paths say.
+    pub fn directory() -> Output {
+        Output::new(Sink::Directory)
+    }
+
+    /// Writes the files of the output directory `root` to a ZIP file
+    /// written to `w` once [`finish_zip`](Output::finish_zip) is called.
+    /// Files elsewhere are written where their paths say.
+    pub fn zip<W: Write + Send + 'static>(w: W, root: &Path) -> Output {
+        Output::new(Sink::Zip(Mutex::new(Some(ZipOutput {
+            root: root.to_owned(),
+            writer: ZipWriter::new(Box::new(w)),
+            entries: Vec::new(),
+            files: Vec::new(),
+        }))))
+    }
+
+    /// Like [`Output::zip`], writing the ZIP file to `path`.
+    pub fn create_zip(path: &Path, root: &Path) -> io::Result<Output> {
+        Ok(Output::zip(BufWriter::new(File::create(path)?), root))
+    }
+
+    /// Writes nothing, only noting the files, see [`Output::planned`].
+    pub fn dry_run() -> Output {
+        Output::new(Sink::DryRun(Mutex::new(Vec::new())))
+    }
+
+    /// Keeps the files in memory, see [`Output::files`].
+    pub fn memory() -> Output {
+        Output::new(Sink::Memory(Mutex::new(BTreeMap::new())))
+    }
+
+    /// Leaves files in the output directory alone if they already hold
+    /// what would be written to them.
+    pub fn incremental(self, incremental: bool) -> Output {
+        Output {
+            incremental,
+            ..self
+        }
+    }
+
+    /// The files a dry run would have written so far, in order of path.
+    pub fn planned(&self) -> Vec<PlannedFile> {
+        let Sink::DryRun(ref planned) = self.sink else {
+            return Vec::new();
+        };
+        let mut planned = std::mem::take(&mut *planned.lock().unwrap());
+        planned.sort_by(|a, b| a.path.cmp(&b.path));
+        planned
+    }
+
+    /// The files kept in memory so far, by path.
+    pub fn files(&self) -> BTreeMap<PathBuf, Vec<u8>> {
+        let Sink::Memory(ref files) = self.sink else {
+            return BTreeMap::new();
+        };
+        std::mem::take(&mut *files.lock().unwrap())
+    }
+
+    /// The number of files left alone so far because they were up to date.
+    pub fn unchanged(&self) -> usize {
+        self.unchanged.load(Ordering::Relaxed)
+    }
+
+    /// Writes the files of the ZIP file in order of name, followed by the
+    /// manifest and the directory. Returns the number of files in it,
+    /// manifest included, or 0 if no ZIP file is being written.
+    pub fn finish_zip(&self, version: Option<&str>) -> io::Result<usize> {
+        let Sink::Zip(ref zip) = self.sink else {
+            return Ok(0);
+        };
+        let Some(mut zip) = zip.lock().unwrap().take() else {
+            return Ok(0);
+        };
+
+        zip.entries.sort_by(|a, b| a.name().cmp(b.name()));
+        for entry in std::mem::take(&mut zip.entries) {
+            zip.writer.add(entry)?;
+        }
+
+        zip.files.sort_by(|a, b| a.path.cmp(&b.path));
+        let manifest = Manifest {
+            version,
+            files: &zip.files,
+        };
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        zip.writer.add(ZipEntry::new(MANIFEST_NAME, &json)?)?;
+
+        let count = zip.writer.len();
+        zip.writer.finish()?.flush()?;
+
+        Ok(count)
+    }
+
+    /// The name in the ZIP file being written of the file at `path`, if
+    /// there is one and `path` is in the output directory.
+    fn zip_name(&self, path: &Path) -> Option<String> {
+        let Sink::Zip(ref zip) = self.sink else {
+            return None;
+        };
+        let zip = zip.lock().unwrap();
+        let relative = path.strip_prefix(&zip.as_ref()?.root).ok()?;
+        let parts: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        Some(parts.join("/"))
+    }
+
+    /// Whether files at `path` are written there, rather than into a ZIP
+    /// file, memory or nowhere in a dry run.
+    pub fn writes_to(&self, path: &Path) -> bool {
+        match self.sink {
+            Sink::Directory => true,
+            Sink::Zip(_) => self.zip_name(path).is_none(),
+            Sink::DryRun(_) | Sink::Memory(_) => false,
+        }
+    }
+
+    /// Creates `path` and its parents like [`fs::create_dir_all`], unless
+    /// nothing is written there.
+    pub fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if !self.writes_to(path) {
+            return Ok(());
+        }
+
+        fs::create_dir_all(path)
+    }
+
+    /// Checks that files can be created in the directory `dir`, so a dump
+    /// that can't be written fails before any work is done rather than once
+    /// for every file.
+    pub fn check_writable(&self, dir: &Path) -> io::Result<()> {
+        if !self.writes_to(dir) {
+            return Ok(());
+        }
+
+        let probe = dir.join(PROBE_NAME);
+        File::create(&probe)?;
+        fs::remove_file(probe)
+    }
+
+    /// Writes `data` to `path` like [`fs::write`], unless in incremental
+    /// mode the file already holds it, or hands it to the sink.
+    pub fn write<P: AsRef<Path>>(&self, path: P, data: &[u8]) -> io::Result<()> {
+        let path = path.as_ref();
+        match self.sink {
+            Sink::DryRun(ref planned) => {
+                let unchanged = self.incremental && holds(path, data);
+                planned.lock().unwrap().push(PlannedFile {
+                    path: path.to_owned(),
+                    size: data.len(),
+                    unchanged,
+                });
+                return Ok(());
+            }
+            Sink::Memory(ref files) => {
+                files.lock().unwrap().insert(path.to_owned(), data.to_vec());
+                return Ok(());
+            }
+            Sink::Zip(ref zip) => {
+                if let Some(name) = self.zip_name(path) {
+                    // Compressing doesn't need the lock, so files can be
+                    // compressed on several threads.
+                    let entry = ZipEntry::new(&name, data)?;
+                    let mut zip = zip.lock().unwrap();
+                    let zip = zip.as_mut().expect("ZIP file is still being written");
+                    zip.files.push(ManifestFile {
+                        path: name,
+                        size: entry.size(),
+                        crc32: format!("{:08x}", entry.crc32()),
+                    });
+                    zip.entries.push(entry);
+                    return Ok(());
+                }
+            }
+            Sink::Directory => {}
+        }
+
+        if self.incremental && holds(path, data) {
+            self.unchanged.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        fs::write(path, data)
+    }
+
+    /// A file at `path`, written by [`finish`](OutputFile::finish).
+    pub fn create<P: AsRef<Path>>(&self, path: P) -> OutputFile<'_> {
+        OutputFile {
+            output: self,
+            path: path.as_ref().to_owned(),
+            data: Vec::new(),
+        }
+    }
+
+    /// A CSV writer into an [`OutputFile`] at `path`, written by
+    /// [`finish_csv`].
+    pub fn csv_writer<P: AsRef<Path>>(&self, path: P) -> Writer<OutputFile<'_>> {
+        Writer::from_writer(self.create(path))
+    }
+
+    /// Writes `value` as pretty-printed JSON to `path`.
+    pub fn write_json<P: AsRef<Path>, T: Serialize + ?Sized>(
+        &self,
+        path: P,
+        value: &T,
+    ) -> io::Result<()> {
+        let mut file = self.create(path);
+        serde_json::to_writer_pretty(&mut file, value)?;
+        file.finish()
+    }
+}
+
+/// Whether the file at `path` holds `data`. The size is compared first, so
+/// files that changed length aren't read.
+fn holds(path: &Path, data: &[u8]) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.len() == data.len() as u64)
+        && fs::read(path).is_ok_and(|old| old == data)
+}
+
+/// A file of the dump, collected in memory and written by
+/// [`finish`](OutputFile::finish).
+pub struct OutputFile<'a> {
+    output: &'a Output,
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+impl OutputFile<'_> {
+    pub fn finish(self) -> io::Result<()> {
+        self.output.write(&self.path, &self.data)
+    }
+}
+
+impl Write for OutputFile<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn finish_csv(wtr: Writer<OutputFile>) -> io::Result<()> {
+    wtr.into_inner().map_err(|e| e.into_error())?.finish()
+}
+
+/// The path of the file `name` in the directory `dir` of the output
+/// directory `out`.
+pub fn dump_path(out: &Path, dir: &str, name: &str) -> PathBuf {
+    out.join(dir).join(name)
+}
+
+/// The path of the file for the resource `number` in the directory `dir`
+/// of the output directory `out`, without an extension.
+pub fn resource_path(out: &Path, dir: &str, number: u16) -> PathBuf {
+    dump_path(out, dir, &format!("{number:05}"))
+}
@@ -0,0 +1,189 @@
+//! Palettes and the pairing of screens with the palettes they are shown
+//! with.
+//!
+//! Most screens are stored next to their palette, so the dump falls back to
+//! trying the neighbouring ids. Screens for which that doesn't work, like
+//! the logos and the intro, are listed here, and further pairs can be read
+//! from a CSV file with `screen,palette` records. Screens shown with
+//! several palettes, such as rooms with a day and a night version, have a
+//! record for each, the first of which is the one used where only one can
+//! be.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Result, image::palette_to_rgb, version::Version};
+
+/// File formats palettes can be exported in for use in image editors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// Paint Shop Pro `.pal`.
+    Jasc,
+    /// GIMP `.gpl`.
+    Gimp,
+    /// Photoshop color table `.act`.
+    Act,
+}
+
+impl PaletteFormat {
+    pub const ALL: [PaletteFormat; 3] =
+        [PaletteFormat::Jasc, PaletteFormat::Gimp, PaletteFormat::Act];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteFormat::Jasc => "jasc",
+            PaletteFormat::Gimp => "gimp",
+            PaletteFormat::Act => "act",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PaletteFormat> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PaletteFormat::Jasc => "pal",
+            PaletteFormat::Gimp => "gpl",
+            PaletteFormat::Act => "act",
+        }
+    }
+}
+
+/// Writes a 6-bit VGA `palette` as stored by the game in `format`, with
+/// colors scaled to 8 bits. `name` is recorded by formats that have one.
+pub fn write_palette<W: Write>(
+    w: &mut W,
+    palette: &[u8],
+    name: &str,
+    format: PaletteFormat,
+) -> io::Result<()> {
+    let rgb = palette_to_rgb(palette);
+    let colors = rgb.chunks_exact(3);
+
+    match format {
+        PaletteFormat::Jasc => {
+            write!(w, "JASC-PAL\r\n0100\r\n{}\r\n", colors.len())?;
+            for c in colors {
+                write!(w, "{} {} {}\r\n", c[0], c[1], c[2])?;
+            }
+        }
+        PaletteFormat::Gimp => {
+            writeln!(w, "GIMP Palette")?;
+            writeln!(w, "Name: {name}")?;
+            writeln!(w, "Columns: 16")?;
+            writeln!(w, "#")?;
+            for (n, c) in colors.enumerate() {
+                writeln!(w, "{:3} {:3} {:3}\tIndex {n}", c[0], c[1], c[2])?;
+            }
+        }
+        PaletteFormat::Act => {
+            // Always 256 entries, unused ones black.
+            let mut table = [0; 3 * 256];
+            let len = rgb.len().min(table.len());
+            table[..len].copy_from_slice(&rgb[..len]);
+            w.write_all(&table)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A screen and the palette it is shown with.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct PalettePair {
+    pub screen: u16,
+    pub palette: u16,
+}
+
+/// Known pairs for the releases with the given version numbers, or for all
+/// releases if no versions are given.
+struct KnownPairs {
+    versions: &'static [u16],
+    pairs: &'static [(u16, u16)],
+}
+
+const KNOWN_PAIRS: &[KnownPairs] = &[
+    // The Virgin, Revolution and Gibbons logos shown at the start of the
+    // intro.
+    KnownPairs {
+        versions: &[],
+        pairs: &[(60110, 60111), (60112, 60113), (60114, 60115)],
+    },
+    // The screens of the floppy intro. The palette stored before the first
+    // one is faded up once at the start and kept for the rest, while the
+    // neighbours of the later screens are the sequences played over them.
+    KnownPairs {
+        versions: &[288, 303, 331],
+        pairs: &[
+            (60081, 60080),
+            (60088, 60080),
+            (60090, 60080),
+            (60092, 60080),
+            (60094, 60080),
+            (60096, 60080),
+            (60098, 60080),
+            (60100, 60080),
+            (60102, 60080),
+            (60104, 60080),
+            (60106, 60080),
+            (60108, 60080),
+        ],
+    },
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct PaletteMap {
+    pairs: HashMap<u16, Vec<u16>>,
+}
+
+impl PaletteMap {
+    /// The pairs known for `version`. If the version is unknown only the
+    /// pairs shared by all releases are included.
+    pub fn builtin(version: Option<&Version>) -> Self {
+        let pairs = KNOWN_PAIRS
+            .iter()
+            .filter(|known| {
+                known.versions.is_empty()
+                    || version.is_some_and(|v| known.versions.contains(&v.number))
+            })
+            .flat_map(|known| known.pairs.iter())
+            .map(|&(screen, palette)| (screen, vec![palette]))
+            .collect();
+
+        PaletteMap { pairs }
+    }
+
+    /// Reads pairs from a CSV file with `screen` and `palette` columns.
+    /// Screens with several records get each of those palettes, in order.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut rdr = csv::Reader::from_path(path)?;
+        let mut pairs: HashMap<_, Vec<_>> = HashMap::new();
+        for pair in rdr.deserialize::<PalettePair>() {
+            let pair = pair?;
+            pairs.entry(pair.screen).or_default().push(pair.palette);
+        }
+
+        Ok(PaletteMap { pairs })
+    }
+
+    /// Adds the pairs of `other`, replacing those for the same screens.
+    pub fn extend(&mut self, other: PaletteMap) {
+        self.pairs.extend(other.pairs);
+    }
+
+    /// The palette for `screen`, if known.
+    pub fn palette(&self, screen: u16) -> Option<u16> {
+        self.palettes(screen).first().copied()
+    }
+
+    /// All palettes `screen` is shown with, empty if none are known.
+    pub fn palettes(&self, screen: u16) -> &[u16] {
+        self.pairs.get(&screen).map_or(&[], Vec::as_slice)
+    }
+}
@@ -0,0 +1,162 @@
+//! Bindings for Python, for poking at the game data from scripts and
+//! notebooks. Only built with the `pyo3` feature; the module is named
+//! `beneath_a_steel_sky_extract` like the library.
+//!
+//! Data comes back as `bytes` and headers as dictionaries of their fields.
+//! Files that can't be read raise `OSError`, and streams that don't
+//! decompress raise `ValueError`.
+
+use std::io::Cursor;
+
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::{PyBytes, PyDict},
+};
+
+use crate::{archive, classify::classify, resource, rnc_compress, rnc_decompress};
+
+/// An archive with all of `sky.dsk` read into memory.
+#[pyclass(name = "Archive", module = "beneath_a_steel_sky_extract", frozen)]
+pub struct PyArchive(archive::Archive<Cursor<Vec<u8>>>);
+
+#[pymethods]
+impl PyArchive {
+    /// Opens the game data files in the directory `path`.
+    #[new]
+    fn new(path: &str) -> PyResult<PyArchive> {
+        Ok(PyArchive(archive::Archive::load(path)?))
+    }
+
+    /// Opens the archive of the contents of `sky.dnr` and `sky.dsk`.
+    #[staticmethod]
+    fn from_bytes(dnr: &[u8], dsk: Vec<u8>) -> PyResult<PyArchive> {
+        Ok(PyArchive(archive::Archive::from_bytes(dnr, dsk)?))
+    }
+
+    /// The release the archive belongs to, if known.
+    #[getter]
+    fn version(&self) -> Option<String> {
+        self.0.version().map(|v| v.to_string())
+    }
+
+    /// The ids of the resources in directory order.
+    fn ids(&self) -> Vec<u16> {
+        self.0.entries().iter().map(|e| e.number).collect()
+    }
+
+    /// Decodes the resource `id`, raising `KeyError` if there is none.
+    fn resource(&self, id: u16) -> PyResult<PyResource> {
+        let resource = self
+            .0
+            .get_resource(id)
+            .ok_or_else(|| PyKeyError::new_err(id))?;
+        let resource_type = classify(&resource, self.0.version()).map(|t| t.name());
+
+        Ok(PyResource {
+            resource,
+            resource_type,
+        })
+    }
+
+    /// The bytes of the resource `id` as stored in `sky.dsk`.
+    fn raw<'py>(&self, py: Python<'py>, id: u16) -> PyResult<Bound<'py, PyBytes>> {
+        let entry = self.0.entry(id).ok_or_else(|| PyKeyError::new_err(id))?;
+        Ok(PyBytes::new(py, self.0.checked_entry_bytes(entry)?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.entries().len()
+    }
+
+    fn __contains__(&self, id: u16) -> bool {
+        self.0.entry(id).is_some()
+    }
+}
+
+/// A decoded resource.
+#[pyclass(name = "Resource", module = "beneath_a_steel_sky_extract", frozen)]
+pub struct PyResource {
+    resource: resource::Resource,
+    resource_type: Option<&'static str>,
+}
+
+#[pymethods]
+impl PyResource {
+    #[getter]
+    fn id(&self) -> u16 {
+        self.resource.entry.number
+    }
+
+    /// The type the resource is taken to be, such as `"screen"`.
+    #[getter]
+    fn r#type(&self) -> Option<&'static str> {
+        self.resource_type
+    }
+
+    /// Whether the resource is stored RNC compressed.
+    #[getter]
+    fn compressed(&self) -> bool {
+        self.resource.is_compressed()
+    }
+
+    /// The decoded data, without the file header.
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.resource.data)
+    }
+
+    /// The fields of the file header, if the resource has one.
+    #[getter]
+    fn header<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(h) = &self.resource.header else {
+            return Ok(None);
+        };
+
+        let dict = PyDict::new(py);
+        dict.set_item("flags", h.flags)?;
+        dict.set_item("x", h.x)?;
+        dict.set_item("y", h.y)?;
+        dict.set_item("width", h.width)?;
+        dict.set_item("height", h.height)?;
+        dict.set_item("sp_size", h.sp_size)?;
+        dict.set_item("tot_size", h.tot_size)?;
+        dict.set_item("n_sprites", h.n_sprites)?;
+        dict.set_item("offset_x", h.offset_x)?;
+        dict.set_item("offset_y", h.offset_y)?;
+        dict.set_item("compressed_size", h.compressed_size)?;
+        Ok(Some(dict))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Resource {} {} {} bytes>",
+            self.resource.entry.number,
+            self.resource_type.unwrap_or("unknown"),
+            self.resource.data.len()
+        )
+    }
+}
+
+/// Compresses `data` as an RNC method 1 stream, header included.
+#[pyfunction]
+fn compress_rnc1<'py>(py: Python<'py>, data: &[u8]) -> Bound<'py, PyBytes> {
+    PyBytes::new(py, &rnc_compress::compress_rnc1(data))
+}
+
+/// Decompresses an RNC stream, header included.
+#[pyfunction]
+fn decompress_rnc1<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let decompressed = rnc_decompress::decompress_rnc1(&mut Cursor::new(data))
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(PyBytes::new(py, &decompressed))
+}
+
+#[pymodule]
+fn beneath_a_steel_sky_extract(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyArchive>()?;
+    m.add_class::<PyResource>()?;
+    m.add_function(wrap_pyfunction!(compress_rnc1, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_rnc1, m)?)?;
+    Ok(())
+}
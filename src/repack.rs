@@ -0,0 +1,423 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::archive::{write_dinner_table, Entry};
+
+/// Name of the file recording the dinner table flags next to raw dumps.
+pub const INDEX_FILE_NAME: &str = "index.csv";
+
+/// The flags of a raw dumped resource, which are not part of its data, and
+/// where it was in `sky.dsk`. Indexes written before the offset and size
+/// were recorded read back without them.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct IndexRecord {
+    pub id: u16,
+    pub has_file_header: bool,
+    pub uses_file_header: bool,
+    #[serde(default)]
+    pub offset: Option<u32>,
+    #[serde(default)]
+    pub size: Option<u32>,
+}
+
+impl From<&Entry> for IndexRecord {
+    fn from(entry: &Entry) -> Self {
+        IndexRecord {
+            id: entry.number,
+            has_file_header: entry.has_file_header,
+            uses_file_header: entry.uses_file_header,
+            offset: Some(entry.offset),
+            size: Some(entry.size),
+        }
+    }
+}
+
+pub fn write_index<P: AsRef<Path>>(path: P, directory: &[Entry]) -> io::Result<()> {
+    let mut wtr = csv::Writer::from_path(path)?;
+    for entry in directory {
+        wtr.serialize(IndexRecord::from(entry))?;
+    }
+    wtr.flush()
+}
+
+pub fn read_index<P: AsRef<Path>>(path: P) -> io::Result<HashMap<u16, IndexRecord>> {
+    let mut rdr = csv::Reader::from_path(path)?;
+    rdr.deserialize::<IndexRecord>()
+        .map(|record| {
+            let record = record?;
+            Ok((record.id, record))
+        })
+        .collect()
+}
+
+/// Sets the flags of `records` in the index file at `path`, creating it if
+/// there is none and keeping the records of other resources. Records
+/// without an offset keep the one already in the index.
+pub fn update_index<P: AsRef<Path>>(path: P, records: &[IndexRecord]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut index = if path.exists() {
+        read_index(path)?
+    } else {
+        HashMap::new()
+    };
+    for record in records {
+        let old = index.get(&record.id);
+        let record = IndexRecord {
+            offset: record.offset.or(old.and_then(|r| r.offset)),
+            size: record.size.or(old.and_then(|r| r.size)),
+            ..*record
+        };
+        index.insert(record.id, record);
+    }
+
+    let mut index: Vec<_> = index.into_values().collect();
+    index.sort_by_key(|r| r.id);
+
+    let mut wtr = csv::Writer::from_path(path)?;
+    for record in index {
+        wtr.serialize(record)?;
+    }
+    wtr.flush()
+}
+
+/// A resource as stored in `sky.dsk`, together with its flags.
+pub struct RawResource {
+    pub number: u16,
+    /// Offset and size of the resource in the `sky.dsk` it was dumped
+    /// from, if known.
+    pub original: Option<(u32, u32)>,
+    pub has_file_header: bool,
+    pub uses_file_header: bool,
+    pub data: Vec<u8>,
+}
+
+/// Loads the `<id>.dmp` files in `dir` as written by the raw dump. Flags are
+/// taken from the index file if present, resources missing from it are
+/// assumed to be stored without a header.
+pub fn read_raw_dump<P: AsRef<Path>>(dir: P) -> io::Result<Vec<RawResource>> {
+    let dir = dir.as_ref();
+
+    let index_path = dir.join(INDEX_FILE_NAME);
+    let index = if index_path.exists() {
+        read_index(index_path)?
+    } else {
+        HashMap::new()
+    };
+
+    let mut resources = Vec::new();
+    for dir_entry in dir.read_dir()? {
+        let path = dir_entry?.path();
+        if !path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("dmp"))
+        {
+            continue;
+        }
+
+        let Some(number) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u16>().ok())
+        else {
+            continue;
+        };
+
+        let record = index.get(&number);
+        resources.push(RawResource {
+            number,
+            original: record.and_then(|r| r.offset.zip(r.size)),
+            has_file_header: record.is_some_and(|r| r.has_file_header),
+            uses_file_header: record.is_some_and(|r| r.uses_file_header),
+            data: std::fs::read(&path)?,
+        });
+    }
+
+    resources.sort_by_key(|r| r.number);
+
+    Ok(resources)
+}
+
+/// Writes `resources` back to back to `dsk` and the matching dinner table
+/// to `dnr`. Returns the written directory.
+pub fn write_archive<D: Write, W: Write>(
+    resources: &[RawResource],
+    dnr: &mut D,
+    dsk: &mut W,
+) -> io::Result<Vec<Entry>> {
+    let mut directory = Vec::with_capacity(resources.len());
+    let mut offset = 0u32;
+
+    for resource in resources {
+        let size = resource.data.len() as u32;
+        directory.push(Entry {
+            number: resource.number,
+            offset,
+            size,
+            has_file_header: resource.has_file_header,
+            uses_file_header: resource.uses_file_header,
+        });
+
+        dsk.write_all(&resource.data)?;
+        offset += size;
+    }
+
+    write_dinner_table(dnr, &directory)?;
+
+    Ok(directory)
+}
+
+/// A range of `sky.dsk` no resource refers to, as listed in `index.csv` in
+/// the `slack` directory of a dump. Only ranges holding anything but zeros
+/// have a file.
+#[derive(Deserialize)]
+struct SlackRecord {
+    offset: String,
+    file: Option<String>,
+}
+
+/// Loads the unreferenced bytes of `sky.dsk` written to `dir` by the raw
+/// dump, as their offset and data.
+pub fn read_slack<P: AsRef<Path>>(dir: P) -> io::Result<Vec<(u32, Vec<u8>)>> {
+    let dir = dir.as_ref();
+
+    let mut slack = Vec::new();
+    let mut rdr = csv::Reader::from_path(dir.join(INDEX_FILE_NAME))?;
+    for record in rdr.deserialize::<SlackRecord>() {
+        let record = record?;
+        let Some(file) = record.file else {
+            continue;
+        };
+        let offset = u32::from_str_radix(record.offset.trim_start_matches("0x"), 16)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        slack.push((offset, std::fs::read(dir.join(file))?));
+    }
+
+    Ok(slack)
+}
+
+/// Writes `resources` to `dsk` at the offsets they were dumped from, as
+/// long as they fit in their old size or before the next resource, and
+/// the matching dinner table to `dnr`. Those that don't, and those without
+/// an offset, are appended back to back in order of id. Bytes between
+/// resources are zero, except for the `slack` put back where it was.
+/// Returns the written directory.
+pub fn write_archive_in_place<D: Write, W: Write>(
+    resources: &[RawResource],
+    slack: &[(u32, Vec<u8>)],
+    dnr: &mut D,
+    dsk: &mut W,
+) -> io::Result<Vec<Entry>> {
+    let mut data = Vec::new();
+    for (offset, bytes) in slack {
+        let start = *offset as usize;
+        let end = start + bytes.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[start..end].copy_from_slice(bytes);
+    }
+
+    let mut in_place: Vec<_> = resources
+        .iter()
+        .filter_map(|r| r.original.map(|original| (original, r)))
+        .collect();
+    in_place.sort_by_key(|&((offset, _), r)| (offset, r.number));
+
+    let mut directory = Vec::with_capacity(resources.len());
+    let mut appended: Vec<_> = resources.iter().filter(|r| r.original.is_none()).collect();
+    let mut end = 0usize;
+    for (n, &((offset, size), resource)) in in_place.iter().enumerate() {
+        let start = offset as usize;
+        let next = in_place[n..]
+            .iter()
+            .map(|&((o, _), _)| o as usize)
+            .find(|&o| o > start)
+            .unwrap_or(usize::MAX);
+        let room = (size as usize).max(next - start);
+
+        // Bytes shared with a resource before it, as some entries are in
+        // the original, must still be the same.
+        let shared = end.saturating_sub(start).min(resource.data.len());
+        if resource.data.len() > room
+            || (shared > 0 && data[start..start + shared] != resource.data[..shared])
+        {
+            appended.push(resource);
+            continue;
+        }
+
+        let resource_end = start + resource.data.len();
+        if data.len() < resource_end {
+            data.resize(resource_end, 0);
+        }
+        data[start..resource_end].copy_from_slice(&resource.data);
+        end = end.max(resource_end);
+
+        directory.push(Entry {
+            number: resource.number,
+            offset,
+            size: resource.data.len() as u32,
+            has_file_header: resource.has_file_header,
+            uses_file_header: resource.uses_file_header,
+        });
+    }
+
+    appended.sort_by_key(|r| r.number);
+    for resource in appended {
+        directory.push(Entry {
+            number: resource.number,
+            offset: data.len() as u32,
+            size: resource.data.len() as u32,
+            has_file_header: resource.has_file_header,
+            uses_file_header: resource.uses_file_header,
+        });
+        data.extend_from_slice(&resource.data);
+    }
+
+    directory.sort_by_key(|e| e.number);
+    dsk.write_all(&data)?;
+    write_dinner_table(dnr, &directory)?;
+
+    Ok(directory)
+}
+
+/// Rebuilds `sky.dnr` and `sky.dsk` in `out_dir` from a raw dump in `dir`.
+/// The output only depends on the dump: resources are stored in order of
+/// id, or with `preserve_layout` where [`write_archive_in_place`] puts
+/// them, filling the gaps with the `slack` directory next to `dir` if the
+/// dump has one.
+pub fn repack<P: AsRef<Path>, Q: AsRef<Path>>(
+    dir: P,
+    out_dir: Q,
+    preserve_layout: bool,
+) -> io::Result<Vec<Entry>> {
+    let dir = dir.as_ref();
+    let resources = read_raw_dump(dir)?;
+
+    let out_dir = out_dir.as_ref();
+    std::fs::create_dir_all(out_dir)?;
+
+    let mut dnr = io::BufWriter::new(File::create(out_dir.join("sky.dnr"))?);
+    let mut dsk = io::BufWriter::new(File::create(out_dir.join("sky.dsk"))?);
+
+    let directory = if preserve_layout {
+        let slack_dir = dir.join("../slack");
+        let slack = if slack_dir.join(INDEX_FILE_NAME).exists() {
+            read_slack(slack_dir)?
+        } else {
+            Vec::new()
+        };
+        write_archive_in_place(&resources, &slack, &mut dnr, &mut dsk)?
+    } else {
+        write_archive(&resources, &mut dnr, &mut dsk)?
+    };
+
+    dnr.flush()?;
+    dsk.flush()?;
+
+    Ok(directory)
+}
+
+/// How [`patch_resource`] made room for a resource.
+#[derive(Copy, Clone, Debug)]
+pub enum Patched {
+    /// The resource fit where the old one was, leaving `padding` zero bytes
+    /// before whatever follows it.
+    InPlace { padding: u32 },
+    /// The resource didn't fit, so the data after it was moved along by
+    /// `shift` bytes, with the `moved` entries stored there.
+    Moved { shift: u32, moved: usize },
+}
+
+/// Replaces the resource `number` in the directory and data of an archive
+/// with `data`, as stored in `sky.dsk`, keeping its flags. If `data` fits
+/// in the bytes of the old resource and the zero bytes after it, such as
+/// padding left by an earlier patch, it is written over them, otherwise
+/// everything after the old resource is moved to make room. Fails if the
+/// resource isn't in the directory or shares bytes with another entry,
+/// which would be changed along with it.
+pub fn patch_resource(
+    directory: &mut [Entry],
+    dsk: &mut Vec<u8>,
+    number: u16,
+    data: &[u8],
+) -> io::Result<Patched> {
+    let entry = *directory
+        .iter()
+        .find(|e| e.number == number)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no entry for resource {number}"),
+            )
+        })?;
+
+    let start = entry.offset as usize;
+    let end = start + entry.size as usize;
+    if end > dsk.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "runs past the end of sky.dsk",
+        ));
+    }
+
+    if let Some(other) = directory.iter().find(|e| {
+        e.number != number
+            && e.size > 0
+            && (e.offset as usize) < end
+            && e.offset as usize + e.size as usize > start
+    }) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("shares bytes with resource {}", other.number),
+        ));
+    }
+
+    let next = directory
+        .iter()
+        .filter(|e| e.number != number && e.offset as usize >= end)
+        .map(|e| e.offset as usize)
+        .min()
+        .unwrap_or(dsk.len())
+        .min(dsk.len());
+    let free = dsk[end..next].iter().take_while(|&&b| b == 0).count();
+    let room = entry.size as usize + free;
+
+    let new_size = data.len() as u32;
+    let patched = if data.len() <= room {
+        dsk[start..start + data.len()].copy_from_slice(data);
+        if data.len() < entry.size as usize {
+            dsk[start + data.len()..end].fill(0);
+        }
+
+        Patched::InPlace {
+            padding: (room - data.len()) as u32,
+        }
+    } else {
+        // The free bytes are taken up as well, so none are left between
+        // the resource and those moved.
+        let shift = (data.len() - room) as u32;
+        dsk.splice(start..end + free, data.iter().copied());
+
+        let mut moved = 0;
+        for other in directory.iter_mut() {
+            if other.number != number && other.offset as usize >= end {
+                other.offset += shift;
+                moved += 1;
+            }
+        }
+
+        Patched::Moved { shift, moved }
+    };
+
+    for e in directory.iter_mut().filter(|e| e.number == number) {
+        e.size = new_size;
+    }
+
+    Ok(patched)
+}
@@ -0,0 +1,262 @@
+use std::{
+    fmt,
+    io::{self, Cursor, Read, Write},
+};
+
+use serde::Serialize;
+
+use crate::{
+    archive::Entry,
+    bytes_ext::{ReadBytesExt, WriteBytesExt},
+    error::{Error, Result},
+    rnc_compress::compress_rnc1,
+    rnc_decompress::{decompress_rnc1, DecompressError},
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Header {
+    pub flags: u16,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub sp_size: u16,
+    pub tot_size: u16,
+    pub n_sprites: u16,
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub compressed_size: u16,
+}
+
+impl Header {
+    pub const SIZE: usize = 22;
+
+    pub fn is_compressed(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    /// The size of the resource once decompressed, including the header if
+    /// the game keeps it. The low 16 bits are stored in `tot_size` and the
+    /// next eight in the high byte of `flags`.
+    pub fn total_size(&self) -> u32 {
+        ((self.flags as u32 & 0xff00) << 8) | self.tot_size as u32
+    }
+
+    pub fn set_total_size(&mut self, size: u32) {
+        self.tot_size = size as u16;
+        self.flags = (self.flags & 0xff) | ((size >> 8) & 0xff00) as u16;
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Header> {
+        Ok(Header {
+            flags: r.read_le_u16()?,
+            x: r.read_le_u16()?,
+            y: r.read_le_u16()?,
+            width: r.read_le_u16()?,
+            height: r.read_le_u16()?,
+            sp_size: r.read_le_u16()?,
+            tot_size: r.read_le_u16()?,
+            n_sprites: r.read_le_u16()?,
+            offset_x: r.read_le_i16()?,
+            offset_y: r.read_le_i16()?,
+            compressed_size: r.read_le_u16()?,
+        })
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_le_u16(self.flags)?;
+        w.write_le_u16(self.x)?;
+        w.write_le_u16(self.y)?;
+        w.write_le_u16(self.width)?;
+        w.write_le_u16(self.height)?;
+        w.write_le_u16(self.sp_size)?;
+        w.write_le_u16(self.tot_size)?;
+        w.write_le_u16(self.n_sprites)?;
+        w.write_le_u16(self.offset_x as u16)?;
+        w.write_le_u16(self.offset_y as u16)?;
+        w.write_le_u16(self.compressed_size)?;
+
+        Ok(())
+    }
+}
+
+pub struct Resource {
+    pub entry: Entry,
+    pub header: Option<Header>,
+    pub data: Vec<u8>,
+}
+
+impl fmt::Debug for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resource")
+            .field("entry", &self.entry)
+            .field("header", &self.header)
+            .finish()
+    }
+}
+
+impl Resource {
+    pub fn is_compressed(&self) -> bool {
+        self.header
+            .as_ref()
+            .map(|h| h.is_compressed())
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceType {
+    Screen,
+    Palette,
+    /// A sprite with a single frame.
+    Sprite,
+    /// A sprite with several frames.
+    Animation,
+    /// A sound effect sample.
+    Audio,
+    Speech,
+    Text,
+    Font,
+    Grid,
+    Script,
+    /// Music and sound effect banks.
+    Music,
+    /// Full-screen animation played over a screen.
+    Sequence,
+}
+
+impl ResourceType {
+    pub const ALL: [ResourceType; 12] = [
+        ResourceType::Screen,
+        ResourceType::Palette,
+        ResourceType::Sprite,
+        ResourceType::Animation,
+        ResourceType::Audio,
+        ResourceType::Speech,
+        ResourceType::Text,
+        ResourceType::Font,
+        ResourceType::Grid,
+        ResourceType::Script,
+        ResourceType::Music,
+        ResourceType::Sequence,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResourceType::Screen => "screen",
+            ResourceType::Palette => "palette",
+            ResourceType::Sprite => "sprite",
+            ResourceType::Animation => "animation",
+            ResourceType::Audio => "audio",
+            ResourceType::Speech => "speech",
+            ResourceType::Text => "text",
+            ResourceType::Font => "font",
+            ResourceType::Grid => "grid",
+            ResourceType::Script => "script",
+            ResourceType::Music => "music",
+            ResourceType::Sequence => "sequence",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ResourceType> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+}
+
+impl fmt::Display for ResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Decodes the raw bytes of `entry` as read from `sky.dsk`. A compressed
+/// resource that doesn't decompress is kept as stored, see
+/// [`read_resource_strict`] for failing instead.
+pub fn read_resource(entry: &Entry, data: Vec<u8>) -> io::Result<Resource> {
+    decode_resource(entry, data).map(|(resource, _)| resource)
+}
+
+/// Decodes the raw bytes of `entry` like [`read_resource`], but fails with
+/// the reason if a compressed resource doesn't decompress, as that means
+/// it is damaged.
+pub fn read_resource_strict(entry: &Entry, data: Vec<u8>) -> Result<Resource> {
+    match decode_resource(entry, data)? {
+        (resource, None) => Ok(resource),
+        (_, Some(e)) => Err(Error::Decompress(e)),
+    }
+}
+
+/// Decodes the raw bytes of `entry` like [`read_resource`], together with
+/// the reason its data was kept as stored if it didn't decompress.
+pub fn decode_resource(
+    entry: &Entry,
+    data: Vec<u8>,
+) -> io::Result<(Resource, Option<DecompressError>)> {
+    if !entry.has_file_header {
+        let resource = Resource {
+            entry: *entry,
+            header: None,
+            data,
+        };
+        return Ok((resource, None));
+    }
+
+    let mut r = Cursor::new(data);
+    let header = Header::read(&mut r)?;
+
+    let data_start = r.position();
+    let (data, error) = match header.is_compressed().then(|| decompress_rnc1(&mut r)) {
+        Some(Ok(data)) => (data, None),
+        error => {
+            // Fall back to the data as stored.
+            r.set_position(data_start);
+            let mut data = Vec::new();
+            r.read_to_end(&mut data)?;
+            (data, error.and_then(|e| e.err()))
+        }
+    };
+
+    let resource = Resource {
+        entry: *entry,
+        header: Some(header),
+        data,
+    };
+    Ok((resource, error))
+}
+
+/// Encodes `data` as stored in `sky.dsk` for `entry`, the inverse of
+/// [`read_resource`].
+///
+/// Entries with a file header get `header`, with its compression flag and
+/// total size updated, and are RNC method 1 compressed if `compress` is
+/// set. Entries without a header store `data` as is.
+pub fn write_resource(entry: &Entry, header: &Header, data: &[u8], compress: bool) -> Vec<u8> {
+    if !entry.has_file_header {
+        return data.to_vec();
+    }
+
+    let mut header = header.clone();
+    if compress {
+        header.flags |= 0x80;
+    } else {
+        header.flags &= !0x80;
+    }
+
+    let header_size = if entry.uses_file_header {
+        Header::SIZE
+    } else {
+        0
+    };
+    header.set_total_size((data.len() + header_size) as u32);
+
+    let mut output = Vec::with_capacity(Header::SIZE + data.len());
+    header.write(&mut output).unwrap();
+    if compress {
+        output.extend(compress_rnc1(data));
+    } else {
+        output.extend_from_slice(data);
+    }
+
+    output
+}
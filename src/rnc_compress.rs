@@ -0,0 +1,344 @@
+//! RNC method 1 compression, the inverse of
+//! [`decompress_rnc1`](crate::rnc_decompress::decompress_rnc1).
+//!
+//! The data is split into runs of literal bytes, each followed by a match
+//! copying earlier output. Literal run lengths, match offsets and match
+//! lengths are each coded with their own Huffman table, written at the
+//! start of every block.
+
+use std::collections::BinaryHeap;
+
+use crate::rnc_decompress::crc16;
+
+/// Matches may reach back this far, the largest offset the tables can code.
+const WINDOW_SIZE: usize = 0x8000;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 0xff + 2;
+/// The largest value the tables can code, which bounds literal runs.
+const MAX_VALUE: usize = 0x7fff;
+/// How many earlier positions with the same hash are tried for a match.
+const MAX_CHAIN: usize = 128;
+const HASH_SIZE: usize = 1 << 13;
+const HEADER_SIZE: usize = 18;
+
+#[derive(Copy, Clone)]
+struct Token {
+    literals: usize,
+    /// Offset and length of the match following the literals, none for the
+    /// last token of a block.
+    copy: Option<(usize, usize)>,
+}
+
+/// Compresses `data` as an RNC method 1 stream, header included.
+pub fn compress_rnc1(data: &[u8]) -> Vec<u8> {
+    let blocks = split_blocks(&find_tokens(data));
+
+    let mut w = BitWriter::new();
+    w.write_bits(0, 2);
+
+    let mut pos = 0;
+    let mut leeway = 0;
+    for block in &blocks {
+        let raw_table = HuffmanCode::new(block.iter().map(|t| t.literals));
+        let offset_table =
+            HuffmanCode::new(block.iter().filter_map(|t| t.copy).map(|(ofs, _)| ofs - 1));
+        let count_table =
+            HuffmanCode::new(block.iter().filter_map(|t| t.copy).map(|(_, len)| len - 2));
+
+        raw_table.write_table(&mut w);
+        offset_table.write_table(&mut w);
+        count_table.write_table(&mut w);
+        w.write_bits(block.len() as u32, 16);
+
+        for token in block {
+            raw_table.write_value(&mut w, token.literals);
+            w.output.extend_from_slice(&data[pos..pos + token.literals]);
+            pos += token.literals;
+
+            if let Some((offset, len)) = token.copy {
+                offset_table.write_value(&mut w, offset - 1);
+                count_table.write_value(&mut w, len - 2);
+                pos += len;
+            }
+
+            leeway = leeway.max(pos as isize - w.output.len() as isize);
+        }
+    }
+
+    let packed = w.output;
+    let leeway = (leeway + 2 - (data.len() as isize - packed.len() as isize)).clamp(0, 0xff);
+
+    let mut output = Vec::with_capacity(HEADER_SIZE + packed.len());
+    output.extend_from_slice(b"RNC\x01");
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(&(packed.len() as u32).to_be_bytes());
+    output.extend_from_slice(&crc16(data).to_be_bytes());
+    output.extend_from_slice(&crc16(&packed).to_be_bytes());
+    output.push(leeway as u8);
+    output.push(blocks.len() as u8);
+    output.extend_from_slice(&packed);
+
+    output
+}
+
+fn hash(data: &[u8]) -> usize {
+    let v = (data[0] as usize) << 10 ^ (data[1] as usize) << 5 ^ data[2] as usize;
+    v & (HASH_SIZE - 1)
+}
+
+/// Greedily finds the longest match at each position through hash chains
+/// of the positions starting with the same three bytes.
+fn find_tokens(data: &[u8]) -> Vec<Token> {
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |pos: usize, head: &mut [usize], prev: &mut [usize]| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..]);
+            prev[pos] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut literals = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        let (offset, len) = longest_match(data, pos, &head, &prev);
+
+        if len < MIN_MATCH || literals == MAX_VALUE {
+            if literals == MAX_VALUE {
+                // A run can't be any longer, end the block here.
+                tokens.push(Token {
+                    literals,
+                    copy: None,
+                });
+                literals = 0;
+            }
+            insert(pos, &mut head, &mut prev);
+            literals += 1;
+            pos += 1;
+            continue;
+        }
+
+        tokens.push(Token {
+            literals,
+            copy: Some((offset, len)),
+        });
+        literals = 0;
+        for p in pos..pos + len {
+            insert(p, &mut head, &mut prev);
+        }
+        pos += len;
+    }
+
+    tokens.push(Token {
+        literals,
+        copy: None,
+    });
+
+    tokens
+}
+
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    if pos + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+
+    let max_len = (data.len() - pos).min(MAX_MATCH);
+    let mut best = (0, 0);
+    let mut candidate = head[hash(&data[pos..])];
+
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW_SIZE {
+            break;
+        }
+
+        let len = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_len])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if len > best.1 {
+            best = (pos - candidate, len);
+            if len == max_len {
+                break;
+            }
+        }
+
+        candidate = prev[candidate];
+    }
+
+    best
+}
+
+/// Splits the tokens into blocks, which end with a token without a match
+/// and hold at most 0xffff tokens. A block cut short by the limit ends with
+/// the literals of the next token, its match opening the following block.
+fn split_blocks(tokens: &[Token]) -> Vec<Vec<Token>> {
+    let mut blocks = Vec::new();
+    let mut block = Vec::new();
+
+    for &token in tokens {
+        if block.len() == 0xffff - 1 && token.copy.is_some() {
+            block.push(Token {
+                literals: token.literals,
+                copy: None,
+            });
+            blocks.push(std::mem::take(&mut block));
+            block.push(Token {
+                literals: 0,
+                copy: token.copy,
+            });
+            continue;
+        }
+
+        block.push(token);
+        if token.copy.is_none() {
+            blocks.push(std::mem::take(&mut block));
+        }
+    }
+
+    blocks
+}
+
+/// Collects bits least significant first into 16 bit little endian words,
+/// interleaved with literal bytes the way the decoder reads them: a new
+/// word is only taken from the stream once the bits of the previous one
+/// run out.
+struct BitWriter {
+    output: Vec<u8>,
+    words: Vec<usize>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            output: Vec::new(),
+            words: Vec::new(),
+            bits: 0,
+        }
+    }
+
+    fn write_bits(&mut self, v: u32, n: u8) {
+        let n = n as usize;
+        if n > 16 * self.words.len() - self.bits {
+            self.words.push(self.output.len());
+            self.output.extend_from_slice(&[0, 0]);
+        }
+
+        for i in 0..n {
+            if v & (1 << i) != 0 {
+                let bit = self.bits + i;
+                let pos = self.words[bit / 16] + (bit % 16) / 8;
+                self.output[pos] |= 1 << (bit % 8);
+            }
+        }
+        self.bits += n;
+    }
+}
+
+/// The symbol coding `value`: its bit length, so the symbol's code is
+/// followed by the value's bits below the leading one.
+fn symbol(value: usize) -> usize {
+    (usize::BITS - value.leading_zeros()) as usize
+}
+
+struct HuffmanCode {
+    depths: [u8; 16],
+    codes: [u32; 16],
+}
+
+impl HuffmanCode {
+    /// Builds the code for the symbols of `values`.
+    fn new<I: Iterator<Item = usize>>(values: I) -> Self {
+        let mut counts = [0usize; 16];
+        for value in values {
+            counts[symbol(value)] += 1;
+        }
+
+        let depths = huffman_depths(&counts);
+        let codes = canonical_codes(&depths);
+
+        HuffmanCode { depths, codes }
+    }
+
+    fn write_table(&self, w: &mut BitWriter) {
+        if self.depths.iter().all(|&d| d == 0) {
+            w.write_bits(0, 5);
+            return;
+        }
+
+        // All sixteen depths are always written, the decoder keeps those of
+        // the previous block for any left out.
+        w.write_bits(16, 5);
+        for &depth in &self.depths {
+            w.write_bits(depth as u32, 4);
+        }
+    }
+
+    fn write_value(&self, w: &mut BitWriter, value: usize) {
+        let symbol = symbol(value);
+        w.write_bits(self.codes[symbol], self.depths[symbol]);
+        if symbol > 1 {
+            w.write_bits((value & !(1 << (symbol - 1))) as u32, (symbol - 1) as u8);
+        }
+    }
+}
+
+/// Code lengths of a Huffman code for `counts`. Sixteen symbols never need
+/// codes longer than fifteen bits, which fits the four bits of a table
+/// entry. A lone symbol gets a one bit code.
+fn huffman_depths(counts: &[usize; 16]) -> [u8; 16] {
+    let mut depths = [0; 16];
+
+    let used: Vec<usize> = (0..16).filter(|&s| counts[s] > 0).collect();
+    if used.len() == 1 {
+        depths[used[0]] = 1;
+        return depths;
+    }
+
+    // Each tree in the heap is its weight and the symbols below it, the
+    // weight negated to pop the lightest first.
+    let mut heap: BinaryHeap<(isize, Vec<usize>)> = used
+        .iter()
+        .map(|&s| (-(counts[s] as isize), vec![s]))
+        .collect();
+
+    while heap.len() > 1 {
+        let (w1, mut s1) = heap.pop().unwrap();
+        let (w2, s2) = heap.pop().unwrap();
+        for &s in s1.iter().chain(&s2) {
+            depths[s] += 1;
+        }
+        s1.extend(s2);
+        heap.push((w1 + w2, s1));
+    }
+
+    depths
+}
+
+/// Assigns codes in the order the decoder does: by increasing length, and
+/// by symbol within the same length. Codes are stored bit reversed since
+/// they are read least significant bit first.
+fn canonical_codes(depths: &[u8; 16]) -> [u32; 16] {
+    let mut codes = [0; 16];
+
+    let mut val = 0u32;
+    let mut div = 0x8000_0000u32;
+    for bits in 1..17 {
+        for (symbol, &depth) in depths.iter().enumerate() {
+            if depth == bits {
+                codes[symbol] = reverse_bits(val / div, bits);
+                val = val.wrapping_add(div);
+            }
+        }
+        div >>= 1;
+    }
+
+    codes
+}
+
+fn reverse_bits(v: u32, count: u8) -> u32 {
+    v.reverse_bits() >> (32 - count as u32)
+}
@@ -1,23 +1,488 @@
 use std::{
-    io::{BufRead, Read},
-    slice, fmt,
+    fmt,
+    io::{self, BufRead, Read},
 };
 
-use crate::ReadBytesExt;
+use crate::{bit_queue::BitQueue, bytes_ext::ReadBytesExt};
 
-pub fn decompress_rnc1<R: BufRead + ReadBytesExt>(
-    r: &mut R,
-) -> Result<Vec<u8>, DecompressError> {
-    let mut decoder = Decoder::new(r);
-    decoder.decode()?;
+/// How much of the output is kept for back-references. Neither method
+/// refers further back than this.
+const WINDOW_SIZE: usize = 0x10000;
+
+/// Size of the header at the start of every RNC stream.
+pub const HEADER_SIZE: usize = 18;
+
+/// The most memory reserved up front from the lengths in a header, which
+/// a damaged header could put at gigabytes.
+const MAX_RESERVE: usize = 0x10_0000;
+
+/// Decompresses an RNC stream.
+///
+/// The method is taken from the signature byte, so besides method 1 this
+/// also accepts method 2 streams. The CRCs of both the packed and the
+/// unpacked data are verified.
+pub fn decompress_rnc1<R: Read + ReadBytesExt>(r: &mut R) -> Result<Vec<u8>, DecompressError> {
+    let header = Header::read(r)?;
+
+    if !header.signature_is_valid() {
+        return Err(DecompressError::SignatureError);
+    }
+
+    decompress(r, header)
+}
+
+/// The method of the RNC stream at the start of `stream`, from its
+/// signature, or `None` if it doesn't start with one.
+pub fn method(stream: &[u8]) -> Option<u8> {
+    match stream {
+        [b'R', b'N', b'C', method, ..] => Some(*method),
+        _ => None,
+    }
+}
+
+/// Decompresses an RNC method 2 stream, verifying its CRCs.
+pub fn decompress_rnc2<R: Read + ReadBytesExt>(r: &mut R) -> Result<Vec<u8>, DecompressError> {
+    let header = Header::read(r)?;
+
+    if !header.signature_is_valid() {
+        return Err(DecompressError::SignatureError);
+    }
+    if header.method() != 2 {
+        return Err(DecompressError::UnsupportedMethod(header.method()));
+    }
+
+    decompress(r, header)
+}
+
+/// Decompresses the RNC stream at `buf[start..]` into the start of `buf`
+/// itself, the way the game unpacks data into the memory it was loaded
+/// to. Returns the unpacked length.
+///
+/// The output overwrites the packed data as it goes, so the packed data
+/// has to end far enough past the end of the output for it never to catch
+/// up with the bytes still to be read. The header gives that distance as
+/// the overlap, and [`in_place_buffer`] lays out a buffer accordingly. It
+/// fails with [`DecompressError::Overlap`] if the packed data ends too
+/// early, and with [`DecompressError::Corrupt`] if the output would still
+/// overwrite packed data before it has been read, which means the overlap
+/// in the header is too small.
+pub fn decompress_in_place(buf: &mut [u8], start: usize) -> Result<usize, DecompressError> {
+    let stream = buf.get(start..).unwrap_or_default();
+    let header = Header::read(&mut &stream[..])?;
+    if !header.signature_is_valid() {
+        return Err(DecompressError::SignatureError);
+    }
+
+    let packed_start = start + HEADER_SIZE;
+    let packed_end = packed_start + header.packed_len as usize;
+    if packed_end > buf.len() {
+        return Err(DecompressError::Corrupt("truncated stream"));
+    }
+    let required = header.unpacked_len as usize + header.overlaps_size as usize;
+    if packed_end < required {
+        return Err(DecompressError::Overlap {
+            required,
+            available: packed_end,
+        });
+    }
+
+    // The packed data is decoded from a copy, with every byte of output
+    // checked against how far the decoder has read, so the result is the
+    // same as if it had been read from the bytes being overwritten.
+    let packed = buf[packed_start..packed_end].to_vec();
+    let mut reader = RncReader::with_header(packed.as_slice(), header)?;
+    let mut written = 0;
+    let mut byte = [0];
+    while reader.decode(&mut byte)? > 0 {
+        let read_to = packed_start + reader.method.input().consumed();
+        if written >= read_to {
+            return Err(DecompressError::Corrupt(
+                "output overwrites packed data before it is read",
+            ));
+        }
+        buf[written] = byte[0];
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Lays out `stream`, a whole RNC stream, for [`decompress_in_place`]:
+/// returns a buffer with the stream at its end, big enough for both the
+/// stream and the unpacked data with the overlap the header asks for, and
+/// where in it the stream starts.
+pub fn in_place_buffer(stream: &[u8]) -> Result<(Vec<u8>, usize), DecompressError> {
+    let header = Header::read(&mut &stream[..])?;
+    let packed_len = HEADER_SIZE + header.packed_len as usize;
+    let stream = stream
+        .get(..packed_len)
+        .ok_or(DecompressError::Corrupt("truncated stream"))?;
+
+    let len = (header.unpacked_len as usize + header.overlaps_size as usize).max(packed_len);
+    let mut buf = vec![0; len];
+    let start = len - packed_len;
+    buf[start..].copy_from_slice(stream);
+
+    Ok((buf, start))
+}
+
+/// Reads the packed data in one go, so `r` is left right after it and a
+/// damaged stream is caught before decoding starts.
+fn decompress<R: Read>(r: &mut R, header: Header) -> Result<Vec<u8>, DecompressError> {
+    let packed = read_packed_data(r, &header)?;
+    let unpacked_len = header.unpacked_len as usize;
+
+    let mut reader = RncReader::with_header(packed.as_slice(), header)?;
+    let mut output = Vec::with_capacity(unpacked_len.min(MAX_RESERVE));
+    let mut buf = [0; 0x1000];
+    loop {
+        let n = reader.decode(&mut buf)?;
+        if n == 0 {
+            return Ok(output);
+        }
+        output.extend_from_slice(&buf[..n]);
+    }
+}
+
+fn read_packed_data<R: Read>(r: &mut R, header: &Header) -> Result<Vec<u8>, DecompressError> {
+    let packed_len = header.packed_len as usize;
+    let mut packed = Vec::with_capacity(packed_len.min(MAX_RESERVE));
+    r.take(packed_len as u64).read_to_end(&mut packed)?;
+    if packed.len() < packed_len {
+        return Err(DecompressError::Corrupt("truncated stream"));
+    }
+
+    let crc = crc16(&packed);
+    if crc != header.crc_packed {
+        return Err(DecompressError::CrcMismatch {
+            packed: true,
+            expected: header.crc_packed,
+            actual: crc,
+        });
+    }
+
+    Ok(packed)
+}
+
+/// Decompresses an RNC stream of either method as it is read.
+///
+/// Only the last 64 KiB of output are kept around for back-references, so
+/// resources of any size can be decoded without holding all of them in
+/// memory. The underlying reader is read no further than the end of the
+/// packed data, so it doesn't need to be buffered. Both CRCs are checked
+/// once the end of the stream is reached; a mismatch fails the read that
+/// would have returned end of file, with an error of kind
+/// [`io::ErrorKind::InvalidData`] wrapping a [`DecompressError`].
+pub struct RncReader<R: Read> {
+    method: Method<PackedReader<R>>,
+    header: Header,
+    window: Vec<u8>,
+    /// Number of bytes decoded so far.
+    written: usize,
+    crc: u16,
+    literals: usize,
+    match_offset: usize,
+    match_count: usize,
+    done: bool,
+}
+
+impl<R: Read> RncReader<R> {
+    /// Reads the header of the stream. Fails if it isn't an RNC stream of a
+    /// method that can be decoded.
+    pub fn new(mut r: R) -> Result<Self, DecompressError> {
+        let header = Header::read(&mut r)?;
+
+        if !header.signature_is_valid() {
+            return Err(DecompressError::SignatureError);
+        }
+
+        Self::with_header(r, header)
+    }
+
+    fn with_header(r: R, header: Header) -> Result<Self, DecompressError> {
+        let input = PackedReader::new(r, header.packed_len);
+        let method = match header.method() {
+            1 => Method::Rnc1(Box::new(Decoder::new(input, &header)?)),
+            2 => Method::Rnc2(Method2Decoder::new(input)?),
+            method => return Err(DecompressError::UnsupportedMethod(method)),
+        };
+
+        Ok(RncReader {
+            method,
+            header,
+            window: vec![0; WINDOW_SIZE],
+            written: 0,
+            crc: 0,
+            literals: 0,
+            match_offset: 0,
+            match_count: 0,
+            done: false,
+        })
+    }
+
+    /// The size of the data once decompressed, as given in the header.
+    pub fn unpacked_len(&self) -> usize {
+        self.header.unpacked_len as usize
+    }
+
+    /// Returns the underlying reader. Once the end of the stream has been
+    /// read, it is positioned right after the packed data.
+    pub fn into_inner(self) -> R {
+        match self.method {
+            Method::Rnc1(decoder) => decoder.r.inner,
+            Method::Rnc2(decoder) => decoder.r.inner,
+        }
+    }
+
+    fn decode(&mut self, buf: &mut [u8]) -> Result<usize, DecompressError> {
+        let mut n = 0;
+        while n < buf.len() {
+            let b = if self.literals > 0 {
+                self.literals -= 1;
+                self.method.literal()?
+            } else if self.match_count > 0 {
+                self.match_count -= 1;
+                self.window[(self.written - self.match_offset) % WINDOW_SIZE]
+            } else if self.done {
+                break;
+            } else {
+                let step = self.method.next_step(self.written, self.unpacked_len())?;
+                if let Some(Step::Literals(count) | Step::Match { count, .. }) = step {
+                    if self.written + count > self.unpacked_len() {
+                        return Err(DecompressError::Corrupt(
+                            "decodes to more than the unpacked length",
+                        ));
+                    }
+                }
+                match step {
+                    Some(Step::Literals(count)) => self.literals = count,
+                    Some(Step::Match { offset, count }) => {
+                        if offset > self.written || offset > WINDOW_SIZE {
+                            return Err(DecompressError::Corrupt(
+                                "back-reference before the start of the data",
+                            ));
+                        }
+                        self.match_offset = offset;
+                        self.match_count = count;
+                    }
+                    None => {
+                        self.crc = crc16_update(self.crc, &buf[..n]);
+                        self.finish()?;
+                        return Ok(n);
+                    }
+                }
+                continue;
+            };
+
+            self.window[self.written % WINDOW_SIZE] = b;
+            self.written += 1;
+            buf[n] = b;
+            n += 1;
+        }
+
+        self.crc = crc16_update(self.crc, &buf[..n]);
+        Ok(n)
+    }
+
+    fn finish(&mut self) -> Result<(), DecompressError> {
+        self.done = true;
+
+        let input = self.method.input();
+        io::copy(input, &mut io::sink())?;
+        if input.remaining > 0 {
+            return Err(DecompressError::Corrupt("truncated stream"));
+        }
+        if input.crc != self.header.crc_packed {
+            return Err(DecompressError::CrcMismatch {
+                packed: true,
+                expected: self.header.crc_packed,
+                actual: input.crc,
+            });
+        }
+
+        if self.crc != self.header.crc_unpacked {
+            return Err(DecompressError::CrcMismatch {
+                packed: false,
+                expected: self.header.crc_unpacked,
+                actual: self.crc,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for RncReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.decode(buf)?)
+    }
+}
+
+/// What the decoders produce next: a run of bytes copied from the packed
+/// data, or a repeat of earlier output.
+enum Step {
+    Literals(usize),
+    Match { offset: usize, count: usize },
+}
+
+enum Method<R: BufRead> {
+    Rnc1(Box<Decoder<R>>),
+    Rnc2(Method2Decoder<R>),
+}
+
+impl<R: Read> Method<PackedReader<R>> {
+    fn next_step(
+        &mut self,
+        written: usize,
+        unpacked_len: usize,
+    ) -> Result<Option<Step>, DecompressError> {
+        Ok(match self {
+            Method::Rnc1(decoder) => decoder.next_step()?,
+            Method::Rnc2(decoder) => decoder.next_step(written, unpacked_len)?,
+        })
+    }
+
+    fn literal(&mut self) -> io::Result<u8> {
+        self.input().read_u8()
+    }
+
+    fn input(&mut self) -> &mut PackedReader<R> {
+        match self {
+            Method::Rnc1(decoder) => &mut decoder.r,
+            Method::Rnc2(decoder) => &mut decoder.r,
+        }
+    }
+}
+
+/// Buffers the packed data, computing its CRC as it is consumed. Method 1
+/// peeks at the next two bytes of the stream, which an arbitrary
+/// [`BufRead`] wouldn't guarantee to have buffered, so this keeps at least
+/// that many available.
+struct PackedReader<R: Read> {
+    inner: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    /// Bytes of packed data not yet read from `inner`.
+    remaining: u64,
+    packed_len: u64,
+    crc: u16,
+}
+
+impl<R: Read> PackedReader<R> {
+    fn new(inner: R, packed_len: u32) -> Self {
+        PackedReader {
+            inner,
+            buf: vec![0; 0x1000].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            remaining: packed_len as u64,
+            packed_len: packed_len as u64,
+            crc: 0,
+        }
+    }
+
+    /// Number of bytes of packed data consumed so far. Bytes only peeked
+    /// at, or buffered ahead, don't count.
+    fn consumed(&self) -> usize {
+        (self.packed_len - self.remaining) as usize - (self.end - self.pos)
+    }
+}
 
-    Ok(decoder.output)
+impl<R: Read> Read for PackedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for PackedReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.end - self.pos < 2 && self.remaining > 0 {
+            self.buf.copy_within(self.pos..self.end, 0);
+            self.end -= self.pos;
+            self.pos = 0;
+
+            while self.end < 2 && self.remaining > 0 {
+                let want = (self.buf.len() - self.end).min(self.remaining as usize);
+                let n = match self.inner.read(&mut self.buf[self.end..self.end + want]) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                self.end += n;
+                self.remaining -= n as u64;
+            }
+        }
+
+        Ok(&self.buf[self.pos..self.end])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.end - self.pos);
+        self.crc = crc16_update(self.crc, &self.buf[self.pos..self.pos + amt]);
+        self.pos += amt;
+    }
+}
+
+const CRC_TABLE: [u16; 256] = make_crc_table();
+
+const fn make_crc_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut v = i as u16;
+        let mut j = 0;
+        while j < 8 {
+            v = if v & 1 != 0 {
+                (v >> 1) ^ 0xa001
+            } else {
+                v >> 1
+            };
+            j += 1;
+        }
+        table[i] = v;
+        i += 1;
+    }
+    table
+}
+
+/// Computes the CRC-16 used by RNC headers (polynomial 0x8005, reflected,
+/// initial value 0).
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(0, data)
+}
+
+fn crc16_update(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |crc, &b| {
+        let crc = crc ^ b as u16;
+        (crc >> 8) ^ CRC_TABLE[(crc & 0xff) as usize]
+    })
 }
 
 #[derive(Debug)]
 pub enum DecompressError {
     Io(std::io::Error),
     SignatureError,
+    UnsupportedMethod(u8),
+    /// The stream is truncated or doesn't decode, as described.
+    Corrupt(&'static str),
+    CrcMismatch {
+        packed: bool,
+        expected: u16,
+        actual: u16,
+    },
+    /// The packed data ends at `available` bytes into the buffer for
+    /// in-place decompression, short of the `required` unpacked length and
+    /// overlap.
+    Overlap {
+        required: usize,
+        available: usize,
+    },
 }
 
 impl fmt::Display for DecompressError {
@@ -25,16 +490,60 @@ impl fmt::Display for DecompressError {
         match *self {
             DecompressError::Io(ref err) => write!(f, "{}", err),
             DecompressError::SignatureError => write!(f, "Invalid signature"),
+            DecompressError::UnsupportedMethod(method) => {
+                write!(f, "Unsupported compression method {}", method)
+            }
+            DecompressError::Corrupt(reason) => write!(f, "Corrupt data: {}", reason),
+            DecompressError::CrcMismatch {
+                packed,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "CRC mismatch in {} data: expected {:04x}, got {:04x}",
+                if packed { "packed" } else { "unpacked" },
+                expected,
+                actual
+            ),
+            DecompressError::Overlap {
+                required,
+                available,
+            } => write!(
+                f,
+                "Too little overlap to unpack in place: packed data ends at {} instead of {}",
+                available, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecompressError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            DecompressError::Io(ref err) => Some(err),
+            _ => None,
         }
     }
 }
 
 impl From<std::io::Error> for DecompressError {
     fn from(err: std::io::Error) -> DecompressError {
+        if err.kind() == std::io::ErrorKind::UnexpectedEof {
+            return DecompressError::Corrupt("truncated stream");
+        }
         DecompressError::Io(err)
     }
 }
 
+impl From<DecompressError> for std::io::Error {
+    fn from(err: DecompressError) -> std::io::Error {
+        match err {
+            DecompressError::Io(err) => err,
+            err => std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Header {
     signature: [u8; 4],
@@ -63,7 +572,11 @@ impl Header {
     }
 
     fn signature_is_valid(&self) -> bool {
-        self.signature == [b'R', b'N', b'C', 0x01]
+        self.signature[0..3] == [b'R', b'N', b'C']
+    }
+
+    fn method(&self) -> u8 {
+        self.signature[3]
     }
 }
 
@@ -73,16 +586,85 @@ struct Node {
     bit_depth: u16,
 }
 
-type Table = [Node; 16];
+/// Number of bits of input the fast lookup of a table is indexed by. Codes
+/// that are longer are looked for one by one.
+const FAST_BITS: u16 = 9;
+const FAST_MASK: u16 = (1 << FAST_BITS) - 1;
+
+/// A slot of the fast lookup: the node whose code the input starts with,
+/// with its length, [`Fast::None`] if no code starts with the input or
+/// [`Fast::Long`] if it may be a code longer than [`FAST_BITS`].
+#[derive(Copy, Clone)]
+enum Fast {
+    None,
+    Code { node: u16, bit_depth: u16 },
+    Long,
+}
 
-fn table_new() -> Table {
-    [Node::default(); 16]
+#[derive(Copy, Clone)]
+struct Table {
+    nodes: [Node; 16],
+    fast: [Fast; 1 << FAST_BITS],
+}
+
+impl Table {
+    fn new() -> Table {
+        Table {
+            nodes: [Node::default(); 16],
+            fast: [Fast::None; 1 << FAST_BITS],
+        }
+    }
+
+    /// Fills in the fast lookup from the codes of the nodes. The first node
+    /// whose code matches the input is the one decoded, so slots are only
+    /// filled if an earlier node hasn't taken them, which keeps the lookup
+    /// in step with [`Table::find`] even for tables that aren't prefix
+    /// free, as a block that redefines a table with fewer nodes leaves.
+    fn build_fast(&mut self) {
+        self.fast = [Fast::None; 1 << FAST_BITS];
+        for (i, node) in (0u16..).zip(&self.nodes) {
+            if node.bit_depth == 0 {
+                continue;
+            }
+
+            let (fill, step) = if node.bit_depth <= FAST_BITS {
+                let fill = Fast::Code {
+                    node: i,
+                    bit_depth: node.bit_depth,
+                };
+                (fill, 1 << node.bit_depth)
+            } else {
+                (Fast::Long, 1 << FAST_BITS)
+            };
+            let start = (node.l3 & FAST_MASK as u32) as usize;
+            for slot in self.fast.iter_mut().skip(start).step_by(step) {
+                if matches!(slot, Fast::None) {
+                    *slot = fill;
+                }
+            }
+        }
+    }
+
+    /// Finds the first node whose code the 16 bits of input `peek` start
+    /// with, returning it with the length of its code.
+    fn find(&self, peek: u16) -> Option<(u16, u16)> {
+        (0u16..).zip(&self.nodes).find_map(|(i, node)| {
+            let mask = (1u32 << node.bit_depth) - 1;
+            (node.bit_depth != 0 && node.l3 == peek as u32 & mask).then_some((i, node.bit_depth))
+        })
+    }
 }
 
 struct Decoder<R: Read + ReadBytesExt> {
     r: R,
     bit_queue: BitQueue,
-    output: Vec<u8>,
+    raw_table: Table,
+    len_table: Table,
+    pos_table: Table,
+    blocks: u8,
+    subchunks: u16,
+    /// Whether the literals just handed out are followed by a match.
+    match_next: bool,
 }
 
 fn inverse_bits(v: u32, count: u16) -> u32 {
@@ -96,175 +678,232 @@ fn inverse_bits(v: u32, count: u16) -> u32 {
 }
 
 impl<R: BufRead + ReadBytesExt> Decoder<R> {
-    fn new(r: R) -> Decoder<R> {
-        Decoder {
+    fn new(r: R, header: &Header) -> std::io::Result<Decoder<R>> {
+        let mut decoder = Decoder {
             r,
             bit_queue: BitQueue::new(),
-            output: Vec::new(),
-        }
+            raw_table: Table::new(),
+            len_table: Table::new(),
+            pos_table: Table::new(),
+            blocks: header.blocks,
+            subchunks: 0,
+            match_next: false,
+        };
+
+        _ = decoder.read_bits(2)?;
+
+        Ok(decoder)
     }
 
     fn read_bits(&mut self, n: u8) -> std::io::Result<u16> {
         self.bit_queue.read_bits(&mut self.r, n)
     }
 
-    fn read_table(&mut self, table: &mut Table) -> std::io::Result<()> {
+    fn read_table(&mut self, table: &mut Table) -> Result<(), DecompressError> {
         let leaf_nodes = self.read_bits(5)?.min(16) as usize;
 
         if leaf_nodes == 0 {
             return Ok(());
         }
 
-        for node in table.iter_mut().take(leaf_nodes) {
+        for node in table.nodes.iter_mut().take(leaf_nodes) {
             node.bit_depth = self.read_bits(4)?;
         }
 
-        let mut val = 0;
-        let mut div = 0x8000_0000u32;
+        // Codes are handed out in order of length, each taking up `div`
+        // of the code space. More codes than fit can't be told apart.
+        let mut val = 0u64;
+        let mut div = 0x8000_0000u64;
         for bits_count in 1..17 {
-            for node in table.iter_mut().take(leaf_nodes) {
+            for node in table.nodes.iter_mut().take(leaf_nodes) {
                 if node.bit_depth == bits_count {
-                    node.l3 = inverse_bits(val / div, bits_count);
-                    val = val.wrapping_add(div);
+                    if val + div > 1 << 32 {
+                        return Err(DecompressError::Corrupt("over-subscribed Huffman table"));
+                    }
+                    node.l3 = inverse_bits((val / div) as u32, bits_count);
+                    val += div;
                 }
             }
             div >>= 1;
         }
+        table.build_fast();
 
         Ok(())
     }
 
-    fn decode(&mut self) -> Result<(), DecompressError> {
-        let header = Header::read(&mut self.r)?;
-
-        if !header.signature_is_valid() {
-            return Err(DecompressError::SignatureError);
+    /// Each block starts with its Huffman tables and the number of
+    /// subchunks in it. A subchunk is a run of literals followed by a
+    /// match, except in the last subchunk of a block.
+    fn next_step(&mut self) -> Result<Option<Step>, DecompressError> {
+        if self.match_next {
+            self.match_next = false;
+
+            let offset =
+                (input_value(&mut self.bit_queue, &mut self.r, &self.len_table)? + 1) as usize;
+            let count =
+                (input_value(&mut self.bit_queue, &mut self.r, &self.pos_table)? + 2) as usize;
+            return Ok(Some(Step::Match { offset, count }));
         }
 
-        self.output = Vec::with_capacity(header.unpacked_len as usize);
+        while self.subchunks == 0 {
+            if self.blocks == 0 {
+                return Ok(None);
+            }
+            self.blocks -= 1;
 
-        _ = self.read_bits(2)?;
+            // Tables not redefined by a block keep their previous codes.
+            let mut tables = [self.raw_table, self.len_table, self.pos_table];
+            for table in &mut tables {
+                self.read_table(table)?;
+            }
+            [self.raw_table, self.len_table, self.pos_table] = tables;
 
-        let mut raw_table = table_new();
-        let mut len_table = table_new();
-        let mut pos_table = table_new();
+            self.subchunks = self.read_bits(16)?;
+        }
 
-        for _ in 0..header.blocks {
-            self.read_table(&mut raw_table)?;
-            self.read_table(&mut len_table)?;
-            self.read_table(&mut pos_table)?;
+        self.subchunks -= 1;
+        self.match_next = self.subchunks > 0;
 
-            let subchunks = self.bit_queue.read_bits(&mut self.r, 16)?;
+        let count = input_value(&mut self.bit_queue, &mut self.r, &self.raw_table)? as usize;
+        Ok(Some(Step::Literals(count)))
+    }
+}
 
-            for subchunk in 0..subchunks {
-                let mut input_length = self.input_value(&raw_table)? as usize;
+/// Decodes a value: the node of the table whose code comes next gives the
+/// number of bits of the value, with the top bit left out.
+fn input_value<R: BufRead + ReadBytesExt>(
+    bit_queue: &mut BitQueue,
+    r: &mut R,
+    table: &Table,
+) -> Result<u16, DecompressError> {
+    let peek = bit_queue.peek(r)?;
+
+    let found = match table.fast[(peek & FAST_MASK) as usize] {
+        Fast::None => None,
+        Fast::Code { node, bit_depth } => Some((node, bit_depth)),
+        Fast::Long => table.find(peek),
+    };
+    let Some((i, bit_depth)) = found else {
+        return Err(DecompressError::Corrupt(
+            "no Huffman code matches the input",
+        ));
+    };
+    bit_queue.read_bits(r, bit_depth as u8)?;
+
+    if i < 2 {
+        return Ok(i);
+    }
 
-                if input_length > 0 {
-                    let mut b = 0u8;
-                    while input_length > 0 {
-                        self.r.read_exact(slice::from_mut(&mut b))?;
-                        self.output.push(b);
+    let v = bit_queue.read_bits(r, (i - 1) as u8)?;
+    Ok(v | (1 << (i - 1)))
+}
 
-                        input_length -= 1;
-                    }
-                }
+/// Decoder for method 2, which trades compression ratio for speed. Instead
+/// of Huffman tables it uses fixed prefix codes read most significant bit
+/// first, interleaved with whole bytes read directly from the stream.
+struct Method2Decoder<R: Read + ReadBytesExt> {
+    r: R,
+    bit_buffer: u8,
+    bits_in_buffer: u8,
+}
 
-                if subchunk < subchunks - 1{
-                    let match_offset = (self.input_value(&len_table)? + 1) as usize;
-                    let match_count = (self.input_value(&pos_table)? + 2) as usize;
+impl<R: Read + ReadBytesExt> Method2Decoder<R> {
+    fn new(r: R) -> std::io::Result<Method2Decoder<R>> {
+        let mut decoder = Method2Decoder {
+            r,
+            bit_buffer: 0,
+            bits_in_buffer: 0,
+        };
 
-                    let len = self.output.len();
-                    for j in 0..match_count {
-                        let b = self.output[len - match_offset + j];
-                        self.output.push(b);
-                    }
-                }
-            }
-        }
+        _ = decoder.read_bits(2)?;
 
-        Ok(())
+        Ok(decoder)
     }
 
-    fn input_value(&mut self, table: &Table) -> std::io::Result<u16> {
-        for i in 0u16.. {
-            let node = &table[i as usize];
-            if node.bit_depth == 0 {
-                continue;
-            }
-
-            let peek = self.bit_queue.peek(&mut self.r)?;
-            let mask = (1 << node.bit_depth) - 1;
-
-            if node.l3 == (peek & mask) as u32 {
-                self.bit_queue
-                    .read_bits(&mut self.r, node.bit_depth as u8)?;
+    fn read_bit(&mut self) -> std::io::Result<u16> {
+        if self.bits_in_buffer == 0 {
+            self.bit_buffer = self.r.read_u8()?;
+            self.bits_in_buffer = 8;
+        }
 
-                if i < 2 {
-                    return Ok(i);
-                }
+        let bit = (self.bit_buffer >> 7) as u16;
+        self.bit_buffer <<= 1;
+        self.bits_in_buffer -= 1;
 
-                let v = self.bit_queue.read_bits(&mut self.r, (i - 1) as u8)?;
-                let v = v | (1 << (i - 1));
-                return Ok(v);
-            }
-        }
-        unreachable!();
+        Ok(bit)
     }
-}
 
-struct BitQueue {
-    bit_queue: u32,
-    bits_in_queue: u16,
-}
-
-impl BitQueue {
-    fn new() -> Self {
-        BitQueue {
-            bit_queue: 0,
-            bits_in_queue: 0,
+    fn read_bits(&mut self, n: u8) -> std::io::Result<u16> {
+        let mut v = 0;
+        for _ in 0..n {
+            v = (v << 1) | self.read_bit()?;
         }
+        Ok(v)
     }
 
-    #[inline]
-    fn refill<R: Read + ReadBytesExt>(&mut self, r: &mut R) -> std::io::Result<()> {
-        // We read two u8's instead of one u16 because read_le_u8 will
-        // fail if onle one byte is left in the input stream.
-        let b0 = r.read_u8().unwrap_or(0) as u32;
-        let b1 = r.read_u8().unwrap_or(0) as u32;
+    /// The stream carries no end marker, it ends once `unpacked_len` bytes
+    /// have been written.
+    fn next_step(
+        &mut self,
+        written: usize,
+        unpacked_len: usize,
+    ) -> Result<Option<Step>, DecompressError> {
+        if written >= unpacked_len {
+            return Ok(None);
+        }
 
-        let new_bits = (b1 << 8) | b0;
+        loop {
+            if self.read_bit()? == 0 {
+                return Ok(Some(Step::Literals(1)));
+            }
 
-        self.bit_queue |= new_bits << self.bits_in_queue;
-        self.bits_in_queue += 16;
+            let (count, offset) = if self.read_bit()? == 0 {
+                let match_count = self.match_count()?;
+                if match_count == 9 {
+                    let literal_count = (self.read_bits(4)? as usize) * 4 + 12;
+                    return Ok(Some(Step::Literals(literal_count)));
+                }
+                (match_count, self.match_offset()?)
+            } else if self.read_bit()? == 0 {
+                (2, self.r.read_u8()? as usize + 1)
+            } else if self.read_bit()? == 0 {
+                (3, self.match_offset()?)
+            } else {
+                let match_count = self.r.read_u8()? as usize + 8;
+                if match_count == 8 {
+                    // End of a pack chunk.
+                    _ = self.read_bit()?;
+                    continue;
+                }
+                (match_count, self.match_offset()?)
+            };
 
-        Ok(())
+            return Ok(Some(Step::Match { offset, count }));
+        }
     }
 
-    #[inline]
-    fn peek<R: BufRead + ReadBytesExt>(&mut self, r: &mut R) -> std::io::Result<u16> {
-        let peek = r.fill_buf()?;
-        let p0 = *peek.first().unwrap_or(&0) as u32;
-        let p1 = *peek.get(1).unwrap_or(&0) as u32;
-        let p = (p1 << 8) | p0;
-        let t = ((p << self.bits_in_queue) | self.bit_queue) as u16;
-
-        Ok(t)
+    fn match_count(&mut self) -> std::io::Result<usize> {
+        let mut count = self.read_bit()? as usize + 4;
+        if self.read_bit()? != 0 {
+            count = ((count - 1) << 1) + self.read_bit()? as usize;
+        }
+        Ok(count)
     }
 
-    fn read_bits<R: Read + ReadBytesExt>(&mut self, r: &mut R, n: u8) -> std::io::Result<u16> {
-        assert!(n <= 16);
-        let n = n as u16;
-
-        if n > self.bits_in_queue {
-            self.refill(r)?;
+    fn match_offset(&mut self) -> std::io::Result<usize> {
+        let mut offset = 0;
+        if self.read_bit()? != 0 {
+            offset = self.read_bit()? as usize;
+            if self.read_bit()? != 0 {
+                offset = ((offset << 1) | self.read_bit()? as usize) | 4;
+                if self.read_bit()? == 0 {
+                    offset = (offset << 1) | self.read_bit()? as usize;
+                }
+            } else if offset == 0 {
+                offset = self.read_bit()? as usize + 2;
+            }
         }
 
-        let mask = ((1u32 << n) - 1) as u16;
-        let v = (self.bit_queue as u16) & mask;
-
-        self.bit_queue >>= n;
-        self.bits_in_queue -= n;
-
-        Ok(v)
+        Ok(((offset << 8) | self.r.read_u8()? as usize) + 1)
     }
 }
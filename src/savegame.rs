@@ -0,0 +1,235 @@
+//! Saved games, in the format ScummVM writes them and as the original DOS
+//! releases are taken to write them.
+//!
+//! A saved game of ScummVM starts with its size and a revision number, followed by
+//! the release it was saved with and the state the engine keeps outside
+//! the compacts: the sounds and music playing, the font, pointer and
+//! palette in use, the script variables and the resources loaded. The rest
+//! are the words of the compacts listed in `sky.cpt` as saved, one after
+//! the other, so `sky.cpt` is needed to tell them apart. Everything is
+//! little endian. ScummVM may gzip the whole file, which is undone first.
+//!
+//! The layout of the saves of the original DOS releases isn't documented.
+//! They are read as the same state without ScummVM's header and the engine
+//! state before the script variables: the script variables, the resources
+//! loaded and the compacts, which is what the engine keeps in its data
+//! segment. Nothing in the file confirms that, so a file is only known to
+//! be such a save once its compacts add up with [`SaveGame::compacts`].
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use serde::Serialize;
+
+use crate::{
+    compact::CptFile,
+    error::{Error, Result},
+};
+
+/// The revision ScummVM writes. Earlier revisions store the compacts in
+/// another layout.
+pub const SAVE_FILE_REVISION: u32 = 6;
+
+pub const SCRIPT_VARIABLES: usize = 838;
+/// The number of slots for resources loaded for the current room.
+pub const LOADED_FILES: usize = 60;
+
+/// The script variable holding the number of the screen shown.
+pub const SCREEN: usize = 1;
+
+/// The number of script variables holding the objects shown in a menu.
+/// Where they start is given to `fn_start_menu` by the scripts, as a byte
+/// offset, and differs between the inventory and LINC space.
+pub const MENU_OBJECTS: usize = 30;
+
+/// The size of what a ScummVM save holds before the compacts.
+const SCUMMVM_HEADER_SIZE: usize = 4 * 7 + 2 * 2 + 4 * (SCRIPT_VARIABLES + LOADED_FILES);
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveFormat {
+    ScummVm,
+    Dos,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SaveGame {
+    pub format: SaveFormat,
+    /// The state only ScummVM saves.
+    #[serde(flatten)]
+    pub engine: Option<EngineState>,
+    pub script_variables: Vec<u32>,
+    /// The resources loaded for the current room, 0 for an empty slot.
+    pub loaded_files: Vec<u32>,
+    /// The words of the saved compacts.
+    #[serde(skip)]
+    pub compact_data: Vec<u16>,
+}
+
+/// The state ScummVM saves besides the script variables, loaded files and
+/// compacts.
+#[derive(Clone, Debug, Serialize)]
+pub struct EngineState {
+    pub revision: u32,
+    /// The version number of the release the game was saved with.
+    pub game_version: u32,
+    /// The looping sounds playing, 0 for none.
+    pub sounds: [u16; 2],
+    pub music: u32,
+    pub char_set: u32,
+    pub mouse: u32,
+    pub palette: u32,
+}
+
+impl SaveGame {
+    pub fn read(data: &[u8]) -> Result<SaveGame> {
+        let mut unpacked = Vec::new();
+        let data = if data.starts_with(&GZIP_MAGIC) {
+            GzDecoder::new(data).read_to_end(&mut unpacked)?;
+            &unpacked[..]
+        } else {
+            data
+        };
+
+        let mut r = Reader { data, pos: 0 };
+        let size = r.u32()? as usize;
+        let revision = r.u32()?;
+        // A DOS save starts with script variables, which are unlikely to
+        // pass for the size and revision of a ScummVM save.
+        let scummvm = (1..=SAVE_FILE_REVISION).contains(&revision)
+            && (SCUMMVM_HEADER_SIZE..=data.len()).contains(&size);
+        if !scummvm {
+            return SaveGame::read_dos(data);
+        }
+        if revision != SAVE_FILE_REVISION {
+            return Err(Error::InvalidData(format!(
+                "unsupported saved game revision {revision}"
+            )));
+        }
+        // Anything after the saved data, such as ScummVM's thumbnail, is
+        // left out.
+        r.data = &data[..size];
+
+        let engine = EngineState {
+            revision,
+            game_version: r.u32()?,
+            sounds: [r.u16()?, r.u16()?],
+            music: r.u32()?,
+            char_set: r.u32()?,
+            mouse: r.u32()?,
+            palette: r.u32()?,
+        };
+        r.state(SaveFormat::ScummVm, Some(engine))
+    }
+
+    fn read_dos(data: &[u8]) -> Result<SaveGame> {
+        let compact_bytes = data
+            .len()
+            .saturating_sub(4 * (SCRIPT_VARIABLES + LOADED_FILES));
+        if !compact_bytes.is_multiple_of(2) {
+            return Err(Error::InvalidData(
+                "neither a saved game of ScummVM nor one of the DOS releases".to_owned(),
+            ));
+        }
+
+        Reader { data, pos: 0 }.state(SaveFormat::Dos, None)
+    }
+
+    /// The number of the screen the game was saved on.
+    pub fn screen(&self) -> u32 {
+        self.script_variables[SCREEN]
+    }
+
+    /// The objects in the menu whose variables start at `first`, as
+    /// `fn_start_menu` puts them there: the non-zero ones in order.
+    pub fn menu_objects(&self, first: usize) -> Vec<u32> {
+        self.script_variables
+            .iter()
+            .skip(first)
+            .take(MENU_OBJECTS)
+            .copied()
+            .filter(|&id| id != 0)
+            .collect()
+    }
+
+    /// Splits the saved compacts up by the entries of `cpt` they belong to,
+    /// in the order they were saved. Fails if their sizes don't add up to
+    /// the data saved, as when `cpt` is for another release or a file read
+    /// as a DOS save isn't one.
+    pub fn compacts<'a>(&'a self, cpt: &CptFile) -> Result<Vec<(u16, &'a [u16])>> {
+        let mut compacts = Vec::with_capacity(cpt.save_ids.len());
+        let mut rest = &self.compact_data[..];
+        for &id in &cpt.save_ids {
+            let len = cpt.entries.get(&id).map_or(0, |e| e.data.len());
+            if len > rest.len() {
+                return Err(Error::InvalidData(format!(
+                    "saved game ends within compact 0x{id:04x}"
+                )));
+            }
+            let (words, tail) = rest.split_at(len);
+            compacts.push((id, words));
+            rest = tail;
+        }
+
+        if !rest.is_empty() {
+            return Err(Error::InvalidData(format!(
+                "{} words of the saved game belong to no compact",
+                rest.len()
+            )));
+        }
+
+        Ok(compacts)
+    }
+}
+
+fn truncated() -> Error {
+    Error::InvalidData("saved game is truncated".to_owned())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    /// Reads what both formats save from the script variables on.
+    fn state(mut self, format: SaveFormat, engine: Option<EngineState>) -> Result<SaveGame> {
+        let script_variables = (0..SCRIPT_VARIABLES)
+            .map(|_| self.u32())
+            .collect::<Result<_>>()?;
+        let loaded_files = (0..LOADED_FILES)
+            .map(|_| self.u32())
+            .collect::<Result<_>>()?;
+        let compact_data = (0..(self.data.len() - self.pos) / 2)
+            .map(|_| self.u16())
+            .collect::<Result<_>>()?;
+
+        Ok(SaveGame {
+            format,
+            engine,
+            script_variables,
+            loaded_files,
+            compact_data,
+        })
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self
+            .data
+            .get(self.pos..self.pos + 2)
+            .ok_or_else(truncated)?;
+        self.pos += 2;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(truncated)?;
+        self.pos += 4;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
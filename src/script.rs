@@ -0,0 +1,597 @@
+//! Disassembly of the logic scripts.
+//!
+//! Scripts are bytecode for a small stack machine and are grouped into
+//! modules, one resource per module starting at [`SCRIPT_FILE_BASE`]. A
+//! script number holds the module in its top four bits and an index into
+//! the table at the start of the module in the rest. Each table entry is
+//! the offset of its script in words, counted from the entry itself.
+//!
+//! Code is a sequence of little endian words: an opcode followed by its
+//! operands. Skips are relative to the end of the instruction, in bytes.
+//! Most of the work is done by `call_mcode`, which pops its arguments and
+//! calls one of the engine's functions. Where the arguments are pushed as
+//! constants right before the call, those that are compact ids, text ids,
+//! animations, sound effects, music or rooms are annotated as such, and
+//! [`Module::references`] collects them for each script.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+};
+
+use serde::Serialize;
+
+use crate::classify::{MAX_SCRIPT_FILES, SCRIPT_FILE_BASE};
+
+/// Names of the engine functions `call_mcode` can call, by number.
+const MCODES: [&str; 115] = [
+    "fn_cache_chip",
+    "fn_cache_fast",
+    "fn_draw_screen",
+    "fn_ar",
+    "fn_ar_animate",
+    "fn_idle",
+    "fn_interact",
+    "fn_start_sub",
+    "fn_they_start_sub",
+    "fn_assign_base",
+    "fn_disk_mouse",
+    "fn_normal_mouse",
+    "fn_blank_mouse",
+    "fn_cross_mouse",
+    "fn_cursor_right",
+    "fn_cursor_left",
+    "fn_cursor_down",
+    "fn_open_hand",
+    "fn_close_hand",
+    "fn_get_to",
+    "fn_set_to_stand",
+    "fn_turn_to",
+    "fn_arrived",
+    "fn_leaving",
+    "fn_set_alternate",
+    "fn_alt_set_alternate",
+    "fn_kill_id",
+    "fn_no_human",
+    "fn_add_human",
+    "fn_add_buttons",
+    "fn_no_buttons",
+    "fn_set_stop",
+    "fn_clear_stop",
+    "fn_pointer_text",
+    "fn_quit",
+    "fn_speak_me",
+    "fn_speak_me_dir",
+    "fn_speak_wait",
+    "fn_speak_wait_dir",
+    "fn_chooser",
+    "fn_highlight",
+    "fn_text_kill",
+    "fn_stop_mode",
+    "fn_we_wait",
+    "fn_send_sync",
+    "fn_send_fast_sync",
+    "fn_send_request",
+    "fn_clear_request",
+    "fn_check_request",
+    "fn_start_menu",
+    "fn_unhighlight",
+    "fn_face_id",
+    "fn_foreground",
+    "fn_background",
+    "fn_new_background",
+    "fn_sort",
+    "fn_no_sprite_engine",
+    "fn_no_sprites_a6",
+    "fn_reset_id",
+    "fn_toggle_grid",
+    "fn_pause",
+    "fn_run_anim_mod",
+    "fn_simple_mod",
+    "fn_run_frames",
+    "fn_await_sync",
+    "fn_inc_mega_set",
+    "fn_dec_mega_set",
+    "fn_set_mega_set",
+    "fn_move_items",
+    "fn_new_list",
+    "fn_ask_this",
+    "fn_random",
+    "fn_person_here",
+    "fn_toggle_mouse",
+    "fn_mouse_on",
+    "fn_mouse_off",
+    "fn_fetch_x",
+    "fn_fetch_y",
+    "fn_test_list",
+    "fn_fetch_place",
+    "fn_custom_joey",
+    "fn_set_palette",
+    "fn_text_module",
+    "fn_change_name",
+    "fn_mini_load",
+    "fn_flush_buffers",
+    "fn_flush_chip",
+    "fn_save_coods",
+    "fn_plot_grid",
+    "fn_remove_grid",
+    "fn_eyeball",
+    "fn_cursor_up",
+    "fn_leave_section",
+    "fn_enter_section",
+    "fn_restore_game",
+    "fn_restart_game",
+    "fn_new_swing_seq",
+    "fn_wait_swing_end",
+    "fn_skip_intro_code",
+    "fn_blank_screen",
+    "fn_print_credit",
+    "fn_look_at",
+    "fn_linc_text_module",
+    "fn_text_kill2",
+    "fn_set_font",
+    "fn_start_fx",
+    "fn_stop_fx",
+    "fn_start_music",
+    "fn_stop_music",
+    "fn_fade_down",
+    "fn_fade_up",
+    "fn_quit_to_dos",
+    "fn_pause_fx",
+    "fn_un_pause_fx",
+    "fn_printf",
+];
+
+/// What an argument of an engine function refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reference {
+    Compact,
+    Text,
+    /// The compact id of an animation sequence the object plays.
+    Animation,
+    /// A sound effect of the current section's bank.
+    Sfx,
+    /// A track of the current section's music.
+    Music,
+    /// The number of a room, as compacts give it in their `screen` field.
+    Room,
+}
+
+impl Reference {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Reference::Compact => "compact",
+            Reference::Text => "text",
+            Reference::Animation => "animation",
+            Reference::Sfx => "sfx",
+            Reference::Music => "music",
+            Reference::Room => "room",
+        }
+    }
+}
+
+/// The arguments of engine function `mcode` that refer to something, by
+/// position.
+pub fn mcode_references(mcode: u16) -> &'static [(usize, Reference)] {
+    use Reference::*;
+
+    match MCODES.get(mcode as usize).copied() {
+        Some(
+            "fn_interact"
+            | "fn_they_start_sub"
+            | "fn_alt_set_alternate"
+            | "fn_kill_id"
+            | "fn_we_wait"
+            | "fn_send_sync"
+            | "fn_send_fast_sync"
+            | "fn_face_id"
+            | "fn_foreground"
+            | "fn_background"
+            | "fn_new_background"
+            | "fn_sort"
+            | "fn_reset_id"
+            | "fn_fetch_x"
+            | "fn_fetch_y",
+        ) => &[(0, Compact)],
+        Some("fn_speak_me" | "fn_speak_me_dir" | "fn_speak_wait" | "fn_speak_wait_dir") => {
+            &[(0, Compact), (1, Text)]
+        }
+        Some("fn_text_module" | "fn_linc_text_module") => &[(1, Text)],
+        Some("fn_pointer_text" | "fn_ask_this" | "fn_print_credit") => &[(0, Text)],
+        Some("fn_person_here") => &[(0, Compact), (1, Room)],
+        Some("fn_run_anim_mod" | "fn_simple_mod" | "fn_run_frames") => &[(0, Animation)],
+        Some("fn_start_fx") => &[(0, Sfx)],
+        Some("fn_start_music") => &[(0, Music)],
+        _ => &[],
+    }
+}
+
+pub fn mcode_name(mcode: u16) -> Option<&'static str> {
+    MCODES.get(mcode as usize).copied()
+}
+
+/// A decoded instruction. Skip targets are word offsets into the module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    PushVariable(u16),
+    LessThan,
+    PushNumber(u16),
+    NotEqual,
+    IfAnd,
+    SkipZero(usize),
+    PopVariable(u16),
+    Minus,
+    Plus,
+    SkipAlways(usize),
+    IfOr,
+    CallMcode {
+        args: u16,
+        mcode: u16,
+    },
+    MoreThan,
+    Switch {
+        cases: Vec<(u16, usize)>,
+        default: usize,
+    },
+    PushOffset(u16),
+    PopOffset(u16),
+    IsEqual,
+    SkipNonZero(usize),
+    Exit,
+    Restart,
+    Unknown(u16),
+}
+
+impl Op {
+    /// Offsets execution may continue at after this instruction, besides
+    /// the one following it.
+    fn targets(&self) -> Vec<usize> {
+        match self {
+            Op::SkipZero(t) | Op::SkipAlways(t) | Op::SkipNonZero(t) => vec![*t],
+            Op::Switch { cases, default } => {
+                cases.iter().map(|&(_, t)| t).chain([*default]).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether execution continues with the following instruction.
+    fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Op::SkipAlways(_) | Op::Switch { .. } | Op::Exit | Op::Restart | Op::Unknown(_)
+        )
+    }
+}
+
+/// A decoded instruction and the word offset it starts at.
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub offset: usize,
+    pub len: usize,
+    pub op: Op,
+}
+
+/// A script module: its number and code as words.
+pub struct Module {
+    pub number: u16,
+    words: Vec<u16>,
+}
+
+impl Module {
+    /// The module stored in resource `id`, or `None` if that isn't a
+    /// script resource.
+    pub fn new(id: u16, data: &[u8]) -> Option<Module> {
+        if !(SCRIPT_FILE_BASE..SCRIPT_FILE_BASE + MAX_SCRIPT_FILES).contains(&id) {
+            return None;
+        }
+
+        Some(Module {
+            number: id - SCRIPT_FILE_BASE,
+            words: data
+                .chunks_exact(2)
+                .map(|w| u16::from_le_bytes([w[0], w[1]]))
+                .collect(),
+        })
+    }
+
+    /// Word offsets of the scripts in the module's table, by index. The
+    /// table ends where the first script starts, entries that don't point
+    /// past the table are `None`.
+    pub fn scripts(&self) -> Vec<Option<usize>> {
+        let mut scripts = Vec::new();
+        let mut table_end = self.words.len();
+
+        let mut n = 0;
+        while n < table_end {
+            let target = n + self.words[n] as usize;
+            if target > n && target < self.words.len() {
+                table_end = table_end.min(target);
+                scripts.push(Some(target));
+            } else {
+                scripts.push(None);
+            }
+            n += 1;
+        }
+
+        scripts
+    }
+
+    fn word(&self, offset: usize) -> Option<u16> {
+        self.words.get(offset).copied()
+    }
+
+    /// Decodes the instruction at word `offset`, `None` if it runs past the
+    /// end of the module.
+    pub fn decode(&self, offset: usize) -> Option<Instruction> {
+        let operand = |n: usize| self.word(offset + n);
+        // Skips are in bytes from the end of the instruction.
+        let skip = |s: i32| (offset as i64 + 2 + (s / 2) as i64) as usize;
+
+        let (op, len) = match self.word(offset)? {
+            0 => (Op::PushVariable(operand(1)? / 4), 2),
+            1 => (Op::LessThan, 1),
+            2 => (Op::PushNumber(operand(1)?), 2),
+            3 => (Op::NotEqual, 1),
+            4 => (Op::IfAnd, 1),
+            5 => (Op::SkipZero(skip(operand(1)? as i32)), 2),
+            6 => (Op::PopVariable(operand(1)? / 4), 2),
+            7 => (Op::Minus, 1),
+            8 => (Op::Plus, 1),
+            9 => (Op::SkipAlways(skip(operand(1)? as i32)), 2),
+            10 => (Op::IfOr, 1),
+            11 => (
+                Op::CallMcode {
+                    args: operand(1)?,
+                    mcode: operand(2)? / 4,
+                },
+                3,
+            ),
+            12 => (Op::MoreThan, 1),
+            14 => {
+                let count = operand(1)? as usize;
+                let mut cases = Vec::with_capacity(count);
+                for n in 0..count {
+                    // Case offsets are relative to the offset itself.
+                    let ofs = offset + 3 + 2 * n;
+                    let value = self.word(ofs - 1)?;
+                    cases.push((value, ofs + self.word(ofs)? as usize / 2));
+                }
+                let default_ofs = offset + 2 + 2 * count;
+                let default = default_ofs + self.word(default_ofs)? as usize / 2;
+                (Op::Switch { cases, default }, 3 + 2 * count)
+            }
+            15 => (Op::PushOffset(operand(1)?), 2),
+            16 => (Op::PopOffset(operand(1)?), 2),
+            17 => (Op::IsEqual, 1),
+            18 => (Op::SkipNonZero(skip(operand(1)? as i16 as i32)), 2),
+            13 | 19 => (Op::Exit, 1),
+            20 => (Op::Restart, 1),
+            op => (Op::Unknown(op), 1),
+        };
+
+        Some(Instruction { offset, len, op })
+    }
+
+    /// Decodes every instruction reachable from word `start`, in order of
+    /// their offsets.
+    pub fn disassemble(&self, start: usize) -> Vec<Instruction> {
+        let mut code = BTreeMap::new();
+        let mut pending = vec![start];
+
+        while let Some(offset) = pending.pop() {
+            if code.contains_key(&offset) {
+                continue;
+            }
+            let Some(instruction) = self.decode(offset) else {
+                continue;
+            };
+
+            pending.extend(instruction.op.targets());
+            if instruction.op.falls_through() {
+                pending.push(offset + instruction.len);
+            }
+            code.insert(offset, instruction);
+        }
+
+        code.into_values().collect()
+    }
+
+    /// Writes a listing of every script in the module. `text` looks up the
+    /// text ids passed to engine functions.
+    pub fn write_asm<W: Write>(
+        &self,
+        w: &mut W,
+        text: impl Fn(u16) -> Option<String>,
+    ) -> io::Result<()> {
+        writeln!(w, "; module {}", self.number)?;
+
+        for (n, start) in self.scripts().into_iter().enumerate() {
+            let Some(start) = start else {
+                continue;
+            };
+
+            let script = (self.number << 12) | n as u16;
+            writeln!(w)?;
+            writeln!(w, "script_{script:04x}:")?;
+
+            let code = self.disassemble(start);
+            let labels = labels(&code);
+            let calls = constant_args(&code, start, &labels);
+
+            let mut next = start;
+            for instruction in &code {
+                if instruction.offset != next || labels.contains(&instruction.offset) {
+                    writeln!(w, "{}:", label(instruction.offset))?;
+                }
+                next = instruction.offset + instruction.len;
+
+                let comment = match instruction.op {
+                    Op::CallMcode { mcode, .. } => {
+                        annotate(mcode, &calls[&instruction.offset], &text)
+                    }
+                    _ => String::new(),
+                };
+
+                let mnemonic = format_op(&instruction.op);
+                if comment.is_empty() {
+                    writeln!(w, "    {:04x}: {mnemonic}", 2 * instruction.offset)?;
+                } else {
+                    writeln!(
+                        w,
+                        "    {:04x}: {mnemonic:<32} ; {comment}",
+                        2 * instruction.offset
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What the constant arguments of the engine functions called by each
+    /// script of the module refer to, by script number. Every reference is
+    /// listed once, in order of kind and value.
+    pub fn references(&self) -> BTreeMap<u16, BTreeSet<(Reference, u16)>> {
+        let mut scripts = BTreeMap::new();
+
+        for (n, start) in self.scripts().into_iter().enumerate() {
+            let Some(start) = start else {
+                continue;
+            };
+
+            let code = self.disassemble(start);
+            let calls = constant_args(&code, start, &labels(&code));
+            let mut refs = BTreeSet::new();
+            for instruction in &code {
+                let Op::CallMcode { mcode, .. } = instruction.op else {
+                    continue;
+                };
+                let args = &calls[&instruction.offset];
+                for &(n, reference) in mcode_references(mcode) {
+                    if let Some(Some(value)) = args.get(n) {
+                        refs.insert((reference, *value));
+                    }
+                }
+            }
+
+            scripts.insert((self.number << 12) | n as u16, refs);
+        }
+
+        scripts
+    }
+}
+
+/// The offsets jumped to within `code`.
+fn labels(code: &[Instruction]) -> BTreeSet<usize> {
+    code.iter().flat_map(|i| i.op.targets()).collect()
+}
+
+/// The arguments of every `call_mcode` in `code` by its offset, in the
+/// order they were pushed: the constants pushed right before the call, or
+/// `None` for values computed at run time. Code reached by a jump may have
+/// been reached with anything on the stack, so nothing is known there.
+fn constant_args(
+    code: &[Instruction],
+    start: usize,
+    labels: &BTreeSet<usize>,
+) -> BTreeMap<usize, Vec<Option<u16>>> {
+    let mut calls = BTreeMap::new();
+
+    // Constants pushed since the last instruction that wasn't a push.
+    let mut pushed: Vec<Option<u16>> = Vec::new();
+    let mut next = start;
+    for instruction in code {
+        if instruction.offset != next || labels.contains(&instruction.offset) {
+            pushed.clear();
+        }
+        next = instruction.offset + instruction.len;
+
+        match instruction.op {
+            Op::PushNumber(v) => pushed.push(Some(v)),
+            Op::PushVariable(_) | Op::PushOffset(_) => pushed.push(None),
+            Op::CallMcode { args, .. } => {
+                let args = pushed
+                    .len()
+                    .checked_sub(args as usize)
+                    .map_or_else(Vec::new, |n| pushed[n..].to_vec());
+                calls.insert(instruction.offset, args);
+                pushed.clear();
+            }
+            _ => pushed.clear(),
+        }
+    }
+
+    calls
+}
+
+pub(crate) fn label(offset: usize) -> String {
+    format!("label_{:04x}", 2 * offset)
+}
+
+fn format_op(op: &Op) -> String {
+    match op {
+        Op::PushVariable(v) => format!("push_variable var[{v}]"),
+        Op::LessThan => "less_than".to_owned(),
+        Op::PushNumber(n) => format!("push_number 0x{n:04x}"),
+        Op::NotEqual => "not_equal".to_owned(),
+        Op::IfAnd => "if_and".to_owned(),
+        Op::SkipZero(t) => format!("skip_zero {}", label(*t)),
+        Op::PopVariable(v) => format!("pop_var var[{v}]"),
+        Op::Minus => "minus".to_owned(),
+        Op::Plus => "plus".to_owned(),
+        Op::SkipAlways(t) => format!("skip_always {}", label(*t)),
+        Op::IfOr => "if_or".to_owned(),
+        Op::CallMcode { args, mcode } => match mcode_name(*mcode) {
+            Some(name) => format!("call_mcode {args}, {name}"),
+            None => format!("call_mcode {args}, mcode_{mcode}"),
+        },
+        Op::MoreThan => "more_than".to_owned(),
+        Op::Switch { cases, default } => {
+            let cases: Vec<_> = cases
+                .iter()
+                .map(|(value, t)| format!("0x{value:04x} => {}", label(*t)))
+                .collect();
+            format!(
+                "switch {}, default => {}",
+                cases.join(", "),
+                label(*default)
+            )
+        }
+        Op::PushOffset(o) => format!("push_offset 0x{o:04x}"),
+        Op::PopOffset(o) => format!("pop_offset 0x{o:04x}"),
+        Op::IsEqual => "is_equal".to_owned(),
+        Op::SkipNonZero(t) => format!("skip_nz {}", label(*t)),
+        Op::Exit => "script_exit".to_owned(),
+        Op::Restart => "restart_script".to_owned(),
+        Op::Unknown(op) => format!("unknown 0x{op:04x}"),
+    }
+}
+
+/// Describes the constant arguments of a call to `mcode` that refer to
+/// something. `args` are in the order they were pushed.
+fn annotate(mcode: u16, args: &[Option<u16>], text: impl Fn(u16) -> Option<String>) -> String {
+    let mut notes = Vec::new();
+
+    for &(n, reference) in mcode_references(mcode) {
+        let Some(Some(value)) = args.get(n) else {
+            continue;
+        };
+
+        notes.push(match reference {
+            Reference::Compact | Reference::Animation => {
+                format!("{} 0x{value:04x}", reference.name())
+            }
+            Reference::Text => match text(*value) {
+                Some(line) => format!("text 0x{value:04x} {line:?}"),
+                None => format!("text 0x{value:04x}"),
+            },
+            Reference::Sfx | Reference::Music | Reference::Room => {
+                format!("{} {value}", reference.name())
+            }
+        });
+    }
+
+    notes.join(", ")
+}
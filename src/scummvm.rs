@@ -0,0 +1,119 @@
+//! Entries for ScummVM's detection tables.
+//!
+//! ScummVM tells releases of the game apart by the number of entries in
+//! `sky.dnr` and the size of `sky.dsk`, which its sky engine keeps in a
+//! table of `{ entries, size, description, version }` rows. Its game
+//! database and the `--md5` option of its detector describe files by the
+//! MD5 hash of their first [`MD5_BYTES`] bytes and their size, written as
+//! `AD_ENTRY` lines. A [`Manifest`] writes both, so a copy of the game or
+//! a repacked one can be compared with what ScummVM expects.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{
+    text::Language,
+    version::{Release, Version},
+};
+
+/// Number of bytes at the start of a file ScummVM hashes to detect it.
+pub const MD5_BYTES: usize = 5000;
+
+/// A data file as ScummVM's detector sees it.
+#[derive(Clone, Debug)]
+pub struct FileInfo {
+    pub name: String,
+    pub size: u64,
+    /// MD5 hash of the first [`MD5_BYTES`] bytes.
+    pub md5: String,
+}
+
+impl FileInfo {
+    /// Describes the file at `path` under the name `name`, reading only as
+    /// much of it as is hashed.
+    pub fn read<P: AsRef<Path>>(name: &str, path: P) -> io::Result<FileInfo> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+
+        let mut start = Vec::with_capacity(MD5_BYTES);
+        file.take(MD5_BYTES as u64).read_to_end(&mut start)?;
+
+        Ok(FileInfo {
+            name: name.to_owned(),
+            size,
+            md5: format!("{:x}", md5::compute(&start)),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    /// Number of entries in `sky.dnr`.
+    pub entries: usize,
+    pub dnr: FileInfo,
+    pub dsk: FileInfo,
+    pub version: Option<&'static Version>,
+    /// Languages there is text for.
+    pub languages: Vec<Language>,
+}
+
+/// The description ScummVM gives releases of each kind in its table.
+fn description(release: Release) -> &'static str {
+    match release {
+        Release::Floppy => "Floppy",
+        Release::Cd => "CD",
+        Release::FloppyDemo => "Floppy Demo",
+        Release::CdDemo => "CD Demo",
+    }
+}
+
+/// The ScummVM language code matching `language`.
+pub fn language_code(language: Language) -> &'static str {
+    match language {
+        Language::English => "EN_GRB",
+        Language::German => "DE_DEU",
+        Language::French => "FR_FRA",
+        Language::Usa => "EN_USA",
+        Language::Swedish => "SE_SWE",
+        Language::Italian => "IT_ITA",
+        Language::Portuguese => "PT_BRA",
+        Language::Spanish => "ES_ESP",
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = self
+            .version
+            .map_or("unknown version".to_owned(), |v| v.to_string());
+        let languages: Vec<_> = self.languages.iter().map(|&l| language_code(l)).collect();
+        writeln!(f, "// Beneath a Steel Sky, {version}")?;
+        if !languages.is_empty() {
+            writeln!(f, "// Languages: {}", languages.join(", "))?;
+        }
+
+        // An unknown version has no description or number to go with it,
+        // but its sizes are still worth having.
+        match self.version {
+            Some(v) => writeln!(
+                f,
+                "{{ {}, {}, \"{}\", {} }},",
+                self.entries,
+                self.dsk.size,
+                description(v.release),
+                v.number
+            )?,
+            None => writeln!(f, "{{ {}, {}, \"\", 0 }},", self.entries, self.dsk.size)?,
+        }
+
+        writeln!(
+            f,
+            "AD_ENTRY2s(\"{}\", \"{}\", {}, \"{}\", \"{}\", {}),",
+            self.dnr.name, self.dnr.md5, self.dnr.size, self.dsk.name, self.dsk.md5, self.dsk.size
+        )
+    }
+}
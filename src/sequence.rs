@@ -0,0 +1,82 @@
+//! Sequences, the full-screen animations of the intro and cutscenes.
+//!
+//! A sequence is played over the screen shown before it. Its first byte is
+//! the number of frames, followed by each frame as the changes to the one
+//! before: runs of pixels to skip and runs of pixels to copy from the data,
+//! alternating until the [`GAME_SCREEN_SIZE`] pixels of the game screen
+//! are covered, leaving the rows below it alone. A run length of 255
+//! continues into the next byte, so longer runs are stored as several
+//! lengths adding up to them. Frames are shown [`FRAME_DELAY_MS`] apart.
+
+use crate::{classify::SCREEN_SIZE, sprite::Frame};
+
+/// The pixels of the game screen, the top 192 rows of the screen, which a
+/// frame covers.
+pub const GAME_SCREEN_SIZE: usize = 320 * 192;
+
+/// Time each frame of a sequence is shown for.
+pub const FRAME_DELAY_MS: u32 = 60;
+
+/// Whether `data` is a sequence: a frame count followed by exactly as many
+/// frames of changes as that.
+pub fn is_sequence(data: &[u8]) -> bool {
+    data.first().is_some_and(|&n| n > 0) && apply(data, &mut [0; SCREEN_SIZE], |_| ()).is_some()
+}
+
+/// Plays the sequence `data` over `screen`, returning every frame and
+/// leaving `screen` showing the last one. Returns `None` if `data` isn't a
+/// sequence.
+pub fn decode(data: &[u8], screen: &mut [u8]) -> Option<Vec<Frame>> {
+    let mut frames = Vec::new();
+    apply(data, screen, |screen| {
+        frames.push(Frame {
+            width: 320,
+            height: 200,
+            data: screen.to_vec(),
+        })
+    })?;
+
+    Some(frames)
+}
+
+/// Applies the frames of `data` to `screen` in turn, calling `frame` after
+/// each. Fails if a frame runs past the end of the data or the screen, or
+/// data is left over after the last frame.
+fn apply(data: &[u8], screen: &mut [u8], mut frame: impl FnMut(&[u8])) -> Option<()> {
+    let (&count, mut data) = data.split_first()?;
+    let screen = screen.get_mut(..SCREEN_SIZE)?;
+
+    let mut next = || {
+        let (&b, rest) = data.split_first()?;
+        data = rest;
+        Some(b)
+    };
+
+    for _ in 0..count {
+        let mut pos = 0;
+        while pos < GAME_SCREEN_SIZE {
+            loop {
+                let skip = next()?;
+                pos += skip as usize;
+                if skip != 0xff {
+                    break;
+                }
+            }
+
+            loop {
+                let copy = next()? as usize;
+                let run = screen.get_mut(pos..pos + copy)?;
+                for pixel in run {
+                    *pixel = next()?;
+                }
+                pos += copy;
+                if copy != 0xff {
+                    break;
+                }
+            }
+        }
+        frame(screen);
+    }
+
+    data.is_empty().then_some(())
+}
@@ -0,0 +1,117 @@
+//! Sound effects of the floppy and CD releases.
+//!
+//! The last music resource of each section is its sound effect bank: the
+//! sound driver followed by the samples of the effects. The driver code
+//! holds the number of effects and the offsets of two tables, one with the
+//! sample rate of each effect and one with where its samples are, how many
+//! there are and how many of them at the end are repeated while the effect
+//! plays. The tables are big endian, unlike the rest of the game's data.
+//! Samples are unsigned 8-bit mono.
+
+use serde::Serialize;
+
+use crate::{
+    classify::{FILES_PER_SECTION, MUSIC_FILE_BASE},
+    error::{Error, Result},
+    version::Version,
+};
+
+/// Index of the sound effect bank among the resources of a section.
+const SFX_FILE: u16 = 3;
+
+/// The fastest rate the driver plays effects at; effects recorded at a
+/// higher rate are played at this one.
+pub const MAX_SAMPLE_RATE: u32 = 11025;
+
+/// Size of an entry of the table locating the samples of each effect.
+const SFX_INFO_SIZE: usize = 8;
+
+/// The id of the sound effect bank of `section`.
+pub fn sfx_file(section: u16) -> u16 {
+    MUSIC_FILE_BASE + section * FILES_PER_SECTION + SFX_FILE
+}
+
+/// A sound effect of a bank.
+#[derive(Clone, Debug, Serialize)]
+pub struct Effect {
+    pub number: u8,
+    /// Offset of the samples from the start of the bank.
+    pub offset: usize,
+    pub length: usize,
+    pub sample_rate: u32,
+    /// The samples repeated while the effect plays, from `loop_start` to
+    /// the end.
+    pub loop_start: Option<usize>,
+    /// The word between the length and the loop, which the driver keeps
+    /// for deciding which effect to cut off when too many play at once.
+    pub priority: u16,
+}
+
+impl Effect {
+    pub fn samples<'a>(&self, bank: &'a [u8]) -> &'a [u8] {
+        &bank[self.offset..self.offset + self.length]
+    }
+}
+
+/// Lists the effects of a sound effect bank. Fails if the driver isn't
+/// one the effects are known to be found in.
+pub fn read_bank(data: &[u8], section: u16, version: Option<&Version>) -> Result<Vec<Effect>> {
+    // Where the driver code reading the tables is, found by the
+    // instructions it starts with and those loading the table offsets.
+    let code = match version {
+        Some(v) if v.number == 109 && section == 0 => 0x78,
+        Some(v) if v.number == 109 => 0x7c,
+        _ => 0x7e,
+    };
+    let signature = [
+        (0, 0x3c),
+        (0x27, 0x8d),
+        (0x28, 0x1e),
+        (0x2f, 0x8d),
+        (0x30, 0x36),
+    ];
+    if !signature
+        .iter()
+        .all(|&(ofs, b)| data.get(code + ofs) == Some(&b))
+    {
+        return Err(Error::InvalidData("unknown sound driver".to_owned()));
+    }
+
+    let truncated = || Error::InvalidData("sound effect bank is truncated".to_owned());
+    let le_u16 = |ofs: usize| {
+        data.get(ofs..ofs + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let be_u16 = |ofs: usize| {
+        data.get(ofs..ofs + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+    };
+
+    let count = data[code + 1];
+    let rates = le_u16(code + 0x29).ok_or_else(truncated)?;
+    let base = le_u16(code + 0x31).ok_or_else(truncated)?;
+
+    (0..count)
+        .map(|number| {
+            let info = base + number as usize * SFX_INFO_SIZE;
+            let sample_rate = be_u16(rates + number as usize * 4).ok_or_else(truncated)? as u32;
+            let offset = base + (be_u16(info).ok_or_else(truncated)? << 4);
+            let length = be_u16(info + 2).ok_or_else(truncated)?;
+            let priority = be_u16(info + 4).ok_or_else(truncated)? as u16;
+            let loop_length = be_u16(info + 6).ok_or_else(truncated)?;
+
+            if offset + length > data.len() || loop_length > length {
+                return Err(truncated());
+            }
+
+            Ok(Effect {
+                number,
+                offset,
+                length,
+                sample_rate: sample_rate.min(MAX_SAMPLE_RATE),
+                loop_start: (loop_length != 0).then(|| length - loop_length),
+                priority,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,99 @@
+//! Digitized speech of the CD release.
+//!
+//! Every line of dialogue has its own resource, numbered from
+//! [`SPEECH_FILE_BASE`] on. Text ids hold the text section in their top
+//! four bits and the line within the section in the remaining twelve; the
+//! speech files of each section follow those of the previous one.
+
+use crate::{
+    archive::Entry,
+    resource::{write_resource, Header, Resource},
+    version::Version,
+};
+
+pub const SPEECH_FILE_BASE: u16 = 50000;
+
+/// Index of the first speech file of each text section.
+const SPEECH_CONVERT_TABLE: [u16; 8] = [
+    0,
+    600,
+    600 + 500,
+    600 + 500 + 1330,
+    600 + 500 + 1330 + 950,
+    600 + 500 + 1330 + 950 + 1150,
+    600 + 500 + 1330 + 950 + 1150 + 550,
+    600 + 500 + 1330 + 950 + 1150 + 550 + 150,
+];
+
+/// Upper bound on the number of speech files, past which ids belong to
+/// other resources.
+const MAX_SPEECH_FILES: u16 = 10000;
+
+/// Whether the resource `id` holds speech. Only the CD release has speech;
+/// if the version is unknown the id alone decides.
+pub fn is_speech_file(id: u16, version: Option<&Version>) -> bool {
+    version.is_none_or(|v| v.is_cd())
+        && (SPEECH_FILE_BASE..SPEECH_FILE_BASE + MAX_SPEECH_FILES).contains(&id)
+}
+
+/// The id of the speech file for the line `text_id`, if its section has
+/// speech.
+pub fn speech_file(text_id: u16) -> Option<u16> {
+    let first = *SPEECH_CONVERT_TABLE.get((text_id >> 12) as usize)?;
+    let id = SPEECH_FILE_BASE + first + (text_id & 0xfff);
+    (self::text_id(id) == Some(text_id)).then_some(id)
+}
+
+/// The id of the line of text spoken in the speech file `id`.
+pub fn text_id(id: u16) -> Option<u16> {
+    let n = id.checked_sub(SPEECH_FILE_BASE)?;
+    if n >= MAX_SPEECH_FILES {
+        return None;
+    }
+
+    let section = SPEECH_CONVERT_TABLE.iter().rposition(|&first| first <= n)?;
+    let line = n - SPEECH_CONVERT_TABLE[section];
+    if line > 0xfff {
+        return None;
+    }
+
+    Some(((section as u16) << 12) | line)
+}
+
+/// The unsigned 8-bit samples of a speech resource. The header's total size
+/// bounds the sample data, which may be followed by padding.
+pub fn samples(resource: &Resource) -> &[u8] {
+    let len = resource.header.as_ref().map_or(resource.data.len(), |h| {
+        (h.total_size() as usize).saturating_sub(Header::SIZE)
+    });
+
+    &resource.data[..len.min(resource.data.len())]
+}
+
+/// Encodes unsigned 8-bit `samples` at [`SAMPLE_RATE`](crate::audio::SAMPLE_RATE)
+/// as the speech resource `id`, stored with a file header the way the CD
+/// release stores speech.
+pub fn write_speech(id: u16, samples: &[u8]) -> Vec<u8> {
+    let entry = Entry {
+        number: id,
+        offset: 0,
+        size: 0,
+        has_file_header: true,
+        uses_file_header: true,
+    };
+    let header = Header {
+        flags: 0,
+        x: 0,
+        y: 0,
+        width: 0,
+        height: 0,
+        sp_size: 0,
+        tot_size: 0,
+        n_sprites: 0,
+        offset_x: 0,
+        offset_y: 0,
+        compressed_size: 0,
+    };
+
+    write_resource(&entry, &header, samples, false)
+}
@@ -0,0 +1,203 @@
+use std::io::{self, Write};
+
+use crate::{image::palette_to_rgb, resource::Resource};
+
+/// The color index sprites leave see-through.
+pub const TRANSPARENT_INDEX: u8 = 0;
+
+/// Delay between animation frames in hundredths of a second.
+const GIF_FRAME_DELAY: u16 = 10;
+
+/// A single 8-bit chunky frame of a sprite resource. Color index
+/// [`TRANSPARENT_INDEX`] is transparent in game.
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+/// How the pixels of the frames of a sprite resource are stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// A byte per pixel, for 256 colors.
+    Chunky,
+    /// Four bitplanes one after the other, each with a bit per pixel, the
+    /// leftmost in the top bit, and rows padded to whole bytes. The first
+    /// plane holds the lowest bit of the color, for 16 colors.
+    Planar,
+}
+
+impl PixelFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PixelFormat::Chunky => "8-bit chunky",
+            PixelFormat::Planar => "4-bit planar",
+        }
+    }
+
+    /// The number of bytes a `width` by `height` frame takes.
+    pub fn frame_size(&self, width: usize, height: usize) -> usize {
+        match self {
+            PixelFormat::Chunky => width * height,
+            PixelFormat::Planar => 4 * width.div_ceil(8) * height,
+        }
+    }
+}
+
+/// The format of the frames of a sprite resource: planar if each frame
+/// takes as many bytes as four bitplanes, going by `sp_size` or else by the
+/// data shared out among the frames, and chunky otherwise. Returns `None`
+/// if the resource has no header describing sprites.
+pub fn pixel_format(resource: &Resource) -> Option<PixelFormat> {
+    let header = resource.header.as_ref()?;
+
+    let (width, height) = (header.width as usize, header.height as usize);
+    let frames = header.n_sprites as usize;
+    if frames == 0 || width * height == 0 {
+        return None;
+    }
+
+    let frame_bytes = match header.sp_size as usize {
+        0 => resource.data.len() / frames,
+        sp_size => sp_size,
+    };
+    let planar = PixelFormat::Planar.frame_size(width, height);
+    if frame_bytes < PixelFormat::Chunky.frame_size(width, height) && frame_bytes == planar {
+        Some(PixelFormat::Planar)
+    } else {
+        Some(PixelFormat::Chunky)
+    }
+}
+
+/// Converts a `width` by `height` frame of four bitplanes to a byte per
+/// pixel.
+pub fn planar_to_chunky(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let row_bytes = width.div_ceil(8);
+    let plane_size = row_bytes * height;
+
+    let mut pixels = vec![0; width * height];
+    for (plane, bits) in data.chunks_exact(plane_size).take(4).enumerate() {
+        for y in 0..height {
+            for x in 0..width {
+                let bit = bits[y * row_bytes + x / 8] >> (7 - x % 8) & 1;
+                pixels[y * width + x] |= bit << plane;
+            }
+        }
+    }
+
+    pixels
+}
+
+/// Splits a sprite resource into its frames.
+///
+/// Frames follow each other `sp_size` bytes apart, each one `width` by
+/// `height` pixels in the resource's [`PixelFormat`], and are converted to
+/// a byte per pixel. Returns `None` if the resource has no header
+/// describing sprites. Frames cut short by the end of the data are dropped.
+pub fn decode_frames(resource: &Resource) -> Option<Vec<Frame>> {
+    let header = resource.header.as_ref()?;
+    let format = pixel_format(resource)?;
+
+    let width = header.width as usize;
+    let height = header.height as usize;
+    let frame_size = format.frame_size(width, height);
+    let stride = (header.sp_size as usize).max(frame_size);
+
+    let frames = (0..header.n_sprites as usize)
+        .map_while(|n| resource.data.get(n * stride..n * stride + frame_size))
+        .map(|data| Frame {
+            width,
+            height,
+            data: match format {
+                PixelFormat::Chunky => data.to_vec(),
+                PixelFormat::Planar => planar_to_chunky(data, width, height),
+            },
+        })
+        .collect();
+
+    Some(frames)
+}
+
+/// Number of columns [`pack_sheet`] lays out `frames` frames in.
+pub fn sheet_columns(frames: usize) -> usize {
+    (frames as f64).sqrt().ceil() as usize
+}
+
+/// Packs `frames` into a single image, left to right and top to bottom in a
+/// roughly square grid. All frames of a resource share the same size.
+pub fn pack_sheet(frames: &[Frame]) -> Option<Frame> {
+    let first = frames.first()?;
+    let (width, height) = (first.width, first.height);
+
+    let columns = sheet_columns(frames.len());
+    let rows = frames.len().div_ceil(columns);
+
+    let sheet_width = columns * width;
+    let mut sheet = Frame {
+        width: sheet_width,
+        height: rows * height,
+        data: vec![0; sheet_width * rows * height],
+    };
+
+    for (n, frame) in frames.iter().enumerate() {
+        let (column, row) = (n % columns, n / columns);
+        for y in 0..height {
+            let out_ofs = (row * height + y) * sheet_width + column * width;
+            sheet.data[out_ofs..out_ofs + width]
+                .copy_from_slice(&frame.data[y * width..(y + 1) * width]);
+        }
+    }
+
+    Some(sheet)
+}
+
+/// Writes `frames` as a looping animated GIF, using the 6-bit VGA `palette`
+/// and treating color index `transparent` as transparent.
+pub fn write_gif<W: Write>(
+    w: W,
+    frames: &[Frame],
+    palette: &[u8],
+    transparent: u8,
+) -> io::Result<()> {
+    write_gif_with(w, frames, palette, GIF_FRAME_DELAY, Some(transparent))
+}
+
+/// Writes `frames` as a looping animated GIF shown `delay` hundredths of a
+/// second apart, with color index `transparent` see-through if given.
+pub fn write_gif_with<W: Write>(
+    w: W,
+    frames: &[Frame],
+    palette: &[u8],
+    delay: u16,
+    transparent: Option<u8>,
+) -> io::Result<()> {
+    let Some(first) = frames.first() else {
+        return Ok(());
+    };
+
+    let mut encoder = gif::Encoder::new(
+        w,
+        first.width as u16,
+        first.height as u16,
+        &palette_to_rgb(palette),
+    )
+    .map_err(io::Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(io::Error::other)?;
+
+    for frame in frames {
+        let mut gif_frame = gif::Frame::from_indexed_pixels(
+            frame.width as u16,
+            frame.height as u16,
+            frame.data.as_slice(),
+            transparent,
+        );
+        gif_frame.delay = delay;
+        gif_frame.dispose = gif::DisposalMethod::Background;
+
+        encoder.write_frame(&gif_frame).map_err(io::Error::other)?;
+    }
+
+    Ok(())
+}
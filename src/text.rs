@@ -0,0 +1,581 @@
+//! Huffman compressed dialogue and object text.
+//!
+//! The text of each language is split over [`NO_OF_TEXT_SECTIONS`]
+//! resources. A text id holds the section in its top four bits, a block of
+//! 32 lines in the next seven and the line within the block in the lowest
+//! five.
+//!
+//! A section starts with three little endian words: the offset of the
+//! per-block skip tables, the offset of the compressed bits and the length
+//! of the first block, followed by the lengths of the remaining blocks.
+//! Each skip table holds one byte per line giving its length, so a line is
+//! found by adding up the lengths of the blocks and lines before it.
+//! Lengths are counted in units of two bits.
+//!
+//! The Huffman tree itself is not part of the data files but of the game
+//! executable.
+//!
+//! Sections can be encoded again, with the tree they were decoded with or
+//! with one built for the text. The game only uses the tree built into it,
+//! so text encoded with a new tree needs its executable patched, or a build
+//! of ScummVM with the tree, to be read.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, VecDeque},
+};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+pub const TEXT_FILE_BASE: u16 = 60600;
+pub const NO_OF_TEXT_SECTIONS: u16 = 8;
+/// The number of languages any release has text for.
+pub const MAX_LANGUAGES: u16 = 8;
+
+/// The languages text can be in, in the order their sections are stored.
+/// Releases only have the sections of the languages they were sold with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Language {
+    English,
+    German,
+    French,
+    /// American English.
+    Usa,
+    Swedish,
+    Italian,
+    Portuguese,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; MAX_LANGUAGES as usize] = [
+        Language::English,
+        Language::German,
+        Language::French,
+        Language::Usa,
+        Language::Swedish,
+        Language::Italian,
+        Language::Portuguese,
+        Language::Spanish,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "english",
+            Language::German => "german",
+            Language::French => "french",
+            Language::Usa => "usa",
+            Language::Swedish => "swedish",
+            Language::Italian => "italian",
+            Language::Portuguese => "portuguese",
+            Language::Spanish => "spanish",
+        }
+    }
+
+    /// The number the game knows the language by.
+    pub fn number(&self) -> u16 {
+        *self as u16
+    }
+}
+
+/// The id of the resource holding `section` of the text in `language`.
+pub fn text_file(language: u16, section: u16) -> u16 {
+    TEXT_FILE_BASE + language * NO_OF_TEXT_SECTIONS + section
+}
+
+/// Whether the resource `id` holds a text section of any language.
+pub fn is_text_file(id: u16) -> bool {
+    (TEXT_FILE_BASE..TEXT_FILE_BASE + MAX_LANGUAGES * NO_OF_TEXT_SECTIONS).contains(&id)
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    l_child: u8,
+    r_child: u8,
+    value: u8,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.l_child == 0 && self.r_child == 0
+    }
+}
+
+/// The tree used to decode text, stored as a table of three byte nodes
+/// `left child, right child, value` with the root at index 0.
+pub struct HuffmanTree {
+    nodes: Vec<Node>,
+}
+
+impl HuffmanTree {
+    /// Reads a tree from its node table, checking that it is well formed.
+    /// Trailing bytes after the last node reachable from the root are
+    /// ignored.
+    pub fn from_bytes(data: &[u8]) -> Option<HuffmanTree> {
+        let (len, _) = Self::validate(data)?;
+
+        let nodes = data[..3 * len]
+            .chunks_exact(3)
+            .map(|n| Node {
+                l_child: n[0],
+                r_child: n[1],
+                value: n[2],
+            })
+            .collect();
+
+        Some(HuffmanTree { nodes })
+    }
+
+    /// Searches a game executable for its text tree.
+    ///
+    /// Every offset is tried in turn, and the first well formed tree with at
+    /// least enough leaves for the letters of the alphabet is returned.
+    pub fn find_in_executable(exe: &[u8]) -> Option<HuffmanTree> {
+        const MIN_LEAVES: usize = 40;
+
+        (0..exe.len())
+            .find(|&ofs| {
+                let end = (ofs + 3 * 256).min(exe.len());
+                Self::validate(&exe[ofs..end]).is_some_and(|(_, leaves)| leaves >= MIN_LEAVES)
+            })
+            .and_then(|ofs| HuffmanTree::from_bytes(&exe[ofs..]))
+    }
+
+    /// Builds the tree giving the shortest encoding of `lines`, from how
+    /// often each character and the terminating zero occur. Fails if more
+    /// than 128 different characters are used, as child indices are bytes.
+    pub fn for_lines<'a>(lines: impl IntoIterator<Item = &'a [u8]>) -> Option<HuffmanTree> {
+        let mut counts = [0u64; 256];
+        for line in lines {
+            for &c in line {
+                counts[c as usize] += 1;
+            }
+            counts[0] += 1;
+        }
+        // The root can't be a leaf, so text without characters gets a
+        // second leaf that's never used.
+        counts[0] = counts[0].max(1);
+        if counts.iter().filter(|&&n| n > 0).count() < 2 {
+            counts[b' ' as usize] = 1;
+        }
+
+        enum Tree {
+            Leaf(u8),
+            Branch(Box<Tree>, Box<Tree>),
+        }
+
+        // Ties go to the older subtree, so the same text always gives the
+        // same tree.
+        let mut heap = BinaryHeap::new();
+        let mut trees = Vec::new();
+        for (value, &count) in counts.iter().enumerate() {
+            if count > 0 {
+                heap.push(Reverse((count, trees.len())));
+                trees.push(Some(Tree::Leaf(value as u8)));
+            }
+        }
+        if 2 * trees.len() - 1 > 256 {
+            return None;
+        }
+
+        while heap.len() > 1 {
+            let Reverse((a_count, a)) = heap.pop()?;
+            let Reverse((b_count, b)) = heap.pop()?;
+            let branch = Tree::Branch(Box::new(trees[a].take()?), Box::new(trees[b].take()?));
+            heap.push(Reverse((a_count + b_count, trees.len())));
+            trees.push(Some(branch));
+        }
+        let Reverse((_, root)) = heap.pop()?;
+
+        // Nodes are numbered breadth first from the root.
+        let mut nodes = Vec::new();
+        let mut queue = VecDeque::from([trees[root].take()?]);
+        while let Some(tree) = queue.pop_front() {
+            match tree {
+                Tree::Leaf(value) => nodes.push(Node {
+                    l_child: 0,
+                    r_child: 0,
+                    value,
+                }),
+                Tree::Branch(l, r) => {
+                    let l_child = (nodes.len() + queue.len() + 1) as u8;
+                    nodes.push(Node {
+                        l_child,
+                        r_child: l_child + 1,
+                        value: 0,
+                    });
+                    queue.push_back(*l);
+                    queue.push_back(*r);
+                }
+            }
+        }
+
+        Some(HuffmanTree { nodes })
+    }
+
+    /// The node table, as [`HuffmanTree::from_bytes`] reads it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.nodes
+            .iter()
+            .flat_map(|n| [n.l_child, n.r_child, n.value])
+            .collect()
+    }
+
+    /// The bits leading to each character, for those with a leaf.
+    fn codes(&self) -> Vec<Option<Vec<bool>>> {
+        let mut codes = vec![None; 256];
+        let mut stack = vec![(0usize, Vec::new())];
+        while let Some((i, code)) = stack.pop() {
+            let node = &self.nodes[i];
+            if node.is_leaf() {
+                codes[node.value as usize] = Some(code);
+            } else {
+                let mut right = code.clone();
+                right.push(true);
+                let mut left = code;
+                left.push(false);
+                stack.push((node.r_child as usize, right));
+                stack.push((node.l_child as usize, left));
+            }
+        }
+        codes
+    }
+
+    /// Checks that the nodes reachable from the root form a tree with
+    /// unique leaf values including the terminating zero. Returns the
+    /// number of nodes used and the number of leaves.
+    fn validate(data: &[u8]) -> Option<(usize, usize)> {
+        let node = |i: usize| {
+            data.get(3 * i..3 * i + 3).map(|n| Node {
+                l_child: n[0],
+                r_child: n[1],
+                value: n[2],
+            })
+        };
+
+        if node(0)?.is_leaf() {
+            return None;
+        }
+
+        let mut visited = [false; 256];
+        let mut seen_values = [false; 256];
+        let mut stack = vec![0usize];
+        let mut len = 0;
+        let mut leaves = 0;
+
+        while let Some(i) = stack.pop() {
+            let node = node(i)?;
+            if visited[i] {
+                return None;
+            }
+            visited[i] = true;
+            len = len.max(i + 1);
+
+            if node.is_leaf() {
+                if seen_values[node.value as usize] {
+                    return None;
+                }
+                seen_values[node.value as usize] = true;
+                leaves += 1;
+            } else {
+                if node.l_child == 0 || node.r_child == 0 {
+                    return None;
+                }
+                stack.push(node.l_child as usize);
+                stack.push(node.r_child as usize);
+            }
+        }
+
+        if !seen_values[0] || visited[..len].iter().any(|v| !v) {
+            return None;
+        }
+
+        Some((len, leaves))
+    }
+}
+
+/// Reads bits most significant first, starting at a two bit boundary.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl BitReader<'_> {
+    fn read_bit(&mut self) -> Option<bool> {
+        if self.bit == 0 {
+            self.pos += 1;
+            self.bit = 8;
+        }
+        self.bit -= 1;
+
+        let byte = *self.data.get(self.pos)?;
+        Some((byte >> self.bit) & 1 != 0)
+    }
+}
+
+fn read_le_u16(data: &[u8], ofs: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(ofs..ofs + 2)?.try_into().ok()?))
+}
+
+/// Decodes the line at `offset`, in two bit units from the start of the
+/// compressed bits, up to but not including its terminating zero.
+fn decode_at(data: &[u8], bits_ofs: usize, offset: usize, tree: &HuffmanTree) -> Option<Vec<u8>> {
+    let mut r = BitReader {
+        data,
+        pos: bits_ofs + offset / 4,
+        bit: 8 - 2 * (offset % 4) as u8,
+    };
+
+    let mut text = Vec::new();
+    loop {
+        let mut node = &tree.nodes[0];
+        while !node.is_leaf() {
+            let child = if r.read_bit()? {
+                node.r_child
+            } else {
+                node.l_child
+            };
+            node = &tree.nodes[child as usize];
+        }
+
+        if node.value == 0 {
+            return Some(text);
+        }
+        text.push(node.value);
+    }
+}
+
+/// Decodes line `text_id` of a section, ignoring the section bits of the
+/// id. Returns the raw bytes of the line without its terminating zero.
+pub fn decode_line(data: &[u8], text_id: u16, tree: &HuffmanTree) -> Option<Vec<u8>> {
+    let skip_tables_ofs = read_le_u16(data, 0)? as usize;
+    let bits_ofs = read_le_u16(data, 2)? as usize;
+
+    let block = ((text_id & 0xfe0) >> 5) as usize;
+    let line = (text_id & 0x1f) as usize;
+
+    let mut offset = 0;
+    for b in 0..block {
+        offset += read_le_u16(data, 4 + 2 * b)? as usize;
+    }
+
+    let skip_table = data.get(skip_tables_ofs + 32 * block..)?;
+    for &skip in skip_table.get(..line)? {
+        offset += line_length(skip);
+    }
+
+    decode_at(data, bits_ofs, offset, tree)
+}
+
+fn line_length(skip: u8) -> usize {
+    if skip & 0x80 != 0 {
+        ((skip & 0x7f) as usize) << 3
+    } else {
+        skip as usize
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TextLine {
+    pub id: u16,
+    pub text: String,
+    /// The text as stored, in the game's code page.
+    #[serde(skip)]
+    pub bytes: Vec<u8>,
+}
+
+/// Decodes every line of a section. The number of lines of each block
+/// follows from the block length, lines are read until their lengths add up
+/// to it.
+pub fn decode_section(section: u16, data: &[u8], tree: &HuffmanTree) -> Vec<TextLine> {
+    let mut lines = Vec::new();
+
+    let (Some(skip_tables_ofs), Some(bits_ofs)) = (read_le_u16(data, 0), read_le_u16(data, 2))
+    else {
+        return lines;
+    };
+    let (skip_tables_ofs, bits_ofs) = (skip_tables_ofs as usize, bits_ofs as usize);
+    let block_count = skip_tables_ofs.saturating_sub(4) / 2;
+
+    let mut block_offset = 0;
+    for block in 0..block_count.min(0x80) {
+        let Some(block_len) = read_le_u16(data, 4 + 2 * block) else {
+            break;
+        };
+        let block_len = block_len as usize;
+
+        let mut offset = block_offset;
+        for line in 0..32 {
+            if offset >= block_offset + block_len {
+                break;
+            }
+            let Some(skip) = data.get(skip_tables_ofs + 32 * block + line) else {
+                break;
+            };
+
+            if let Some(text) = decode_at(data, bits_ofs, offset, tree) {
+                lines.push(TextLine {
+                    id: (section << 12) | ((block as u16) << 5) | line as u16,
+                    text: to_utf8(&text),
+                    bytes: text,
+                });
+            }
+
+            let len = line_length(*skip);
+            if len == 0 {
+                break;
+            }
+            offset += len;
+        }
+
+        block_offset += block_len;
+    }
+
+    lines
+}
+
+/// Writes bits most significant first.
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.bits.is_multiple_of(8) {
+            self.data.push(0);
+        }
+        if bit {
+            *self.data.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+        }
+        self.bits += 1;
+    }
+}
+
+/// The longest line a skip table can hold, in two bit units.
+const MAX_LINE_LENGTH: usize = 0x7f << 3;
+
+/// Encodes `lines` into a section with `tree`, the inverse of
+/// [`decode_section`]. The section bits of the ids are ignored, and lines
+/// missing from a block before the last line given are left empty.
+///
+/// Fails if a line has a character the tree has no leaf for or takes more
+/// bits than a skip table can hold, or if an id is given twice.
+pub fn encode_section(lines: &[TextLine], tree: &HuffmanTree) -> Result<Vec<u8>> {
+    let codes = tree.codes();
+
+    let mut blocks: BTreeMap<usize, BTreeMap<usize, &TextLine>> = BTreeMap::new();
+    for line in lines {
+        let block = ((line.id & 0xfe0) >> 5) as usize;
+        let n = (line.id & 0x1f) as usize;
+        if blocks.entry(block).or_default().insert(n, line).is_some() {
+            return Err(Error::InvalidData(format!(
+                "line 0x{:04x} is given twice",
+                line.id
+            )));
+        }
+    }
+    let block_count = blocks.keys().next_back().map_or(1, |&b| b + 1);
+
+    let mut block_lengths = vec![0u16; block_count];
+    let mut skip_tables = vec![0u8; 32 * block_count];
+    let mut w = BitWriter::default();
+    for (&block, block_lines) in &blocks {
+        let last = *block_lines.keys().next_back().unwrap_or(&0);
+        let mut block_len = 0;
+        for n in 0..=last {
+            let start = w.bits;
+            let bytes = block_lines.get(&n).map_or(&[][..], |l| &l.bytes[..]);
+            for &c in bytes.iter().chain(&[0]) {
+                let code = codes[c as usize].as_ref().ok_or_else(|| {
+                    Error::InvalidData(format!(
+                        "line 0x{:04x} has {:?}, which the tree has no code for",
+                        block_lines[&n].id,
+                        to_utf8(&[c])
+                    ))
+                })?;
+                code.iter().for_each(|&bit| w.write_bit(bit));
+            }
+
+            // Lines start on two bit boundaries, and lengths from 0x80 up
+            // are stored in units of eight.
+            let mut len = (w.bits - start).div_ceil(2);
+            if len >= 0x80 {
+                len = len.next_multiple_of(8);
+            }
+            if len > MAX_LINE_LENGTH {
+                return Err(Error::InvalidData(format!(
+                    "line 0x{:04x} is too long to encode",
+                    block_lines[&n].id
+                )));
+            }
+            while w.bits < start + 2 * len {
+                w.write_bit(false);
+            }
+
+            skip_tables[32 * block + n] = if len >= 0x80 {
+                0x80 | (len >> 3) as u8
+            } else {
+                len as u8
+            };
+            block_len += len;
+        }
+        block_lengths[block] = block_len as u16;
+    }
+
+    let skip_tables_ofs = 4 + 2 * block_count;
+    let bits_ofs = skip_tables_ofs + skip_tables.len();
+    let mut data = Vec::with_capacity(bits_ofs + w.data.len());
+    data.extend((skip_tables_ofs as u16).to_le_bytes());
+    data.extend((bits_ofs as u16).to_le_bytes());
+    block_lengths
+        .iter()
+        .for_each(|len| data.extend(len.to_le_bytes()));
+    data.extend(skip_tables);
+    data.extend(w.data);
+
+    Ok(data)
+}
+
+/// Converts text from the DOS code page the game was written in.
+pub fn to_utf8(text: &[u8]) -> String {
+    text.iter()
+        .map(|&c| {
+            if c < 0x80 {
+                c as char
+            } else {
+                CP437_HIGH[(c - 0x80) as usize]
+            }
+        })
+        .collect()
+}
+
+/// Converts text to the DOS code page the game was written in, the inverse
+/// of [`to_utf8`]. Fails on characters the code page doesn't have.
+pub fn from_utf8(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii() {
+                Some(c as u8)
+            } else {
+                let n = CP437_HIGH.iter().position(|&h| h == c)?;
+                Some(0x80 + n as u8)
+            }
+        })
+        .collect()
+}
+
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
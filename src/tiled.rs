@@ -0,0 +1,197 @@
+//! Maps of rooms for the Tiled map editor.
+//!
+//! A room is written as a map in Tiled's JSON format, made up of 8 by 8
+//! pixel tiles so that its walk grid lines up with the tiles. The screen is
+//! the bottom layer, as an image layer. Foreground layers aren't aligned to
+//! tiles, so each is an image layer of its own placed at its position, and
+//! the walk grid is a tile layer on top with a single tile standing for a
+//! blocked cell. Images are referred to by file names relative to the map.
+
+use serde::Serialize;
+
+use crate::grid::{Grid, GRID_BLOCK, GRID_HEIGHT, GRID_WIDTH};
+
+/// Version of the map format the maps are written in.
+const FORMAT_VERSION: &str = "1.10";
+
+/// Size of a map in tiles: the whole screen, not just the game area the
+/// walk grid covers.
+const MAP_WIDTH: usize = 320 / GRID_BLOCK;
+const MAP_HEIGHT: usize = 200 / GRID_BLOCK;
+
+/// Opacity of the walk grid layer, so the room shows through.
+const GRID_OPACITY: f32 = 0.5;
+
+#[derive(Debug, Serialize)]
+pub struct Map {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    version: &'static str,
+    orientation: &'static str,
+    renderorder: &'static str,
+    infinite: bool,
+    width: usize,
+    height: usize,
+    tilewidth: usize,
+    tileheight: usize,
+    nextlayerid: usize,
+    nextobjectid: usize,
+    layers: Vec<Layer>,
+    tilesets: Vec<Tileset>,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Layer {
+    ImageLayer {
+        id: usize,
+        name: String,
+        image: String,
+        x: i32,
+        y: i32,
+        offsetx: i32,
+        offsety: i32,
+        opacity: f32,
+        visible: bool,
+    },
+    TileLayer {
+        id: usize,
+        name: String,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+        data: Vec<u32>,
+        opacity: f32,
+        visible: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct Tileset {
+    firstgid: u32,
+    name: String,
+    image: String,
+    imagewidth: usize,
+    imageheight: usize,
+    tilewidth: usize,
+    tileheight: usize,
+    tilecount: usize,
+    columns: usize,
+    margin: usize,
+    spacing: usize,
+}
+
+/// A custom property of a map, used for the resource ids it was made from.
+#[derive(Debug, Serialize)]
+struct Property {
+    name: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    value: u32,
+}
+
+impl Map {
+    /// A map of the room with the screen in the image `backdrop`.
+    pub fn new(backdrop: &str) -> Map {
+        let mut map = Map {
+            kind: "map",
+            version: FORMAT_VERSION,
+            orientation: "orthogonal",
+            renderorder: "right-down",
+            infinite: false,
+            width: MAP_WIDTH,
+            height: MAP_HEIGHT,
+            tilewidth: GRID_BLOCK,
+            tileheight: GRID_BLOCK,
+            nextlayerid: 1,
+            nextobjectid: 1,
+            layers: Vec::new(),
+            tilesets: Vec::new(),
+            properties: Vec::new(),
+        };
+        map.add_image_layer("screen", backdrop, 0, 0);
+        map
+    }
+
+    /// Adds a layer showing `image` with its top left corner at (`x`, `y`)
+    /// in pixels, over the layers before it.
+    pub fn add_image_layer(&mut self, name: &str, image: &str, x: i32, y: i32) {
+        let id = self.next_layer_id();
+        self.layers.push(Layer::ImageLayer {
+            id,
+            name: name.to_owned(),
+            image: image.to_owned(),
+            x: 0,
+            y: 0,
+            offsetx: x,
+            offsety: y,
+            opacity: 1.0,
+            visible: true,
+        });
+    }
+
+    /// Adds `grid` as a tile layer over the layers before it, blocked cells
+    /// showing the single tile of the image `tile`, which is
+    /// [`GRID_BLOCK`] pixels square.
+    pub fn add_grid(&mut self, grid: &Grid, tile: &str) {
+        let firstgid = 1;
+        self.tilesets.push(Tileset {
+            firstgid,
+            name: "walk grid".to_owned(),
+            image: tile.to_owned(),
+            imagewidth: GRID_BLOCK,
+            imageheight: GRID_BLOCK,
+            tilewidth: GRID_BLOCK,
+            tileheight: GRID_BLOCK,
+            tilecount: 1,
+            columns: 1,
+            margin: 0,
+            spacing: 0,
+        });
+
+        // The grid stops short of the bottom of the screen, leaving the
+        // rows below it empty.
+        let data = (0..MAP_HEIGHT)
+            .flat_map(|y| (0..MAP_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let blocked = x < GRID_WIDTH && y < GRID_HEIGHT && grid.is_blocked(x, y);
+                if blocked {
+                    firstgid
+                } else {
+                    0
+                }
+            })
+            .collect();
+
+        let id = self.next_layer_id();
+        self.layers.push(Layer::TileLayer {
+            id,
+            name: "walk grid".to_owned(),
+            x: 0,
+            y: 0,
+            width: MAP_WIDTH,
+            height: MAP_HEIGHT,
+            data,
+            opacity: GRID_OPACITY,
+            visible: true,
+        });
+    }
+
+    /// Records the resource a part of the map was made from as an integer
+    /// property, such as `screen` or `palette`.
+    pub fn add_resource(&mut self, name: &str, id: u16) {
+        self.properties.push(Property {
+            name: name.to_owned(),
+            kind: "int",
+            value: id as u32,
+        });
+    }
+
+    fn next_layer_id(&mut self) -> usize {
+        let id = self.nextlayerid;
+        self.nextlayerid += 1;
+        id
+    }
+}
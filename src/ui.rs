@@ -0,0 +1,86 @@
+//! The graphics of the game's user interface: the control panel with its
+//! buttons, sliders and save panel, the mouse pointers and the fonts.
+//!
+//! These live at fixed ids. The panel sprites are drawn with a palette of
+//! their own, set when the panel opens, rather than that of the room
+//! behind it; the pointers have none, so they are shown with it too. The
+//! names are those ScummVM gives the resources.
+
+use serde::Serialize;
+
+use crate::{cursor, font, version::Version};
+
+/// The palette of the control panel, the first in releases before
+/// v0.0331 and the second from then on.
+const OLD_PANEL_PALETTE: u16 = 60509;
+const PANEL_PALETTE: u16 = 60510;
+
+/// The first release with the music volume sprite, which took over the id
+/// of the old panel palette. Earlier releases have no sprite there.
+const MUSIC_BODGE_VERSION: u16 = 368;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UiKind {
+    Sprite,
+    Font,
+}
+
+/// A resource of the user interface.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct UiElement {
+    pub id: u16,
+    pub name: &'static str,
+    pub kind: UiKind,
+}
+
+const fn sprite(id: u16, name: &'static str) -> UiElement {
+    UiElement {
+        id,
+        name,
+        kind: UiKind::Sprite,
+    }
+}
+
+const fn font(id: u16, name: &'static str) -> UiElement {
+    UiElement {
+        id,
+        name,
+        kind: UiKind::Font,
+    }
+}
+
+pub const ELEMENTS: &[UiElement] = &[
+    sprite(cursor::MICE_FILE, "pointers"),
+    sprite(cursor::OBJECT_MICE_FILE, "object-pointers"),
+    sprite(60500, "control-panel"),
+    sprite(60501, "button"),
+    sprite(60502, "button-down"),
+    sprite(60503, "save-panel"),
+    sprite(60504, "yes-no"),
+    sprite(60505, "slide"),
+    sprite(60506, "slode"),
+    sprite(60507, "slode2"),
+    sprite(60508, "slide2"),
+    sprite(60509, "music-bodge"),
+    font(font::CHAR_SET_FILE, "font"),
+    font(font::CONTROL_CHAR_SET_FILE, "control-font"),
+    font(font::LINK_CHAR_SET_FILE, "linc-font"),
+];
+
+/// The elements of the user interface of `version`, or of the latest
+/// releases if it is unknown. Demos may lack some of them.
+pub fn elements(version: Option<&Version>) -> impl Iterator<Item = &'static UiElement> {
+    let old = version.is_some_and(|v| v.number < MUSIC_BODGE_VERSION);
+    ELEMENTS
+        .iter()
+        .filter(move |e| !(old && e.id == OLD_PANEL_PALETTE))
+}
+
+/// The id of the palette the control panel is shown with in `version`.
+pub fn palette(version: Option<&Version>) -> u16 {
+    match version {
+        Some(v) if v.number < 331 => OLD_PANEL_PALETTE,
+        _ => PANEL_PALETTE,
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Release {
+    Floppy,
+    Cd,
+    FloppyDemo,
+    CdDemo,
+}
+
+/// A known release of the game.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    /// The version number as shown by the game, e.g. 368 for v0.0368.
+    pub number: u16,
+    pub release: Release,
+    pub description: &'static str,
+    /// Number of entries in the dinner table.
+    pub entries: u32,
+}
+
+impl Version {
+    pub fn is_cd(&self) -> bool {
+        matches!(self.release, Release::Cd | Release::CdDemo)
+    }
+
+    pub fn is_demo(&self) -> bool {
+        matches!(self.release, Release::FloppyDemo | Release::CdDemo)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (v0.{:04})", self.description, self.number)
+    }
+}
+
+/// The releases that can be told apart by the size of their dinner table.
+pub const KNOWN_VERSIONS: &[Version] = &[
+    Version {
+        number: 109,
+        release: Release::FloppyDemo,
+        description: "PC Gamer demo",
+        entries: 243,
+    },
+    Version {
+        number: 267,
+        release: Release::FloppyDemo,
+        description: "floppy demo",
+        entries: 247,
+    },
+    Version {
+        number: 272,
+        release: Release::FloppyDemo,
+        description: "German floppy demo",
+        entries: 232,
+    },
+    Version {
+        number: 288,
+        release: Release::Floppy,
+        description: "floppy",
+        entries: 1404,
+    },
+    Version {
+        number: 303,
+        release: Release::Floppy,
+        description: "floppy",
+        entries: 1413,
+    },
+    Version {
+        number: 331,
+        release: Release::Floppy,
+        description: "floppy",
+        entries: 1445,
+    },
+    Version {
+        number: 365,
+        release: Release::CdDemo,
+        description: "CD demo",
+        entries: 1711,
+    },
+    Version {
+        number: 368,
+        release: Release::Cd,
+        description: "CD",
+        entries: 5099,
+    },
+    Version {
+        number: 372,
+        release: Release::Cd,
+        description: "CD",
+        entries: 5097,
+    },
+];
+
+/// Identifies the release from the number of entries in its dinner table.
+///
+/// The floppy versions 0.0331 and 0.0348 share the same directory size and
+/// are both reported as 0.0331.
+pub fn detect(entries: usize) -> Option<&'static Version> {
+    KNOWN_VERSIONS
+        .iter()
+        .find(|v| v.entries as usize == entries)
+}
@@ -0,0 +1,519 @@
+//! A small Vorbis encoder, for engines and players that expect `.ogg` files
+//! to hold Vorbis, as Godot does.
+//!
+//! Every block is a long one of 2048 samples. Its floor is flat, at the
+//! level that brings the loudest coefficient of the block to
+//! [`MAX_RESIDUE`], and the residue is the coefficients quantized to steps
+//! of that level, coded in partitions of [`PARTITION_SIZE`] with a Huffman
+//! code made for the file. Partitions that quantize to nothing cost a bit,
+//! and blocks that do a byte. That falls well short of what a real
+//! encoder achieves, with no psychoacoustic model at all, and the files are
+//! not much smaller than the 8-bit samples, but the noise it adds is about
+//! 40 dB below the sound, close to the noise the 8-bit samples already
+//! have. It is meant for engines that can't read anything else. Loops
+//! are recorded as `LOOPSTART` and `LOOPLENGTH` comments, as for FLAC.
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    f64::consts::PI,
+    io::{self, Write},
+};
+
+use crate::{audio::Sound, ogg::OggWriter};
+
+/// Samples in a block, as a power of two.
+const BLOCK_SIZE_EXP: u32 = 11;
+/// Samples in a block.
+const BLOCK_SIZE: usize = 1 << BLOCK_SIZE_EXP;
+/// Samples a block moves on from the one before, and coefficients in it.
+const HOP: usize = BLOCK_SIZE / 2;
+/// Exponent of the size of short blocks, which are declared but not used.
+const SHORT_BLOCK_SIZE_EXP: u32 = 8;
+
+/// The largest residue value, in steps of the floor.
+const MAX_RESIDUE: i32 = 255;
+/// Entries of the residue codebook, one for each value from
+/// `-MAX_RESIDUE` to `MAX_RESIDUE`.
+const RESIDUE_ENTRIES: usize = 2 * MAX_RESIDUE as usize + 1;
+/// Coefficients in a residue partition.
+const PARTITION_SIZE: usize = 16;
+
+/// Bits of the floor's range: its two points are at 0 and `1 << 10`, the
+/// end of the coefficients of a long block.
+const FLOOR_RANGE_BITS: u32 = 10;
+/// Bits of a floor value, with a multiplier of 1.
+const FLOOR_VALUE_BITS: u32 = 8;
+
+const CODEBOOK_SYNC: u64 = 0x56_4342;
+/// The codebook of the classification of partitions: silent or coded.
+const CLASS_BOOK: u64 = 0;
+/// The codebook of the residue values.
+const RESIDUE_BOOK: u64 = 1;
+
+const PACKET_IDENTIFICATION: u8 = 1;
+const PACKET_COMMENT: u8 = 3;
+const PACKET_SETUP: u8 = 5;
+
+const VENDOR: &str = concat!("beneath-a-steel-sky-extract ", env!("CARGO_PKG_VERSION"));
+
+/// Writes `sound` as Vorbis in an Ogg container.
+pub fn write_vorbis<W: Write>(w: &mut W, sound: &Sound) -> io::Result<()> {
+    let blocks = blocks(sound);
+    let lengths = huffman_lengths(&residue_counts(&blocks));
+
+    let mut ogg = OggWriter::new(w);
+    ogg.page(&identification_header(sound), 0, false)?;
+    ogg.page(&comment_header(sound), 0, false)?;
+    ogg.page(&setup_header(&lengths), 0, false)?;
+
+    let codewords = codewords(&lengths);
+    let len = sound.samples.len() as u64;
+    for (n, block) in blocks.iter().enumerate() {
+        // The first block only overlaps the second, and each after that
+        // completes the samples up to its middle.
+        let granule = (n * HOP) as u64;
+        ogg.page(
+            &audio_packet(block.as_ref(), &codewords),
+            granule.min(len),
+            n == blocks.len() - 1,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn identification_header(sound: &Sound) -> Vec<u8> {
+    let mut packet = vec![PACKET_IDENTIFICATION];
+    packet.extend_from_slice(b"vorbis");
+    packet.extend_from_slice(&0u32.to_le_bytes()); // version
+    packet.push(1); // channels
+    packet.extend_from_slice(&sound.sample_rate.to_le_bytes());
+    packet.extend_from_slice(&[0; 12]); // bitrates, unset
+    packet.push((BLOCK_SIZE_EXP << 4 | SHORT_BLOCK_SIZE_EXP) as u8);
+    packet.push(1); // framing
+    packet
+}
+
+fn comment_header(sound: &Sound) -> Vec<u8> {
+    let mut comments = Vec::new();
+    if let Some(loop_start) = sound.loop_start {
+        comments.push(format!("LOOPSTART={loop_start}"));
+        comments.push(format!("LOOPLENGTH={}", sound.samples.len() - loop_start));
+    }
+
+    let mut packet = vec![PACKET_COMMENT];
+    packet.extend_from_slice(b"vorbis");
+    packet.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    packet.extend_from_slice(VENDOR.as_bytes());
+    packet.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        packet.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        packet.extend_from_slice(comment.as_bytes());
+    }
+    packet.push(1); // framing
+    packet
+}
+
+/// The codebooks, a flat floor, a residue of two classes of partition, a
+/// mapping of the one channel and a mode of long blocks.
+fn setup_header(residue_lengths: &[u8]) -> Vec<u8> {
+    let mut w = BitPacker::new();
+    w.write(PACKET_SETUP as u64, 8);
+    for &b in b"vorbis" {
+        w.write(b as u64, 8);
+    }
+
+    w.write(2 - 1, 8); // codebooks
+    write_codebook(&mut w, &[1, 1], None);
+    let values: Vec<u64> = (0..RESIDUE_ENTRIES as u64).collect();
+    write_codebook(
+        &mut w,
+        residue_lengths,
+        Some((-MAX_RESIDUE as f64, 1.0, &values)),
+    );
+
+    w.write(0, 6); // one time domain transform, a placeholder
+    w.write(0, 16);
+
+    w.write(0, 6); // one floor
+    w.write(1, 16); // type 1
+    w.write(0, 5); // partitions, leaving only the two end points
+    w.write(0, 2); // multiplier of one
+    w.write(FLOOR_RANGE_BITS as u64, 4);
+
+    w.write(0, 6); // one residue
+    w.write(1, 16); // type 1
+    w.write(0, 24); // begin
+    w.write(HOP as u64, 24); // end
+    w.write(PARTITION_SIZE as u64 - 1, 24);
+    w.write(2 - 1, 6); // classifications
+    w.write(CLASS_BOOK, 8);
+    w.write(0b000, 3); // silent partitions have no books
+    w.write(0, 1);
+    w.write(0b001, 3); // coded partitions have one, in the first pass
+    w.write(0, 1);
+    w.write(RESIDUE_BOOK, 8);
+
+    w.write(0, 6); // one mapping
+    w.write(0, 16); // type 0
+    w.write(0, 1); // one submap
+    w.write(0, 1); // no coupling
+    w.write(0, 2); // reserved
+    w.write(0, 8); // time domain transform, unused
+    w.write(0, 8); // floor
+    w.write(0, 8); // residue
+
+    w.write(0, 6); // one mode
+    w.write(1, 1); // long blocks
+    w.write(0, 16); // window type
+    w.write(0, 16); // transform type
+    w.write(0, 8); // mapping
+
+    w.write(1, 1); // framing
+    w.finish()
+}
+
+/// Writes a codebook of one dimension with the codeword lengths `lengths`,
+/// and if given, values from `min` in steps of `delta` for each entry.
+fn write_codebook(w: &mut BitPacker, lengths: &[u8], lookup: Option<(f64, f64, &[u64])>) {
+    w.write(CODEBOOK_SYNC, 24);
+    w.write(1, 16); // dimensions
+    w.write(lengths.len() as u64, 24);
+    w.write(0, 1); // not ordered
+    w.write(0, 1); // not sparse
+    for &len in lengths {
+        w.write(len as u64 - 1, 5);
+    }
+
+    match lookup {
+        None => w.write(0, 4),
+        Some((min, delta, values)) => {
+            let bits = values
+                .iter()
+                .max()
+                .map_or(1, |&v| 64 - v.leading_zeros())
+                .max(1);
+            w.write(1, 4);
+            w.write(float32_pack(min) as u64, 32);
+            w.write(float32_pack(delta) as u64, 32);
+            w.write(bits as u64 - 1, 4);
+            w.write(0, 1); // values don't accumulate
+            for &v in values {
+                w.write(v, bits);
+            }
+        }
+    }
+}
+
+/// `v` in the float format of codebooks: a 21-bit mantissa, a 10-bit
+/// exponent biased by 788, and a sign. Only whole numbers are needed.
+fn float32_pack(v: f64) -> u32 {
+    let sign = if v < 0.0 { 0x8000_0000 } else { 0 };
+    sign | 788 << 21 | v.abs() as u32
+}
+
+/// The floor value and the residue of a block, or `None` for silence.
+type Block = Option<(u8, Vec<i32>)>;
+
+fn audio_packet(block: Option<&(u8, Vec<i32>)>, codewords: &[(u32, u8)]) -> Vec<u8> {
+    let mut w = BitPacker::new();
+    w.write(0, 1); // audio
+    w.write(1, 1); // the previous and next blocks are long too
+    w.write(1, 1);
+
+    let Some((floor, residue)) = block else {
+        w.write(0, 1); // floor unused, so no residue either
+        return w.finish();
+    };
+    w.write(1, 1);
+    w.write(*floor as u64, FLOOR_VALUE_BITS);
+    w.write(*floor as u64, FLOOR_VALUE_BITS);
+
+    for partition in residue.chunks(PARTITION_SIZE) {
+        let coded = partition.iter().any(|&r| r != 0);
+        w.write(coded as u64, 1);
+        if coded {
+            for &r in partition {
+                let (codeword, len) = codewords[(r + MAX_RESIDUE) as usize];
+                w.write_codeword(codeword, len);
+            }
+        }
+    }
+
+    w.finish()
+}
+
+/// The blocks of `sound`, the first starting half a block before it and
+/// the last ending at least half a block after it.
+fn blocks(sound: &Sound) -> Vec<Block> {
+    let scale = 1.0 / (1 << (sound.bits_per_sample - 1)) as f64;
+    let window: Vec<f64> = (0..BLOCK_SIZE)
+        .map(|n| {
+            let x = ((n as f64 + 0.5) / BLOCK_SIZE as f64 * PI).sin();
+            (PI / 2.0 * x * x).sin()
+        })
+        .collect();
+    let mdct = Mdct::new();
+
+    let count = sound.samples.len().div_ceil(HOP) + 1;
+    (0..count)
+        .map(|n| {
+            let start = (n * HOP) as isize - HOP as isize;
+            let input: Vec<f64> = (0..BLOCK_SIZE)
+                .map(|i| {
+                    let pos = start + i as isize;
+                    let sample = usize::try_from(pos)
+                        .ok()
+                        .and_then(|pos| sound.samples.get(pos))
+                        .map_or(0.0, |&s| s as f64 * scale);
+                    sample * window[i]
+                })
+                .collect();
+            quantize(&mdct.forward(&input))
+        })
+        .collect()
+}
+
+/// The flat floor that fits the loudest of `coefficients` into
+/// [`MAX_RESIDUE`] steps, and the coefficients in those steps.
+fn quantize(coefficients: &[f64]) -> Block {
+    let peak = coefficients
+        .iter()
+        .fold(0.0, |peak: f64, c| peak.max(c.abs()));
+    if peak == 0.0 {
+        return None;
+    }
+    let floor = (0..=u8::MAX)
+        .find(|&y| floor_amplitude(y) * MAX_RESIDUE as f64 >= peak)
+        .unwrap_or(u8::MAX);
+
+    let step = floor_amplitude(floor);
+    let residue: Vec<i32> = coefficients
+        .iter()
+        .map(|c| ((c / step).round() as i32).clamp(-MAX_RESIDUE, MAX_RESIDUE))
+        .collect();
+    residue.iter().any(|&r| r != 0).then_some((floor, residue))
+}
+
+/// The amplitude a floor value stands for, from the decibel table of
+/// floor type 1, which runs from about -140 dB to 0 dB in equal steps.
+fn floor_amplitude(y: u8) -> f64 {
+    const LOWEST: f64 = 1.064_986_3e-7;
+    LOWEST.powf((u8::MAX - y) as f64 / u8::MAX as f64)
+}
+
+/// How often each residue value is coded, with one added to each so every
+/// value has a codeword.
+fn residue_counts(blocks: &[Block]) -> Vec<u64> {
+    let mut counts = vec![1; RESIDUE_ENTRIES];
+    for (_, residue) in blocks.iter().flatten() {
+        for partition in residue.chunks(PARTITION_SIZE) {
+            if partition.iter().any(|&r| r != 0) {
+                for &r in partition {
+                    counts[(r + MAX_RESIDUE) as usize] += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// The lengths of a Huffman code for symbols seen `counts` times. Counts
+/// are evened out until no codeword is longer than the 32 bits a codebook
+/// allows.
+fn huffman_lengths(counts: &[u64]) -> Vec<u8> {
+    let mut counts = counts.to_vec();
+    loop {
+        let mut heap: BinaryHeap<_> = counts
+            .iter()
+            .enumerate()
+            .map(|(n, &count)| Reverse((count, vec![n])))
+            .collect();
+        let mut lengths = vec![0u8; counts.len()];
+        while heap.len() > 1 {
+            let Reverse((a, mut left)) = heap.pop().unwrap();
+            let Reverse((b, right)) = heap.pop().unwrap();
+            left.extend(right);
+            for &n in &left {
+                lengths[n] += 1;
+            }
+            heap.push(Reverse((a + b, left)));
+        }
+
+        if lengths.iter().all(|&len| len <= 32) {
+            return lengths;
+        }
+        counts.iter_mut().for_each(|c| *c = c.isqrt().max(1));
+    }
+}
+
+/// The codewords of the entries of a codebook with the codeword lengths
+/// `lengths`, most significant bit first. Entries take the lowest codeword
+/// of their length still free, in order, as decoders assign them.
+fn codewords(lengths: &[u8]) -> Vec<(u32, u8)> {
+    // The lowest free codeword of each length, aligned to the top bit.
+    let mut available = [0u32; 33];
+    let mut codewords = Vec::with_capacity(lengths.len());
+    for (n, &len) in lengths.iter().enumerate() {
+        let len = len as usize;
+        if n == 0 {
+            for (i, free) in available.iter_mut().enumerate().take(len + 1).skip(1) {
+                *free = 1 << (32 - i);
+            }
+            codewords.push((0, len as u8));
+            continue;
+        }
+
+        let from = (1..=len).rev().find(|&i| available[i] != 0).unwrap_or(0);
+        let codeword = available[from];
+        available[from] = 0;
+        for (i, free) in available
+            .iter_mut()
+            .enumerate()
+            .take(len + 1)
+            .skip(from + 1)
+        {
+            *free = codeword + (1 << (32 - i));
+        }
+        codewords.push((codeword, len as u8));
+    }
+    codewords
+}
+
+/// The modified discrete cosine transform of a block, computed through a
+/// complex FFT of an eighth of its size.
+struct Mdct {
+    /// `e^(-iπ(n + 1/4) / M)` for each `n` of the FFT, where `M` is the
+    /// number of coefficients.
+    twiddle: Vec<(f64, f64)>,
+}
+
+impl Mdct {
+    const COEFFICIENTS: usize = HOP;
+    const FFT_SIZE: usize = HOP / 2;
+
+    fn new() -> Mdct {
+        let m = Self::COEFFICIENTS as f64;
+        let twiddle = (0..Self::FFT_SIZE)
+            .map(|n| {
+                let angle = -PI * (n as f64 + 0.25) / m;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        Mdct { twiddle }
+    }
+
+    /// The coefficients of a windowed block of [`BLOCK_SIZE`] samples,
+    /// scaled so the decoder's inverse transform brings the samples back.
+    fn forward(&self, x: &[f64]) -> Vec<f64> {
+        let m = Self::COEFFICIENTS;
+        let h = m / 2;
+
+        // Fold the block into the input of a DCT-IV of half its size.
+        let mut u = vec![0.0; m];
+        for n in 0..h {
+            u[n] = -x[3 * h - 1 - n] - x[3 * h + n];
+            u[h + n] = x[n] - x[m - 1 - n];
+        }
+
+        let mut z: Vec<(f64, f64)> = (0..Self::FFT_SIZE)
+            .map(|n| mul((u[2 * n], u[m - 1 - 2 * n]), self.twiddle[n]))
+            .collect();
+        fft(&mut z);
+
+        let scale = 2.0 / m as f64;
+        let mut out = vec![0.0; m];
+        for k in 0..Self::FFT_SIZE {
+            let (re, im) = mul(z[k], self.twiddle[k]);
+            out[2 * k] = re * scale;
+            out[m - 1 - 2 * k] = -im * scale;
+        }
+        out
+    }
+}
+
+fn mul(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+/// An in-place radix-2 FFT of a power of two of complex values.
+fn fft(data: &mut [(f64, f64)]) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        let step = (angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let mut w = (1.0, 0.0);
+            for i in 0..len / 2 {
+                let a = data[start + i];
+                let b = mul(data[start + i + len / 2], w);
+                data[start + i] = (a.0 + b.0, a.1 + b.1);
+                data[start + i + len / 2] = (a.0 - b.0, a.1 - b.1);
+                w = mul(w, step);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Packs bits the way Vorbis reads them, from the least significant bit of
+/// each byte up.
+struct BitPacker {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitPacker {
+    fn new() -> Self {
+        BitPacker {
+            bytes: Vec::new(),
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    /// Writes the low `n` bits of `v`, least significant first.
+    fn write(&mut self, v: u64, n: u32) {
+        for i in 0..n {
+            self.acc |= ((v >> i) & 1) << self.bits;
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    /// Writes a codeword of `len` bits aligned to the top bit of
+    /// `codeword`, in the order a decoder walks the tree.
+    fn write_codeword(&mut self, codeword: u32, len: u8) {
+        for i in 0..len as u32 {
+            self.write((codeword >> (31 - i)) as u64 & 1, 1);
+        }
+    }
+
+    /// Pads the last byte with zero bits.
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
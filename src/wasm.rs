@@ -0,0 +1,59 @@
+//! Bindings for JavaScript, for viewing resources in a browser with the
+//! library built for WebAssembly. Only built with the `wasm` feature.
+//!
+//! There are no files to open there, so the archive is made from the
+//! contents of `sky.dnr` and `sky.dsk`, as read by the page. Resources
+//! come back as `Uint8Array`s of their decoded data and headers as JSON.
+
+use std::io::Cursor;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{archive::Archive, classify::classify, rnc_decompress};
+
+#[wasm_bindgen]
+pub struct WasmArchive(Archive<Cursor<Vec<u8>>>);
+
+#[wasm_bindgen]
+impl WasmArchive {
+    /// Opens the archive of the contents of `sky.dnr` and `sky.dsk`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(dnr: &[u8], dsk: Vec<u8>) -> Result<WasmArchive, JsError> {
+        Ok(WasmArchive(Archive::from_bytes(dnr, dsk)?))
+    }
+
+    /// The release the archive belongs to, if known.
+    pub fn version(&self) -> Option<String> {
+        self.0.version().map(|v| v.to_string())
+    }
+
+    /// The ids of the resources in directory order.
+    #[wasm_bindgen(js_name = resourceIds)]
+    pub fn resource_ids(&self) -> Vec<u16> {
+        self.0.entries().iter().map(|e| e.number).collect()
+    }
+
+    /// The decoded data of the resource `id`, without its file header.
+    pub fn resource(&self, id: u16) -> Option<Vec<u8>> {
+        self.0.get_resource(id).map(|r| r.data)
+    }
+
+    /// The file header of the resource `id` as JSON, if it has one.
+    pub fn header(&self, id: u16) -> Option<String> {
+        let header = self.0.get_resource(id)?.header?;
+        serde_json::to_string(&header).ok()
+    }
+
+    /// The type the resource `id` is taken to be, such as `screen`.
+    #[wasm_bindgen(js_name = resourceType)]
+    pub fn resource_type(&self, id: u16) -> Option<String> {
+        let resource = self.0.get_resource(id)?;
+        classify(&resource, self.0.version()).map(|t| t.name().to_owned())
+    }
+}
+
+/// Decompresses an RNC stream, header included.
+#[wasm_bindgen(js_name = decompressRnc1)]
+pub fn decompress_rnc1(data: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(rnc_decompress::decompress_rnc1(&mut Cursor::new(data))?)
+}
@@ -0,0 +1,215 @@
+//! A small ZIP writer, for handing out a whole dump as a single file.
+//!
+//! Files are deflated, or stored if that comes out no smaller, and given
+//! a fixed modification time so the same files added in the same order
+//! always make the same archive. Entries are compressed by
+//! [`ZipEntry::new`] apart from the writer, so that can happen on several
+//! threads, and added in an order of the caller's choosing. Past 65535 files the end
+//! of the archive gets the ZIP64 records that count them; offsets and
+//! sizes beyond 4 GiB aren't supported.
+
+use std::io::{self, Write};
+
+use flate2::{write::DeflateEncoder, Compression};
+
+use crate::{bytes_ext::WriteBytesExt, checksum::crc32};
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Version 2.0, needed for deflate, and 4.5 for ZIP64.
+const VERSION: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+
+/// Bit 11 of the general purpose flags marks names as UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+
+/// 1980-01-01 00:00:00, the earliest MS-DOS date.
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// A file compressed and ready to be added to an archive.
+pub struct ZipEntry {
+    name: String,
+    method: u16,
+    crc32: u32,
+    size: u32,
+    data: Vec<u8>,
+}
+
+impl ZipEntry {
+    /// Compresses `data` as the file `name`, with `/` between directories.
+    pub fn new(name: &str, data: &[u8]) -> io::Result<ZipEntry> {
+        let size = u32::try_from(data.len()).map_err(|_| too_large(name))?;
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let deflated = encoder.finish()?;
+        let (method, stored) = if deflated.len() < data.len() {
+            (METHOD_DEFLATED, deflated)
+        } else {
+            (METHOD_STORED, data.to_vec())
+        };
+
+        Ok(ZipEntry {
+            name: name.to_owned(),
+            method,
+            crc32: crc32(data),
+            size,
+            data: stored,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The size of the file before compression.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+}
+
+/// Where an entry went, for the central directory.
+struct Written {
+    name: String,
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Writes entries one after the other, and the central directory listing
+/// them on [`finish`](ZipWriter::finish).
+pub struct ZipWriter<W: Write> {
+    w: W,
+    offset: u64,
+    written: Vec<Written>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(w: W) -> Self {
+        ZipWriter {
+            w,
+            offset: 0,
+            written: Vec::new(),
+        }
+    }
+
+    /// The number of entries added so far.
+    pub fn len(&self) -> usize {
+        self.written.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.written.is_empty()
+    }
+
+    pub fn add(&mut self, entry: ZipEntry) -> io::Result<()> {
+        let offset = u32::try_from(self.offset).map_err(|_| too_large(&entry.name))?;
+        let compressed_size =
+            u32::try_from(entry.data.len()).map_err(|_| too_large(&entry.name))?;
+
+        let mut header = Vec::with_capacity(30 + entry.name.len());
+        header.write_all(b"PK\x03\x04")?;
+        header.write_le_u16(VERSION)?;
+        header.write_le_u16(FLAG_UTF8)?;
+        header.write_le_u16(entry.method)?;
+        header.write_le_u16(DOS_TIME)?;
+        header.write_le_u16(DOS_DATE)?;
+        header.write_le_u32(entry.crc32)?;
+        header.write_le_u32(compressed_size)?;
+        header.write_le_u32(entry.size)?;
+        header.write_le_u16(entry.name.len() as u16)?;
+        header.write_le_u16(0)?; // extra field length
+        header.write_all(entry.name.as_bytes())?;
+
+        self.w.write_all(&header)?;
+        self.w.write_all(&entry.data)?;
+        self.offset += (header.len() + entry.data.len()) as u64;
+
+        self.written.push(Written {
+            name: entry.name,
+            method: entry.method,
+            crc32: entry.crc32,
+            compressed_size,
+            size: entry.size,
+            offset,
+        });
+
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let start = self.offset;
+        let mut directory = Vec::new();
+        for written in &self.written {
+            directory.write_all(b"PK\x01\x02")?;
+            directory.write_le_u16(VERSION)?; // made by
+            directory.write_le_u16(VERSION)?; // needed to extract
+            directory.write_le_u16(FLAG_UTF8)?;
+            directory.write_le_u16(written.method)?;
+            directory.write_le_u16(DOS_TIME)?;
+            directory.write_le_u16(DOS_DATE)?;
+            directory.write_le_u32(written.crc32)?;
+            directory.write_le_u32(written.compressed_size)?;
+            directory.write_le_u32(written.size)?;
+            directory.write_le_u16(written.name.len() as u16)?;
+            directory.write_le_u16(0)?; // extra field length
+            directory.write_le_u16(0)?; // comment length
+            directory.write_le_u16(0)?; // disk number
+            directory.write_le_u16(0)?; // internal attributes
+            directory.write_le_u32(0)?; // external attributes
+            directory.write_le_u32(written.offset)?;
+            directory.write_all(written.name.as_bytes())?;
+        }
+        self.w.write_all(&directory)?;
+
+        let end = start + directory.len() as u64;
+        let start = u32::try_from(start).map_err(|_| too_large("the central directory"))?;
+        let count = self.written.len();
+
+        if count > 0xffff {
+            self.w.write_all(b"PK\x06\x06")?;
+            self.w.write_all(&44u64.to_le_bytes())?; // size of the rest of the record
+            self.w.write_le_u16(VERSION_ZIP64)?;
+            self.w.write_le_u16(VERSION_ZIP64)?;
+            self.w.write_le_u32(0)?; // this disk
+            self.w.write_le_u32(0)?; // disk with the central directory
+            self.w.write_all(&(count as u64).to_le_bytes())?;
+            self.w.write_all(&(count as u64).to_le_bytes())?;
+            self.w.write_all(&(directory.len() as u64).to_le_bytes())?;
+            self.w.write_all(&(start as u64).to_le_bytes())?;
+
+            self.w.write_all(b"PK\x06\x07")?;
+            self.w.write_le_u32(0)?; // disk with the ZIP64 end record
+            self.w.write_all(&end.to_le_bytes())?;
+            self.w.write_le_u32(1)?; // number of disks
+        }
+
+        let count = count.min(0xffff) as u16;
+        self.w.write_all(b"PK\x05\x06")?;
+        self.w.write_le_u16(0)?; // this disk
+        self.w.write_le_u16(0)?; // disk with the central directory
+        self.w.write_le_u16(count)?;
+        self.w.write_le_u16(count)?;
+        self.w.write_le_u32(directory.len() as u32)?;
+        self.w.write_le_u32(start)?;
+        self.w.write_le_u16(0)?; // comment length
+
+        Ok(self.w)
+    }
+}
+
+fn too_large(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{name} is past the 4 GiB a ZIP file can hold without ZIP64"),
+    )
+}
@@ -0,0 +1,71 @@
+use beneath_a_steel_sky_extract::compact::{turn_frames, AnimSequence, AnimStep, TurnTable};
+
+fn frame(x: u16, y: u16, frame: u16, cycles: usize) -> AnimStep {
+    AnimStep::Frame {
+        x,
+        y,
+        frame,
+        cycles,
+    }
+}
+
+#[test]
+fn anim_sequences_merge_repeated_frames() {
+    let data = [
+        &[40 << 6][..],
+        &[200, 150, 0],
+        &[200, 150, 0],
+        &[202, 150, 1],
+        &[0xfffe, 12, 100],
+        &[0xffff, 0x1001, 2],
+        &[200, 150, 0],
+        &[0],
+    ]
+    .concat();
+    let anim = AnimSequence::decode(&data).unwrap();
+
+    assert_eq!(anim.sprite, 40);
+    assert_eq!(
+        anim.steps,
+        [
+            frame(200, 150, 0, 2),
+            frame(202, 150, 1, 1),
+            AnimStep::Sound {
+                sound: 12,
+                volume: 100
+            },
+            AnimStep::Sync {
+                compact: 0x1001,
+                value: 2
+            },
+            frame(200, 150, 0, 1),
+        ]
+    );
+    assert_eq!(anim.cycles(), 4);
+}
+
+#[test]
+fn anim_sequences_must_be_terminated_steps() {
+    assert!(AnimSequence::decode(&[]).is_none());
+    assert!(AnimSequence::decode(&[40 << 6, 200, 150]).is_none());
+    assert!(AnimSequence::decode(&[(40 << 6) | 1, 0]).is_none());
+    assert!(AnimSequence::decode(&[40 << 6, 200, 150, 64, 0]).is_none());
+    assert_eq!(AnimSequence::decode(&[40 << 6, 0]).unwrap().steps, []);
+}
+
+#[test]
+fn turn_tables_list_the_turns_from_each_direction() {
+    let mut data = [0; 25];
+    data[1] = 0x0003;
+    data[13] = 0x0004;
+    let table = TurnTable::decode(&data).unwrap();
+
+    assert_eq!(table.turns[0][1], 0x0003);
+    assert_eq!(table.turns[2][3], 0x0004);
+    assert!(TurnTable::decode(&data[..24]).is_none());
+
+    assert_eq!(
+        turn_frames(&[(40 << 6) | 3, (40 << 6) | 4, 0, 7]),
+        [(40, 3), (40, 4)]
+    );
+}
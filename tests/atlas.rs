@@ -0,0 +1,73 @@
+use beneath_a_steel_sky_extract::{
+    atlas::{pack, trim, Placement},
+    sprite::Frame,
+};
+
+fn frame(width: usize, height: usize, color: u8) -> Frame {
+    Frame {
+        width,
+        height,
+        data: vec![color; width * height],
+    }
+}
+
+#[test]
+fn frames_are_trimmed_to_their_visible_pixels() {
+    let mut frame = frame(6, 5, 0);
+    frame.data[6 + 2] = 7;
+    frame.data[3 * 6 + 4] = 9;
+
+    let trimmed = trim(&frame, 0);
+    assert_eq!((trimmed.x, trimmed.y), (2, 1));
+    assert_eq!((trimmed.frame.width, trimmed.frame.height), (3, 3));
+    assert_eq!(trimmed.frame.data, [7, 0, 0, 0, 0, 0, 0, 0, 9]);
+
+    let empty = trim(&self::frame(4, 4, 0), 0);
+    assert!(empty.frame.data.is_empty());
+}
+
+#[test]
+fn frames_pack_into_atlases_without_overlapping() {
+    let frames = [frame(4, 2, 1), frame(3, 5, 2), frame(4, 4, 3)];
+    let (atlases, placements) = pack(&frames, 16, 0).unwrap();
+    assert_eq!(atlases.len(), 1);
+
+    // Tallest first, with a pixel of padding around each frame, and the
+    // last on a shelf of its own as it doesn't fit beside the others.
+    assert_eq!(
+        placements,
+        [
+            Placement {
+                atlas: 0,
+                x: 1,
+                y: 8
+            },
+            Placement {
+                atlas: 0,
+                x: 1,
+                y: 1
+            },
+            Placement {
+                atlas: 0,
+                x: 6,
+                y: 1
+            },
+        ]
+    );
+    let atlas = &atlases[0];
+    assert_eq!((atlas.width, atlas.height), (11, 11));
+    for (frame, placement) in frames.iter().zip(&placements) {
+        let corner = atlas.data[placement.y * atlas.width + placement.x];
+        assert_eq!(corner, frame.data[0]);
+    }
+}
+
+#[test]
+fn frames_spill_over_into_further_atlases() {
+    let frames = [frame(6, 6, 1), frame(6, 6, 2), frame(6, 6, 3)];
+    let (atlases, placements) = pack(&frames, 10, 0).unwrap();
+    assert_eq!(atlases.len(), 3);
+    assert_eq!(placements[2].atlas, 2);
+
+    assert!(pack(&[frame(10, 1, 1)], 10, 0).is_none());
+}
@@ -0,0 +1,86 @@
+use beneath_a_steel_sky_extract::{
+    archive::Entry,
+    classify::{audio_evidence, classify, AudioEvidence},
+    Header, Resource, ResourceType,
+};
+
+fn resource(
+    number: u16,
+    x: u16,
+    width: u16,
+    height: u16,
+    n_sprites: u16,
+    data: Vec<u8>,
+) -> Resource {
+    Resource {
+        entry: Entry {
+            number,
+            offset: 0,
+            size: 0,
+            has_file_header: true,
+            uses_file_header: true,
+        },
+        header: Some(Header {
+            flags: 0,
+            x,
+            y: 0,
+            width,
+            height,
+            sp_size: 0,
+            tot_size: 0,
+            n_sprites,
+            offset_x: 0,
+            offset_y: 0,
+            compressed_size: 0,
+        }),
+        data,
+    }
+}
+
+/// A slow wave around silence.
+fn samples(len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (0x80 as f64 + 40.0 * (i as f64 / 20.0).sin()) as u8)
+        .collect()
+}
+
+/// Pixels jumping all over the range.
+fn noise(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 97 % 251) as u8).collect()
+}
+
+#[test]
+fn flagged_sounds_are_audio() {
+    let sound = resource(100, 0x8000, 0, 0, 0, noise(3000));
+    assert_eq!(
+        audio_evidence(&sound, None),
+        Some(AudioEvidence::HeaderFlag)
+    );
+    assert_eq!(classify(&sound, None), Some(ResourceType::Audio));
+}
+
+#[test]
+fn flagged_sprites_stay_sprites() {
+    let sprite = resource(100, 0x8010, 16, 16, 2, noise(512));
+    assert_eq!(audio_evidence(&sprite, None), None);
+    assert_eq!(classify(&sprite, None), Some(ResourceType::Animation));
+}
+
+#[test]
+fn unflagged_samples_are_audio() {
+    let sound = resource(100, 0, 0, 0, 0, samples(3000));
+    assert_eq!(audio_evidence(&sound, None), Some(AudioEvidence::Samples));
+    assert_eq!(classify(&sound, None), Some(ResourceType::Audio));
+
+    let short = resource(100, 0, 0, 0, 0, samples(100));
+    assert_eq!(audio_evidence(&short, None), None);
+    let other = resource(100, 0, 0, 0, 0, noise(3000));
+    assert_eq!(audio_evidence(&other, None), None);
+}
+
+#[test]
+fn speech_is_found_by_id() {
+    let speech = resource(50001, 0, 0, 0, 0, noise(3000));
+    assert_eq!(audio_evidence(&speech, None), Some(AudioEvidence::SpeechId));
+    assert_eq!(classify(&speech, None), Some(ResourceType::Speech));
+}
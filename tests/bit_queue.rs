@@ -0,0 +1,86 @@
+use std::io::{Cursor, Read};
+
+use beneath_a_steel_sky_extract::{BitQueue, ReadBytesExt};
+
+#[test]
+fn reads_least_significant_bits_first() {
+    let mut r = Cursor::new([0b1010_0110, 0b0000_0001]);
+    let mut q = BitQueue::new();
+
+    assert_eq!(q.read_bits(&mut r, 1).unwrap(), 0);
+    assert_eq!(q.read_bits(&mut r, 2).unwrap(), 0b11);
+    assert_eq!(q.read_bits(&mut r, 5).unwrap(), 0b10100);
+    assert_eq!(q.read_bits(&mut r, 8).unwrap(), 1);
+    assert!(q.is_empty());
+}
+
+#[test]
+fn reads_words_little_endian() {
+    let mut r = Cursor::new([0x34, 0x12, 0x78, 0x56]);
+    let mut q = BitQueue::new();
+
+    assert_eq!(q.read_bits(&mut r, 16).unwrap(), 0x1234);
+    assert_eq!(q.read_bits(&mut r, 4).unwrap(), 0x8);
+    assert_eq!(q.len(), 12);
+    assert_eq!(q.read_bits(&mut r, 12).unwrap(), 0x567);
+}
+
+#[test]
+fn reads_across_words() {
+    let mut r = Cursor::new([0xff, 0xff, 0x00, 0x00]);
+    let mut q = BitQueue::new();
+
+    assert_eq!(q.read_bits(&mut r, 12).unwrap(), 0xfff);
+    assert_eq!(q.read_bits(&mut r, 8).unwrap(), 0x0f);
+}
+
+#[test]
+fn reads_zero_bits() {
+    let mut r = Cursor::new([0xff, 0xff]);
+    let mut q = BitQueue::new();
+
+    assert_eq!(q.read_bits(&mut r, 0).unwrap(), 0);
+    assert_eq!(r.position(), 0);
+}
+
+#[test]
+fn pads_short_streams_with_zeros() {
+    let mut r = Cursor::new([0xab]);
+    let mut q = BitQueue::new();
+
+    assert_eq!(q.read_bits(&mut r, 16).unwrap(), 0x00ab);
+    assert_eq!(q.read_bits(&mut r, 16).unwrap(), 0);
+}
+
+#[test]
+fn peek_does_not_take_bits() {
+    let mut r = Cursor::new([0x34, 0x12, 0x78, 0x56]);
+    let mut q = BitQueue::new();
+
+    assert_eq!(q.peek(&mut r).unwrap(), 0x1234);
+    assert_eq!(q.read_bits(&mut r, 4).unwrap(), 0x4);
+    assert_eq!(q.peek(&mut r).unwrap(), 0x8123);
+    assert_eq!(q.read_bits(&mut r, 16).unwrap(), 0x8123);
+}
+
+#[test]
+fn leaves_bytes_between_words_to_the_reader() {
+    let mut r = Cursor::new([0x01, 0x00, 0xaa, 0x02, 0x00]);
+    let mut q = BitQueue::new();
+
+    assert_eq!(q.read_bits(&mut r, 1).unwrap(), 1);
+    assert_eq!(r.read_u8().unwrap(), 0xaa);
+    assert_eq!(q.read_bits(&mut r, 15).unwrap(), 0);
+    assert_eq!(q.read_bits(&mut r, 2).unwrap(), 0b10);
+
+    let mut rest = Vec::new();
+    r.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+}
+
+#[test]
+#[should_panic]
+fn rejects_more_than_16_bits() {
+    let mut r = Cursor::new([0; 4]);
+    _ = BitQueue::new().read_bits(&mut r, 17);
+}
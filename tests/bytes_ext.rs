@@ -0,0 +1,47 @@
+use std::io::{Cursor, ErrorKind};
+
+use beneath_a_steel_sky_extract::{ReadBytesExt, WriteBytesExt};
+
+#[test]
+fn reads_in_both_byte_orders() {
+    let mut r = Cursor::new([
+        0x01, 0x02, 0x01, 0x02, 0x01, 0x02, 0x03, 0x01, 0x02, 0x03, 0x04, 0x01, 0x02, 0x03, 0x04,
+        0xfe, 0xff, 0x7f,
+    ]);
+
+    assert_eq!(r.read_le_u16().unwrap(), 0x0201);
+    assert_eq!(r.read_be_u16().unwrap(), 0x0102);
+    assert_eq!(r.read_le_u24().unwrap(), 0x03_0201);
+    assert_eq!(r.read_le_u32().unwrap(), 0x0403_0201);
+    assert_eq!(r.read_be_u32().unwrap(), 0x0102_0304);
+    assert_eq!(r.read_le_i16().unwrap(), -2);
+    assert_eq!(r.read_u8().unwrap(), 0x7f);
+}
+
+#[test]
+fn fails_on_short_input() {
+    let mut r = Cursor::new([0x01, 0x02, 0x03]);
+
+    let err = r.read_le_u32().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn writes_what_is_read() {
+    let mut w = Vec::new();
+    w.write_le_u16(0x1234).unwrap();
+    w.write_be_u16(0x1234).unwrap();
+    w.write_le_u32(0x1234_5678).unwrap();
+    w.write_be_u32(0x1234_5678).unwrap();
+
+    assert_eq!(
+        w,
+        [0x34, 0x12, 0x12, 0x34, 0x78, 0x56, 0x34, 0x12, 0x12, 0x34, 0x56, 0x78]
+    );
+
+    let mut r = Cursor::new(w);
+    assert_eq!(r.read_le_u16().unwrap(), 0x1234);
+    assert_eq!(r.read_be_u16().unwrap(), 0x1234);
+    assert_eq!(r.read_le_u32().unwrap(), 0x1234_5678);
+    assert_eq!(r.read_be_u32().unwrap(), 0x1234_5678);
+}
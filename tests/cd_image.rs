@@ -0,0 +1,118 @@
+mod common;
+
+use std::io::Cursor;
+
+use beneath_a_steel_sky_extract::{iso::IsoImage, Archive};
+use common::{sample, ArchiveBuilder, TempDir};
+
+const SECTOR: usize = 2048;
+
+/// Builds an ISO 9660 image with files in the root directory, stored one
+/// after the other from sector 20.
+fn iso(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let root_extent = 18;
+    let mut records = vec![
+        record(&[0], root_extent, SECTOR as u32, true),
+        record(&[1], root_extent, SECTOR as u32, true),
+    ];
+    let mut data = Vec::new();
+    let first = 20;
+    for &(name, contents) in files {
+        let extent = first + (data.len() / SECTOR) as u32;
+        let name = format!("{name};1");
+        records.push(record(
+            name.as_bytes(),
+            extent,
+            contents.len() as u32,
+            false,
+        ));
+        data.extend_from_slice(contents);
+        data.resize(data.len().next_multiple_of(SECTOR), 0);
+    }
+
+    let mut image = vec![0; first as usize * SECTOR];
+    let pvd = &mut image[16 * SECTOR..17 * SECTOR];
+    pvd[0] = 1;
+    pvd[1..6].copy_from_slice(b"CD001");
+    pvd[156..156 + 34].copy_from_slice(&records[0]);
+    image[17 * SECTOR] = 0xff;
+    image[17 * SECTOR + 1..17 * SECTOR + 6].copy_from_slice(b"CD001");
+    let root = records.concat();
+    image[18 * SECTOR..18 * SECTOR + root.len()].copy_from_slice(&root);
+    image.extend(data);
+    image
+}
+
+fn record(name: &[u8], extent: u32, size: u32, dir: bool) -> Vec<u8> {
+    let len = (33 + name.len()).next_multiple_of(2);
+    let mut record = vec![0; len];
+    record[0] = len as u8;
+    record[2..6].copy_from_slice(&extent.to_le_bytes());
+    record[6..10].copy_from_slice(&extent.to_be_bytes());
+    record[10..14].copy_from_slice(&size.to_le_bytes());
+    record[14..18].copy_from_slice(&size.to_be_bytes());
+    record[25] = if dir { 2 } else { 0 };
+    record[32] = name.len() as u8;
+    record[33..33 + name.len()].copy_from_slice(name);
+    record
+}
+
+/// Stores the sectors of `image` as raw mode 1 sectors, with zeroed error
+/// correction codes.
+fn raw_sectors(image: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::new();
+    for sector in image.chunks(SECTOR) {
+        raw.push(0);
+        raw.extend([0xff; 10]);
+        raw.extend([0, 0, 2, 0, 1]);
+        raw.extend_from_slice(sector);
+        raw.extend([0; 288]);
+    }
+    raw
+}
+
+#[test]
+fn files_are_read_from_iso_9660_images() {
+    let data = sample(5000, 9);
+    let image = iso(&[("README.TXT", b"hello"), ("SKY.DSK", &data)]);
+
+    for image in [image.clone(), raw_sectors(&image)] {
+        let mut iso = IsoImage::new(Cursor::new(image)).unwrap();
+        let names: Vec<_> = iso.root().unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["README.TXT", "SKY.DSK"]);
+
+        let file = iso.find("sky.dsk").unwrap().unwrap();
+        assert_eq!(iso.read(&file).unwrap(), data);
+        assert!(iso.find("sky.dnr").unwrap().is_none());
+    }
+
+    assert!(IsoImage::new(Cursor::new(vec![0; 20 * SECTOR])).is_err());
+}
+
+#[test]
+fn archives_load_from_cd_images() {
+    let dir = TempDir::new("cd");
+    let (dnr, dsk) = ArchiveBuilder::new()
+        .raw(10, b"plain")
+        .raw(50000, &sample(3000, 4))
+        .build();
+    let image = iso(&[("SKY.DNR", &dnr), ("SKY.DSK", &dsk)]);
+
+    let iso_path = dir.path().join("bass.iso");
+    std::fs::write(&iso_path, &image).unwrap();
+    let archive = Archive::load(&iso_path).unwrap();
+    let entry = archive.entry(50000).unwrap();
+    assert_eq!(archive.entry_bytes(entry), sample(3000, 4));
+
+    // A raw image is found through its cue sheet in the directory given.
+    let raw = dir.path().join("raw");
+    std::fs::create_dir(&raw).unwrap();
+    std::fs::write(raw.join("Beneath a Steel Sky.bin"), raw_sectors(&image)).unwrap();
+    std::fs::write(
+        raw.join("Beneath a Steel Sky.cue"),
+        "FILE \"Beneath a Steel Sky.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n",
+    )
+    .unwrap();
+    let archive = Archive::load(&raw).unwrap();
+    assert_eq!(archive.entries().len(), 2);
+}
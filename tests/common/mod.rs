@@ -0,0 +1,258 @@
+//! Synthetic game data for tests.
+//!
+//! The game's own data files can't be shipped with the tests, so archives
+//! are built from scratch out of made up resources. [`ArchiveBuilder`]
+//! stores them the way the game does, with or without file headers and
+//! compression, and writes the resulting `sky.dnr` and `sky.dsk` to memory
+//! or to a [`TempDir`]; [`CptBuilder`] does the same for the compacts of
+//! `sky.cpt`. Expected output too long to write out in a test is
+//! kept in `tests/fixtures` and compared with [`assert_golden`].
+
+#![allow(dead_code)]
+
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use beneath_a_steel_sky_extract::{
+    archive::Entry,
+    repack::{write_archive, RawResource},
+    resource::{write_resource, Header},
+    Archive,
+};
+
+/// Environment variable that makes [`assert_golden`] write the fixtures
+/// instead of comparing with them.
+pub const BLESS_VAR: &str = "BLESS";
+
+/// Deterministic data that compresses well in places and not at all in
+/// others, so both literals and matches get exercised.
+pub fn sample(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if (i / 700) % 2 == 0 {
+                (i % 13) as u8
+            } else {
+                (state >> 16) as u8
+            }
+        })
+        .collect()
+}
+
+/// A file header for a resource of `width` by `height` pixels and a single
+/// frame. Flags and sizes are filled in when the resource is written.
+pub fn header(width: u16, height: u16) -> Header {
+    Header {
+        flags: 0,
+        x: 0,
+        y: 0,
+        width,
+        height,
+        sp_size: width * height,
+        tot_size: 0,
+        n_sprites: 1,
+        offset_x: 0,
+        offset_y: 0,
+        compressed_size: 0,
+    }
+}
+
+/// Builds a `sky.dnr` and `sky.dsk` from resources added in any order. The
+/// resources are stored back to back in order of id, as `repack` does.
+#[derive(Default)]
+pub struct ArchiveBuilder {
+    resources: Vec<RawResource>,
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        ArchiveBuilder::default()
+    }
+
+    /// Adds a resource stored as is, without a file header.
+    pub fn raw(mut self, id: u16, data: &[u8]) -> Self {
+        self.resources.push(RawResource {
+            number: id,
+            original: None,
+            has_file_header: false,
+            uses_file_header: false,
+            data: data.to_vec(),
+        });
+        self
+    }
+
+    /// Adds a resource with a file header, RNC compressed if `compress` is
+    /// set.
+    pub fn with_header(mut self, id: u16, header: &Header, data: &[u8], compress: bool) -> Self {
+        let entry = Entry {
+            number: id,
+            offset: 0,
+            size: 0,
+            has_file_header: true,
+            uses_file_header: true,
+        };
+        self.resources.push(RawResource {
+            number: id,
+            original: None,
+            has_file_header: true,
+            uses_file_header: true,
+            data: write_resource(&entry, header, data, compress),
+        });
+        self
+    }
+
+    /// Adds a resource that is flagged as RNC compressed but doesn't
+    /// decompress, as one damaged on disk.
+    pub fn damaged(mut self, id: u16, header: &Header, data: &[u8]) -> Self {
+        self = self.with_header(id, header, data, true);
+        let stored = &mut self.resources.last_mut().unwrap().data;
+        let last = stored.len() - 1;
+        stored[last] ^= 0xff;
+        self
+    }
+
+    /// The contents of `sky.dnr` and `sky.dsk`.
+    pub fn build(mut self) -> (Vec<u8>, Vec<u8>) {
+        self.resources.sort_by_key(|r| r.number);
+
+        let mut dnr = Vec::new();
+        let mut dsk = Vec::new();
+        write_archive(&self.resources, &mut dnr, &mut dsk).unwrap();
+
+        (dnr, dsk)
+    }
+
+    /// The archive opened from memory.
+    pub fn open(self) -> Archive<Cursor<Vec<u8>>> {
+        let (dnr, dsk) = self.build();
+        Archive::new(&mut dnr.as_slice(), Cursor::new(dsk)).unwrap()
+    }
+
+    /// Writes `sky.dnr` and `sky.dsk` to `dir`, returning `dir`.
+    pub fn write(self, dir: &Path) -> PathBuf {
+        let (dnr, dsk) = self.build();
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("sky.dnr"), dnr).unwrap();
+        std::fs::write(dir.join("sky.dsk"), dsk).unwrap();
+        dir.to_path_buf()
+    }
+}
+
+/// `words` as little-endian bytes, as the game stores them.
+pub fn words(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+/// Builds a `sky.cpt` with a single list of entries, added in order of
+/// index, and the ids of the entries saved games hold.
+#[derive(Default)]
+pub struct CptBuilder {
+    len: u16,
+    src: Vec<u16>,
+    names: Vec<u8>,
+    save_ids: Vec<u16>,
+}
+
+impl CptBuilder {
+    pub fn new() -> Self {
+        CptBuilder::default()
+    }
+
+    /// Adds the next entry, of type `kind`. An empty `data` leaves the slot
+    /// empty, which still has a type and a name.
+    pub fn entry(mut self, name: &str, kind: u16, data: &[u16]) -> Self {
+        self.len += 1;
+        self.src.extend([data.len() as u16, kind]);
+        self.src.extend(data);
+        self.names.extend(name.as_bytes());
+        self.names.push(0);
+        self
+    }
+
+    /// Sets the ids of the entries saved games hold.
+    pub fn save_ids(mut self, ids: &[u16]) -> Self {
+        self.save_ids = ids.to_vec();
+        self
+    }
+
+    /// The contents of `sky.cpt`, without entries standing for others or
+    /// changes for v0.0288.
+    pub fn build(self) -> Vec<u8> {
+        let src_len = self.src.len() as u32;
+        let names_len = self.names.len() as u32;
+        [
+            words(&[0, 1, self.len]),       // version, lists and entries in the list
+            src_len.to_le_bytes().to_vec(), // size of the compacts once loaded
+            src_len.to_le_bytes().to_vec(),
+            words(&self.src),
+            names_len.to_le_bytes().to_vec(),
+            self.names,
+            words(&[0]),    // entries standing for others
+            words(&[0, 0]), // changes for v0.0288
+            words(&[self.save_ids.len() as u16]),
+            words(&self.save_ids),
+        ]
+        .concat()
+    }
+}
+
+/// A directory under the system's temporary directory, removed again when
+/// dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "bass-extract-{}-{}-{name}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Compares `actual` with the fixture `tests/fixtures/<name>`. With the
+/// environment variable [`BLESS_VAR`] set the fixture is written instead,
+/// for when the output is meant to change.
+pub fn assert_golden(name: &str, actual: &[u8]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+
+    if std::env::var_os(BLESS_VAR).is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "can't read fixture {}: {e}, run with {BLESS_VAR}=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "output differs from fixture {}, run with {BLESS_VAR}=1 to update it",
+        path.display()
+    );
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use beneath_a_steel_sky_extract::compact::{CptFile, CptType};
+use common::CptBuilder;
+
+#[test]
+fn entries_after_an_empty_slot_are_read() {
+    // `hand` and `bag` with an empty slot between them, which has a type
+    // and a name like any other.
+    let data = CptBuilder::new()
+        .entry("hand", 4, &[7])
+        .entry("", 0, &[])
+        .entry("bag", 4, &[8, 9])
+        .save_ids(&[0x0002])
+        .build();
+
+    let cpt = CptFile::read(&data).unwrap();
+    assert_eq!(cpt.entries.keys().copied().collect::<Vec<_>>(), [0, 2]);
+    assert_eq!(cpt.name(0), Some("hand"));
+    assert_eq!(cpt.entries[&0].data, [7]);
+    assert_eq!(cpt.name(2), Some("bag"));
+    assert_eq!(cpt.entries[&2].kind, CptType::MiscBinary);
+    assert_eq!(cpt.entries[&2].data, [8, 9]);
+    assert_eq!(cpt.save_ids, [0x0002]);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::process::Command;
+
+use beneath_a_steel_sky_extract::checksum::crc32;
+use common::{header, sample, ArchiveBuilder, TempDir};
+
+#[test]
+fn crc32_matches_the_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn resources_csv_has_checksums_of_each_resource() {
+    let dir = TempDir::new("csv");
+    let data = sample(3000, 1);
+    let game = ArchiveBuilder::new()
+        .raw(10, b"plain")
+        .with_header(11, &header(0, 0), &data, true)
+        .write(&dir.path().join("game"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .arg("--dump-csv")
+        .current_dir(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut rdr = csv::Reader::from_path(dir.path().join("resources.csv")).unwrap();
+    let headers = rdr.headers().unwrap().clone();
+    let column = |record: &csv::StringRecord, name: &str| {
+        let n = headers.iter().position(|h| h == name).unwrap();
+        record[n].to_owned()
+    };
+    let records: Vec<_> = rdr.records().map(|r| r.unwrap()).collect();
+
+    assert_eq!(column(&records[0], "compressed"), "false");
+    assert_eq!(column(&records[0], "stored_size"), "5");
+    assert_eq!(
+        column(&records[0], "crc32"),
+        format!("{:08x}", crc32(b"plain"))
+    );
+
+    assert_eq!(column(&records[1], "compressed"), "true");
+    assert_eq!(column(&records[1], "size"), "3000");
+    assert_eq!(
+        column(&records[1], "sha1"),
+        sha1_smol::Sha1::from(&data).digest().to_string()
+    );
+}
+
+#[test]
+fn resources_that_dont_decompress_are_reported() {
+    let dir = TempDir::new("strict");
+    let game = ArchiveBuilder::new()
+        .raw(10, b"plain")
+        .damaged(11, &header(0, 0), &sample(3000, 1))
+        .write(&dir.path().join("game"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .args(["--dump-csv", "--strict", "-o", "dump"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Resource 00011: Decompression failed"),
+        "{stderr}"
+    );
+    assert!(!dir.path().join("dump/raw/00011.dmp").exists());
+
+    let mut rdr = csv::Reader::from_path(dir.path().join("resources.csv")).unwrap();
+    let n = rdr
+        .headers()
+        .unwrap()
+        .iter()
+        .position(|h| h == "decompress_error")
+        .unwrap();
+    let errors: Vec<_> = rdr.records().map(|r| r.unwrap()[n].to_owned()).collect();
+    assert_eq!(errors[0], "");
+    assert!(!errors[1].is_empty());
+}
+
+#[test]
+fn volumes_merge_into_one_directory() {
+    let dir = TempDir::new("volumes");
+    let disk1 = ArchiveBuilder::new()
+        .raw(10, b"first")
+        .raw(12, b"shadowed")
+        .write(&dir.path().join("disk1"));
+    let disk2 = ArchiveBuilder::new()
+        .raw(11, b"second")
+        .raw(12, b"ignored")
+        .write(&dir.path().join("disk2"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&disk1)
+        .arg("--volume")
+        .arg(&disk2)
+        .args(["--dump-csv", "--dump", "raw", "-o", "dump"])
+        .current_dir(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let raw = dir.path().join("dump/raw");
+    assert_eq!(std::fs::read(raw.join("00011.dmp")).unwrap(), b"second");
+    assert_eq!(std::fs::read(raw.join("00012.dmp")).unwrap(), b"shadowed");
+
+    let mut rdr = csv::Reader::from_path(dir.path().join("resources.csv")).unwrap();
+    let n = rdr
+        .headers()
+        .unwrap()
+        .iter()
+        .position(|h| h == "volume")
+        .unwrap();
+    let volumes: Vec<_> = rdr.records().map(|r| r.unwrap()[n].to_owned()).collect();
+    assert_eq!(volumes, ["0", "1", "0"]);
+}
@@ -0,0 +1,53 @@
+use beneath_a_steel_sky_extract::cycle::{cycle_palettes, detect, CycleRange};
+
+/// A palette of colors far apart, with a ramp that rises and falls again
+/// at `first`.
+fn palette_with_ramp(first: usize, ramp: &[u8]) -> Vec<u8> {
+    let mut palette: Vec<u8> = (0..768).map(|i| ((i * 37) % 64) as u8).collect();
+    for (n, &v) in ramp.iter().enumerate() {
+        let ofs = 3 * (first + n);
+        palette[ofs..ofs + 3].copy_from_slice(&[v, v, 63]);
+    }
+    palette
+}
+
+fn range(first: u8, last: u8) -> CycleRange {
+    CycleRange {
+        screen: 20,
+        first,
+        last,
+    }
+}
+
+#[test]
+fn detects_seamless_ramps_the_screen_uses() {
+    let palette = palette_with_ramp(32, &[0, 10, 20, 30, 40, 30, 20, 10]);
+    let screen: Vec<u8> = (0..=255).collect();
+
+    assert_eq!(detect(20, &screen, &palette), [range(32, 39)]);
+}
+
+#[test]
+fn ignores_ramps_with_a_seam_or_not_used() {
+    let palette = palette_with_ramp(32, &[0, 8, 16, 24, 32, 40, 48, 56]);
+    let screen: Vec<u8> = (0..=255).collect();
+    assert!(detect(20, &screen, &palette).is_empty());
+
+    let palette = palette_with_ramp(32, &[0, 10, 20, 30, 40, 30, 20, 10]);
+    let screen: Vec<u8> = (0..=255).filter(|&c| c != 35).collect();
+    assert!(detect(20, &screen, &palette).is_empty());
+}
+
+#[test]
+fn cycles_rotate_each_range_until_they_line_up_again() {
+    let palette: Vec<u8> = (0..768).map(|i| (i / 3) as u8).collect();
+    let palettes = cycle_palettes(&palette, &[range(1, 2), range(4, 6)]);
+
+    assert_eq!(palettes.len(), 6);
+    assert_eq!(palettes[0], palette);
+    assert_eq!(
+        &palettes[1][..24],
+        &[0, 0, 0, 2, 2, 2, 1, 1, 1, 3, 3, 3, 6, 6, 6, 4, 4, 4, 5, 5, 5, 7, 7, 7]
+    );
+    assert_eq!(&palettes[1][24..], &palette[24..]);
+}
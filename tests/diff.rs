@@ -0,0 +1,66 @@
+mod common;
+
+use std::process::Command;
+
+use beneath_a_steel_sky_extract::{
+    checksum::Checksum,
+    diff::{diff, Change},
+};
+use common::{header, sample, ArchiveBuilder, TempDir};
+
+#[test]
+fn changes_are_listed_in_order_of_id() {
+    let before = [
+        Checksum::new(1, b"kept"),
+        Checksum::new(2, b"removed"),
+        Checksum::new(4, b"old"),
+    ];
+    let after = [
+        Checksum::new(4, b"new!"),
+        Checksum::new(3, b"added"),
+        Checksum::new(1, b"kept"),
+    ];
+
+    assert_eq!(
+        diff(&before, &after),
+        [
+            Change::Removed(Checksum::new(2, b"removed")),
+            Change::Added(Checksum::new(3, b"added")),
+            Change::Changed {
+                before: Checksum::new(4, b"old"),
+                after: Checksum::new(4, b"new!"),
+            },
+        ]
+    );
+    assert!(diff(&after, &after).is_empty());
+}
+
+#[test]
+fn recompressed_resources_are_the_same_once_decoded() {
+    let dir = TempDir::new("diff");
+    let data = sample(5000, 3);
+    let before = ArchiveBuilder::new()
+        .with_header(60, &header(0, 0), &data, false)
+        .raw(61, b"unchanged")
+        .write(&dir.path().join("before"));
+    let after = ArchiveBuilder::new()
+        .with_header(60, &header(0, 0), &data, true)
+        .raw(61, b"unchanged")
+        .raw(62, b"added")
+        .write(&dir.path().join("after"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("diff")
+        .args([&before, &after])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().skip(2).collect();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    assert!(lines[0].starts_with("00060  changed"), "{stdout}");
+    assert!(lines[0].ends_with("same data once decoded"), "{stdout}");
+    assert!(lines[1].starts_with("00062  added"));
+    assert_eq!(lines[2], "1 added, 0 removed, 1 changed, 1 unchanged");
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use std::process::Command;
+
+use beneath_a_steel_sky_extract::{fat::FatImage, Archive};
+use common::{sample, ArchiveBuilder, TempDir};
+
+const SECTOR: usize = 512;
+/// The layout of a 1.44 MB disk: a boot sector, two tables of nine
+/// sectors and a root directory of fourteen.
+const DATA_START: usize = 33 * SECTOR;
+const IMAGE_SIZE: usize = 2880 * SECTOR;
+
+/// Builds a 1.44 MB FAT12 image, a cluster to a sector, with files in the
+/// root directory and in subdirectories of it.
+struct Floppy {
+    image: Vec<u8>,
+    next_cluster: u16,
+}
+
+impl Floppy {
+    fn new() -> Self {
+        let mut image = vec![0; IMAGE_SIZE];
+        image[11..13].copy_from_slice(&(SECTOR as u16).to_le_bytes());
+        image[13] = 1;
+        image[14..16].copy_from_slice(&1u16.to_le_bytes());
+        image[16] = 2;
+        image[17..19].copy_from_slice(&224u16.to_le_bytes());
+        image[19..21].copy_from_slice(&2880u16.to_le_bytes());
+        image[22..24].copy_from_slice(&9u16.to_le_bytes());
+        Floppy {
+            image,
+            next_cluster: 2,
+        }
+    }
+
+    /// Stores `data` in a chain of clusters, returning the first.
+    fn store(&mut self, data: &[u8]) -> u16 {
+        if data.is_empty() {
+            return 0;
+        }
+        let first = self.next_cluster;
+        let count = data.len().div_ceil(SECTOR) as u16;
+        for (n, chunk) in data.chunks(SECTOR).enumerate() {
+            let cluster = first + n as u16;
+            let start = DATA_START + (cluster as usize - 2) * SECTOR;
+            self.image[start..start + chunk.len()].copy_from_slice(chunk);
+            let next = if n as u16 + 1 == count {
+                0xfff
+            } else {
+                cluster + 1
+            };
+            self.set_fat(cluster, next);
+        }
+        self.next_cluster += count;
+        first
+    }
+
+    fn set_fat(&mut self, cluster: u16, value: u16) {
+        let offset = SECTOR + cluster as usize * 3 / 2;
+        let pair = u16::from_le_bytes([self.image[offset], self.image[offset + 1]]);
+        let pair = if cluster & 1 == 0 {
+            (pair & 0xf000) | value
+        } else {
+            (pair & 0x000f) | (value << 4)
+        };
+        self.image[offset..offset + 2].copy_from_slice(&pair.to_le_bytes());
+    }
+
+    /// Adds the file `name` to the root directory, or to the subdirectory
+    /// `dir` of it, which is created with the file.
+    fn add(mut self, dir: Option<&str>, name: &str, data: &[u8]) -> Self {
+        let cluster = self.store(data);
+        let entry = dir_entry(name, 0x20, cluster, data.len() as u32);
+        let entry = match dir {
+            Some(dir) => {
+                let cluster = self.store(&[entry, [0; 32]].concat());
+                dir_entry(dir, 0x10, cluster, 0)
+            }
+            None => entry,
+        };
+
+        let root = (1 + 18) * SECTOR;
+        let free = (root..DATA_START)
+            .step_by(32)
+            .find(|&at| self.image[at] == 0)
+            .unwrap();
+        self.image[free..free + 32].copy_from_slice(&entry);
+        self
+    }
+}
+
+fn dir_entry(name: &str, attributes: u8, cluster: u16, size: u32) -> [u8; 32] {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    let mut entry = [0; 32];
+    entry[..11].copy_from_slice(format!("{base:<8}{ext:<3}").as_bytes());
+    entry[11] = attributes;
+    entry[26..28].copy_from_slice(&cluster.to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+#[test]
+fn files_are_read_from_fat12_images() {
+    let data = sample(3000, 7);
+    let image = Floppy::new()
+        .add(None, "README.TXT", b"")
+        .add(Some("SKY"), "SKY.DSK", &data)
+        .image;
+
+    let fat = FatImage::new(&image).unwrap();
+    let names: Vec<_> = fat.root().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, ["README.TXT", "SKY"]);
+
+    let file = fat.find("sky.dsk").unwrap().unwrap();
+    assert_eq!(file.size, 3000);
+    assert_eq!(fat.read(&file).unwrap(), data);
+    assert!(fat.find("sky.dnr").unwrap().is_none());
+
+    assert!(FatImage::new(&[0; 64]).is_err());
+}
+
+#[test]
+fn archives_load_from_a_directory_of_disk_images() {
+    let dir = TempDir::new("floppy");
+    let (dnr, dsk) = ArchiveBuilder::new()
+        .raw(10, b"plain")
+        .raw(11, &sample(2000, 3))
+        .build();
+    std::fs::write(
+        dir.path().join("disk1.img"),
+        Floppy::new().add(None, "SKY.DNR", &dnr).image,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("DISK2.IMA"),
+        Floppy::new().add(Some("SKY"), "SKY.DSK", &dsk).image,
+    )
+    .unwrap();
+
+    let archive = Archive::load(dir.path()).unwrap();
+    let entry = archive.entry(11).unwrap();
+    assert_eq!(archive.entry_bytes(entry), sample(2000, 3));
+
+    // A single image lacks `sky.dsk`.
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("list")
+        .arg(dir.path().join("disk1.img"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("sky.dsk not found"), "{stderr}");
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::{ffi::CString, ptr, slice};
+
+use beneath_a_steel_sky_extract::{ffi::*, rnc_compress::compress_rnc1};
+use common::{header, sample, ArchiveBuilder, TempDir};
+
+#[test]
+fn resources_can_be_read_through_the_c_interface() {
+    let dir = TempDir::new("ffi");
+    let data = sample(3000, 1);
+    let game = ArchiveBuilder::new()
+        .raw(10, b"plain")
+        .with_header(11, &header(16, 8), &data, true)
+        .write(&dir.path().join("game"));
+    let path = CString::new(game.to_str().unwrap()).unwrap();
+
+    unsafe {
+        let archive = sky_open_archive(path.as_ptr());
+        assert!(!archive.is_null());
+        assert_eq!(sky_resource_count(archive), 2);
+
+        let mut id = 0;
+        assert_eq!(sky_resource_id(archive, 1, &mut id), SKY_OK);
+        assert_eq!(id, 11);
+        assert_eq!(sky_resource_id(archive, 2, &mut id), SKY_ERROR_NOT_FOUND);
+
+        let (mut out, mut len) = (ptr::null_mut(), 0);
+        assert_eq!(sky_get_resource(archive, 11, &mut out, &mut len), SKY_OK);
+        assert_eq!(slice::from_raw_parts(out, len), data);
+        sky_free(out, len);
+
+        let mut h = SkyHeader::default();
+        assert_eq!(sky_get_header(archive, 11, &mut h), SKY_OK);
+        assert_eq!((h.width, h.height), (16, 8));
+        assert_eq!(sky_get_header(archive, 10, &mut h), SKY_ERROR_NO_HEADER);
+        assert_eq!(
+            sky_get_resource(archive, 12, &mut out, &mut len),
+            SKY_ERROR_NOT_FOUND
+        );
+
+        sky_close_archive(archive);
+        assert!(sky_open_archive(ptr::null()).is_null());
+    }
+}
+
+#[test]
+fn rnc_streams_decompress_through_the_c_interface() {
+    let data = sample(5000, 2);
+    let packed = compress_rnc1(&data);
+
+    unsafe {
+        let (mut out, mut len) = (ptr::null_mut(), 0);
+        assert_eq!(
+            sky_decompress_rnc1(packed.as_ptr(), packed.len(), &mut out, &mut len),
+            SKY_OK
+        );
+        assert_eq!(slice::from_raw_parts(out, len), data);
+        sky_free(out, len);
+
+        assert_eq!(
+            sky_decompress_rnc1(data.as_ptr(), data.len(), &mut out, &mut len),
+            SKY_ERROR_DECOMPRESS
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use beneath_a_steel_sky_extract::{audio::Sound, flac::write_flac};
+
+/// The samples of a FLAC file as another decoder reads them.
+fn decoded(reader: &mut claxon::FlacReader<&[u8]>) -> Vec<i32> {
+    reader.samples().map(|s| s.unwrap()).collect()
+}
+
+#[test]
+fn flac_files_decode_to_the_samples_written() {
+    // More than one block, some of it smooth enough to predict and some
+    // noise that is stored verbatim.
+    let mut state = 1u32;
+    let samples: Vec<u8> = (0..10000)
+        .map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if i < 5000 {
+                (128.0 + 100.0 * (i as f64 / 40.0).sin()) as u8
+            } else {
+                (state >> 16) as u8
+            }
+        })
+        .collect();
+    let sound = Sound::from_u8(&samples, 11025).with_loop(Some(300));
+    let mut flac = Vec::new();
+    write_flac(&mut flac, &sound).unwrap();
+
+    let mut reader = claxon::FlacReader::new(flac.as_slice()).unwrap();
+    let info = reader.streaminfo();
+    assert_eq!(info.sample_rate, 11025);
+    assert_eq!(info.bits_per_sample, 8);
+    assert_eq!(info.channels, 1);
+    assert_eq!(info.samples, Some(10000));
+    assert_eq!(reader.get_tag("LOOPSTART").next(), Some("300"));
+    assert_eq!(reader.get_tag("LOOPLENGTH").next(), Some("9700"));
+    let expected: Vec<i32> = sound.samples.iter().map(|&s| s as i32).collect();
+    assert_eq!(decoded(&mut reader), expected);
+
+    let loud = Sound {
+        sample_rate: 22050,
+        bits_per_sample: 16,
+        samples: vec![-32768, -129, 0, 127, 32767, 1000, -1000],
+        loop_start: None,
+    };
+    let mut flac = Vec::new();
+    write_flac(&mut flac, &loud).unwrap();
+
+    let mut reader = claxon::FlacReader::new(flac.as_slice()).unwrap();
+    assert_eq!(reader.streaminfo().bits_per_sample, 16);
+    assert_eq!(reader.get_tag("LOOPSTART").next(), None);
+    assert_eq!(
+        decoded(&mut reader),
+        [-32768, -129, 0, 127, 32767, 1000, -1000]
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::{fs::File, io::BufReader, process::Command};
+
+use beneath_a_steel_sky_extract::{
+    archive::Entry,
+    font::{self, Font},
+    image::{read_png, RgbImage},
+    resource::read_resource,
+};
+use common::{ArchiveBuilder, TempDir};
+
+/// A character set with a blank space four pixels wide and an outlined
+/// `!` two pixels wide.
+fn char_set() -> Vec<u8> {
+    let mut data = vec![0; font::CHAR_SET_HEADER];
+    data[0] = 3;
+    data[1] = 1;
+    data.extend([0; 4 * font::CHAR_HEIGHT]);
+    for y in 0..font::CHAR_HEIGHT {
+        let bits: u16 = if y < 8 { 0x4000 } else { 0 };
+        data.extend(bits.to_be_bytes());
+        data.extend(0xc000u16.to_be_bytes());
+    }
+    data
+}
+
+#[test]
+fn char_sets_encode_as_decoded() {
+    let data = char_set();
+    let font = Font::decode(&data, font::CHAR_HEIGHT).unwrap();
+    assert_eq!(font.encode(), data);
+
+    let sheet = font.render_sheet(font::SHEET_COLUMNS);
+    let widths: Vec<_> = font.glyphs.iter().map(|g| g.width).collect();
+    let cut = Font::from_sheet(&sheet, font::SHEET_COLUMNS, &widths, font::CHAR_HEIGHT).unwrap();
+    assert_eq!(cut.encode(), data);
+
+    assert!(Font::from_sheet(&sheet, font::SHEET_COLUMNS, &[17], font::CHAR_HEIGHT).is_none());
+    assert!(Font::from_sheet(&sheet, font::SHEET_COLUMNS, &[4; 17], font::CHAR_HEIGHT).is_none());
+}
+
+#[test]
+fn import_font_adds_glyphs_drawn_on_the_sheet() {
+    let dir = TempDir::new("import-font");
+    let game = ArchiveBuilder::new()
+        .with_header(
+            font::CHAR_SET_FILE,
+            &common::header(0, 0),
+            &char_set(),
+            false,
+        )
+        .write(&dir.path().join("game"));
+
+    let dump = dir.path().join("dump");
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .arg("-o")
+        .arg(&dump)
+        .args(["--dump", "images"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // A third glyph, `"`, three pixels wide with ink in its top left
+    // corner, drawn in the next cell of the sheet.
+    let sheet_path = dump.join("fonts/60150.png");
+    let mut sheet = read_png(BufReader::new(File::open(&sheet_path).unwrap())).unwrap();
+    let cell = 2 * (font::MAX_CHAR_WIDTH + 1);
+    for x in cell..cell + 3 {
+        sheet.data[3 * x..3 * x + 3].fill(0x60);
+    }
+    sheet.data[3 * cell..3 * cell + 3].fill(0xff);
+    let edited = dir.path().join("font.png");
+    write_png(&sheet, &edited);
+
+    let widths_path = dump.join("fonts/60150.json");
+    let mut widths: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&widths_path).unwrap()).unwrap();
+    widths
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({ "code": 0x22, "width": 3 }));
+    std::fs::write(&widths_path, widths.to_string()).unwrap();
+
+    let out = dir.path().join("60150.dmp");
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("import-font")
+        .arg(&game)
+        .arg("60150")
+        .arg(&edited)
+        .arg("--widths")
+        .arg(&widths_path)
+        .arg("-o")
+        .arg(&out)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let data = std::fs::read(&out).unwrap();
+    let entry = Entry {
+        number: font::CHAR_SET_FILE,
+        offset: 0,
+        size: data.len() as u32,
+        has_file_header: true,
+        uses_file_header: true,
+    };
+    let resource = read_resource(&entry, data).unwrap();
+    // The old glyphs are as they were.
+    let old = char_set();
+    assert_eq!(resource.data[..2], old[..2]);
+    assert_eq!(
+        resource.data[font::CHAR_SET_HEADER..old.len()],
+        old[font::CHAR_SET_HEADER..]
+    );
+
+    let font = Font::decode(&resource.data, font::CHAR_HEIGHT).unwrap();
+    let quote = font.glyph(b'"').unwrap();
+    assert_eq!(quote.width, 3);
+    assert_eq!(quote.data[..3], [font::INK, font::OUTLINE, font::OUTLINE]);
+    assert!(quote.data[3..].iter().all(|&p| p == font::TRANSPARENT));
+}
+
+fn write_png(image: &RgbImage, path: &std::path::Path) {
+    let mut file = File::create(path).unwrap();
+    image.write_png(&mut file).unwrap();
+}
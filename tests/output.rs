@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use beneath_a_steel_sky_extract::output::{self, Output};
+
+#[test]
+fn memory_output_keeps_what_would_be_written() {
+    let out = Path::new("memory-output");
+    let output = Output::memory();
+    output
+        .write(
+            output::resource_path(out, "raw", 7).with_extension("dmp"),
+            b"raw",
+        )
+        .unwrap();
+    output
+        .write_json(output::dump_path(out, "audio", "sfx.json"), &[1, 2])
+        .unwrap();
+    let mut wtr = output.csv_writer(out.join("resources.csv"));
+    wtr.write_record(["id", "size"]).unwrap();
+    output::finish_csv(wtr).unwrap();
+    // Nothing is made on disk.
+    output.create_dir_all(out.join("raw")).unwrap();
+    assert!(!out.exists());
+
+    let files = output.files();
+    let names: Vec<_> = files.keys().map(|p| p.to_str().unwrap()).collect();
+    assert_eq!(
+        names,
+        [
+            "memory-output/audio/sfx.json",
+            "memory-output/raw/00007.dmp",
+            "memory-output/resources.csv"
+        ]
+    );
+    assert_eq!(files[Path::new("memory-output/raw/00007.dmp")], b"raw");
+    assert_eq!(
+        files[Path::new("memory-output/audio/sfx.json")],
+        b"[\n  1,\n  2\n]"
+    );
+    assert_eq!(
+        files[Path::new("memory-output/resources.csv")],
+        b"id,size\n"
+    );
+    assert!(output.files().is_empty());
+}
+
+#[test]
+fn dry_runs_only_list_the_files() {
+    let output = Output::dry_run();
+    output.write("dry-run-output/b.png", &[0; 10]).unwrap();
+    output.write("dry-run-output/a.png", &[0; 3]).unwrap();
+    assert!(!Path::new("memory-output").exists());
+
+    let planned = output.planned();
+    let listed: Vec<_> = planned
+        .iter()
+        .map(|f| (f.path.to_str().unwrap(), f.size, f.unchanged))
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            ("dry-run-output/a.png", 3, false),
+            ("dry-run-output/b.png", 10, false)
+        ]
+    );
+    assert!(output.files().is_empty());
+}
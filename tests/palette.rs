@@ -0,0 +1,29 @@
+mod common;
+
+use beneath_a_steel_sky_extract::{palette::PaletteMap, version::KNOWN_VERSIONS};
+use common::TempDir;
+
+#[test]
+fn floppy_intro_screens_use_the_palette_faded_up_first() {
+    let floppy = KNOWN_VERSIONS.iter().find(|v| v.number == 331);
+    let palettes = PaletteMap::builtin(floppy);
+    assert_eq!(palettes.palette(60081), Some(60080));
+    assert_eq!(palettes.palette(60100), Some(60080));
+    assert_eq!(palettes.palette(60112), Some(60113));
+
+    let cd = KNOWN_VERSIONS.iter().find(|v| v.number == 368);
+    assert_eq!(PaletteMap::builtin(cd).palette(60100), None);
+}
+
+#[test]
+fn screens_listed_more_than_once_get_each_palette() {
+    let dir = TempDir::new("palette-map");
+    let path = dir.path().join("palettes.csv");
+    std::fs::write(&path, "screen,palette\n20,21\n20,25\n30,31\n").unwrap();
+
+    let palettes = PaletteMap::read(&path).unwrap();
+    assert_eq!(palettes.palettes(20), [21, 25]);
+    assert_eq!(palettes.palette(20), Some(21));
+    assert_eq!(palettes.palettes(30), [31]);
+    assert!(palettes.palettes(40).is_empty());
+}
@@ -0,0 +1,68 @@
+use beneath_a_steel_sky_extract::{
+    archive::Entry,
+    sprite::{decode_frames, pixel_format, planar_to_chunky, PixelFormat},
+    Header, Resource,
+};
+
+fn sprite(width: u16, height: u16, n_sprites: u16, sp_size: u16, data: Vec<u8>) -> Resource {
+    Resource {
+        entry: Entry {
+            number: 40,
+            offset: 0,
+            size: 0,
+            has_file_header: true,
+            uses_file_header: true,
+        },
+        header: Some(Header {
+            flags: 0,
+            x: 0,
+            y: 0,
+            width,
+            height,
+            sp_size,
+            tot_size: 0,
+            n_sprites,
+            offset_x: 0,
+            offset_y: 0,
+            compressed_size: 0,
+        }),
+        data,
+    }
+}
+
+#[test]
+fn planes_combine_into_colors() {
+    // 8 by 1 pixels: each plane sets one bit of the colors of some pixels.
+    let planes = [0b1010_1010, 0b1100_1100, 0b1111_0000, 0b0000_0001];
+    assert_eq!(planar_to_chunky(&planes, 8, 1), [7, 6, 5, 4, 3, 2, 1, 8]);
+
+    // Rows are padded to whole bytes.
+    let planes = [0b1000_0000, 0b0100_0000, 0, 0, 0, 0, 0, 0];
+    assert_eq!(planar_to_chunky(&planes, 3, 2), [1, 0, 0, 0, 1, 0]);
+}
+
+#[test]
+fn frames_the_size_of_four_planes_are_planar() {
+    let planar = sprite(16, 4, 2, 32, vec![0xff; 64]);
+    assert_eq!(pixel_format(&planar), Some(PixelFormat::Planar));
+
+    let frames = decode_frames(&planar).unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1].data, [15; 64]);
+
+    let unsized_planar = sprite(16, 4, 2, 0, vec![0; 64]);
+    assert_eq!(pixel_format(&unsized_planar), Some(PixelFormat::Planar));
+}
+
+#[test]
+fn frames_with_a_byte_per_pixel_are_chunky() {
+    let chunky = sprite(16, 4, 2, 64, (0..128).collect());
+    assert_eq!(pixel_format(&chunky), Some(PixelFormat::Chunky));
+
+    let frames = decode_frames(&chunky).unwrap();
+    assert_eq!(frames[1].data, (64..128).collect::<Vec<u8>>());
+
+    // Frames too narrow to tell apart are taken to be chunky.
+    let narrow = sprite(4, 4, 1, 16, vec![0; 16]);
+    assert_eq!(pixel_format(&narrow), Some(PixelFormat::Chunky));
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use std::{path::Path, process::Command};
+
+use beneath_a_steel_sky_extract::{
+    archive::{read_dinner_table, Entry},
+    repack::{patch_resource, write_archive_in_place, Patched, RawResource},
+    Archive,
+};
+use common::{header, sample, ArchiveBuilder, TempDir};
+
+fn resource(number: u16, original: Option<(u32, u32)>, data: &[u8]) -> RawResource {
+    RawResource {
+        number,
+        original,
+        has_file_header: false,
+        uses_file_header: false,
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn resources_stay_where_they_fit() {
+    let resources = [
+        resource(1, Some((0, 4)), b"grown!"),
+        resource(2, Some((4, 4)), b"two"),
+        resource(3, Some((12, 4)), b"last"),
+        resource(4, None, b"new"),
+    ];
+    let slack = [(8, b"slak".to_vec())];
+
+    let mut dnr = Vec::new();
+    let mut dsk = Vec::new();
+    let directory = write_archive_in_place(&resources, &slack, &mut dnr, &mut dsk).unwrap();
+
+    assert_eq!(dsk, b"\0\0\0\0two\0slaklastgrown!new");
+    let offsets: Vec<_> = directory.iter().map(|e| (e.number, e.offset)).collect();
+    assert_eq!(offsets, [(1, 16), (2, 4), (3, 12), (4, 22)]);
+    let read: Vec<_> = read_dinner_table(&mut dnr.as_slice())
+        .unwrap()
+        .iter()
+        .map(|e| (e.number, e.offset))
+        .collect();
+    assert_eq!(read, offsets);
+}
+
+#[test]
+fn entries_sharing_bytes_stay_shared_unless_changed() {
+    let resources = [
+        resource(1, Some((0, 6)), b"abcdef"),
+        resource(2, Some((2, 6)), b"cdefgh"),
+    ];
+    let mut dsk = Vec::new();
+    let directory = write_archive_in_place(&resources, &[], &mut Vec::new(), &mut dsk).unwrap();
+    assert_eq!(dsk, b"abcdefgh");
+    assert_eq!(directory[1].offset, 2);
+
+    let resources = [
+        resource(1, Some((0, 6)), b"abcdef"),
+        resource(2, Some((2, 6)), b"CDEFGH"),
+    ];
+    let mut dsk = Vec::new();
+    let directory = write_archive_in_place(&resources, &[], &mut Vec::new(), &mut dsk).unwrap();
+    assert_eq!(dsk, b"abcdefCDEFGH");
+    assert_eq!(directory[1].offset, 6);
+}
+
+fn entry(number: u16, offset: u32, size: u32) -> Entry {
+    Entry {
+        number,
+        offset,
+        size,
+        has_file_header: false,
+        uses_file_header: false,
+    }
+}
+
+#[test]
+fn patches_that_fit_are_written_in_place() {
+    // Resource 2 is followed by two bytes of padding.
+    let mut directory = [entry(1, 0, 4), entry(2, 4, 4), entry(3, 10, 4)];
+    let mut dsk = b"one.two.\0\0last".to_vec();
+
+    let patched = patch_resource(&mut directory, &mut dsk, 2, b"twenty").unwrap();
+    assert!(matches!(patched, Patched::InPlace { padding: 0 }));
+    assert_eq!(dsk, b"one.twentylast");
+    assert_eq!(directory[1].size, 6);
+    assert_eq!(directory[2].offset, 10);
+
+    let patched = patch_resource(&mut directory, &mut dsk, 2, b"2").unwrap();
+    assert!(matches!(patched, Patched::InPlace { padding: 5 }));
+    assert_eq!(dsk, b"one.2\0\0\0\0\0last");
+    assert_eq!(directory[1].size, 1);
+}
+
+#[test]
+fn patches_that_dont_fit_move_what_follows() {
+    let mut directory = [entry(1, 0, 4), entry(2, 4, 4), entry(3, 8, 4)];
+    let mut dsk = b"one.two.last".to_vec();
+
+    let patched = patch_resource(&mut directory, &mut dsk, 1, b"first.").unwrap();
+    assert!(matches!(patched, Patched::Moved { shift: 2, moved: 2 }));
+    assert_eq!(dsk, b"first.two.last");
+    assert_eq!(
+        directory.map(|e| (e.offset, e.size)),
+        [(0, 6), (6, 4), (10, 4)]
+    );
+}
+
+#[test]
+fn patches_of_shared_bytes_are_refused() {
+    let mut directory = [entry(1, 0, 8), entry(2, 4, 4)];
+    let mut dsk = b"one.two.".to_vec();
+
+    let e = patch_resource(&mut directory, &mut dsk, 1, b"x").unwrap_err();
+    assert!(
+        e.to_string().contains("shares bytes with resource 2"),
+        "{e}"
+    );
+    assert_eq!(dsk, b"one.two.");
+    assert!(patch_resource(&mut directory, &mut dsk, 3, b"x").is_err());
+}
+
+#[test]
+fn patch_replaces_both_files() {
+    let dir = TempDir::new("patch");
+    let game = ArchiveBuilder::new()
+        .raw(10, b"first")
+        .raw(11, b"second")
+        .write(&dir.path().join("game"));
+    let file = dir.path().join("10.dmp");
+    std::fs::write(&file, b"the first resource").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("patch")
+        .arg(&game)
+        .args(["--id", "10", "--file"])
+        .arg(&file)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let archive = Archive::load(&game).unwrap();
+    assert_eq!(
+        archive.get_resource(10).unwrap().data,
+        b"the first resource"
+    );
+    assert_eq!(archive.get_resource(11).unwrap().data, b"second");
+    let mut names: Vec<_> = std::fs::read_dir(&game)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["sky.dnr", "sky.dsk"]);
+}
+
+fn repack(dump: &Path, out: &Path) -> (Vec<u8>, Vec<u8>) {
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("repack")
+        .args([&dump.join("raw"), out])
+        .arg("--preserve-layout")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    (
+        std::fs::read(out.join("sky.dnr")).unwrap(),
+        std::fs::read(out.join("sky.dsk")).unwrap(),
+    )
+}
+
+#[test]
+fn repacking_with_the_layout_preserved_is_byte_stable() {
+    let dir = TempDir::new("preserve-layout");
+    let game = ArchiveBuilder::new()
+        .raw(10, b"first")
+        .with_header(11, &header(0, 0), &sample(3000, 1), true)
+        .raw(12, b"last")
+        .write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .arg("-o")
+        .arg(&dump)
+        .args(["--dump", "raw"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let original = std::fs::read(game.join("sky.dsk")).unwrap();
+    let (_, dsk) = repack(&dump, &dir.path().join("unchanged"));
+    assert_eq!(dsk, original);
+
+    std::fs::write(dump.join("raw/00010.dmp"), b"first, but longer").unwrap();
+    let (dnr, dsk) = repack(&dump, &dir.path().join("a"));
+    assert_eq!(
+        repack(&dump, &dir.path().join("b")),
+        (dnr.clone(), dsk.clone())
+    );
+
+    // Only the grown resource moves, to the end.
+    let directory = read_dinner_table(&mut dnr.as_slice()).unwrap();
+    assert_eq!(directory[0].offset as usize, original.len());
+    assert_eq!(&dsk[..5], b"\0\0\0\0\0");
+    assert_eq!(&dsk[5..original.len()], &original[5..]);
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::io::Cursor;
+
+use beneath_a_steel_sky_extract::{
+    rnc_compress::compress_rnc1,
+    rnc_decompress::{decompress_in_place, decompress_rnc1, in_place_buffer, DecompressError},
+};
+use common::sample;
+
+#[test]
+fn unpacks_in_place() {
+    for len in [1, 100, 5000, 70000] {
+        let data = sample(len, 0x1234_5678);
+        let stream = compress_rnc1(&data);
+
+        let (mut buf, start) = in_place_buffer(&stream).unwrap();
+        let unpacked = decompress_in_place(&mut buf, start).unwrap();
+
+        assert_eq!(&buf[..unpacked], data.as_slice());
+        assert_eq!(
+            decompress_rnc1(&mut Cursor::new(&stream)).unwrap(),
+            buf[..unpacked]
+        );
+    }
+}
+
+#[test]
+fn rejects_packed_data_ending_too_early() {
+    let data = sample(5000, 0x1234_5678);
+    let stream = compress_rnc1(&data);
+
+    let (buf, start) = in_place_buffer(&stream).unwrap();
+    // Moving the stream towards the start of the buffer, and dropping the
+    // bytes left after it, leaves too little room.
+    let mut short = buf[..start].to_vec();
+    short.truncate(start - 1);
+    short.extend_from_slice(&stream);
+
+    assert!(matches!(
+        decompress_in_place(&mut short, start - 1),
+        Err(DecompressError::Overlap { .. })
+    ));
+}
+
+#[test]
+fn detects_an_overlap_too_small() {
+    // Output runs ahead of the packed data while the zeros are unpacked,
+    // and the packed data only catches up once it turns incompressible.
+    let mut data = vec![0; 20000];
+    data.extend(sample(1400, 0x1234_5678).iter().skip(700));
+    let mut stream = compress_rnc1(&data);
+    assert!(stream[16] > 0);
+    stream[16] = 0;
+
+    let (mut buf, start) = in_place_buffer(&stream).unwrap();
+    assert!(matches!(
+        decompress_in_place(&mut buf, start),
+        Err(DecompressError::Corrupt(_))
+    ));
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use beneath_a_steel_sky_extract::{compact::COMPACT_FIELDS, script::mcode_name, sfx};
+use common::{header, words, ArchiveBuilder, CptBuilder, TempDir};
+
+fn compact(fields: &[(&str, u16)]) -> Vec<u16> {
+    let mut data = vec![0; COMPACT_FIELDS.len()];
+    for &(name, value) in fields {
+        data[COMPACT_FIELDS.iter().position(|&f| f == name).unwrap()] = value;
+    }
+    data
+}
+
+/// A `sky.cpt` with a `door` on screen 5, drawn with frame 1 of sprite 20
+/// and clicked on by script 0x1001, and a `lamp` on screen 6.
+fn sky_cpt() -> Vec<u8> {
+    let door = compact(&[
+        ("screen", 5),
+        ("frame", (20 << 6) | 1),
+        ("cursor_text", 0x0001),
+        ("mouse_click", 0x1001),
+    ]);
+    let lamp = compact(&[("screen", 6), ("frame", 21 << 6)]);
+
+    CptBuilder::new()
+        .entry("door", 1, &door)
+        .entry("lamp", 1, &lamp)
+        .build()
+}
+
+#[test]
+fn extract_room_gathers_what_the_room_uses() {
+    let dir = TempDir::new("extract-room");
+    fs::write(
+        dir.path().join("layers.csv"),
+        "screen,layer,x,y\n100,21,130,140\n",
+    )
+    .unwrap();
+
+    let mcode = (0..)
+        .find(|&n| mcode_name(n) == Some("fn_speak_me"))
+        .unwrap();
+    let mut sprite = header(2, 1);
+    sprite.n_sprites = 2;
+    let module = words(&[2, 2, 13, 2, 3, 2, 0x2345, 11, 2, 4 * mcode, 13]);
+    let game = ArchiveBuilder::new()
+        .with_header(20, &sprite, &[1, 2, 3, 4], false)
+        .with_header(21, &header(2, 1), &[5, 6], false)
+        .raw(100, &vec![0; 320 * 200])
+        .raw(101, &[0; 768])
+        .with_header(60401, &header(0, 0), &module, false)
+        .write(&dir.path().join("game"));
+    fs::write(game.join("sky.cpt"), sky_cpt()).unwrap();
+
+    let out = dir.path().join("room");
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("extract-room")
+        .arg(&game)
+        .args(["100", "--screen", "5", "--layer-map"])
+        .arg(dir.path().join("layers.csv"))
+        .arg("-o")
+        .arg(&out)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(out.join("manifest.json")).unwrap()).unwrap();
+    assert_eq!(manifest["palette"], 101);
+    assert_eq!(manifest["compacts"][0]["name"], "door");
+    assert_eq!(manifest["compacts"].as_array().unwrap().len(), 1);
+    assert_eq!(manifest["sprites"][0]["id"], 20);
+    assert_eq!(manifest["sprites"][0]["files"].as_array().unwrap().len(), 2);
+    assert_eq!(manifest["layers"][0]["id"], 21);
+    assert_eq!(manifest["scripts"], serde_json::json!(["0x1001"]));
+    assert_eq!(manifest["text"], serde_json::json!(["0x0001", "0x2345"]));
+
+    for file in manifest["files"].as_array().unwrap() {
+        assert!(out.join(file.as_str().unwrap()).exists(), "{file}");
+    }
+    assert!(out.join("scripts/60401.txt").exists());
+    assert!(out.join("sprites/00020-001.png").exists());
+}
+
+#[test]
+fn rooms_reject_palettes_that_arent_palettes() {
+    let dir = TempDir::new("room-palette");
+    let game = ArchiveBuilder::new()
+        .with_header(20, &header(2, 1), &[1, 2], false)
+        .raw(100, &vec![0; 320 * 200])
+        .write(&dir.path().join("game"));
+    fs::write(game.join("sky.cpt"), sky_cpt()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("render-room")
+        .arg(&game)
+        .args(["100", "--screen", "5", "--palette", "20", "-o"])
+        .arg(dir.path().join("room.png"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no palette with id 20"), "{stderr}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("extract-room")
+        .arg(&game)
+        .args(["100", "--screen", "5", "--palette", "20", "-o"])
+        .arg(dir.path().join("room"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no palette with id 20"), "{stderr}");
+}
+
+#[test]
+fn dumped_files_are_named_from_the_name_table() {
+    let dir = TempDir::new("names");
+    fs::write(dir.path().join("names.csv"), "id,name\n21,Reading Lamp\n").unwrap();
+    let game = ArchiveBuilder::new()
+        .with_header(20, &header(2, 1), &[1, 2], false)
+        .with_header(21, &header(2, 1), &[3, 4], false)
+        .raw(sfx::sfx_file(0), b"bank")
+        .write(&dir.path().join("game"));
+    fs::write(game.join("sky.cpt"), sky_cpt()).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .args(["--dump", "images", "--dump-csv", "--names", "names.csv"])
+        .args(["-o", "dump"])
+        .current_dir(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Sprites are named after the objects drawn with them, unless named
+    // otherwise.
+    let sprites = dir.path().join("dump/sprites");
+    assert!(sprites.join("00020-door-000.png").exists());
+    assert!(sprites.join("00020-door.json").exists());
+    assert!(sprites.join("00021-reading-lamp-000.png").exists());
+
+    let resources = fs::read_to_string(dir.path().join("resources.csv")).unwrap();
+    assert!(resources.contains(",20,,door,"), "{resources}");
+    let bank = format!(",{},,sound-effects,", sfx::sfx_file(0));
+    assert!(resources.contains(&bank), "{resources}");
+}
+
+#[test]
+fn walk_grids_are_written_as_json() {
+    let dir = TempDir::new("grid-json");
+    fs::write(dir.path().join("grids.csv"), "screen,grid\n100,60000\n").unwrap();
+
+    // The top left cell and the last cell of the second row are blocked.
+    let mut grid = vec![0; 120];
+    grid[3] = 0x80;
+    grid[10] = 0x01;
+    let game = ArchiveBuilder::new()
+        .raw(60000, &grid)
+        .write(&dir.path().join("game"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .args(["--dump", "images", "--grid-map", "grids.csv", "-o", "dump"])
+        .current_dir(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&fs::read(dir.path().join("dump/grids/60000.json")).unwrap())
+            .unwrap();
+    assert_eq!(json["screens"], serde_json::json!([100]));
+    let rows = json["blocked"].as_array().unwrap();
+    assert_eq!(rows.len(), 24);
+    let blocked: Vec<_> = rows
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            let row = row.as_array().unwrap();
+            assert_eq!(row.len(), 40);
+            row.iter()
+                .enumerate()
+                .filter(|(_, cell)| **cell == 1)
+                .map(move |(x, _)| (x, y))
+        })
+        .collect();
+    assert_eq!(blocked, [(0, 0), (39, 1)]);
+}
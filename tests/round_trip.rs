@@ -0,0 +1,476 @@
+mod common;
+
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+    process::Command,
+};
+
+use beneath_a_steel_sky_extract::{
+    checksum::{Checksum, Fingerprint, FINGERPRINT_FORMAT},
+    rnc_compress::compress_rnc1,
+    rnc_decompress::{self, decompress_rnc1, RncReader},
+    Archive,
+};
+use common::{assert_golden, header, sample, ArchiveBuilder, TempDir};
+
+fn run(args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+/// A bit of everything: resources with and without headers, compressed
+/// and not, a palette and a screen.
+fn synthetic_archive() -> ArchiveBuilder {
+    let palette: Vec<u8> = (0..768).map(|i| (i % 64) as u8).collect();
+    ArchiveBuilder::new()
+        .with_header(20, &header(320, 200), &sample(64000, 1), true)
+        .raw(21, &palette)
+        .with_header(40, &header(16, 8), &sample(128, 2), false)
+        .raw(50, b"not a resource of any known type")
+        .with_header(60, &header(0, 0), &sample(5000, 3), true)
+}
+
+#[test]
+fn rnc_streams_decompress_to_what_was_compressed() {
+    for (len, seed) in [(0, 0), (1, 1), (100, 2), (5000, 3), (70000, 4)] {
+        let data = sample(len, seed);
+        let stream = compress_rnc1(&data);
+
+        assert_eq!(decompress_rnc1(&mut Cursor::new(&stream)).unwrap(), data);
+
+        let mut streamed = Vec::new();
+        RncReader::new(stream.as_slice())
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, data);
+    }
+}
+
+#[test]
+fn rnc_methods_are_read_from_the_signature() {
+    let mut stream = compress_rnc1(&sample(100, 1));
+    assert_eq!(rnc_decompress::method(&stream), Some(1));
+    stream[3] = 2;
+    assert_eq!(rnc_decompress::method(&stream), Some(2));
+    assert_eq!(rnc_decompress::method(b"RN"), None);
+    assert_eq!(rnc_decompress::method(b"PK\x03\x04"), None);
+}
+
+#[test]
+fn rnc_stream_matches_fixture() {
+    let data = sample(3000, 5);
+    let stream = compress_rnc1(&data);
+    assert_golden("sample.rnc", &stream);
+
+    let fixture =
+        std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sample.rnc"))
+            .unwrap();
+    assert_eq!(decompress_rnc1(&mut Cursor::new(fixture)).unwrap(), data);
+}
+
+#[test]
+fn resources_read_back_as_written() {
+    let archive = synthetic_archive().open();
+    assert_eq!(archive.entries().len(), 5);
+
+    let screen = archive.get_resource(20).unwrap();
+    let screen_header = screen.header.as_ref().unwrap();
+    assert!(screen_header.is_compressed());
+    assert_eq!((screen_header.width, screen_header.height), (320, 200));
+    assert_eq!(screen.data, sample(64000, 1));
+
+    let sprite = archive.get_resource(40).unwrap();
+    assert!(!sprite.header.as_ref().unwrap().is_compressed());
+    assert_eq!(sprite.data, sample(128, 2));
+
+    let raw = archive.get_resource(50).unwrap();
+    assert!(raw.header.is_none());
+    assert_eq!(raw.data, b"not a resource of any known type");
+
+    assert_eq!(archive.get_resource(60).unwrap().data, sample(5000, 3));
+    assert!(archive.get_resource(61).is_none());
+}
+
+#[test]
+fn archive_files_open_from_disk() {
+    let dir = TempDir::new("open");
+    let game = synthetic_archive().write(dir.path());
+
+    let mut archive = Archive::open(&game).unwrap();
+    let ids: Vec<_> = archive
+        .resources()
+        .map(|r| r.unwrap().entry.number)
+        .collect();
+    assert_eq!(ids, [20, 21, 40, 50, 60]);
+}
+
+#[test]
+fn readers_read_entries_from_several_threads() {
+    let dir = TempDir::new("readers");
+    let game = synthetic_archive().write(dir.path());
+    let (dnr, dsk) = synthetic_archive().build();
+
+    let file = Archive::open(&game).unwrap();
+    let memory = Archive::from_bytes(&dnr, dsk).unwrap();
+    let expected: Vec<_> = memory
+        .entries()
+        .iter()
+        .map(|e| memory.get_resource(e.number).unwrap().data)
+        .collect();
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let mut reader = file.reader().unwrap();
+                // Backwards, so the readers don't follow each other.
+                for (entry, data) in file.entries().iter().zip(&expected).rev() {
+                    assert_eq!(&reader.read_resource(entry).unwrap().data, data);
+                }
+            });
+            s.spawn(|| {
+                let mut reader = memory.reader().unwrap();
+                for (entry, data) in memory.entries().iter().zip(&expected) {
+                    assert_eq!(&reader.resource(entry.number).unwrap().data, data);
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn archives_open_from_bytes() {
+    let (dnr, dsk) = synthetic_archive().build();
+
+    let archive = Archive::from_bytes(&dnr, dsk).unwrap();
+    assert_eq!(archive.entries().len(), 5);
+    assert_eq!(archive.get_resource(21).unwrap().data.len(), 768);
+    assert!(Archive::from_bytes(&dnr[..2], Vec::new()).is_err());
+}
+
+#[test]
+fn merged_entries_stop_at_the_end_of_their_volume() {
+    // The first volume is cut short, so its entry would run on into the
+    // data of the second.
+    let (dnr, mut dsk) = ArchiveBuilder::new().raw(10, b"first").build();
+    dsk.truncate(3);
+    let first = Archive::from_bytes(&dnr, dsk).unwrap();
+    let (dnr, dsk) = ArchiveBuilder::new().raw(11, b"second").build();
+    let second = Archive::from_bytes(&dnr, dsk).unwrap();
+
+    let mut archive = Archive::merge(vec![first, second]).unwrap();
+    let entry = *archive.entry(10).unwrap();
+    assert_eq!(archive.entry_bytes(&entry), b"fir");
+    assert!(archive.checked_entry_bytes(&entry).is_err());
+    assert!(archive.read_entry(&entry).is_err());
+    assert!(archive.reader().unwrap().read_entry(&entry).is_err());
+    assert_eq!(archive.get_resource(11).unwrap().data, b"second");
+}
+
+#[test]
+fn files_that_arent_dinner_tables_are_rejected() {
+    let not_a_table = |dnr: &[u8], dsk: Vec<u8>| {
+        let e = Archive::from_bytes(dnr, dsk).err().unwrap();
+        assert!(e.to_string().contains("doesn't look like sky.dnr"), "{e}");
+        e.to_string()
+    };
+
+    // An entry count that only fits big endian, a table cut short and
+    // sky.dsk given for sky.dnr.
+    let (_, dsk) = synthetic_archive().build();
+    let e = not_a_table(&[0, 0, 0, 5, 1, 2, 3], dsk.clone());
+    assert!(e.contains("big endian"), "{e}");
+    let e = not_a_table(&[5, 0, 0, 0, 1, 2, 3], dsk.clone());
+    assert!(e.contains("after 0 of its 5 entries"), "{e}");
+    not_a_table(&dsk, dsk.clone());
+
+    // Entries all over the place, and entries beyond the data.
+    let entry = |number: u16, offset: u32| {
+        [
+            &number.to_le_bytes()[..],
+            &offset.to_le_bytes()[..3],
+            &[16, 0, 0],
+        ]
+        .concat()
+    };
+    let table = |offsets: &[u32]| {
+        let mut dnr = (offsets.len() as u32).to_le_bytes().to_vec();
+        for (n, &offset) in offsets.iter().enumerate() {
+            dnr.extend(entry(n as u16, offset));
+        }
+        dnr
+    };
+    let shuffled = table(&[64, 0, 48, 16, 80, 32, 112, 96]);
+    assert!(not_a_table(&shuffled, vec![0; 128]).contains("before the entry"));
+    let ordered = table(&[0, 16, 32, 48, 64, 80, 96, 112]);
+    assert!(not_a_table(&ordered, vec![0; 48]).contains("past the end"));
+
+    // A truncated copy still opens.
+    assert!(Archive::from_bytes(&ordered, vec![0; 100]).is_ok());
+}
+
+#[test]
+fn dumps_fail_where_the_output_directory_cant_be_made() {
+    let dir = TempDir::new("unwritable");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let file = dir.path().join("file");
+    std::fs::write(&file, b"in the way").unwrap();
+    let dump = file.join("dump");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .args([path_str(&game), "-o", path_str(&dump), "--dump", "raw"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(path_str(&dump)), "{stderr}");
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn raw_image_keeps_entries_at_their_offsets() {
+    let dir = TempDir::new("raw-image");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+
+    run(&[
+        path_str(&game),
+        "-o",
+        path_str(&dump),
+        "--dump",
+        "raw",
+        "--raw-image",
+    ]);
+
+    let raw = dump.join("raw");
+    assert_eq!(
+        std::fs::read(raw.join("sky.dsk")).unwrap(),
+        std::fs::read(game.join("sky.dsk")).unwrap()
+    );
+    assert!(!raw.join("00020.dmp").exists());
+
+    let index = std::fs::read_to_string(raw.join("sky.dsk.csv")).unwrap();
+    let lines: Vec<_> = index.lines().collect();
+    assert_eq!(
+        lines[0],
+        "offset,end,len,id,type,has_file_header,compressed,zero"
+    );
+    assert_eq!(lines.len(), 6, "{index}");
+    assert!(lines[1].starts_with("0x000000,"), "{index}");
+    assert!(
+        lines.iter().any(|l| l.contains(",50,,false,false,")),
+        "{index}"
+    );
+}
+
+#[test]
+fn raw_dump_repacks_to_the_same_files() {
+    let dir = TempDir::new("repack");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+    let repacked = dir.path().join("repacked");
+
+    run(&[path_str(&game), "-o", path_str(&dump), "--dump", "raw"]);
+    run(&["repack", path_str(&dump.join("raw")), path_str(&repacked)]);
+
+    for name in ["sky.dnr", "sky.dsk"] {
+        assert_eq!(
+            std::fs::read(repacked.join(name)).unwrap(),
+            std::fs::read(game.join(name)).unwrap(),
+            "{name} differs"
+        );
+    }
+}
+
+#[test]
+fn extracted_resources_match_what_was_stored() {
+    let dir = TempDir::new("extract");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let out = dir.path().join("60.bin");
+
+    run(&["extract", path_str(&game), "60", "-o", path_str(&out)]);
+    assert_eq!(std::fs::read(&out).unwrap(), sample(5000, 3));
+}
+
+#[test]
+fn cat_writes_the_resource_to_stdout() {
+    let dir = TempDir::new("cat");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+
+    assert_eq!(run(&["cat", path_str(&game), "60"]), sample(5000, 3));
+    let extracted = dir.path().join("60.dmp");
+    run(&[
+        "extract",
+        path_str(&game),
+        "60",
+        "--raw",
+        "-o",
+        path_str(&extracted),
+    ]);
+    assert_eq!(
+        run(&["cat", path_str(&game), "60", "--raw"]),
+        std::fs::read(&extracted).unwrap()
+    );
+}
+
+#[test]
+fn stats_match_fixture() {
+    let dir = TempDir::new("stats");
+    let game = synthetic_archive().write(dir.path());
+
+    assert_golden("stats.txt", &run(&["stats", path_str(&game)]));
+}
+
+#[test]
+fn fingerprint_checksums_every_resource() {
+    let dir = TempDir::new("fingerprint");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+
+    let fingerprint = Fingerprint::read(run(&["fingerprint", path_str(&game)]).as_slice()).unwrap();
+    assert_eq!(fingerprint.format, FINGERPRINT_FORMAT);
+    assert_eq!(fingerprint.version, None);
+    assert_eq!(fingerprint.entries, 5);
+
+    let archive = Archive::load(&game).unwrap();
+    let entry = *archive.entry(50).unwrap();
+    assert!(fingerprint
+        .checksums
+        .contains(&Checksum::new(50, archive.entry_bytes(&entry))));
+}
+
+#[test]
+fn listing_matches_fixture() {
+    let dir = TempDir::new("list");
+    let game = synthetic_archive().write(dir.path());
+
+    assert_golden("list.txt", &run(&["list", path_str(&game)]));
+}
+
+#[test]
+fn incremental_dump_leaves_unchanged_files_alone() {
+    let dir = TempDir::new("incremental");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+    let args = [path_str(&game), "-o", path_str(&dump), "--incremental"];
+
+    let stdout = String::from_utf8(run(&args)).unwrap();
+    assert!(
+        stdout.contains("\n0 files were already up to date"),
+        "{stdout}"
+    );
+
+    let raw = dump.join("raw/00050.dmp");
+    let modified = std::fs::metadata(&raw).unwrap().modified().unwrap();
+    std::fs::write(dump.join("raw/00021.dmp"), b"changed").unwrap();
+
+    let stdout = String::from_utf8(run(&args)).unwrap();
+    assert!(!stdout.contains("\n0 files were"), "{stdout}");
+    assert_eq!(
+        std::fs::metadata(&raw).unwrap().modified().unwrap(),
+        modified
+    );
+    assert_eq!(
+        std::fs::read(dump.join("raw/00021.dmp")).unwrap().len(),
+        768
+    );
+}
+
+#[test]
+fn export_pack_writes_decoded_assets_and_their_layout() {
+    let dir = TempDir::new("export-pack");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let pack = dir.path().join("pack");
+
+    run(&["export-pack", path_str(&game), "-o", path_str(&pack)]);
+    assert!(!pack.join("raw").exists());
+    assert!(pack.join("screen/00020.png").exists());
+    assert!(pack.join("sprites/00040-sheet.png").exists());
+    assert!(pack.join("sprites/00040-atlas-0.png").exists());
+
+    let atlas: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(pack.join("sprites/00040.json")).unwrap()).unwrap();
+    assert_eq!(atlas["sheet"], "00040-sheet.png");
+
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(pack.join("pack.json")).unwrap()).unwrap();
+    assert_eq!(manifest["audio_format"], "ogg");
+    assert_eq!(manifest["audio_codec"], "vorbis");
+    assert_eq!(manifest["sample_rate"], 44100);
+    assert!(manifest["directories"]["sprites"].is_string());
+}
+
+#[test]
+fn screens_followed_by_several_palettes_are_written_with_each() {
+    let dir = TempDir::new("palette-variants");
+    let day: Vec<u8> = (0..768).map(|i| (i % 64) as u8).collect();
+    let night: Vec<u8> = day.iter().map(|c| c / 2).collect();
+    let game = synthetic_archive()
+        .with_header(30, &header(320, 200), &sample(64000, 6), true)
+        .raw(31, &day)
+        .raw(32, &night)
+        .write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+
+    run(&[path_str(&game), "-o", path_str(&dump), "--dump", "images"]);
+    assert!(dump.join("screen/00030-a.png").exists());
+    assert!(dump.join("screen/00030-b.png").exists());
+    assert!(!dump.join("screen/00030.png").exists());
+    assert!(dump.join("screen/00020.png").exists());
+}
+
+#[test]
+fn screens_paired_with_something_other_than_a_palette_are_still_written() {
+    let dir = TempDir::new("palette-not-a-palette");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let palette_map = dir.path().join("palettes.csv");
+    std::fs::write(&palette_map, "screen,palette\n20,50\n").unwrap();
+    let dump = dir.path().join("dump");
+
+    let stdout = String::from_utf8(run(&[
+        path_str(&game),
+        "-o",
+        path_str(&dump),
+        "--dump",
+        "images",
+        "--palette-map",
+        path_str(&palette_map),
+    ]))
+    .unwrap();
+    assert!(dump.join("screen/00020.png").exists());
+    assert!(!stdout.contains("failed"), "{stdout}");
+}
+
+#[test]
+fn dry_run_lists_files_without_writing_them() {
+    let dir = TempDir::new("dry-run");
+    let game = synthetic_archive().write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+
+    let stdout =
+        String::from_utf8(run(&[path_str(&game), "-o", path_str(&dump), "--dry-run"])).unwrap();
+    assert!(!dump.exists());
+
+    let raw = dump.join("raw/00021.dmp");
+    let line = stdout
+        .lines()
+        .find(|line| line.ends_with(path_str(&raw)))
+        .unwrap_or_else(|| panic!("{stdout}"));
+    assert_eq!(
+        line.split_whitespace().take(2).collect::<Vec<_>>(),
+        ["768", "dmp"]
+    );
+    assert!(stdout.contains("Would write "), "{stdout}");
+}
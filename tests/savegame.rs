@@ -0,0 +1,142 @@
+mod common;
+
+use std::{fs, io::Write, process::Command};
+
+use beneath_a_steel_sky_extract::{
+    compact::CptFile,
+    savegame::{SaveFormat, SaveGame, LOADED_FILES, SAVE_FILE_REVISION, SCREEN, SCRIPT_VARIABLES},
+};
+use common::{CptBuilder, TempDir};
+use flate2::{write::GzEncoder, Compression};
+
+/// A `sky.cpt` with a compact `foster` of three words and a binary entry
+/// `card` of two in the first list, both held by saved games.
+fn sky_cpt() -> Vec<u8> {
+    CptBuilder::new()
+        .entry("foster", 1, &[0, 0, 0])
+        .entry("card", 4, &[0, 0])
+        .save_ids(&[0x0000, 0x0001])
+        .build()
+}
+
+/// The state saved by both ScummVM and the DOS releases.
+fn saved_state(screen: u32, inventory: &[(usize, u32)], compacts: &[u16]) -> Vec<u8> {
+    let mut vars = vec![0u32; SCRIPT_VARIABLES];
+    vars[SCREEN] = screen;
+    for &(n, id) in inventory {
+        vars[n] = id;
+    }
+
+    let mut data = Vec::new();
+    vars.iter().for_each(|v| data.extend(v.to_le_bytes()));
+    data.extend(vec![0; 4 * LOADED_FILES]);
+    compacts.iter().for_each(|w| data.extend(w.to_le_bytes()));
+    data
+}
+
+fn save_game(screen: u32, inventory: &[(usize, u32)], compacts: &[u16]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend(0u32.to_le_bytes());
+    data.extend(SAVE_FILE_REVISION.to_le_bytes());
+    data.extend(368u32.to_le_bytes());
+    data.extend([0; 4]); // sounds
+    data.extend([0; 16]); // music, font, pointer and palette
+    data.extend(saved_state(screen, inventory, compacts));
+    let size = data.len() as u32;
+    data[..4].copy_from_slice(&size.to_le_bytes());
+    // Trailing metadata isn't part of the save.
+    data.extend(b"SVMCR");
+    data
+}
+
+#[test]
+fn saved_games_split_into_the_compacts_of_sky_cpt() {
+    let cpt = CptFile::read(&sky_cpt()).unwrap();
+    assert_eq!(cpt.save_ids, [0x0000, 0x0001]);
+
+    let data = save_game(12, &[(200, 1), (202, 5)], &[9, 8, 7, 6, 5]);
+    let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+    gzipped.write_all(&data).unwrap();
+    let gzipped = gzipped.finish().unwrap();
+
+    for data in [data, gzipped] {
+        let save = SaveGame::read(&data).unwrap();
+        assert_eq!(save.format, SaveFormat::ScummVm);
+        assert_eq!(save.engine.as_ref().unwrap().game_version, 368);
+        assert_eq!(save.screen(), 12);
+        assert_eq!(save.menu_objects(200), [1, 5]);
+        assert_eq!(
+            save.compacts(&cpt).unwrap(),
+            [(0x0000, &[9, 8, 7][..]), (0x0001, &[6, 5][..])]
+        );
+    }
+
+    let short = save_game(12, &[], &[9, 8, 7, 6]);
+    assert!(SaveGame::read(&short).unwrap().compacts(&cpt).is_err());
+    assert!(SaveGame::read(&short[..100]).is_err());
+}
+
+#[test]
+fn other_files_are_read_as_dos_saves() {
+    let cpt = CptFile::read(&sky_cpt()).unwrap();
+
+    let data = saved_state(12, &[(200, 1)], &[9, 8, 7, 6, 5]);
+    let save = SaveGame::read(&data).unwrap();
+    assert_eq!(save.format, SaveFormat::Dos);
+    assert!(save.engine.is_none());
+    assert_eq!(save.screen(), 12);
+    assert_eq!(save.menu_objects(200), [1]);
+    assert_eq!(
+        save.compacts(&cpt).unwrap(),
+        [(0x0000, &[9, 8, 7][..]), (0x0001, &[6, 5][..])]
+    );
+
+    // Only the compacts adding up tells a DOS save from any other file.
+    let other = saved_state(12, &[], &[9, 8, 7, 6, 5, 4]);
+    assert!(SaveGame::read(&other).unwrap().compacts(&cpt).is_err());
+    assert!(SaveGame::read(&data[..data.len() - 1]).is_err());
+
+    let mut data = save_game(12, &[], &[9, 8, 7, 6, 5]);
+    data[4..8].copy_from_slice(&3u32.to_le_bytes());
+    let e = SaveGame::read(&data).unwrap_err().to_string();
+    assert!(e.contains("revision 3"), "{e}");
+}
+
+#[test]
+fn savegame_prints_the_state_with_names() {
+    let dir = TempDir::new("savegame");
+    fs::write(dir.path().join("sky.cpt"), sky_cpt()).unwrap();
+    let save_path = dir.path().join("sky-vm.001");
+    fs::write(&save_path, save_game(7, &[(200, 1)], &[9, 8, 7, 6, 5])).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("savegame")
+        .arg(&save_path)
+        .args(["--inventory", "200"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["screen"], 7);
+    assert_eq!(json["inventory"][0]["name"], "card");
+    assert_eq!(json["compacts"][0]["name"], "foster");
+    assert_eq!(json["compacts"][0]["fields"]["logic"], 9);
+    assert_eq!(json["compacts"][1]["data"][1], 5);
+    assert_eq!(json["format"], "scummvm");
+    assert_eq!(json["game_version"], 368);
+
+    fs::write(&save_path, saved_state(7, &[], &[9, 8, 7, 6, 5])).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("savegame")
+        .arg(&save_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["format"], "dos");
+    assert_eq!(json["screen"], 7);
+    assert!(json.get("game_version").is_none());
+    assert_eq!(json["compacts"][0]["fields"]["logic"], 9);
+}
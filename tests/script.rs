@@ -0,0 +1,125 @@
+use beneath_a_steel_sky_extract::{
+    decompile,
+    script::{mcode_name, Module, Reference},
+};
+
+fn mcode(name: &str) -> u16 {
+    (0..).find(|&n| mcode_name(n) == Some(name)).unwrap()
+}
+
+fn module(words: &[u16]) -> Module {
+    let data: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    Module::new(60401, &data).unwrap()
+}
+
+#[test]
+fn constant_arguments_are_collected_per_script() {
+    let push = 2;
+    let push_variable = 0;
+    let call = 11;
+    let exit = 13;
+    // Script 0 is at word 2 and script 1 at word 3, both counted from
+    // their table entry.
+    let table = [2, 2];
+    // Script 1 starts sound effect 12, says text 0x2345 as compact 3 twice,
+    // then starts whatever music var[5] holds.
+    let module = module(
+        &[
+            &table[..],
+            &[exit],
+            &[push, 12, push, 1, call, 2, 4 * mcode("fn_start_fx")],
+            &[push, 3, push, 0x2345, call, 2, 4 * mcode("fn_speak_me")],
+            &[push, 3, push, 0x2345, call, 2, 4 * mcode("fn_speak_me")],
+            &[push_variable, 4 * 5, call, 1, 4 * mcode("fn_start_music")],
+            &[exit],
+        ]
+        .concat(),
+    );
+
+    let refs = module.references();
+    assert!(refs[&0x1000].is_empty());
+    assert_eq!(
+        refs[&0x1001].iter().copied().collect::<Vec<_>>(),
+        [
+            (Reference::Compact, 3),
+            (Reference::Text, 0x2345),
+            (Reference::Sfx, 12),
+        ]
+    );
+}
+
+#[test]
+fn scripts_decompile_into_structured_code() {
+    let (push_variable, push, pop_variable) = (0, 2, 6);
+    let (less_than, plus, is_equal) = (1, 8, 17);
+    let (skip_zero, skip_always, skip_nz) = (5, 9, 18);
+    let (call, switch, exit) = (11, 14, 13);
+    // Skips are in bytes from the end of the instruction, switch cases
+    // from their own word.
+    let module = module(
+        &[
+            &[1][..],
+            // 1: if (var[3] == 2)
+            &[push_variable, 4 * 3, push, 2, is_equal, skip_zero, 12],
+            &[push, 1, pop_variable, 4 * 4, skip_always, 8],
+            // 14: else
+            &[push, 0, pop_variable, 4 * 4],
+            // 18: do ... while (var[4] < 10)
+            &[push_variable, 4 * 4, push, 1, plus, pop_variable, 4 * 4],
+            &[
+                push_variable,
+                4 * 4,
+                push,
+                10,
+                less_than,
+                skip_nz,
+                -28i16 as u16,
+            ],
+            // 32: switch (var[5]) with cases at 41 and 50, ending at 56
+            &[push_variable, 4 * 5, switch, 2, 1, 8, 2, 22, 32],
+            &[
+                push,
+                3,
+                push,
+                0,
+                call,
+                2,
+                4 * mcode("fn_start_fx"),
+                skip_always,
+                12,
+            ],
+            &[push_variable, 4 * 4, pop_variable, 4 * 5, skip_always, 0],
+            &[exit],
+        ]
+        .concat(),
+    );
+
+    let mut out = Vec::new();
+    decompile::write_module(&mut out, &module, |_| None).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+// module 1
+
+script_1000 {
+    if (var[3] == 2) {
+        var[4] = 1;
+    } else {
+        var[4] = 0;
+    }
+    do {
+        var[4] = var[4] + 1;
+    } while (var[4] < 10);
+    switch (var[5]) {
+    case 0x0001:
+        fn_start_fx(3, 0);
+        break;
+    case 0x0002:
+        var[5] = var[4];
+        break;
+    }
+    return;
+}
+"
+    );
+}
@@ -0,0 +1,29 @@
+use beneath_a_steel_sky_extract::{
+    classify::SCREEN_SIZE,
+    sequence::{decode, is_sequence, GAME_SCREEN_SIZE},
+};
+
+/// A run of `len` pixels as stored: lengths of 255 followed by the rest.
+fn run(len: usize) -> Vec<u8> {
+    let mut run = vec![0xff; len / 255];
+    run.push((len % 255) as u8);
+    run
+}
+
+#[test]
+fn frames_end_with_the_game_screen() {
+    // One frame skipping to the last pixel of the game screen and copying
+    // it, which covers the game screen and ends the frame.
+    let data = [&[1][..], &run(GAME_SCREEN_SIZE - 1), &run(1), &[7]].concat();
+    assert!(is_sequence(&data));
+
+    let mut screen = vec![3; SCREEN_SIZE];
+    let frames = decode(&data, &mut screen).unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].data[GAME_SCREEN_SIZE - 1], 7);
+    assert_eq!(frames[0].data[GAME_SCREEN_SIZE], 3);
+
+    // A frame a pixel short of it goes on into data there isn't.
+    let short = [&[1][..], &run(GAME_SCREEN_SIZE - 2), &run(1), &[7]].concat();
+    assert!(!is_sequence(&short));
+}
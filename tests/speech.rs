@@ -0,0 +1,88 @@
+use beneath_a_steel_sky_extract::{
+    archive::Entry,
+    audio::{self, Sound},
+    resource::read_resource,
+    speech,
+};
+
+#[test]
+fn speech_files_map_to_text_ids_and_back() {
+    assert_eq!(speech::speech_file(0x0001), Some(50001));
+    assert_eq!(speech::speech_file(0x1001), Some(50601));
+    assert_eq!(speech::text_id(50601), Some(0x1001));
+
+    // Past the end of section 0, and a section without speech.
+    assert_eq!(speech::speech_file(0x0258), None);
+    assert_eq!(speech::speech_file(0x9001), None);
+}
+
+#[test]
+fn wav_files_read_back_as_written() {
+    let sound = Sound::from_u8(&[0, 64, 128, 200, 255], audio::SAMPLE_RATE);
+    let mut wav = Vec::new();
+    sound.write_wav(&mut wav).unwrap();
+
+    let read = audio::read_wav(&mut wav.as_slice()).unwrap();
+    assert_eq!(read.sample_rate, audio::SAMPLE_RATE);
+    assert_eq!(read.to_u8(), [0, 64, 128, 200, 255]);
+
+    let loud = Sound {
+        sample_rate: 22050,
+        bits_per_sample: 16,
+        samples: vec![-32768, -129, 127, 32767],
+        loop_start: None,
+    };
+    assert_eq!(loud.to_u8(), [0, 127, 128, 255]);
+}
+
+#[test]
+fn wav_loops_read_back_from_the_sampler_chunk() {
+    // An odd number of samples, so the sampler chunk comes after a pad
+    // byte.
+    let sound = Sound::from_u8(&[10, 20, 30, 40, 50], 8000).with_loop(Some(2));
+    let mut wav = Vec::new();
+    sound.write_wav(&mut wav).unwrap();
+    assert_eq!(&wav[4..8], &(wav.len() as u32 - 8).to_le_bytes());
+
+    let read = audio::read_wav(&mut wav.as_slice()).unwrap();
+    assert_eq!(read.loop_start, Some(2));
+    assert_eq!(read.to_u8(), [10, 20, 30, 40, 50]);
+
+    let mut wav = Vec::new();
+    sound.with_loop(None).write_wav(&mut wav).unwrap();
+    assert_eq!(wav.len() % 2, 0);
+    assert_eq!(
+        audio::read_wav(&mut wav.as_slice()).unwrap().loop_start,
+        None
+    );
+}
+
+#[test]
+fn wav_chunks_longer_than_the_file_are_refused() {
+    let mut wav = Vec::new();
+    Sound::from_u8(&[1, 2, 3, 4], 8000)
+        .write_wav(&mut wav)
+        .unwrap();
+    // The data chunk claims 4 GB, which isn't there to read.
+    let data = wav.windows(4).position(|w| w == b"data").unwrap();
+    wav[data + 4..data + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let e = audio::read_wav(&mut wav.as_slice()).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn packed_speech_reads_back() {
+    let samples: Vec<u8> = (0..70000).map(|i| (i % 251) as u8).collect();
+    let data = speech::write_speech(50001, &samples);
+
+    let entry = Entry {
+        number: 50001,
+        offset: 0,
+        size: data.len() as u32,
+        has_file_header: true,
+        uses_file_header: true,
+    };
+    let resource = read_resource(&entry, data).unwrap();
+    assert_eq!(speech::samples(&resource), samples);
+}
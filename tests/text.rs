@@ -0,0 +1,123 @@
+mod common;
+
+use std::process::Command;
+
+use beneath_a_steel_sky_extract::{
+    archive::Entry,
+    resource::read_resource,
+    text::{self, HuffmanTree, TextLine},
+};
+use common::{ArchiveBuilder, TempDir};
+
+fn line(id: u16, text: &str) -> TextLine {
+    TextLine {
+        id,
+        text: text.to_owned(),
+        bytes: text::from_utf8(text).unwrap(),
+    }
+}
+
+#[test]
+fn sections_decode_as_encoded() {
+    // A line long enough for its length to be stored in units of eight, a
+    // gap before the last line of the first block and an empty block.
+    let long = "Foster! ".repeat(40);
+    let lines = [
+        line(0x2000, "Hello, Joey."),
+        line(0x2001, &long),
+        line(0x2003, "Señor"),
+        line(0x2040, ""),
+        line(0x2041, "Bye."),
+    ];
+
+    let tree = HuffmanTree::for_lines(lines.iter().map(|l| &l.bytes[..])).unwrap();
+    let tree = HuffmanTree::from_bytes(&tree.to_bytes()).unwrap();
+    let data = text::encode_section(&lines, &tree).unwrap();
+
+    let decoded: Vec<_> = text::decode_section(2, &data, &tree)
+        .into_iter()
+        .map(|l| (l.id, l.text))
+        .collect();
+    assert_eq!(
+        decoded,
+        [
+            (0x2000, "Hello, Joey.".to_owned()),
+            (0x2001, long),
+            (0x2002, String::new()),
+            (0x2003, "Señor".to_owned()),
+            (0x2040, String::new()),
+            (0x2041, "Bye.".to_owned()),
+        ]
+    );
+    assert_eq!(
+        text::decode_line(&data, 0x0041, &tree).unwrap(),
+        b"Bye.".to_vec()
+    );
+
+    // The tree has no leaf for characters the text it was built for
+    // doesn't have.
+    assert!(text::encode_section(&[line(0x2000, "Zzz")], &tree).is_err());
+    assert!(text::encode_section(&[lines[0].clone(), lines[0].clone()], &tree).is_err());
+}
+
+#[test]
+fn pack_text_encodes_edited_text() {
+    let dir = TempDir::new("pack-text");
+    let original = [line(0x0000, "Hello."), line(0x0001, "Goodbye.")];
+    let tree = HuffmanTree::for_lines(original.iter().map(|l| &l.bytes[..])).unwrap();
+    let tree_path = dir.path().join("tree.bin");
+    std::fs::write(&tree_path, tree.to_bytes()).unwrap();
+
+    let section = text::encode_section(&original, &tree).unwrap();
+    let game = ArchiveBuilder::new()
+        .with_header(60600, &common::header(0, 0), &section, true)
+        .write(&dir.path().join("game"));
+
+    let dump = dir.path().join("dump");
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .arg("-o")
+        .arg(&dump)
+        .args(["--dump", "decoded", "--huffman-tree"])
+        .arg(&tree_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let section_path = dump.join("text/english/0.txt");
+    let edited = std::fs::read_to_string(&section_path)
+        .unwrap()
+        .replace("Goodbye.", "Auf Wiedersehen, Straße!");
+    std::fs::write(&section_path, edited).unwrap();
+
+    let raw = dir.path().join("raw");
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg("pack-text")
+        .args([&game, &dump.join("text/english"), &raw])
+        .args(["--new-tree", "--compress"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let data = std::fs::read(raw.join("60600.dmp")).unwrap();
+    let entry = Entry {
+        number: 60600,
+        offset: 0,
+        size: data.len() as u32,
+        has_file_header: true,
+        uses_file_header: true,
+    };
+    let resource = read_resource(&entry, data).unwrap();
+    assert!(resource.header.unwrap().is_compressed());
+
+    let new_tree =
+        HuffmanTree::from_bytes(&std::fs::read(raw.join("huffman-tree.bin")).unwrap()).unwrap();
+    let lines: Vec<_> = text::decode_section(0, &resource.data, &new_tree)
+        .into_iter()
+        .map(|l| l.text)
+        .collect();
+    assert_eq!(lines, ["Hello.", "Auf Wiedersehen, Straße!"]);
+
+    let index = std::fs::read_to_string(raw.join("index.csv")).unwrap();
+    assert!(index.contains("60600,true,true"));
+}
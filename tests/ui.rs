@@ -0,0 +1,84 @@
+mod common;
+
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    process::Command,
+};
+
+use common::{header, ArchiveBuilder, TempDir};
+
+#[test]
+fn ui_sprites_are_drawn_with_the_panel_palette() {
+    let dir = TempDir::new("ui");
+    let mut palette = vec![0; 768];
+    palette[3] = 63;
+    let mut font = vec![3; 128];
+    font.extend([0xff; 48]);
+    let game = ArchiveBuilder::new()
+        .with_header(60500, &header(2, 1), &[1, 1], false)
+        .raw(60510, &palette)
+        .raw(60520, &font)
+        .write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .arg("-o")
+        .arg(&dump)
+        .arg("--dump=images")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let json = fs::read_to_string(dump.join("ui/ui.json")).unwrap();
+    let record: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(record["palette"], 60510);
+    let elements = record["elements"].as_array().unwrap();
+    assert_eq!(elements.len(), 2, "{json}");
+    assert_eq!(elements[0]["name"], "control-panel");
+    assert_eq!(elements[0]["kind"], "sprite");
+    assert_eq!(elements[0]["files"][0], "60500-control-panel-000.png");
+    assert_eq!(elements[1]["name"], "control-font");
+    assert!(dump.join("ui/60520-control-font.json").exists());
+
+    let decoder = png::Decoder::new(BufReader::new(
+        File::open(dump.join("ui/60500-control-panel-000.png")).unwrap(),
+    ));
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+    reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(&pixels[..3], [255, 0, 0]);
+}
+
+#[test]
+fn cursor_frames_are_listed_with_their_hotspots() {
+    let dir = TempDir::new("cursors");
+    let mut pointers = header(3, 2);
+    pointers.n_sprites = 2;
+    pointers.offset_x = -1;
+    pointers.offset_y = -2;
+    let game = ArchiveBuilder::new()
+        .with_header(60300, &pointers, &[1; 12], false)
+        .write(&dir.path().join("game"));
+    let dump = dir.path().join("dump");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .arg("-o")
+        .arg(&dump)
+        .arg("--dump=images")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let json = fs::read_to_string(dump.join("cursors/cursors.json")).unwrap();
+    let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let records = records.as_array().unwrap();
+    assert_eq!(records.len(), 2, "{json}");
+    assert_eq!(records[1]["file"], "60300-001.png");
+    assert_eq!(records[1]["width"], 3);
+    assert_eq!(records[1]["hotspot"]["x"], 1);
+    assert_eq!(records[1]["hotspot"]["y"], 2);
+    assert!(dump.join("cursors/60300-001.png").exists());
+}
@@ -0,0 +1,68 @@
+use std::f64::consts::TAU;
+
+use beneath_a_steel_sky_extract::{audio::Sound, vorbis::write_vorbis};
+use lewton::inside_ogg::OggStreamReader;
+
+/// The samples of a Vorbis file as another decoder reads them.
+fn decoded(reader: &mut OggStreamReader<std::io::Cursor<Vec<u8>>>) -> Vec<i16> {
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_itl().unwrap() {
+        samples.extend(packet);
+    }
+    samples
+}
+
+/// The ratio of the power of `expected` to that of the difference, in dB.
+fn snr(expected: &[i16], actual: &[i16]) -> f64 {
+    let signal: f64 = expected.iter().map(|&s| (s as f64).powi(2)).sum();
+    let noise: f64 = expected
+        .iter()
+        .zip(actual)
+        .map(|(&e, &a)| (e as f64 - a as f64).powi(2))
+        .sum();
+    10.0 * (signal / noise).log10()
+}
+
+#[test]
+fn vorbis_files_decode_close_to_the_samples_written() {
+    // Tones, a stretch of silence and noise, over several blocks and not a
+    // whole number of them.
+    let mut state = 1u32;
+    let samples: Vec<u8> = (0..12000)
+        .map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match i {
+                0..4000 => {
+                    let t = i as f64 / 11025.0;
+                    (128.0 + 60.0 * (t * 440.0 * TAU).sin() + 30.0 * (t * 2500.0 * TAU).sin()) as u8
+                }
+                4000..8000 => 128,
+                _ => (state >> 16) as u8 / 2 + 64,
+            }
+        })
+        .collect();
+    let sound = Sound::from_u8(&samples, 11025).with_loop(Some(300));
+    let mut ogg = Vec::new();
+    write_vorbis(&mut ogg, &sound).unwrap();
+
+    let mut reader = OggStreamReader::new(std::io::Cursor::new(ogg)).unwrap();
+    assert_eq!(reader.ident_hdr.audio_sample_rate, 11025);
+    assert_eq!(reader.ident_hdr.audio_channels, 1);
+    assert!(reader
+        .comment_hdr
+        .comment_list
+        .contains(&("LOOPSTART".to_owned(), "300".to_owned())));
+    assert!(reader
+        .comment_hdr
+        .comment_list
+        .contains(&("LOOPLENGTH".to_owned(), "11700".to_owned())));
+
+    // lewton gives 16-bit samples whatever the source.
+    let decoded = decoded(&mut reader);
+    assert_eq!(decoded.len(), 12000);
+    let expected: Vec<i16> = sound.samples.iter().map(|&s| s << 8).collect();
+    assert!(snr(&expected[..4000], &decoded[..4000]) > 40.0);
+    // Samples only in blocks that are all silence come out as silence.
+    assert!(decoded[5120..6144].iter().all(|&s| s == 0));
+    assert!(snr(&expected[8000..], &decoded[8000..]) > 40.0);
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use std::{io::Read, process::Command};
+
+use beneath_a_steel_sky_extract::{
+    checksum::crc32,
+    zip::{ZipEntry, ZipWriter},
+};
+use common::{sample, ArchiveBuilder, TempDir};
+use flate2::read::DeflateDecoder;
+
+fn u16_at(data: &[u8], pos: usize) -> usize {
+    u16::from_le_bytes([data[pos], data[pos + 1]]) as usize
+}
+
+fn u32_at(data: &[u8], pos: usize) -> usize {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize
+}
+
+/// The names and contents of the files in `zip`, walking the local headers,
+/// after checking the entry count at the end.
+fn read_zip(zip: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let end = zip.len() - 22;
+    assert_eq!(&zip[end..end + 4], b"PK\x05\x06");
+
+    let mut files = Vec::new();
+    let mut pos = 0;
+    while zip[pos..pos + 4] == *b"PK\x03\x04" {
+        let method = u16_at(zip, pos + 8);
+        let crc = u32_at(zip, pos + 14);
+        let compressed_size = u32_at(zip, pos + 18);
+        let size = u32_at(zip, pos + 22);
+        let name_len = u16_at(zip, pos + 26);
+        let name = String::from_utf8(zip[pos + 30..pos + 30 + name_len].to_vec()).unwrap();
+
+        let start = pos + 30 + name_len;
+        let stored = &zip[start..start + compressed_size];
+        let data = match method {
+            0 => stored.to_vec(),
+            8 => {
+                let mut data = Vec::new();
+                DeflateDecoder::new(stored).read_to_end(&mut data).unwrap();
+                data
+            }
+            _ => panic!("unknown method {method}"),
+        };
+        assert_eq!(data.len(), size, "{name}");
+        assert_eq!(crc32(&data) as usize, crc, "{name}");
+
+        files.push((name, data));
+        pos = start + compressed_size;
+    }
+    assert_eq!(u16_at(zip, end + 10), files.len());
+
+    files
+}
+
+#[test]
+fn zip_files_read_back_as_written() {
+    let big = sample(5000, 1);
+    let mut zip = ZipWriter::new(Vec::new());
+    zip.add(ZipEntry::new("dir/big.bin", &big).unwrap())
+        .unwrap();
+    zip.add(ZipEntry::new("tiny.txt", b"x").unwrap()).unwrap();
+    let zip = zip.finish().unwrap();
+
+    let files = read_zip(&zip);
+    assert_eq!(
+        files,
+        [
+            ("dir/big.bin".to_owned(), big),
+            ("tiny.txt".to_owned(), b"x".to_vec())
+        ]
+    );
+    // A byte doesn't deflate to anything smaller, so it's stored, as the
+    // method in the last directory entry says.
+    let last_entry = zip.len() - 22 - (46 + "tiny.txt".len());
+    assert_eq!(u16_at(&zip, last_entry + 10), 0);
+}
+
+#[test]
+fn dump_zip_holds_the_dump_and_a_manifest() {
+    let dir = TempDir::new("dump-zip");
+    let game = ArchiveBuilder::new()
+        .raw(10, b"first")
+        .raw(11, b"second")
+        .write(&dir.path().join("game"));
+    let zip_path = dir.path().join("dump.zip");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .arg("--dump-zip")
+        .arg(&zip_path)
+        .arg("-o")
+        .arg(dir.path().join("dump"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(!dir.path().join("dump").exists());
+
+    let files = read_zip(&std::fs::read(&zip_path).unwrap());
+    let file = |name: &str| {
+        files
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, data)| data.as_slice())
+    };
+    assert_eq!(file("raw/00011.dmp"), Some(&b"second"[..]));
+
+    let (name, manifest) = files.last().unwrap();
+    assert_eq!(name, "manifest.json");
+    let manifest: serde_json::Value = serde_json::from_slice(manifest).unwrap();
+    let listed = manifest["files"].as_array().unwrap();
+    assert_eq!(listed.len(), files.len() - 1);
+    assert!(listed
+        .iter()
+        .any(|f| f["path"] == "raw/00010.dmp" && f["size"] == 5));
+}
+
+#[test]
+fn dump_zips_are_reproducible() {
+    let dir = TempDir::new("dump-zip-twice");
+    let game = (0..64)
+        .fold(ArchiveBuilder::new(), |game, n| {
+            game.raw(100 + n, &sample(100 + 997 * n as usize % 5000, n as u32))
+        })
+        .write(&dir.path().join("game"));
+
+    let dump = |name: &str| {
+        let zip_path = dir.path().join(name);
+        let status = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+            .arg(&game)
+            .arg("--dump-zip")
+            .arg(&zip_path)
+            .arg("-o")
+            .arg(dir.path().join("dump"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::read(zip_path).unwrap()
+    };
+
+    let first = dump("first.zip");
+    assert_eq!(first, dump("second.zip"));
+
+    let names: Vec<_> = read_zip(&first).into_iter().map(|(name, _)| name).collect();
+    let (manifest, files) = names.split_last().unwrap();
+    assert_eq!(manifest, "manifest.json");
+    assert!(files.is_sorted(), "{files:?}");
+}
+
+#[test]
+fn dump_zips_go_to_stdout_for_a_dash() {
+    let dir = TempDir::new("dump-zip-stdout");
+    let game = ArchiveBuilder::new()
+        .raw(10, b"first")
+        .write(&dir.path().join("game"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_beneath-a-steel-sky-extract"))
+        .arg(&game)
+        .args(["--dump-zip", "-", "-o", "dump"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!dir.path().join("dump").exists());
+
+    // Only the ZIP file is on stdout, the messages went to stderr.
+    let files = read_zip(&output.stdout);
+    assert!(files
+        .iter()
+        .any(|(name, data)| name == "raw/00010.dmp" && data == b"first"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Dumping resources"), "{stderr}");
+}